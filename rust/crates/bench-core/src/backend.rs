@@ -0,0 +1,204 @@
+// The 13 read queries, and the correlated-subquery product filter, behind one trait, so
+// drivers can be compared within one server instead of across servers. QUERY_BACKEND picks the implementation at startup:
+//
+//   (unset)   the regular handlers, with snapshots, NDJSON, pagination links and so on
+//   diesel    the Diesel queries through `QueryBackend`, i.e. the same thin routes as sqlx
+//   sqlx      `sqlx_backend`, in servers built with the `backend-sqlx` feature
+//   raw       `raw_backend`, hand-written SQL on tokio-postgres (`backend-raw` feature)
+//   diesel-sync
+//             `sync_backend`, the Diesel queries on blocking connections through
+//             spawn_blocking (`backend-diesel-sync` feature)
+//   mysql     `mysql_backend`, the queries on MySQL at MYSQL_DATABASE_URL (`backend-mysql`
+//             feature)
+//   sqlite    `sqlite_backend`, the queries on an embedded SQLite copy of the database
+//             (`backend-sqlite` feature)
+//
+// A name the build doesn't have, or a backend failing to set up, is an error rather than a
+// fallback to the regular handlers, whose numbers would be reported under the wrong name.
+// `bench_http::backend_routes` serves them over HTTP, and with DIFF_BACKEND naming a second
+// one `bench_http::backend_diff` runs each query on both and logs where they disagree.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    BenchResult, DbPool,
+    config::PoolConfig,
+    models::{Customer, Employee, Product, Supplier},
+    queries::{self, *},
+};
+
+#[async_trait]
+pub trait QueryBackend: Send + Sync {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>>;
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>>;
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>>;
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>>;
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>>;
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>>;
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>>;
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>>;
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>>;
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>>;
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>>;
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>>;
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>>;
+    // Products above their supplier's average price
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>>;
+}
+
+// `queries`, on a pooled connection per call
+pub struct DieselBackend(pub DbPool);
+
+#[async_trait]
+impl QueryBackend for DieselBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        Ok(queries::p1(&mut *self.0.get().await?, limit, offset).await?)
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        Ok(queries::p2(&mut *self.0.get().await?, id).await?)
+    }
+
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        Ok(queries::p3(&mut *self.0.get().await?, term, dictionary).await?)
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        Ok(queries::p4(&mut *self.0.get().await?, limit, offset).await?)
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        Ok(queries::p5(&mut *self.0.get().await?, id).await?)
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        Ok(queries::p6(&mut *self.0.get().await?, limit, offset).await?)
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        Ok(queries::p7(&mut *self.0.get().await?, id).await?)
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        Ok(queries::p8(&mut *self.0.get().await?, limit, offset).await?)
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        Ok(queries::p9(&mut *self.0.get().await?, id).await?)
+    }
+
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        Ok(queries::p10(&mut *self.0.get().await?, term, dictionary).await?)
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        Ok(queries::p11(&mut *self.0.get().await?, limit, offset).await?)
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        Ok(queries::p12(&mut *self.0.get().await?, id).await?)
+    }
+
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        Ok(queries::p13(&mut *self.0.get().await?, id).await?)
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        Ok(queries::products_above_average_price(&mut *self.0.get().await?, limit, offset).await?)
+    }
+}
+
+// Every name QUERY_BACKEND takes, whether or not this build has the backend
+const NAMES: [&str; 6] = ["diesel", "sqlx", "raw", "diesel-sync", "mysql", "sqlite"];
+
+// The backend called `name`, as QUERY_BACKEND names them; an error for a name this build
+// doesn't have or a backend that fails to set up. The sqlx, raw, sync Diesel, MySQL and
+// SQLite backends open their own pools, sized by `pool_config`
+#[cfg_attr(
+    not(any(
+        feature = "backend-sqlx",
+        feature = "backend-raw",
+        feature = "backend-diesel-sync",
+        feature = "backend-mysql",
+        feature = "backend-sqlite"
+    )),
+    allow(unused_variables)
+)]
+pub fn named(
+    name: &str,
+    pool: &DbPool,
+    pool_config: &PoolConfig,
+) -> BenchResult<Arc<dyn QueryBackend>> {
+    match name {
+        "diesel" => Ok(Arc::new(DieselBackend(pool.clone()))),
+        #[cfg(feature = "backend-sqlx")]
+        "sqlx" => set_up(
+            "sqlx",
+            crate::sqlx_backend::SqlxBackend::from_env(pool_config),
+        ),
+        #[cfg(feature = "backend-raw")]
+        "raw" => set_up(
+            "raw tokio-postgres",
+            crate::raw_backend::RawBackend::from_env(pool_config),
+        ),
+        #[cfg(feature = "backend-diesel-sync")]
+        "diesel-sync" => set_up(
+            "sync Diesel",
+            crate::sync_backend::SyncDieselBackend::from_env(pool_config),
+        ),
+        #[cfg(feature = "backend-mysql")]
+        "mysql" => set_up(
+            "MySQL",
+            crate::mysql_backend::MysqlBackend::from_env(pool_config),
+        ),
+        #[cfg(feature = "backend-sqlite")]
+        "sqlite" => set_up(
+            "SQLite",
+            crate::sqlite_backend::SqliteBackend::from_env(pool_config),
+        ),
+        other if NAMES.contains(&other) => Err(format!(
+            "Query backend {:?} isn't in this build (the backend-{} feature)",
+            other, other
+        )
+        .into()),
+        other => Err(format!("Unknown query backend {:?}", other).into()),
+    }
+}
+
+#[cfg(any(
+    feature = "backend-sqlx",
+    feature = "backend-raw",
+    feature = "backend-diesel-sync",
+    feature = "backend-mysql",
+    feature = "backend-sqlite"
+))]
+fn set_up<B: QueryBackend + 'static>(
+    what: &str,
+    backend: BenchResult<B>,
+) -> BenchResult<Arc<dyn QueryBackend>> {
+    match backend {
+        Ok(backend) => Ok(Arc::new(backend)),
+        Err(err) => Err(format!("Failed to set up the {} backend: {}", what, err).into()),
+    }
+}
+
+// QUERY_BACKEND's; None serves the regular handlers
+pub fn from_env(
+    pool: &DbPool,
+    pool_config: &PoolConfig,
+) -> BenchResult<Option<Arc<dyn QueryBackend>>> {
+    match std::env::var("QUERY_BACKEND") {
+        Ok(name) => named(&name, pool, pool_config).map(Some),
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,84 @@
+// Pool settings, as flags of the `rust` binary with environment fallbacks, so parameter
+// sweeps can change them between runs without recompiling. The defaults are what the
+// server always used.
+//
+// `load_env` runs before the flags are parsed, so .env can hold those fallbacks as well as
+// the variables read directly.
+
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use diesel::connection::CacheSize;
+use serde::Serialize;
+
+// Reads .env into the environment, without overriding variables that are already set
+pub fn load_env() {
+    dotenvy::dotenv().ok();
+}
+
+// Connection pool sizes, shared by the Diesel pool, the DATASETS pools and the sqlx/raw
+// query backends
+#[derive(Args, Clone, Copy, Debug)]
+pub struct PoolConfig {
+    /// Maximum number of database connections per pool
+    #[arg(long = "pool-max-size", env = "POOL_MAX_SIZE", default_value_t = 128)]
+    pub max_size: u32,
+
+    /// Idle connections the pool keeps open
+    #[arg(long = "pool-min-idle", env = "POOL_MIN_IDLE", default_value_t = 16)]
+    pub min_idle: u32,
+
+    /// How long a request waits for a free connection, in milliseconds
+    #[arg(
+        long = "pool-connection-timeout-ms",
+        env = "POOL_CONNECTION_TIMEOUT_MS",
+        default_value_t = 5000
+    )]
+    pub connection_timeout_ms: u64,
+
+    /// Prepared statement cache of each diesel-async connection: `unbounded` prepares every
+    /// distinct query once per connection and reuses it, `disabled` prepares it anew on
+    /// every execution
+    #[arg(
+        long = "statement-cache",
+        env = "STATEMENT_CACHE",
+        value_enum,
+        default_value_t = StatementCache::Unbounded
+    )]
+    pub statement_cache: StatementCache,
+}
+
+// The sizes Diesel's statement cache comes in; it has no bounded one. The sqlx and raw
+// backends keep their own caches and aren't affected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementCache {
+    Unbounded,
+    Disabled,
+}
+
+impl StatementCache {
+    pub fn size(self) -> CacheSize {
+        match self {
+            StatementCache::Unbounded => CacheSize::Unbounded,
+            StatementCache::Disabled => CacheSize::Disabled,
+        }
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 128,
+            min_idle: 16,
+            connection_timeout_ms: 5000,
+            statement_cache: StatementCache::Unbounded,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_millis(self.connection_timeout_ms)
+    }
+}
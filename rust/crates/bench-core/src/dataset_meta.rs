@@ -0,0 +1,74 @@
+// The `dataset_meta` table: which data a database holds, written by the seeder next to the
+// rows it loads and returned by `GET /debug/dataset`, so result files record the dataset
+// they were measured against. One row: the generator version, seed and scale the data came
+// from and a checksum of the data itself, so two databases seeded alike can be told apart
+// from ones that only claim to be (edited rows, a different seeder).
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Bool, Integer, Text, Timestamptz},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+// Bumped whenever the seeder would generate different rows for the same seed and scale
+pub const GENERATOR_VERSION: i32 = 1;
+
+pub(crate) const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS dataset_meta ( \
+     generator_version integer NOT NULL, \
+     seed bigint NOT NULL, \
+     scale text NOT NULL, \
+     checksum text NOT NULL, \
+     seeded_at timestamptz NOT NULL DEFAULT now() \
+ )";
+
+// md5 over the md5s of every table's rows as text, in id order. Reads all the data, which
+// at the seeded sizes takes about as long as a sequential scan of each table.
+pub(crate) const CHECKSUM: &str = "SELECT md5(string_agg(checksum, '' ORDER BY n)) FROM ( \
+     SELECT 1 AS n, md5(string_agg(t::text, ',' ORDER BY t.id)) AS checksum FROM customers t \
+     UNION ALL SELECT 2, md5(string_agg(t::text, ',' ORDER BY t.id)) FROM employees t \
+     UNION ALL SELECT 3, md5(string_agg(t::text, ',' ORDER BY t.id)) FROM orders t \
+     UNION ALL SELECT 4, md5(string_agg(t::text, ',' ORDER BY t.id)) FROM suppliers t \
+     UNION ALL SELECT 5, md5(string_agg(t::text, ',' ORDER BY t.id)) FROM products t \
+     UNION ALL SELECT 6, md5(string_agg(t::text, ',' ORDER BY t.id)) FROM order_details t \
+ ) tables";
+
+#[derive(QueryableByName, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetMeta {
+    #[diesel(sql_type = Integer)]
+    pub generator_version: i32,
+    #[diesel(sql_type = BigInt)]
+    pub seed: i64,
+    // The --size preset, followed by any row counts that overrode it
+    #[diesel(sql_type = Text)]
+    pub scale: String,
+    #[diesel(sql_type = Text)]
+    pub checksum: String,
+    #[diesel(sql_type = Timestamptz)]
+    pub seeded_at: DateTime<Utc>,
+}
+
+#[derive(QueryableByName)]
+struct Exists {
+    #[diesel(sql_type = Bool)]
+    exists: bool,
+}
+
+// None for a database the seeder didn't fill (or one seeded before it recorded this)
+pub async fn dataset_meta(conn: &mut AsyncPgConnection) -> QueryResult<Option<DatasetMeta>> {
+    let table = diesel::sql_query("SELECT to_regclass('dataset_meta') IS NOT NULL AS exists")
+        .get_result::<Exists>(conn)
+        .await?;
+    if !table.exists {
+        return Ok(None);
+    }
+
+    diesel::sql_query(
+        "SELECT generator_version, seed, scale, checksum, seeded_at FROM dataset_meta LIMIT 1",
+    )
+    .get_result(conn)
+    .await
+    .optional()
+}
@@ -0,0 +1,91 @@
+// Postgres full-text search as typed Diesel expressions, for the searches in `queries`: the
+// tsvector and tsquery types, the functions building and ranking them, and `@@`. This is
+// the part of diesel_full_text_search the searches need, with one difference: the text
+// search configuration is written into the SQL as a literal, not bound. The drizzle
+// migrations' GIN indexes are on `to_tsvector('english', ...)`, and the planner only uses
+// them for a query spelling out the same constant.
+
+use diesel::{
+    expression::{AppearsOnTable, Expression, SelectableExpression, ValidGrouping},
+    pg::Pg,
+    query_builder::{AstPass, QueryFragment, QueryId},
+    result::QueryResult,
+    sql_types::{SqlType, Text},
+};
+
+use crate::queries::SearchDictionary;
+
+#[derive(SqlType, QueryId, Clone, Copy, Debug)]
+#[diesel(postgres_type(oid = 3614, array_oid = 3643))]
+pub struct TsVector;
+
+#[derive(SqlType, QueryId, Clone, Copy, Debug)]
+#[diesel(postgres_type(oid = 3615, array_oid = 3645))]
+pub struct TsQuery;
+
+#[derive(SqlType, QueryId, Clone, Copy, Debug)]
+#[diesel(postgres_type(oid = 3734, array_oid = 3735))]
+pub struct RegConfig;
+
+// A dictionary as the regconfig argument of the functions below, e.g. `'english'`
+#[derive(Clone, Copy, Debug, ValidGrouping)]
+pub struct Config(pub SearchDictionary);
+
+impl Expression for Config {
+    type SqlType = RegConfig;
+}
+
+impl<QS> AppearsOnTable<QS> for Config {}
+
+impl<QS> SelectableExpression<QS> for Config {}
+
+impl QueryFragment<Pg> for Config {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("'");
+        out.push_sql(self.0.as_str());
+        out.push_sql("'");
+        Ok(())
+    }
+}
+
+// The SQL differs by dictionary, so statements are cached by their text
+impl QueryId for Config {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+diesel::define_sql_function! {
+    fn to_tsvector(config: RegConfig, document: Text) -> TsVector;
+}
+
+diesel::define_sql_function! {
+    // Every word of `query` ANDed, whatever punctuation or operators it holds
+    fn plainto_tsquery(config: RegConfig, query: Text) -> TsQuery;
+}
+
+diesel::define_sql_function! {
+    // `query` in tsquery syntax, for the prefix matches of autocomplete
+    fn to_tsquery(config: RegConfig, query: Text) -> TsQuery;
+}
+
+diesel::define_sql_function! {
+    fn ts_rank(document: TsVector, query: TsQuery) -> diesel::sql_types::Float;
+}
+
+diesel::define_sql_function! {
+    fn ts_headline(config: RegConfig, document: Text, query: TsQuery) -> Text;
+}
+
+diesel::infix_operator!(Matches, " @@ ", backend: Pg);
+
+pub trait TsVectorExtensions: Expression<SqlType = TsVector> + Sized {
+    // `self @@ query`
+    fn matches<Q>(self, query: Q) -> Matches<Self, Q>
+    where
+        Q: Expression<SqlType = TsQuery>,
+    {
+        Matches::new(self, query)
+    }
+}
+
+impl<T: Expression<SqlType = TsVector>> TsVectorExtensions for T {}
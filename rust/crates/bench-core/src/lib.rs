@@ -0,0 +1,80 @@
+// Models, queries and query backends of the benchmark, without a server: the HTTP server
+// (`bench-http`), the load generator (`bench-driver`) and other frontends build on this.
+
+use diesel_async::pooled_connection::bb8::Pool;
+use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use futures_util::FutureExt;
+
+use dotenvy::dotenv;
+use std::env;
+
+use config::PoolConfig;
+
+pub type DbPool = Pool<AsyncPgConnection>;
+
+pub type BenchError = Box<dyn std::error::Error + Send + Sync>;
+pub type BenchResult<T> = Result<T, BenchError>;
+
+pub async fn establish_connection_pool() -> DbPool {
+    establish_connection_pool_with(&PoolConfig::default()).await
+}
+
+pub fn database_url() -> String {
+    dotenv().ok();
+
+    env::var("DATABASE_URL").expect("DATABASE_URL must be set")
+}
+
+pub async fn establish_connection_pool_with(pool_config: &PoolConfig) -> DbPool {
+    establish_async_pool(&database_url(), pool_config).await
+}
+
+pub async fn establish_async_pool(database_url: &str, pool_config: &PoolConfig) -> DbPool {
+    // Manager for AsyncPgConnection (postgres), with the configured statement cache
+    let cache_size = pool_config.statement_cache.size();
+    let mut manager_config = ManagerConfig::default();
+    manager_config.custom_setup = Box::new(move |url| {
+        async move {
+            let mut conn = AsyncPgConnection::establish(url).await?;
+            conn.set_prepared_statement_cache_size(cache_size);
+            Ok(conn)
+        }
+        .boxed()
+    });
+    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        database_url,
+        manager_config,
+    );
+
+    // bb8 pool
+    Pool::builder()
+        .max_size(pool_config.max_size)
+        .min_idle(pool_config.min_idle)
+        .connection_timeout(pool_config.connection_timeout())
+        .build(config)
+        .await
+        .expect("Failed to create async pool")
+}
+
+pub mod backend;
+pub mod config;
+pub mod dataset_meta;
+pub mod full_text;
+pub mod models;
+#[cfg(feature = "backend-mysql")]
+pub mod mysql_backend;
+pub mod output;
+pub mod queries;
+pub mod query_catalog;
+#[cfg(feature = "backend-raw")]
+pub mod raw_backend;
+pub mod reports;
+pub mod schema;
+pub mod seed;
+#[cfg(feature = "backend-sqlite")]
+pub mod sqlite_backend;
+#[cfg(feature = "backend-sqlx")]
+pub mod sqlx_backend;
+#[cfg(feature = "backend-diesel-sync")]
+pub mod sync_backend;
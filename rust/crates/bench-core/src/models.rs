@@ -5,7 +5,7 @@
 
 use chrono::NaiveDate;
 use diesel::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Queryable, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -23,6 +23,25 @@ pub struct Customer {
     pub fax: Option<String>,
 }
 
+// Body of POST /customers and PUT /customer-by-id; a PUT replaces every column, so a
+// missing nullable field clears it
+#[derive(Insertable, AsChangeset, Deserialize, Debug)]
+#[diesel(table_name = crate::schema::customers)]
+#[diesel(treat_none_as_null = true)]
+#[serde(rename_all = "camelCase")]
+pub struct NewCustomer {
+    pub company_name: String,
+    pub contact_name: String,
+    pub contact_title: String,
+    pub address: String,
+    pub city: String,
+    pub postal_code: Option<String>,
+    pub region: Option<String>,
+    pub country: String,
+    pub phone: String,
+    pub fax: Option<String>,
+}
+
 #[derive(Queryable, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Employee {
@@ -31,7 +50,9 @@ pub struct Employee {
     pub first_name: Option<String>,
     pub title: String,
     pub title_of_courtesy: String,
+    #[serde(serialize_with = "crate::output::date")]
     pub birth_date: NaiveDate,
+    #[serde(serialize_with = "crate::output::date")]
     pub hire_date: NaiveDate,
     pub address: String,
     pub city: String,
@@ -46,22 +67,57 @@ pub struct Employee {
 #[derive(Queryable, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderDetail {
+    #[serde(serialize_with = "crate::output::money")]
     pub unit_price: f64,
     pub quantity: i32,
+    #[serde(serialize_with = "crate::output::float")]
     pub discount: f64,
     pub order_id: i32,
     pub product_id: i32,
     pub id: i64,
 }
 
+// An order of POST /orders, without its id; the order lines come separately
+#[derive(Insertable, Deserialize, Default, Debug)]
+#[diesel(table_name = crate::schema::orders)]
+#[serde(rename_all = "camelCase")]
+pub struct NewOrder {
+    pub order_date: NaiveDate,
+    pub required_date: NaiveDate,
+    pub shipped_date: Option<NaiveDate>,
+    pub ship_via: i32,
+    pub freight: f64,
+    pub ship_name: String,
+    pub ship_city: String,
+    pub ship_region: Option<String>,
+    pub ship_postal_code: Option<String>,
+    pub ship_country: String,
+    pub customer_id: i32,
+    pub employee_id: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::order_details)]
+pub struct NewOrderDetail {
+    pub unit_price: f64,
+    pub quantity: i32,
+    pub discount: f64,
+    pub order_id: i32,
+    pub product_id: i32,
+}
+
 #[derive(Queryable, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Order {
     pub id: i32,
+    #[serde(serialize_with = "crate::output::date")]
     pub order_date: NaiveDate,
+    #[serde(serialize_with = "crate::output::date")]
     pub required_date: NaiveDate,
+    #[serde(serialize_with = "crate::output::optional_date")]
     pub shipped_date: Option<NaiveDate>,
     pub ship_via: i32,
+    #[serde(serialize_with = "crate::output::money")]
     pub freight: f64,
     pub ship_name: String,
     pub ship_city: String,
@@ -72,7 +128,7 @@ pub struct Order {
     pub employee_id: i32,
 }
 
-#[derive(Queryable, Selectable, Serialize)]
+#[derive(Queryable, Selectable, Debug, Serialize)]
 #[diesel(table_name = crate::schema::products)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[serde(rename_all = "camelCase")]
@@ -80,6 +136,7 @@ pub struct Product {
     pub id: i32,
     pub name: String,
     pub qt_per_unit: String,
+    #[serde(serialize_with = "crate::output::money")]
     pub unit_price: f64,
     pub units_in_stock: i32,
     pub units_on_order: i32,
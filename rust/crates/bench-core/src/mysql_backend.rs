@@ -0,0 +1,328 @@
+// `QueryBackend` on MySQL (`QUERY_BACKEND=mysql`, `backend-mysql` feature): p1–p13 in the
+// Diesel DSL of `queries`, loaded over diesel-async's AsyncMysqlConnection from a bb8 pool
+// connected to MYSQL_DATABASE_URL, so MySQL and Postgres are compared on the same endpoints
+// (and with DIFF_BACKEND=mysql, on the same requests). The builders in `queries` are typed for
+// Postgres, so the queries are written out again here, differing where MySQL does:
+//
+//   p3, p10   MATCH (column) AGAINST (term IN BOOLEAN MODE) on the FULLTEXT indexes of
+//             `migrations-mysql`, for to_tsvector @@ plainto_tsquery: each word of the term
+//             is required (`+word`) and any operators in it dropped. Unlike natural language
+//             mode, boolean mode doesn't sort by relevance, so rows come back unordered as on
+//             Postgres. The dictionary is ignored: MySQL tokenizes as the index and the server
+//             settings say, see the migration.
+//   p11, p12  the line totals are multiplied by MySQL as INT * DOUBLE, which is a DOUBLE;
+//             Diesel has no CAST to DOUBLE for MySQL. SUM of the INT quantities is a DECIMAL,
+//             read as the same i64.
+//
+// The schema and how to copy a seeded database over are in `migrations-mysql/`.
+
+use async_trait::async_trait;
+use diesel::{
+    dsl::{avg, count, sql, sum},
+    expression::SqlLiteral,
+    mysql::Mysql,
+    prelude::*,
+    query_builder::QueryFragment,
+    sql_types::{Bool, Double, Nullable, Text},
+};
+use diesel_async::{
+    AsyncMysqlConnection, RunQueryDsl,
+    pooled_connection::{AsyncDieselConnectionManager, bb8::Pool},
+};
+
+use crate::{
+    BenchResult,
+    backend::QueryBackend,
+    config::PoolConfig,
+    models::{Customer, Employee, Order, Product, Supplier},
+    queries::*,
+    schema::{customers, employees, order_details, orders, products, suppliers},
+};
+
+pub struct MysqlBackend {
+    pool: Pool<AsyncMysqlConnection>,
+}
+
+impl MysqlBackend {
+    // Sized like the Postgres pool; connections open in the background
+    pub fn from_env(pool_config: &PoolConfig) -> BenchResult<Self> {
+        let database_url =
+            std::env::var("MYSQL_DATABASE_URL").map_err(|_| "MYSQL_DATABASE_URL must be set")?;
+        let pool = Pool::builder()
+            .max_size(pool_config.max_size)
+            .min_idle(pool_config.min_idle)
+            .connection_timeout(pool_config.connection_timeout())
+            .build_unchecked(AsyncDieselConnectionManager::<AsyncMysqlConnection>::new(
+                database_url,
+            ));
+        Ok(MysqlBackend { pool })
+    }
+}
+
+// The words of `term`, each one required
+fn boolean_query(term: &str) -> String {
+    term.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("+{}", word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// The boolean-mode full-text match of `term` on `column`
+fn matches(column: &str, term: &str) -> SqlLiteral<Bool, impl QueryFragment<Mysql>> {
+    sql::<Bool>(&format!("MATCH ({}) AGAINST (", column))
+        .bind::<Text, _>(boolean_query(term))
+        .sql(" IN BOOLEAN MODE)")
+}
+
+// quantity * unit_price of an order line, NULL without one
+fn line_total() -> SqlLiteral<Nullable<Double>> {
+    sql::<Nullable<Double>>("order_details.quantity * order_details.unit_price")
+}
+
+#[async_trait]
+impl QueryBackend for MysqlBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        Ok(customers::table
+            .order_by(customers::id.asc())
+            .limit(limit)
+            .offset(offset)
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        Ok(customers::table
+            .filter(customers::id.eq(id))
+            .limit(1)
+            .get_result(&mut *self.pool.get().await?)
+            .await
+            .optional()?)
+    }
+
+    async fn p3(&self, term: &str, _dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        Ok(customers::table
+            .filter(matches("company_name", term))
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        Ok(employees::table
+            .order_by(employees::id.asc())
+            .limit(limit)
+            .offset(offset)
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        let recipient = diesel::alias!(employees as recipient);
+
+        Ok(employees::table
+            .left_join(
+                recipient.on(employees::recipient_id.eq(recipient.field(employees::id).nullable())),
+            )
+            .filter(employees::id.eq(id))
+            .select((
+                employees::id,
+                employees::last_name,
+                employees::first_name,
+                employees::title,
+                employees::title_of_courtesy,
+                employees::birth_date,
+                employees::hire_date,
+                employees::address,
+                employees::city,
+                employees::postal_code,
+                employees::country,
+                employees::home_phone,
+                employees::extension,
+                employees::notes,
+                employees::recipient_id,
+                recipient.field(employees::id).nullable(),
+                recipient.field(employees::last_name).nullable(),
+                recipient.field(employees::first_name).nullable(),
+                recipient.field(employees::title).nullable(),
+                recipient.field(employees::title_of_courtesy).nullable(),
+                recipient.field(employees::birth_date).nullable(),
+                recipient.field(employees::hire_date).nullable(),
+                recipient.field(employees::address).nullable(),
+                recipient.field(employees::city).nullable(),
+                recipient.field(employees::postal_code).nullable(),
+                recipient.field(employees::country).nullable(),
+                recipient.field(employees::home_phone).nullable(),
+                recipient.field(employees::extension).nullable(),
+                recipient.field(employees::notes).nullable(),
+                recipient.field(employees::recipient_id).nullable(),
+            ))
+            .limit(1)
+            .get_result(&mut *self.pool.get().await?)
+            .await
+            .optional()?)
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        Ok(suppliers::table
+            .order_by(suppliers::id.asc())
+            .limit(limit)
+            .offset(offset)
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        Ok(suppliers::table
+            .filter(suppliers::id.eq(id))
+            .limit(1)
+            .get_result(&mut *self.pool.get().await?)
+            .await
+            .optional()?)
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        Ok(products::table
+            .order_by(products::id.asc())
+            .limit(limit)
+            .offset(offset)
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        Ok(products::table
+            .inner_join(suppliers::table)
+            .filter(products::id.eq(id))
+            .select((
+                products::id,
+                products::name,
+                products::qt_per_unit,
+                products::unit_price,
+                products::units_in_stock,
+                products::units_on_order,
+                products::reorder_level,
+                products::discontinued,
+                products::supplier_id,
+                suppliers::id,
+                suppliers::company_name,
+                suppliers::contact_name,
+                suppliers::contact_title,
+                suppliers::address,
+                suppliers::city,
+                suppliers::region,
+                suppliers::postal_code,
+                suppliers::country,
+                suppliers::phone,
+            ))
+            .limit(1)
+            .get_result(&mut *self.pool.get().await?)
+            .await
+            .optional()?)
+    }
+
+    async fn p10(&self, term: &str, _dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        Ok(products::table
+            .filter(matches("name", term))
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        Ok(orders::table
+            .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+            .group_by(orders::id)
+            .select((
+                orders::id,
+                orders::shipped_date,
+                orders::ship_name,
+                orders::ship_city,
+                orders::ship_country,
+                count(order_details::product_id.nullable()),
+                sum(order_details::quantity.nullable()),
+                sum(line_total()),
+            ))
+            .order_by(orders::id.asc())
+            .limit(limit)
+            .offset(offset)
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        Ok(orders::table
+            .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+            .filter(orders::id.eq(id))
+            .group_by(orders::id)
+            .select((
+                orders::id,
+                orders::shipped_date,
+                orders::ship_name,
+                orders::ship_city,
+                orders::ship_country,
+                count(order_details::product_id.nullable()),
+                sum(order_details::quantity.nullable()),
+                sum(line_total()),
+            ))
+            .limit(1)
+            .get_result(&mut *self.pool.get().await?)
+            .await
+            .optional()?)
+    }
+
+    // Both statements on one connection, as `queries::p13` runs them
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        let mut conn = self.pool.get().await?;
+        let order: Option<Order> = orders::table
+            .filter(orders::id.eq(id))
+            .limit(1)
+            .get_result(&mut *conn)
+            .await
+            .optional()?;
+        let Some(order) = order else {
+            return Ok(None);
+        };
+        let details: Vec<OrderDetail> = order_details::table
+            .inner_join(products::table)
+            .filter(order_details::order_id.eq(id))
+            .select((
+                order_details::unit_price,
+                order_details::quantity,
+                order_details::discount,
+                order_details::order_id,
+                order_details::product_id,
+                order_details::id,
+                products::id,
+                products::name,
+                products::qt_per_unit,
+                products::unit_price,
+                products::units_in_stock,
+                products::units_on_order,
+                products::reorder_level,
+                products::discontinued,
+                products::supplier_id,
+            ))
+            .load(&mut *conn)
+            .await?;
+        Ok(Some(OrderWithDetailsAndProducts::new(order, details)))
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        let peers = diesel::alias!(products as peers);
+        let supplier_average = peers
+            .filter(peers.field(products::supplier_id).eq(products::supplier_id))
+            .select(avg(peers.field(products::unit_price)))
+            .single_value();
+
+        Ok(products::table
+            .filter(products::unit_price.nullable().gt(supplier_average))
+            .order_by(products::id.asc())
+            .limit(limit)
+            .offset(offset)
+            .load(&mut *self.pool.get().await?)
+            .await?)
+    }
+}
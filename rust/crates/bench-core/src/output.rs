@@ -0,0 +1,122 @@
+// How the query results write their dates and floats, so a server's responses can match
+// another implementation's byte for byte: otherwise payload sizes differ by formatting alone,
+// which skews the transfer-rate comparisons. The models' date and f64 fields serialize
+// through the functions here, whichever encoder (JSON, MessagePack, CSV) writes them.
+//
+//   --date-format      date (default): 1996-07-04, as Postgres returns DATE
+//                      datetime: 1996-07-04T00:00:00.000Z, a JavaScript Date in JSON
+//                      epoch-millis: 836438400000, that Date's getTime()
+//   --float-precision  decimal places floats are rounded to; none by default, which writes
+//                      the shortest representation that round-trips
+//   --money-precision  the same for the monetary fields alone (prices, freight, order totals
+//                      and revenues), where computed sums otherwise come out as
+//                      1234.5600000000002; --float-precision's without it
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use clap::{Args, ValueEnum};
+use serde::{Serialize, Serializer};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateFormat {
+    #[default]
+    Date,
+    Datetime,
+    EpochMillis,
+}
+
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct OutputFormat {
+    /// How dates are written: `date` (1996-07-04), `datetime` (1996-07-04T00:00:00.000Z, as
+    /// Node servers send a Date) or `epoch-millis` (836438400000)
+    #[arg(long, env = "DATE_FORMAT", value_enum, default_value_t = DateFormat::Date)]
+    pub date_format: DateFormat,
+
+    /// Decimal places to round floats to; unrounded without it
+    #[arg(
+        long,
+        env = "FLOAT_PRECISION",
+        value_parser = clap::value_parser!(u8).range(0..=15)
+    )]
+    pub float_precision: Option<u8>,
+
+    /// Decimal places to round prices, freight, order totals and revenues to, e.g. 2;
+    /// --float-precision's without it
+    #[arg(
+        long,
+        env = "MONEY_PRECISION",
+        value_parser = clap::value_parser!(u8).range(0..=15)
+    )]
+    pub money_precision: Option<u8>,
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+impl OutputFormat {
+    // Sets the format of every result serialized from now on. Call before serving.
+    pub fn select(self) {
+        let _ = OUTPUT_FORMAT.set(self);
+    }
+
+    pub fn current() -> Self {
+        OUTPUT_FORMAT.get().copied().unwrap_or_default()
+    }
+
+    fn money_precision(self) -> Option<u8> {
+        self.money_precision.or(self.float_precision)
+    }
+}
+
+fn round(value: f64, precision: Option<u8>) -> f64 {
+    match precision {
+        Some(places) => {
+            let scale = 10f64.powi(places.into());
+            (value * scale).round() / scale
+        }
+        None => value,
+    }
+}
+
+fn midnight(date: &NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+pub fn date<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+    match OutputFormat::current().date_format {
+        DateFormat::Date => date.serialize(serializer),
+        DateFormat::Datetime => {
+            serializer.collect_str(&midnight(date).format("%Y-%m-%dT%H:%M:%S%.3fZ"))
+        }
+        DateFormat::EpochMillis => serializer.serialize_i64(midnight(date).timestamp_millis()),
+    }
+}
+
+pub fn optional_date<S: Serializer>(
+    value: &Option<NaiveDate>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => date(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub fn float<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(round(*value, OutputFormat::current().float_precision))
+}
+
+pub fn money<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(round(*value, OutputFormat::current().money_precision()))
+}
+
+pub fn optional_money<S: Serializer>(
+    value: &Option<f64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => money(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,1429 @@
+use std::str::FromStr;
+
+use diesel::{
+    dsl::{avg, count, sum},
+    pg::Pg,
+    prelude::*,
+    query_builder::QueryFragment,
+    sql_types::{BigInt, Double, Integer, Nullable, Text},
+};
+use diesel_async::{
+    AsyncConnection, AsyncPgConnection, RunQueryDsl, methods::LoadQuery,
+    scoped_futures::ScopedFutureExt,
+};
+use futures_util::{StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+
+use crate::full_text::{
+    Config, TsVectorExtensions, plainto_tsquery, to_tsquery, to_tsvector, ts_headline, ts_rank,
+};
+use crate::models::{
+    self, Customer, Employee, NewCustomer, NewOrder, NewOrderDetail, Order, Product, Supplier,
+};
+use crate::schema::{customers, employees, order_details, orders, products, suppliers};
+
+// Rows of a list query as they arrive, for streamed responses
+pub type RowStream<'conn, T> = BoxStream<'conn, QueryResult<T>>;
+
+// What the `_query` builders return: loadable over diesel-async and renderable for the
+// query catalog, and with the `backend-diesel-sync` feature loadable over a blocking
+// PgConnection too, for `sync_backend`
+#[cfg(not(feature = "backend-diesel-sync"))]
+pub(crate) trait BenchQuery<'q, T>:
+    LoadQuery<'q, AsyncPgConnection, T> + QueryFragment<Pg>
+{
+}
+
+#[cfg(not(feature = "backend-diesel-sync"))]
+impl<'q, T, Q> BenchQuery<'q, T> for Q where
+    Q: LoadQuery<'q, AsyncPgConnection, T> + QueryFragment<Pg>
+{
+}
+
+#[cfg(feature = "backend-diesel-sync")]
+pub(crate) trait BenchQuery<'q, T>:
+    LoadQuery<'q, AsyncPgConnection, T>
+    + diesel::query_dsl::LoadQuery<'q, PgConnection, T>
+    + QueryFragment<Pg>
+{
+}
+
+#[cfg(feature = "backend-diesel-sync")]
+impl<'q, T, Q> BenchQuery<'q, T> for Q where
+    Q: LoadQuery<'q, AsyncPgConnection, T>
+        + diesel::query_dsl::LoadQuery<'q, PgConnection, T>
+        + QueryFragment<Pg>
+{
+}
+
+#[derive(Queryable, Debug, Serialize)]
+pub struct P11Row {
+    pub id: i32,
+    #[serde(serialize_with = "crate::output::optional_date")]
+    pub shipped_date: Option<chrono::NaiveDate>,
+    pub ship_name: String,
+    pub ship_city: String,
+    pub ship_country: String,
+    pub products_count: i64,
+    pub quantity_sum: Option<i64>,
+    #[serde(serialize_with = "crate::output::optional_money")]
+    pub total_price: Option<f64>,
+}
+
+pub(crate) fn p11_query(limit_: i64, offset_: i64) -> impl BenchQuery<'static, P11Row> {
+    let qty_f64 = order_details::quantity
+        .nullable()
+        .cast::<diesel::sql_types::Nullable<Double>>();
+
+    let unit_price = order_details::unit_price.nullable();
+
+    let total_price_expr = sum(qty_f64 * unit_price);
+
+    orders::table
+        .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+        .group_by(orders::id)
+        .select((
+            orders::id,
+            orders::shipped_date,
+            orders::ship_name,
+            orders::ship_city,
+            orders::ship_country,
+            count(order_details::product_id.nullable()),
+            sum(order_details::quantity.nullable()),
+            total_price_expr,
+        ))
+        .order_by(orders::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn p11(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<P11Row>> {
+    p11_query(limit_, offset_).load(conn).await
+}
+
+// p11 row by row, as diesel-async decodes them
+pub async fn p11_stream(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<RowStream<'_, P11Row>> {
+    let rows = p11_query(limit_, offset_).load_stream(conn).await?;
+    Ok(rows.boxed())
+}
+
+// p11 by keyset instead of offset: the orders after id `cursor`
+pub(crate) fn p11_cursor_query(cursor: i32, limit_: i64) -> impl BenchQuery<'static, P11Row> {
+    let qty_f64 = order_details::quantity
+        .nullable()
+        .cast::<diesel::sql_types::Nullable<Double>>();
+
+    let unit_price = order_details::unit_price.nullable();
+
+    let total_price_expr = sum(qty_f64 * unit_price);
+
+    orders::table
+        .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+        .filter(orders::id.gt(cursor))
+        .group_by(orders::id)
+        .select((
+            orders::id,
+            orders::shipped_date,
+            orders::ship_name,
+            orders::ship_city,
+            orders::ship_country,
+            count(order_details::product_id.nullable()),
+            sum(order_details::quantity.nullable()),
+            total_price_expr,
+        ))
+        .order_by(orders::id.asc())
+        .limit(limit_)
+}
+
+pub async fn p11_cursor(
+    conn: &mut AsyncPgConnection,
+    cursor: i32,
+    limit_: i64,
+) -> QueryResult<Vec<P11Row>> {
+    p11_cursor_query(cursor, limit_).load(conn).await
+}
+
+// p1: Get customers with limit/offset, ordered by id asc
+pub(crate) fn p1_query(limit_: i64, offset_: i64) -> impl BenchQuery<'static, Customer> {
+    customers::table
+        .order_by(customers::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn p1(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Customer>> {
+    p1_query(limit_, offset_).load(conn).await
+}
+
+pub async fn p1_stream(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<RowStream<'_, Customer>> {
+    let rows = p1_query(limit_, offset_).load_stream(conn).await?;
+    Ok(rows.boxed())
+}
+
+// p1 by keyset instead of offset: the customers after id `cursor` (0 for the first page)
+pub(crate) fn p1_cursor_query(cursor: i32, limit_: i64) -> impl BenchQuery<'static, Customer> {
+    customers::table
+        .filter(customers::id.gt(cursor))
+        .order_by(customers::id.asc())
+        .limit(limit_)
+}
+
+pub async fn p1_cursor(
+    conn: &mut AsyncPgConnection,
+    cursor: i32,
+    limit_: i64,
+) -> QueryResult<Vec<Customer>> {
+    p1_cursor_query(cursor, limit_).load(conn).await
+}
+
+// Row counts of the list routes' tables, for pagination links
+pub async fn count_customers(conn: &mut AsyncPgConnection) -> QueryResult<i64> {
+    customers::table.count().get_result(conn).await
+}
+
+pub async fn count_employees(conn: &mut AsyncPgConnection) -> QueryResult<i64> {
+    employees::table.count().get_result(conn).await
+}
+
+pub async fn count_suppliers(conn: &mut AsyncPgConnection) -> QueryResult<i64> {
+    suppliers::table.count().get_result(conn).await
+}
+
+pub async fn count_products(conn: &mut AsyncPgConnection) -> QueryResult<i64> {
+    products::table.count().get_result(conn).await
+}
+
+// p11 has a row per order
+pub async fn count_orders(conn: &mut AsyncPgConnection) -> QueryResult<i64> {
+    orders::table.count().get_result(conn).await
+}
+
+// How the customers without orders are found: the same anti-join written either way, as
+// Postgres plans both as one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AntiJoin {
+    // WHERE NOT EXISTS (SELECT ... FROM orders WHERE customer_id = customers.id)
+    #[default]
+    NotExists,
+    // LEFT JOIN orders ... WHERE orders.id IS NULL
+    LeftJoin,
+}
+
+// Customers without any orders, with limit/offset, ordered by id asc
+pub(crate) fn customers_without_orders_not_exists_query(
+    limit_: i64,
+    offset_: i64,
+) -> impl BenchQuery<'static, Customer> {
+    customers::table
+        .filter(diesel::dsl::not(diesel::dsl::exists(
+            orders::table
+                .select(orders::id)
+                .filter(orders::customer_id.eq(customers::id)),
+        )))
+        .order_by(customers::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub(crate) fn customers_without_orders_left_join_query(
+    limit_: i64,
+    offset_: i64,
+) -> impl BenchQuery<'static, Customer> {
+    customers::table
+        .left_join(orders::table)
+        .filter(orders::id.nullable().is_null())
+        .select(customers::all_columns)
+        .order_by(customers::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn customers_without_orders(
+    conn: &mut AsyncPgConnection,
+    anti_join: AntiJoin,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Customer>> {
+    match anti_join {
+        AntiJoin::NotExists => {
+            customers_without_orders_not_exists_query(limit_, offset_)
+                .load(conn)
+                .await
+        }
+        AntiJoin::LeftJoin => {
+            customers_without_orders_left_join_query(limit_, offset_)
+                .load(conn)
+                .await
+        }
+    }
+}
+
+// p2: Find first customer by id
+pub(crate) fn p2_query(id_: i32) -> impl BenchQuery<'static, Customer> {
+    customers::table.filter(customers::id.eq(id_)).limit(1)
+}
+
+pub async fn p2(conn: &mut AsyncPgConnection, id_: i32) -> QueryResult<Option<Customer>> {
+    p2_query(id_).get_result(conn).await.optional()
+}
+
+// A customer with every one of their orders, each with p11's totals
+#[derive(Debug, Serialize)]
+pub struct CustomerWithOrders {
+    #[serde(flatten)]
+    pub customer: Customer,
+    pub orders: Vec<P11Row>,
+}
+
+// p11's aggregation over one customer's orders, in id order
+pub(crate) fn customer_orders_query(customer_id_: i32) -> impl BenchQuery<'static, P11Row> {
+    let qty_f64 = order_details::quantity
+        .nullable()
+        .cast::<diesel::sql_types::Nullable<Double>>();
+
+    let unit_price = order_details::unit_price.nullable();
+
+    let total_price_expr = sum(qty_f64 * unit_price);
+
+    orders::table
+        .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+        .filter(orders::customer_id.eq(customer_id_))
+        .group_by(orders::id)
+        .select((
+            orders::id,
+            orders::shipped_date,
+            orders::ship_name,
+            orders::ship_city,
+            orders::ship_country,
+            count(order_details::product_id.nullable()),
+            sum(order_details::quantity.nullable()),
+            total_price_expr,
+        ))
+        .order_by(orders::id.asc())
+}
+
+// Two queries, like p13: the customer (p2), then their orders
+pub async fn customer_orders(
+    conn: &mut AsyncPgConnection,
+    id_: i32,
+) -> QueryResult<Option<CustomerWithOrders>> {
+    let Some(customer) = p2(conn, id_).await? else {
+        return Ok(None);
+    };
+    let orders = customer_orders_query(id_).load(conn).await?;
+    Ok(Some(CustomerWithOrders { customer, orders }))
+}
+
+// Insert a customer, returning it with its generated id
+pub async fn insert_customer(
+    conn: &mut AsyncPgConnection,
+    customer: &NewCustomer,
+) -> QueryResult<Customer> {
+    diesel::insert_into(customers::table)
+        .values(customer)
+        .get_result(conn)
+        .await
+}
+
+// Replace a customer's columns by id, returning the updated row
+pub async fn update_customer(
+    conn: &mut AsyncPgConnection,
+    id_: i32,
+    customer: &NewCustomer,
+) -> QueryResult<Option<Customer>> {
+    diesel::update(customers::table.filter(customers::id.eq(id_)))
+        .set(customer)
+        .get_result(conn)
+        .await
+        .optional()
+}
+
+// Delete a customer by id; false when there was none
+pub async fn delete_customer(conn: &mut AsyncPgConnection, id_: i32) -> QueryResult<bool> {
+    diesel::delete(customers::table.filter(customers::id.eq(id_)))
+        .execute(conn)
+        .await
+        .map(|deleted| deleted > 0)
+}
+
+// Text search configuration of p3 and p10 (and their filtered variants). `english` stems
+// words and drops stop words, and is the one the drizzle migrations' GIN indexes are built
+// for; `simple` only lowercases, for datasets in other languages, and scans the table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchDictionary {
+    #[default]
+    English,
+    Simple,
+}
+
+impl SearchDictionary {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchDictionary::English => "english",
+            SearchDictionary::Simple => "simple",
+        }
+    }
+
+    // The deployment's default, SEARCH_DICTIONARY (english when unset)
+    pub fn from_env() -> Self {
+        match std::env::var("SEARCH_DICTIONARY") {
+            Err(_) => SearchDictionary::default(),
+            Ok(name) => name.parse().unwrap_or_else(|_| {
+                eprintln!("Unknown SEARCH_DICTIONARY {:?}, searching in english", name);
+                SearchDictionary::default()
+            }),
+        }
+    }
+}
+
+impl FromStr for SearchDictionary {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "english" => Ok(SearchDictionary::English),
+            "simple" => Ok(SearchDictionary::Simple),
+            _ => Err(format!("unknown search dictionary {:?}", name)),
+        }
+    }
+}
+
+// p3: Full-text search on customers.company_name. The term is read as plain words, all of
+// which have to match (plainto_tsquery), so user input can't be a tsquery syntax error.
+pub(crate) fn p3_query(
+    term: &str,
+    dictionary: SearchDictionary,
+) -> impl BenchQuery<'_, Customer> + '_ {
+    let config = Config(dictionary);
+    customers::table
+        .filter(to_tsvector(config, customers::company_name).matches(plainto_tsquery(config, term)))
+}
+
+pub async fn p3(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+) -> QueryResult<Vec<Customer>> {
+    p3_query(term, dictionary).load(conn).await
+}
+
+// p3's matches, unordered like p3 or with `ranked` best first by ts_rank (ties in id order)
+fn p3_search_query(
+    term: &str,
+    dictionary: SearchDictionary,
+    ranked: bool,
+) -> customers::BoxedQuery<'_, Pg> {
+    let config = Config(dictionary);
+    let document = to_tsvector(config, customers::company_name);
+    let query = plainto_tsquery(config, term);
+    let matches = customers::table
+        .filter(document.matches(query))
+        .into_boxed();
+    if ranked {
+        matches.order_by((ts_rank(document, query).desc(), customers::id.asc()))
+    } else {
+        matches
+    }
+}
+
+pub async fn p3_ranked(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+) -> QueryResult<Vec<Customer>> {
+    p3_search_query(term, dictionary, true).load(conn).await
+}
+
+// A p3 match with its company name highlighted by ts_headline: the matched words wrapped in
+// <b></b>, the rest left as is
+#[derive(Queryable, Debug, Serialize)]
+pub struct CustomerSearchHit {
+    #[serde(flatten)]
+    pub customer: Customer,
+    pub headline: String,
+}
+
+pub async fn p3_highlighted(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+    ranked: bool,
+) -> QueryResult<Vec<CustomerSearchHit>> {
+    let config = Config(dictionary);
+    p3_search_query(term, dictionary, ranked)
+        .select((
+            customers::all_columns,
+            ts_headline(
+                config,
+                customers::company_name,
+                plainto_tsquery(config, term),
+            ),
+        ))
+        .load(conn)
+        .await
+}
+
+// Structured filters of the POST search, ANDed with p3's match; unset ones don't filter
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CustomerFilters {
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+}
+
+// p3 with filters and a limit, in id order so limited results are stable
+fn p3_filtered_query<'a>(
+    term: &'a str,
+    dictionary: SearchDictionary,
+    filters: &'a CustomerFilters,
+    limit_: i64,
+) -> customers::BoxedQuery<'a, Pg> {
+    let config = Config(dictionary);
+    let mut query = customers::table
+        .filter(to_tsvector(config, customers::company_name).matches(plainto_tsquery(config, term)))
+        .into_boxed();
+    if let Some(country) = &filters.country {
+        query = query.filter(customers::country.eq(country));
+    }
+    if let Some(city) = &filters.city {
+        query = query.filter(customers::city.eq(city));
+    }
+    if let Some(region) = &filters.region {
+        query = query.filter(customers::region.eq(region));
+    }
+    query.order_by(customers::id.asc()).limit(limit_)
+}
+
+pub async fn p3_filtered(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+    filters: &CustomerFilters,
+    limit_: i64,
+) -> QueryResult<Vec<Customer>> {
+    p3_filtered_query(term, dictionary, filters, limit_)
+        .load(conn)
+        .await
+}
+
+pub async fn p3_filtered_highlighted(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+    filters: &CustomerFilters,
+    limit_: i64,
+) -> QueryResult<Vec<CustomerSearchHit>> {
+    let config = Config(dictionary);
+    p3_filtered_query(term, dictionary, filters, limit_)
+        .select((
+            customers::all_columns,
+            ts_headline(
+                config,
+                customers::company_name,
+                plainto_tsquery(config, term),
+            ),
+        ))
+        .load(conn)
+        .await
+}
+
+// p4: Get employees with limit/offset, ordered by id asc
+pub(crate) fn p4_query(limit_: i64, offset_: i64) -> impl BenchQuery<'static, Employee> {
+    employees::table
+        .order_by(employees::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn p4(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Employee>> {
+    p4_query(limit_, offset_).load(conn).await
+}
+
+// p5: Get employee with recipient (self-join), filtered by id
+#[derive(Queryable, Debug, Serialize)]
+pub struct EmployeeWithRecipient {
+    pub id: i32,
+    pub last_name: String,
+    pub first_name: Option<String>,
+    pub title: String,
+    pub title_of_courtesy: String,
+    #[serde(serialize_with = "crate::output::date")]
+    pub birth_date: chrono::NaiveDate,
+    #[serde(serialize_with = "crate::output::date")]
+    pub hire_date: chrono::NaiveDate,
+    pub address: String,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+    pub home_phone: String,
+    pub extension: i32,
+    pub notes: String,
+    pub recipient_id: Option<i32>,
+    // Recipient fields (nullable because of LEFT JOIN)
+    pub recipient_employee_id: Option<i32>,
+    pub recipient_last_name: Option<String>,
+    pub recipient_first_name: Option<String>,
+    pub recipient_title: Option<String>,
+    pub recipient_title_of_courtesy: Option<String>,
+    #[serde(serialize_with = "crate::output::optional_date")]
+    pub recipient_birth_date: Option<chrono::NaiveDate>,
+    #[serde(serialize_with = "crate::output::optional_date")]
+    pub recipient_hire_date: Option<chrono::NaiveDate>,
+    pub recipient_address: Option<String>,
+    pub recipient_city: Option<String>,
+    pub recipient_postal_code: Option<String>,
+    pub recipient_country: Option<String>,
+    pub recipient_home_phone: Option<String>,
+    pub recipient_extension: Option<i32>,
+    pub recipient_notes: Option<String>,
+    pub recipient_recipient_id: Option<i32>,
+}
+
+pub(crate) fn p5_query(id_: i32) -> impl BenchQuery<'static, EmployeeWithRecipient> {
+    let recipient = diesel::alias!(employees as recipient);
+
+    employees::table
+        .left_join(
+            recipient.on(employees::recipient_id.eq(recipient.field(employees::id).nullable())),
+        )
+        .filter(employees::id.eq(id_))
+        .select((
+            employees::id,
+            employees::last_name,
+            employees::first_name,
+            employees::title,
+            employees::title_of_courtesy,
+            employees::birth_date,
+            employees::hire_date,
+            employees::address,
+            employees::city,
+            employees::postal_code,
+            employees::country,
+            employees::home_phone,
+            employees::extension,
+            employees::notes,
+            employees::recipient_id,
+            recipient.field(employees::id).nullable(),
+            recipient.field(employees::last_name).nullable(),
+            recipient.field(employees::first_name).nullable(),
+            recipient.field(employees::title).nullable(),
+            recipient.field(employees::title_of_courtesy).nullable(),
+            recipient.field(employees::birth_date).nullable(),
+            recipient.field(employees::hire_date).nullable(),
+            recipient.field(employees::address).nullable(),
+            recipient.field(employees::city).nullable(),
+            recipient.field(employees::postal_code).nullable(),
+            recipient.field(employees::country).nullable(),
+            recipient.field(employees::home_phone).nullable(),
+            recipient.field(employees::extension).nullable(),
+            recipient.field(employees::notes).nullable(),
+            recipient.field(employees::recipient_id).nullable(),
+        ))
+        .limit(1)
+}
+
+pub async fn p5(
+    conn: &mut AsyncPgConnection,
+    id_: i32,
+) -> QueryResult<Option<EmployeeWithRecipient>> {
+    p5_query(id_).get_result(conn).await.optional()
+}
+
+// p6: Get suppliers with limit/offset, ordered by id asc
+pub(crate) fn p6_query(limit_: i64, offset_: i64) -> impl BenchQuery<'static, Supplier> {
+    suppliers::table
+        .order_by(suppliers::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn p6(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Supplier>> {
+    p6_query(limit_, offset_).load(conn).await
+}
+
+// p7: Find first supplier by id
+pub(crate) fn p7_query(id_: i32) -> impl BenchQuery<'static, Supplier> {
+    suppliers::table.filter(suppliers::id.eq(id_)).limit(1)
+}
+
+pub async fn p7(conn: &mut AsyncPgConnection, id_: i32) -> QueryResult<Option<Supplier>> {
+    p7_query(id_).get_result(conn).await.optional()
+}
+
+// p8: Get products with limit/offset, ordered by id asc
+pub(crate) fn p8_query(limit_: i64, offset_: i64) -> impl BenchQuery<'static, Product> {
+    products::table
+        .order_by(products::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn p8(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Product>> {
+    p8_query(limit_, offset_).load(conn).await
+}
+
+pub async fn p8_stream(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<RowStream<'_, Product>> {
+    let rows = p8_query(limit_, offset_).load_stream(conn).await?;
+    Ok(rows.boxed())
+}
+
+// p8 by keyset instead of offset: the products after id `cursor`
+pub(crate) fn p8_cursor_query(cursor: i32, limit_: i64) -> impl BenchQuery<'static, Product> {
+    products::table
+        .filter(products::id.gt(cursor))
+        .order_by(products::id.asc())
+        .limit(limit_)
+}
+
+pub async fn p8_cursor(
+    conn: &mut AsyncPgConnection,
+    cursor: i32,
+    limit_: i64,
+) -> QueryResult<Vec<Product>> {
+    p8_cursor_query(cursor, limit_).load(conn).await
+}
+
+// Columns the supplier product listing can be sorted by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProductSortColumn {
+    Id,
+    Name,
+    UnitPrice,
+    UnitsInStock,
+    UnitsOnOrder,
+    ReorderLevel,
+}
+
+// One key of a composite ordering: `unit_price:desc`, or `name` (ascending)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProductSortKey {
+    pub column: ProductSortColumn,
+    pub descending: bool,
+}
+
+impl FromStr for ProductSortKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let (column, direction) = key.split_once(':').unwrap_or((key, "asc"));
+        let column = match column {
+            "id" => ProductSortColumn::Id,
+            "name" => ProductSortColumn::Name,
+            "unit_price" => ProductSortColumn::UnitPrice,
+            "units_in_stock" => ProductSortColumn::UnitsInStock,
+            "units_on_order" => ProductSortColumn::UnitsOnOrder,
+            "reorder_level" => ProductSortColumn::ReorderLevel,
+            _ => return Err(format!("unknown sort column {:?}", column)),
+        };
+        let descending = match direction {
+            "asc" => false,
+            "desc" => true,
+            _ => return Err(format!("unknown sort direction {:?}", direction)),
+        };
+        Ok(ProductSortKey { column, descending })
+    }
+}
+
+// A `sort` of comma-separated keys, most significant first, each column at most once
+pub fn parse_product_sort(sort: &str) -> Result<Vec<ProductSortKey>, String> {
+    let mut keys: Vec<ProductSortKey> = Vec::new();
+    for text in sort.split(',') {
+        let key: ProductSortKey = text.parse()?;
+        if keys.iter().any(|other| other.column == key.column) {
+            let column = text.split(':').next().unwrap_or(text);
+            return Err(format!("sort column {:?} given twice", column));
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+// A supplier's products with limit/offset, in the order of `sort` built up key by key, and
+// by id after the keys (unless one of them is id) so rows tying on all of them page stably
+pub(crate) fn supplier_products_query(
+    supplier_id_: i32,
+    sort: &[ProductSortKey],
+    limit_: i64,
+    offset_: i64,
+) -> products::BoxedQuery<'static, Pg> {
+    let mut query = products::table
+        .filter(products::supplier_id.eq(supplier_id_))
+        .into_boxed();
+    for key in sort {
+        query = match (key.column, key.descending) {
+            (ProductSortColumn::Id, false) => query.then_order_by(products::id.asc()),
+            (ProductSortColumn::Id, true) => query.then_order_by(products::id.desc()),
+            (ProductSortColumn::Name, false) => query.then_order_by(products::name.asc()),
+            (ProductSortColumn::Name, true) => query.then_order_by(products::name.desc()),
+            (ProductSortColumn::UnitPrice, false) => {
+                query.then_order_by(products::unit_price.asc())
+            }
+            (ProductSortColumn::UnitPrice, true) => {
+                query.then_order_by(products::unit_price.desc())
+            }
+            (ProductSortColumn::UnitsInStock, false) => {
+                query.then_order_by(products::units_in_stock.asc())
+            }
+            (ProductSortColumn::UnitsInStock, true) => {
+                query.then_order_by(products::units_in_stock.desc())
+            }
+            (ProductSortColumn::UnitsOnOrder, false) => {
+                query.then_order_by(products::units_on_order.asc())
+            }
+            (ProductSortColumn::UnitsOnOrder, true) => {
+                query.then_order_by(products::units_on_order.desc())
+            }
+            (ProductSortColumn::ReorderLevel, false) => {
+                query.then_order_by(products::reorder_level.asc())
+            }
+            (ProductSortColumn::ReorderLevel, true) => {
+                query.then_order_by(products::reorder_level.desc())
+            }
+        };
+    }
+    if !sort.iter().any(|key| key.column == ProductSortColumn::Id) {
+        query = query.then_order_by(products::id.asc());
+    }
+    query.limit(limit_).offset(offset_)
+}
+
+pub async fn supplier_products(
+    conn: &mut AsyncPgConnection,
+    supplier_id_: i32,
+    sort: &[ProductSortKey],
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Product>> {
+    supplier_products_query(supplier_id_, sort, limit_, offset_)
+        .load(conn)
+        .await
+}
+
+// Products priced above the average of their supplier's products, with limit/offset in id
+// order. The average is a correlated subquery over a second reference to products, evaluated
+// per outer row.
+pub(crate) fn products_above_average_price_query(
+    limit_: i64,
+    offset_: i64,
+) -> impl BenchQuery<'static, Product> {
+    let peers = diesel::alias!(products as peers);
+    let supplier_average = peers
+        .filter(peers.field(products::supplier_id).eq(products::supplier_id))
+        .select(avg(peers.field(products::unit_price)))
+        .single_value();
+
+    products::table
+        .filter(products::unit_price.nullable().gt(supplier_average))
+        .order_by(products::id.asc())
+        .limit(limit_)
+        .offset(offset_)
+}
+
+pub async fn products_above_average_price(
+    conn: &mut AsyncPgConnection,
+    limit_: i64,
+    offset_: i64,
+) -> QueryResult<Vec<Product>> {
+    products_above_average_price_query(limit_, offset_)
+        .load(conn)
+        .await
+}
+
+// p9: Get product with supplier (join), filtered by id
+#[derive(Queryable, Debug, Serialize)]
+pub struct ProductWithSupplier {
+    pub id: i32,
+    pub name: String,
+    pub qt_per_unit: String,
+    #[serde(serialize_with = "crate::output::money")]
+    pub unit_price: f64,
+    pub units_in_stock: i32,
+    pub units_on_order: i32,
+    pub reorder_level: i32,
+    pub discontinued: i32,
+    pub supplier_id: i32,
+    // Supplier fields
+    pub supplier_supplier_id: i32,
+    pub supplier_company_name: String,
+    pub supplier_contact_name: String,
+    pub supplier_contact_title: String,
+    pub supplier_address: String,
+    pub supplier_city: String,
+    pub supplier_region: Option<String>,
+    pub supplier_postal_code: String,
+    pub supplier_country: String,
+    pub supplier_phone: String,
+}
+
+pub(crate) fn p9_query(id_: i32) -> impl BenchQuery<'static, ProductWithSupplier> {
+    products::table
+        .inner_join(suppliers::table)
+        .filter(products::id.eq(id_))
+        .select((
+            products::id,
+            products::name,
+            products::qt_per_unit,
+            products::unit_price,
+            products::units_in_stock,
+            products::units_on_order,
+            products::reorder_level,
+            products::discontinued,
+            products::supplier_id,
+            suppliers::id,
+            suppliers::company_name,
+            suppliers::contact_name,
+            suppliers::contact_title,
+            suppliers::address,
+            suppliers::city,
+            suppliers::region,
+            suppliers::postal_code,
+            suppliers::country,
+            suppliers::phone,
+        ))
+        .limit(1)
+}
+
+pub async fn p9(
+    conn: &mut AsyncPgConnection,
+    id_: i32,
+) -> QueryResult<Option<ProductWithSupplier>> {
+    p9_query(id_).get_result(conn).await.optional()
+}
+
+// p10: Full-text search on products.name, the term read as in p3
+pub(crate) fn p10_query(
+    term: &str,
+    dictionary: SearchDictionary,
+) -> impl BenchQuery<'_, Product> + '_ {
+    let config = Config(dictionary);
+    products::table
+        .filter(to_tsvector(config, products::name).matches(plainto_tsquery(config, term)))
+}
+
+pub async fn p10(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+) -> QueryResult<Vec<Product>> {
+    p10_query(term, dictionary).load(conn).await
+}
+
+// p10's matches, optionally ranked as in `p3_search_query`
+fn p10_search_query(
+    term: &str,
+    dictionary: SearchDictionary,
+    ranked: bool,
+) -> products::BoxedQuery<'_, Pg> {
+    let config = Config(dictionary);
+    let document = to_tsvector(config, products::name);
+    let query = plainto_tsquery(config, term);
+    let matches = products::table.filter(document.matches(query)).into_boxed();
+    if ranked {
+        matches.order_by((ts_rank(document, query).desc(), products::id.asc()))
+    } else {
+        matches
+    }
+}
+
+pub async fn p10_ranked(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+) -> QueryResult<Vec<Product>> {
+    p10_search_query(term, dictionary, true).load(conn).await
+}
+
+// A p10 match with its name highlighted, as in `CustomerSearchHit`
+#[derive(Queryable, Debug, Serialize)]
+pub struct ProductSearchHit {
+    #[serde(flatten)]
+    pub product: Product,
+    pub headline: String,
+}
+
+pub async fn p10_highlighted(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+    ranked: bool,
+) -> QueryResult<Vec<ProductSearchHit>> {
+    let config = Config(dictionary);
+    p10_search_query(term, dictionary, ranked)
+        .select((
+            products::all_columns,
+            ts_headline(config, products::name, plainto_tsquery(config, term)),
+        ))
+        .load(conn)
+        .await
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProductFilters {
+    pub supplier_id: Option<i32>,
+    pub discontinued: Option<bool>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+}
+
+// p10 with filters and a limit, in id order
+fn p10_filtered_query<'a>(
+    term: &'a str,
+    dictionary: SearchDictionary,
+    filters: &ProductFilters,
+    limit_: i64,
+) -> products::BoxedQuery<'a, Pg> {
+    let config = Config(dictionary);
+    let mut query = products::table
+        .filter(to_tsvector(config, products::name).matches(plainto_tsquery(config, term)))
+        .into_boxed();
+    if let Some(supplier_id) = filters.supplier_id {
+        query = query.filter(products::supplier_id.eq(supplier_id));
+    }
+    if let Some(discontinued) = filters.discontinued {
+        query = query.filter(products::discontinued.eq(discontinued as i32));
+    }
+    if let Some(min_price) = filters.min_price {
+        query = query.filter(products::unit_price.ge(min_price));
+    }
+    if let Some(max_price) = filters.max_price {
+        query = query.filter(products::unit_price.le(max_price));
+    }
+    query.order_by(products::id.asc()).limit(limit_)
+}
+
+pub async fn p10_filtered(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+    filters: &ProductFilters,
+    limit_: i64,
+) -> QueryResult<Vec<Product>> {
+    p10_filtered_query(term, dictionary, filters, limit_)
+        .load(conn)
+        .await
+}
+
+pub async fn p10_filtered_highlighted(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+    filters: &ProductFilters,
+    limit_: i64,
+) -> QueryResult<Vec<ProductSearchHit>> {
+    let config = Config(dictionary);
+    p10_filtered_query(term, dictionary, filters, limit_)
+        .select((
+            products::all_columns,
+            ts_headline(config, products::name, plainto_tsquery(config, term)),
+        ))
+        .load(conn)
+        .await
+}
+
+// Facets of a p10 search: how its matches split by supplier and by discontinued status,
+// counted in one pass with GROUPING SETS. Each row belongs to one of the two groupings, and
+// has the other's column null (both are NOT NULL in the table). The DSL has no GROUPING
+// SETS, so this one stays SQL, matching as `full_text` does.
+#[derive(QueryableByName, Debug)]
+pub(crate) struct FacetRow {
+    #[diesel(sql_type = Nullable<Integer>)]
+    supplier_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    discontinued: Option<i32>,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupplierFacet {
+    pub supplier_id: i32,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscontinuedFacet {
+    pub discontinued: i32,
+    pub count: i64,
+}
+
+// Largest counts first
+#[derive(Debug, Default, Serialize)]
+pub struct ProductFacets {
+    pub suppliers: Vec<SupplierFacet>,
+    pub discontinued: Vec<DiscontinuedFacet>,
+}
+
+// Matches of a faceted search (p10's rows, or their highlighted form) and their facets
+#[derive(Debug, Serialize)]
+pub struct FacetedSearch<T> {
+    pub matches: Vec<T>,
+    pub facets: ProductFacets,
+}
+
+pub(crate) fn p10_facets_query(
+    term: &str,
+    dictionary: SearchDictionary,
+) -> impl BenchQuery<'_, FacetRow> + '_ {
+    diesel::sql_query(format!(
+        "SELECT supplier_id, discontinued, count(*) AS count FROM products \
+         WHERE to_tsvector('{0}', name) @@ plainto_tsquery('{0}', $1) \
+         GROUP BY GROUPING SETS ((supplier_id), (discontinued)) \
+         ORDER BY count DESC, supplier_id, discontinued",
+        dictionary.as_str()
+    ))
+    .bind::<Text, _>(term)
+}
+
+pub async fn p10_facets(
+    conn: &mut AsyncPgConnection,
+    term: &str,
+    dictionary: SearchDictionary,
+) -> QueryResult<ProductFacets> {
+    let rows: Vec<FacetRow> = p10_facets_query(term, dictionary).load(conn).await?;
+
+    let mut facets = ProductFacets::default();
+    for row in rows {
+        match (row.supplier_id, row.discontinued) {
+            (Some(supplier_id), _) => facets.suppliers.push(SupplierFacet {
+                supplier_id,
+                count: row.count,
+            }),
+            (None, Some(discontinued)) => facets.discontinued.push(DiscontinuedFacet {
+                discontinued,
+                count: row.count,
+            }),
+            (None, None) => {}
+        }
+    }
+    Ok(facets)
+}
+
+// Autocomplete on products.name: every word typed so far as a prefix (`cha te` matches
+// "Chai tea"). Words are reduced to letters and digits, as tsquery syntax isn't theirs to
+// write; None when nothing is left.
+pub fn prefix_tsquery(input: &str) -> Option<String> {
+    let words: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" & "))
+}
+
+#[derive(Queryable, Debug, Serialize)]
+pub struct ProductSuggestion {
+    pub id: i32,
+    pub name: String,
+}
+
+// In name order, so a prefix's suggestions are stable and the shortest completions of a
+// word come first
+pub(crate) fn product_suggestions_query(
+    tsquery: &str,
+    dictionary: SearchDictionary,
+    limit_: i64,
+) -> impl BenchQuery<'_, ProductSuggestion> + '_ {
+    let config = Config(dictionary);
+    products::table
+        .select((products::id, products::name))
+        .filter(to_tsvector(config, products::name).matches(to_tsquery(config, tsquery)))
+        .order_by((products::name.asc(), products::id.asc()))
+        .limit(limit_)
+}
+
+pub async fn product_suggestions(
+    conn: &mut AsyncPgConnection,
+    input: &str,
+    dictionary: SearchDictionary,
+    limit_: i64,
+) -> QueryResult<Vec<ProductSuggestion>> {
+    let Some(tsquery) = prefix_tsquery(input) else {
+        return Ok(Vec::new());
+    };
+    product_suggestions_query(&tsquery, dictionary, limit_)
+        .load(conn)
+        .await
+}
+
+// p12: Get single order with details by id
+pub(crate) fn p12_query(id_: i32) -> impl BenchQuery<'static, P11Row> {
+    let qty_f64 = order_details::quantity
+        .nullable()
+        .cast::<diesel::sql_types::Nullable<Double>>();
+
+    let unit_price = order_details::unit_price.nullable();
+
+    let total_price_expr = sum(qty_f64 * unit_price);
+
+    orders::table
+        .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+        .filter(orders::id.eq(id_))
+        .group_by(orders::id)
+        .select((
+            orders::id,
+            orders::shipped_date,
+            orders::ship_name,
+            orders::ship_city,
+            orders::ship_country,
+            count(order_details::product_id.nullable()),
+            sum(order_details::quantity.nullable()),
+            total_price_expr,
+        ))
+        .limit(1)
+}
+
+pub async fn p12(conn: &mut AsyncPgConnection, id_: i32) -> QueryResult<Option<P11Row>> {
+    p12_query(id_).get_result(conn).await.optional()
+}
+
+// p13: Get order with details and products by id
+#[derive(Queryable, Debug, Serialize)]
+pub struct OrderDetail {
+    #[serde(serialize_with = "crate::output::money")]
+    pub unit_price: f64,
+    pub quantity: i32,
+    #[serde(serialize_with = "crate::output::float")]
+    pub discount: f64,
+    pub order_id: i32,
+    pub product_id: i32,
+    pub id: i64,
+    // Product fields
+    pub product_product_id: i32,
+    pub product_name: String,
+    pub product_qt_per_unit: String,
+    #[serde(serialize_with = "crate::output::money")]
+    pub product_unit_price: f64,
+    pub product_units_in_stock: i32,
+    pub product_units_on_order: i32,
+    pub product_reorder_level: i32,
+    pub product_discontinued: i32,
+    pub product_supplier_id: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderWithDetailsAndProducts {
+    pub id: i32,
+    #[serde(serialize_with = "crate::output::date")]
+    pub order_date: chrono::NaiveDate,
+    #[serde(serialize_with = "crate::output::date")]
+    pub required_date: chrono::NaiveDate,
+    #[serde(serialize_with = "crate::output::optional_date")]
+    pub shipped_date: Option<chrono::NaiveDate>,
+    pub ship_via: i32,
+    #[serde(serialize_with = "crate::output::money")]
+    pub freight: f64,
+    pub ship_name: String,
+    pub ship_city: String,
+    pub ship_region: Option<String>,
+    pub ship_postal_code: Option<String>,
+    pub ship_country: String,
+    pub customer_id: i32,
+    pub employee_id: i32,
+    pub details: Vec<OrderDetail>,
+}
+
+impl OrderWithDetailsAndProducts {
+    pub(crate) fn new(order: Order, details: Vec<OrderDetail>) -> Self {
+        OrderWithDetailsAndProducts {
+            id: order.id,
+            order_date: order.order_date,
+            required_date: order.required_date,
+            shipped_date: order.shipped_date,
+            ship_via: order.ship_via,
+            freight: order.freight,
+            ship_name: order.ship_name,
+            ship_city: order.ship_city,
+            ship_region: order.ship_region,
+            ship_postal_code: order.ship_postal_code,
+            ship_country: order.ship_country,
+            customer_id: order.customer_id,
+            employee_id: order.employee_id,
+            details,
+        }
+    }
+}
+
+// p13 runs two queries: the order, then its details
+pub(crate) fn p13_order_query(id_: i32) -> impl BenchQuery<'static, Order> {
+    orders::table.filter(orders::id.eq(id_)).limit(1)
+}
+
+pub(crate) fn p13_details_query(id_: i32) -> impl BenchQuery<'static, OrderDetail> {
+    order_details::table
+        .inner_join(products::table)
+        .filter(order_details::order_id.eq(id_))
+        .select((
+            order_details::unit_price,
+            order_details::quantity,
+            order_details::discount,
+            order_details::order_id,
+            order_details::product_id,
+            order_details::id,
+            products::id,
+            products::name,
+            products::qt_per_unit,
+            products::unit_price,
+            products::units_in_stock,
+            products::units_on_order,
+            products::reorder_level,
+            products::discontinued,
+            products::supplier_id,
+        ))
+}
+
+pub async fn p13(
+    conn: &mut AsyncPgConnection,
+    id_: i32,
+) -> QueryResult<Option<OrderWithDetailsAndProducts>> {
+    let order: Option<Order> = p13_order_query(id_).get_result(conn).await.optional()?;
+
+    let order = match order {
+        Some(o) => o,
+        None => return Ok(None),
+    };
+
+    let details: Vec<OrderDetail> = p13_details_query(id_).load(conn).await?;
+
+    Ok(Some(OrderWithDetailsAndProducts::new(order, details)))
+}
+
+// p14: Place an order: take each line's quantity out of its product's stock, then insert the
+// order and its details, all in one transaction. Lines are priced at their product's
+// current unit_price.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OrderLine {
+    pub product_id: i32,
+    pub quantity: i32,
+    #[serde(default)]
+    pub discount: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacedOrder {
+    #[serde(flatten)]
+    pub order: Order,
+    pub details: Vec<models::OrderDetail>,
+}
+
+#[derive(Debug)]
+pub enum PlaceOrderError {
+    // The line's product doesn't exist or has fewer than `quantity` in stock
+    OutOfStock { product_id: i32, quantity: i32 },
+    Query(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for PlaceOrderError {
+    fn from(err: diesel::result::Error) -> Self {
+        PlaceOrderError::Query(err)
+    }
+}
+
+// Only updates a product with enough stock, so a miss leaves nothing to undo but the
+// transaction
+pub(crate) fn p14_stock_query(product_id: i32, quantity: i32) -> impl BenchQuery<'static, f64> {
+    diesel::update(
+        products::table
+            .filter(products::id.eq(product_id))
+            .filter(products::units_in_stock.ge(quantity)),
+    )
+    .set(products::units_in_stock.eq(products::units_in_stock - quantity))
+    .returning(products::unit_price)
+}
+
+pub(crate) fn p14_order_query(order: &NewOrder) -> impl BenchQuery<'_, Order> + '_ {
+    diesel::insert_into(orders::table)
+        .values(order)
+        .returning(orders::all_columns)
+}
+
+// All of the order's details in one multi-row INSERT
+pub(crate) fn p14_details_query(
+    details: &[NewOrderDetail],
+) -> impl BenchQuery<'_, models::OrderDetail> + '_ {
+    diesel::insert_into(order_details::table)
+        .values(details)
+        .returning(order_details::all_columns)
+}
+
+// `lines` must not be empty
+pub async fn p14_create_order(
+    conn: &mut AsyncPgConnection,
+    order: &NewOrder,
+    lines: &[OrderLine],
+) -> Result<PlacedOrder, PlaceOrderError> {
+    conn.transaction(|conn| {
+        async move {
+            // In product order, so orders sharing products lock them in the same order
+            // instead of deadlocking
+            let mut lines: Vec<&OrderLine> = lines.iter().collect();
+            lines.sort_by_key(|line| line.product_id);
+
+            let mut details = Vec::with_capacity(lines.len());
+            for line in lines {
+                let unit_price: f64 = p14_stock_query(line.product_id, line.quantity)
+                    .get_result(conn)
+                    .await
+                    .optional()?
+                    .ok_or(PlaceOrderError::OutOfStock {
+                        product_id: line.product_id,
+                        quantity: line.quantity,
+                    })?;
+                details.push(NewOrderDetail {
+                    unit_price,
+                    quantity: line.quantity,
+                    discount: line.discount,
+                    order_id: 0,
+                    product_id: line.product_id,
+                });
+            }
+
+            let order: Order = p14_order_query(order).get_result(conn).await?;
+            for detail in &mut details {
+                detail.order_id = order.id;
+            }
+            let details = p14_details_query(&details).load(conn).await?;
+
+            Ok(PlacedOrder { order, details })
+        }
+        .scope_boxed()
+    })
+    .await
+}
@@ -0,0 +1,379 @@
+// Machine-readable description of the 14 benchmark queries (and the keyset pagination
+// variants, faceted search, autocomplete, the supplier product listing and the reports) for
+// `GET /debug/queries`: name, route, parameters and SQL, so external tooling and the docs
+// site can follow what this server implements. The SQL is rendered from the same Diesel
+// query builders the handlers run, with $n placeholders for the parameters; p13, p14, the
+// faceted search and the customer order history list each of their statements, the
+// anti-join both of its forms.
+
+use diesel::{
+    pg::{Pg, PgQueryBuilder},
+    query_builder::{QueryBuilder, QueryFragment},
+};
+use serde::Serialize;
+
+use crate::models::{NewOrder, NewOrderDetail};
+use crate::queries::*;
+use crate::reports::*;
+
+#[derive(Serialize)]
+pub struct Parameter {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    // Absent for required parameters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<DefaultValue>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum DefaultValue {
+    Integer(i64),
+    String(&'static str),
+}
+
+#[derive(Serialize)]
+pub struct QueryDefinition {
+    pub name: &'static str,
+    pub method: &'static str,
+    pub route: &'static str,
+    pub parameters: Vec<Parameter>,
+    pub sql: Vec<String>,
+}
+
+fn render(query: &impl QueryFragment<Pg>) -> String {
+    let mut builder = PgQueryBuilder::default();
+    match query.to_sql(&mut builder, &Pg) {
+        Ok(()) => builder.finish(),
+        Err(err) => {
+            eprintln!("Failed to render query SQL: {:?}", err);
+            String::new()
+        }
+    }
+}
+
+fn paginated() -> Vec<Parameter> {
+    vec![
+        Parameter {
+            name: "limit",
+            kind: "integer",
+            default: Some(DefaultValue::Integer(100)),
+        },
+        Parameter {
+            name: "offset",
+            kind: "integer",
+            default: Some(DefaultValue::Integer(0)),
+        },
+    ]
+}
+
+fn keyset() -> Vec<Parameter> {
+    vec![
+        Parameter {
+            name: "cursor",
+            kind: "integer",
+            default: Some(DefaultValue::Integer(0)),
+        },
+        Parameter {
+            name: "limit",
+            kind: "integer",
+            default: Some(DefaultValue::Integer(100)),
+        },
+    ]
+}
+
+fn by_id() -> Vec<Parameter> {
+    vec![Parameter {
+        name: "id",
+        kind: "integer",
+        default: None,
+    }]
+}
+
+// The dictionary defaults to the server's SEARCH_DICTIONARY; the SQL shows `english`
+fn search() -> Vec<Parameter> {
+    vec![
+        Parameter {
+            name: "term",
+            kind: "string",
+            default: None,
+        },
+        Parameter {
+            name: "dictionary",
+            kind: "string",
+            default: Some(DefaultValue::String(SearchDictionary::from_env().as_str())),
+        },
+    ]
+}
+
+// `id` is a path segment; the SQL shows the ordering of `sort=unit_price:desc,name:asc`
+fn supplier_products() -> Vec<Parameter> {
+    let mut parameters = by_id();
+    parameters.push(Parameter {
+        name: "sort",
+        kind: "string",
+        default: Some(DefaultValue::String("id:asc")),
+    });
+    parameters.extend(paginated());
+    parameters
+}
+
+// The SQL lists the `not-exists` statement, then the `left-join` one
+fn anti_join() -> Vec<Parameter> {
+    let mut parameters = vec![Parameter {
+        name: "strategy",
+        kind: "string",
+        default: Some(DefaultValue::String("not-exists")),
+    }];
+    parameters.extend(paginated());
+    parameters
+}
+
+fn top() -> Vec<Parameter> {
+    vec![Parameter {
+        name: "count",
+        kind: "integer",
+        default: Some(DefaultValue::Integer(10)),
+    }]
+}
+
+fn autocomplete() -> Vec<Parameter> {
+    vec![
+        Parameter {
+            name: "q",
+            kind: "string",
+            default: None,
+        },
+        Parameter {
+            name: "limit",
+            kind: "integer",
+            default: Some(DefaultValue::Integer(10)),
+        },
+    ]
+}
+
+pub fn query_definitions() -> Vec<QueryDefinition> {
+    let get = |name, route, parameters, sql| QueryDefinition {
+        name,
+        method: "GET",
+        route,
+        parameters,
+        sql,
+    };
+
+    vec![
+        get(
+            "p1",
+            "/customers",
+            paginated(),
+            vec![render(&p1_query(0, 0))],
+        ),
+        get("p2", "/customer-by-id", by_id(), vec![render(&p2_query(0))]),
+        get(
+            "p3",
+            "/search-customer",
+            search(),
+            vec![render(&p3_query("", SearchDictionary::English))],
+        ),
+        get(
+            "p4",
+            "/employees",
+            paginated(),
+            vec![render(&p4_query(0, 0))],
+        ),
+        get(
+            "p5",
+            "/employee-with-recipient",
+            by_id(),
+            vec![render(&p5_query(0))],
+        ),
+        get(
+            "p6",
+            "/suppliers",
+            paginated(),
+            vec![render(&p6_query(0, 0))],
+        ),
+        get("p7", "/supplier-by-id", by_id(), vec![render(&p7_query(0))]),
+        get(
+            "p8",
+            "/products",
+            paginated(),
+            vec![render(&p8_query(0, 0))],
+        ),
+        get(
+            "p9",
+            "/product-with-supplier",
+            by_id(),
+            vec![render(&p9_query(0))],
+        ),
+        get(
+            "p10",
+            "/search-product",
+            search(),
+            vec![render(&p10_query("", SearchDictionary::English))],
+        ),
+        get(
+            "p11",
+            "/orders-with-details",
+            paginated(),
+            vec![render(&p11_query(0, 0))],
+        ),
+        get(
+            "p12",
+            "/order-with-details",
+            by_id(),
+            vec![render(&p12_query(0))],
+        ),
+        get(
+            "p13",
+            "/order-with-details-and-products",
+            by_id(),
+            vec![render(&p13_order_query(0)), render(&p13_details_query(0))],
+        ),
+        // A transaction of a stock update per order line, then the order and its details
+        QueryDefinition {
+            name: "p14",
+            method: "POST",
+            route: "/orders",
+            parameters: vec![Parameter {
+                name: "body",
+                kind: "object",
+                default: None,
+            }],
+            sql: vec![
+                render(&p14_stock_query(0, 0)),
+                render(&p14_order_query(&NewOrder::default())),
+                render(&p14_details_query(&[NewOrderDetail {
+                    unit_price: 0.0,
+                    quantity: 0,
+                    discount: 0.0,
+                    order_id: 0,
+                    product_id: 0,
+                }])),
+            ],
+        },
+        // p10 with facet counts of its matches, as a second, grouped query
+        get(
+            "p10-faceted",
+            "/search-product-faceted",
+            search(),
+            vec![
+                render(&p10_query("", SearchDictionary::English)),
+                render(&p10_facets_query("", SearchDictionary::English)),
+            ],
+        ),
+        // Prefix search on products.name for autocomplete, `q` made into `word:*` terms
+        get(
+            "autocomplete-products",
+            "/autocomplete/products",
+            autocomplete(),
+            vec![render(&product_suggestions_query(
+                "",
+                SearchDictionary::English,
+                0,
+            ))],
+        ),
+        // A customer and their orders with p11's totals, nested
+        get(
+            "customer-orders",
+            "/customer-orders",
+            by_id(),
+            vec![render(&p2_query(0)), render(&customer_orders_query(0))],
+        ),
+        // Customers without orders, an anti-join in either form of `strategy`
+        get(
+            "customers-without-orders",
+            "/customers/without-orders",
+            anti_join(),
+            vec![
+                render(&customers_without_orders_not_exists_query(0, 0)),
+                render(&customers_without_orders_left_join_query(0, 0)),
+            ],
+        ),
+        // p8 filtered by a correlated subquery
+        get(
+            "products-above-average-price",
+            "/products/above-average-price",
+            paginated(),
+            vec![render(&products_above_average_price_query(0, 0))],
+        ),
+        // p8 for one supplier, with a composite ORDER BY from `sort`
+        get(
+            "supplier-products",
+            "/suppliers/:id/products",
+            supplier_products(),
+            vec![render(&supplier_products_query(
+                0,
+                &[
+                    ProductSortKey {
+                        column: ProductSortColumn::UnitPrice,
+                        descending: true,
+                    },
+                    ProductSortKey {
+                        column: ProductSortColumn::Name,
+                        descending: false,
+                    },
+                ],
+                0,
+                0,
+            ))],
+        ),
+        // Keyset variants of p1, p8 and p11
+        get(
+            "p1-cursor",
+            "/customers-cursor",
+            keyset(),
+            vec![render(&p1_cursor_query(0, 0))],
+        ),
+        get(
+            "p8-cursor",
+            "/products-cursor",
+            keyset(),
+            vec![render(&p8_cursor_query(0, 0))],
+        ),
+        get(
+            "p11-cursor",
+            "/orders-cursor",
+            keyset(),
+            vec![render(&p11_cursor_query(0, 0))],
+        ),
+        // Reports
+        get(
+            "shipping-status",
+            "/reports/shipping-status",
+            Vec::new(),
+            vec![render(&shipping_status_query())],
+        ),
+        get(
+            "order-value-percentiles",
+            "/reports/order-value-percentiles",
+            Vec::new(),
+            vec![render(&order_value_percentiles_query())],
+        ),
+        get(
+            "repeat-customers",
+            "/reports/repeat-customers",
+            Vec::new(),
+            vec![render(&repeat_customers_query())],
+        ),
+        get(
+            "top-products",
+            "/top-products",
+            top(),
+            vec![render(&top_products_query(0))],
+        ),
+        get(
+            "employee-leaderboard",
+            "/employee-leaderboard",
+            top(),
+            vec![render(&employee_leaderboard_query(0))],
+        ),
+        get(
+            "sales-by-country",
+            "/sales-by-country",
+            Vec::new(),
+            vec![render(&sales_by_country_query())],
+        ),
+    ]
+}
@@ -0,0 +1,403 @@
+// `QueryBackend` on plain tokio-postgres (`QUERY_BACKEND=raw`, `backend-raw` feature): p1–p13
+// as hand-written SQL on a deadpool pool connected to DATABASE_URL, with statements prepared
+// once per connection and rows read by column index. No query builder and no row mapping
+// beyond the struct literals, so it is the floor Diesel's and sqlx's overhead is measured
+// against. The SQL matches what Diesel generates, selecting the columns in the same order.
+
+use async_trait::async_trait;
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, RecyclingMethod};
+use tokio_postgres::{NoTls, Row};
+
+use crate::{
+    BenchResult,
+    backend::QueryBackend,
+    config::PoolConfig,
+    models::{Customer, Employee, Order, Product, Supplier},
+    queries::*,
+};
+
+const P1: &str = "SELECT id, company_name, contact_name, contact_title, address, city, postal_code, region, country, phone, fax \
+     FROM customers ORDER BY id ASC LIMIT $1 OFFSET $2";
+const P2: &str = "SELECT id, company_name, contact_name, contact_title, address, city, postal_code, region, country, phone, fax \
+     FROM customers WHERE id = $1 LIMIT 1";
+// p3 and p10 in each dictionary, which has to be a literal for the indexes to apply
+macro_rules! p3 {
+    ($dictionary:literal) => {
+        concat!(
+            "SELECT id, company_name, contact_name, contact_title, address, city, postal_code, region, country, phone, fax \
+             FROM customers WHERE to_tsvector('", $dictionary, "', company_name) @@ plainto_tsquery('", $dictionary, "', $1)"
+        )
+    };
+}
+macro_rules! p10 {
+    ($dictionary:literal) => {
+        concat!(
+            "SELECT id, name, qt_per_unit, unit_price, units_in_stock, units_on_order, reorder_level, \
+             discontinued, supplier_id \
+             FROM products WHERE to_tsvector('", $dictionary, "', name) @@ plainto_tsquery('", $dictionary, "', $1)"
+        )
+    };
+}
+const P4: &str = "SELECT id, last_name, first_name, title, title_of_courtesy, birth_date, hire_date, address, city, \
+     postal_code, country, home_phone, extension, notes, recipient_id \
+     FROM employees ORDER BY id ASC LIMIT $1 OFFSET $2";
+const P5: &str = "SELECT e.id, e.last_name, e.first_name, e.title, e.title_of_courtesy, e.birth_date, e.hire_date, \
+     e.address, e.city, e.postal_code, e.country, e.home_phone, e.extension, e.notes, e.recipient_id, \
+     r.id, r.last_name, r.first_name, r.title, r.title_of_courtesy, r.birth_date, r.hire_date, \
+     r.address, r.city, r.postal_code, r.country, r.home_phone, r.extension, r.notes, r.recipient_id \
+     FROM employees e LEFT OUTER JOIN employees r ON e.recipient_id = r.id WHERE e.id = $1 LIMIT 1";
+const P6: &str = "SELECT id, company_name, contact_name, contact_title, address, city, region, postal_code, country, phone \
+     FROM suppliers ORDER BY id ASC LIMIT $1 OFFSET $2";
+const P7: &str = "SELECT id, company_name, contact_name, contact_title, address, city, region, postal_code, country, phone \
+     FROM suppliers WHERE id = $1 LIMIT 1";
+const P8: &str = "SELECT id, name, qt_per_unit, unit_price, units_in_stock, units_on_order, reorder_level, \
+     discontinued, supplier_id \
+     FROM products ORDER BY id ASC LIMIT $1 OFFSET $2";
+const P9: &str = "SELECT p.id, p.name, p.qt_per_unit, p.unit_price, p.units_in_stock, p.units_on_order, \
+     p.reorder_level, p.discontinued, p.supplier_id, \
+     s.id, s.company_name, s.contact_name, s.contact_title, s.address, s.city, s.region, s.postal_code, \
+     s.country, s.phone \
+     FROM products p INNER JOIN suppliers s ON p.supplier_id = s.id WHERE p.id = $1 LIMIT 1";
+const P11: &str = "SELECT o.id, o.shipped_date, o.ship_name, o.ship_city, o.ship_country, count(od.product_id), \
+     sum(od.quantity), sum(CAST(od.quantity AS double precision) * od.unit_price) \
+     FROM orders o LEFT OUTER JOIN order_details od ON od.order_id = o.id \
+     GROUP BY o.id ORDER BY o.id ASC LIMIT $1 OFFSET $2";
+const P12: &str = "SELECT o.id, o.shipped_date, o.ship_name, o.ship_city, o.ship_country, count(od.product_id), \
+     sum(od.quantity), sum(CAST(od.quantity AS double precision) * od.unit_price) \
+     FROM orders o LEFT OUTER JOIN order_details od ON od.order_id = o.id \
+     WHERE o.id = $1 GROUP BY o.id LIMIT 1";
+const P13_ORDER: &str = "SELECT id, order_date, required_date, shipped_date, ship_via, freight, ship_name, ship_city, \
+     ship_region, ship_postal_code, ship_country, customer_id, employee_id \
+     FROM orders WHERE id = $1 LIMIT 1";
+const P13_DETAILS: &str = "SELECT od.unit_price, od.quantity, od.discount, od.order_id, od.product_id, od.id, \
+     p.id, p.name, p.qt_per_unit, p.unit_price, p.units_in_stock, p.units_on_order, p.reorder_level, \
+     p.discontinued, p.supplier_id \
+     FROM order_details od INNER JOIN products p ON od.product_id = p.id WHERE od.order_id = $1";
+const ABOVE_AVERAGE_PRICE: &str = "SELECT id, name, qt_per_unit, unit_price, units_in_stock, units_on_order, \
+     reorder_level, discontinued, supplier_id \
+     FROM products WHERE unit_price > (SELECT avg(peers.unit_price) FROM products AS peers \
+     WHERE peers.supplier_id = products.supplier_id) \
+     ORDER BY id ASC LIMIT $1 OFFSET $2";
+
+fn customer(row: &Row) -> Customer {
+    Customer {
+        id: row.get(0),
+        company_name: row.get(1),
+        contact_name: row.get(2),
+        contact_title: row.get(3),
+        address: row.get(4),
+        city: row.get(5),
+        postal_code: row.get(6),
+        region: row.get(7),
+        country: row.get(8),
+        phone: row.get(9),
+        fax: row.get(10),
+    }
+}
+
+fn employee(row: &Row) -> Employee {
+    Employee {
+        id: row.get(0),
+        last_name: row.get(1),
+        first_name: row.get(2),
+        title: row.get(3),
+        title_of_courtesy: row.get(4),
+        birth_date: row.get(5),
+        hire_date: row.get(6),
+        address: row.get(7),
+        city: row.get(8),
+        postal_code: row.get(9),
+        country: row.get(10),
+        home_phone: row.get(11),
+        extension: row.get(12),
+        notes: row.get(13),
+        recipient_id: row.get(14),
+    }
+}
+
+fn employee_with_recipient(row: &Row) -> EmployeeWithRecipient {
+    EmployeeWithRecipient {
+        id: row.get(0),
+        last_name: row.get(1),
+        first_name: row.get(2),
+        title: row.get(3),
+        title_of_courtesy: row.get(4),
+        birth_date: row.get(5),
+        hire_date: row.get(6),
+        address: row.get(7),
+        city: row.get(8),
+        postal_code: row.get(9),
+        country: row.get(10),
+        home_phone: row.get(11),
+        extension: row.get(12),
+        notes: row.get(13),
+        recipient_id: row.get(14),
+        recipient_employee_id: row.get(15),
+        recipient_last_name: row.get(16),
+        recipient_first_name: row.get(17),
+        recipient_title: row.get(18),
+        recipient_title_of_courtesy: row.get(19),
+        recipient_birth_date: row.get(20),
+        recipient_hire_date: row.get(21),
+        recipient_address: row.get(22),
+        recipient_city: row.get(23),
+        recipient_postal_code: row.get(24),
+        recipient_country: row.get(25),
+        recipient_home_phone: row.get(26),
+        recipient_extension: row.get(27),
+        recipient_notes: row.get(28),
+        recipient_recipient_id: row.get(29),
+    }
+}
+
+fn supplier(row: &Row) -> Supplier {
+    Supplier {
+        id: row.get(0),
+        company_name: row.get(1),
+        contact_name: row.get(2),
+        contact_title: row.get(3),
+        address: row.get(4),
+        city: row.get(5),
+        region: row.get(6),
+        postal_code: row.get(7),
+        country: row.get(8),
+        phone: row.get(9),
+    }
+}
+
+fn product(row: &Row) -> Product {
+    Product {
+        id: row.get(0),
+        name: row.get(1),
+        qt_per_unit: row.get(2),
+        unit_price: row.get(3),
+        units_in_stock: row.get(4),
+        units_on_order: row.get(5),
+        reorder_level: row.get(6),
+        discontinued: row.get(7),
+        supplier_id: row.get(8),
+    }
+}
+
+fn product_with_supplier(row: &Row) -> ProductWithSupplier {
+    ProductWithSupplier {
+        id: row.get(0),
+        name: row.get(1),
+        qt_per_unit: row.get(2),
+        unit_price: row.get(3),
+        units_in_stock: row.get(4),
+        units_on_order: row.get(5),
+        reorder_level: row.get(6),
+        discontinued: row.get(7),
+        supplier_id: row.get(8),
+        supplier_supplier_id: row.get(9),
+        supplier_company_name: row.get(10),
+        supplier_contact_name: row.get(11),
+        supplier_contact_title: row.get(12),
+        supplier_address: row.get(13),
+        supplier_city: row.get(14),
+        supplier_region: row.get(15),
+        supplier_postal_code: row.get(16),
+        supplier_country: row.get(17),
+        supplier_phone: row.get(18),
+    }
+}
+
+fn p11_row(row: &Row) -> P11Row {
+    P11Row {
+        id: row.get(0),
+        shipped_date: row.get(1),
+        ship_name: row.get(2),
+        ship_city: row.get(3),
+        ship_country: row.get(4),
+        products_count: row.get(5),
+        quantity_sum: row.get(6),
+        total_price: row.get(7),
+    }
+}
+
+fn order(row: &Row) -> Order {
+    Order {
+        id: row.get(0),
+        order_date: row.get(1),
+        required_date: row.get(2),
+        shipped_date: row.get(3),
+        ship_via: row.get(4),
+        freight: row.get(5),
+        ship_name: row.get(6),
+        ship_city: row.get(7),
+        ship_region: row.get(8),
+        ship_postal_code: row.get(9),
+        ship_country: row.get(10),
+        customer_id: row.get(11),
+        employee_id: row.get(12),
+    }
+}
+
+fn order_detail(row: &Row) -> OrderDetail {
+    OrderDetail {
+        unit_price: row.get(0),
+        quantity: row.get(1),
+        discount: row.get(2),
+        order_id: row.get(3),
+        product_id: row.get(4),
+        id: row.get(5),
+        product_product_id: row.get(6),
+        product_name: row.get(7),
+        product_qt_per_unit: row.get(8),
+        product_unit_price: row.get(9),
+        product_units_in_stock: row.get(10),
+        product_units_on_order: row.get(11),
+        product_reorder_level: row.get(12),
+        product_discontinued: row.get(13),
+        product_supplier_id: row.get(14),
+    }
+}
+
+pub struct RawBackend {
+    pool: Pool,
+}
+
+impl RawBackend {
+    // Sized like the Diesel pool; connections open on first use
+    pub fn from_env(pool_config: &PoolConfig) -> BenchResult<Self> {
+        let config: tokio_postgres::Config = std::env::var("DATABASE_URL")?.parse()?;
+        let manager = Manager::from_config(
+            config,
+            NoTls,
+            ManagerConfig {
+                recycling_method: RecyclingMethod::Fast,
+            },
+        );
+        let pool = Pool::builder(manager)
+            .max_size(pool_config.max_size as usize)
+            .build()?;
+        Ok(RawBackend { pool })
+    }
+
+    async fn client(&self) -> BenchResult<Object> {
+        Ok(self.pool.get().await?)
+    }
+}
+
+#[async_trait]
+impl QueryBackend for RawBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P1).await?;
+        let rows = client.query(&statement, &[&limit, &offset]).await?;
+        Ok(rows.iter().map(customer).collect())
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P2).await?;
+        let row = client.query_opt(&statement, &[&id]).await?;
+        Ok(row.as_ref().map(customer))
+    }
+
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        let sql = match dictionary {
+            SearchDictionary::English => p3!("english"),
+            SearchDictionary::Simple => p3!("simple"),
+        };
+        let client = self.client().await?;
+        let statement = client.prepare_cached(sql).await?;
+        let rows = client.query(&statement, &[&term]).await?;
+        Ok(rows.iter().map(customer).collect())
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P4).await?;
+        let rows = client.query(&statement, &[&limit, &offset]).await?;
+        Ok(rows.iter().map(employee).collect())
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P5).await?;
+        let row = client.query_opt(&statement, &[&id]).await?;
+        Ok(row.as_ref().map(employee_with_recipient))
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P6).await?;
+        let rows = client.query(&statement, &[&limit, &offset]).await?;
+        Ok(rows.iter().map(supplier).collect())
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P7).await?;
+        let row = client.query_opt(&statement, &[&id]).await?;
+        Ok(row.as_ref().map(supplier))
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P8).await?;
+        let rows = client.query(&statement, &[&limit, &offset]).await?;
+        Ok(rows.iter().map(product).collect())
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P9).await?;
+        let row = client.query_opt(&statement, &[&id]).await?;
+        Ok(row.as_ref().map(product_with_supplier))
+    }
+
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        let sql = match dictionary {
+            SearchDictionary::English => p10!("english"),
+            SearchDictionary::Simple => p10!("simple"),
+        };
+        let client = self.client().await?;
+        let statement = client.prepare_cached(sql).await?;
+        let rows = client.query(&statement, &[&term]).await?;
+        Ok(rows.iter().map(product).collect())
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P11).await?;
+        let rows = client.query(&statement, &[&limit, &offset]).await?;
+        Ok(rows.iter().map(p11_row).collect())
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P12).await?;
+        let row = client.query_opt(&statement, &[&id]).await?;
+        Ok(row.as_ref().map(p11_row))
+    }
+
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(P13_ORDER).await?;
+        let Some(row) = client.query_opt(&statement, &[&id]).await? else {
+            return Ok(None);
+        };
+
+        let statement = client.prepare_cached(P13_DETAILS).await?;
+        let details = client.query(&statement, &[&id]).await?;
+
+        Ok(Some(OrderWithDetailsAndProducts::new(
+            order(&row),
+            details.iter().map(order_detail).collect(),
+        )))
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        let client = self.client().await?;
+        let statement = client.prepare_cached(ABOVE_AVERAGE_PRICE).await?;
+        let rows = client.query(&statement, &[&limit, &offset]).await?;
+        Ok(rows.iter().map(product).collect())
+    }
+}
@@ -0,0 +1,275 @@
+// Reporting queries: aggregates over whole tables rather than a page or a row, the heavier
+// tier of the workload next to the benchmark queries.
+
+use diesel::{
+    dsl::{avg, count, count_star, sql, sum},
+    prelude::*,
+    sql_types::{BigInt, Double, Text},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+
+use crate::queries::BenchQuery;
+use crate::schema::{customers, employees, order_details, orders, products};
+
+// Orders shipped to a country: how many there are, how many have shipped and how many are
+// still waiting, and their average freight
+#[derive(Queryable, Debug, Serialize)]
+pub struct ShippingStatus {
+    pub ship_country: String,
+    pub orders: i64,
+    pub shipped: i64,
+    pub unshipped: i64,
+    #[serde(serialize_with = "crate::output::optional_money")]
+    pub avg_freight: Option<f64>,
+}
+
+// One pass over orders grouped by country; an order has shipped once it has a shipped_date,
+// which count() of the column counts. Countries with the most orders first.
+pub(crate) fn shipping_status_query() -> impl BenchQuery<'static, ShippingStatus> {
+    orders::table
+        .group_by(orders::ship_country)
+        .select((
+            orders::ship_country,
+            count_star(),
+            count(orders::shipped_date),
+            count_star() - count(orders::shipped_date),
+            avg(orders::freight),
+        ))
+        .order_by((count_star().desc(), orders::ship_country.asc()))
+}
+
+pub async fn shipping_status(conn: &mut AsyncPgConnection) -> QueryResult<Vec<ShippingStatus>> {
+    shipping_status_query().load(conn).await
+}
+
+// Percentiles of the order totals of a country, a total being what p11 reports per order
+// (the sum of quantity × unit price of its details)
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct OrderValuePercentiles {
+    #[diesel(sql_type = Text)]
+    pub ship_country: String,
+    #[diesel(sql_type = BigInt)]
+    pub orders: i64,
+    #[diesel(sql_type = Double)]
+    #[serde(serialize_with = "crate::output::money")]
+    pub p50: f64,
+    #[diesel(sql_type = Double)]
+    #[serde(serialize_with = "crate::output::money")]
+    pub p95: f64,
+    #[diesel(sql_type = Double)]
+    #[serde(serialize_with = "crate::output::money")]
+    pub p99: f64,
+}
+
+// The totals are computed once in a CTE and ranked per country by the ordered-set
+// percentile_cont, interpolating between neighbouring totals. Orders without details have no
+// total and aren't counted.
+pub(crate) fn order_value_percentiles_query() -> impl BenchQuery<'static, OrderValuePercentiles> {
+    diesel::sql_query(
+        "WITH order_totals AS ( \
+             SELECT orders.ship_country, \
+                    sum(order_details.quantity * order_details.unit_price) AS total \
+             FROM orders JOIN order_details ON order_details.order_id = orders.id \
+             GROUP BY orders.id \
+         ) \
+         SELECT ship_country, count(*) AS orders, \
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY total) AS p50, \
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY total) AS p95, \
+                percentile_cont(0.99) WITHIN GROUP (ORDER BY total) AS p99 \
+         FROM order_totals \
+         GROUP BY ship_country \
+         ORDER BY ship_country",
+    )
+}
+
+pub async fn order_value_percentiles(
+    conn: &mut AsyncPgConnection,
+) -> QueryResult<Vec<OrderValuePercentiles>> {
+    order_value_percentiles_query().load(conn).await
+}
+
+// Customers grouped by whether and how soon they ordered again after their first order
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct RetentionBucket {
+    // `one-time`, `within 30 days`, `within 90 days` or `after 90 days`
+    #[diesel(sql_type = Text)]
+    pub bucket: String,
+    #[diesel(sql_type = BigInt)]
+    pub customers: i64,
+    // Their orders after the first
+    #[diesel(sql_type = BigInt)]
+    pub repeat_orders: i64,
+}
+
+// Three CTEs, each building on the last: every customer's first order date, their orders on
+// later days (same-day orders count as the first), and the bucket the gap between the two
+// puts them in. Buckets in retention order, empty ones left out.
+pub(crate) fn repeat_customers_query() -> impl BenchQuery<'static, RetentionBucket> {
+    diesel::sql_query(
+        "WITH first_orders AS ( \
+             SELECT customer_id, min(order_date) AS first_order_date \
+             FROM orders \
+             GROUP BY customer_id \
+         ), \
+         subsequent_orders AS ( \
+             SELECT orders.customer_id, count(*) AS orders, \
+                    min(orders.order_date) AS second_order_date \
+             FROM orders JOIN first_orders USING (customer_id) \
+             WHERE orders.order_date > first_orders.first_order_date \
+             GROUP BY orders.customer_id \
+         ), \
+         retention AS ( \
+             SELECT CASE \
+                        WHEN subsequent_orders.customer_id IS NULL THEN 0 \
+                        WHEN second_order_date - first_order_date <= 30 THEN 1 \
+                        WHEN second_order_date - first_order_date <= 90 THEN 2 \
+                        ELSE 3 \
+                    END AS rank, \
+                    coalesce(subsequent_orders.orders, 0) AS repeat_orders \
+             FROM first_orders LEFT JOIN subsequent_orders USING (customer_id) \
+         ) \
+         SELECT (ARRAY['one-time', 'within 30 days', 'within 90 days', 'after 90 days'])[rank + 1] \
+                    AS bucket, \
+                count(*) AS customers, \
+                sum(repeat_orders)::bigint AS repeat_orders \
+         FROM retention \
+         GROUP BY rank \
+         ORDER BY rank",
+    )
+}
+
+pub async fn repeat_customers(conn: &mut AsyncPgConnection) -> QueryResult<Vec<RetentionBucket>> {
+    repeat_customers_query().load(conn).await
+}
+
+// A product's sales over all orders: units sold and revenue at the order lines' prices
+#[derive(Queryable, Debug, Serialize)]
+pub struct TopProduct {
+    pub product_id: i32,
+    pub name: String,
+    pub units_sold: i64,
+    #[serde(serialize_with = "crate::output::money")]
+    pub revenue: f64,
+}
+
+// Every order line joined to its product and summed per product, highest revenue first (ties
+// by id), so the whole of order_details is aggregated before the limit applies
+pub(crate) fn top_products_query(count_: i64) -> impl BenchQuery<'static, TopProduct> {
+    // Built for the select and again for the ORDER BY
+    let revenue = || {
+        let qty_f64 = order_details::quantity
+            .nullable()
+            .cast::<diesel::sql_types::Nullable<Double>>();
+
+        let unit_price = order_details::unit_price.nullable();
+
+        sum(qty_f64 * unit_price).assume_not_null()
+    };
+
+    order_details::table
+        .inner_join(products::table)
+        .group_by(products::id)
+        .select((
+            products::id,
+            products::name,
+            sum(order_details::quantity).assume_not_null(),
+            revenue(),
+        ))
+        .order_by((revenue().desc(), products::id.asc()))
+        .limit(count_)
+}
+
+pub async fn top_products(
+    conn: &mut AsyncPgConnection,
+    count_: i64,
+) -> QueryResult<Vec<TopProduct>> {
+    top_products_query(count_).load(conn).await
+}
+
+// Revenue and orders per customer country, by where the customer is rather than where the
+// order shipped
+#[derive(Queryable, Debug, Serialize)]
+pub struct CountrySales {
+    pub country: String,
+    pub orders: i64,
+    #[serde(serialize_with = "crate::output::money")]
+    pub revenue: f64,
+}
+
+// Every order line joined to its order and the order's customer, then grouped by the
+// customer's country; an order spans several lines, hence the DISTINCT count. Highest
+// revenue first.
+pub(crate) fn sales_by_country_query() -> impl BenchQuery<'static, CountrySales> {
+    // Built for the select and again for the ORDER BY
+    let revenue = || {
+        let qty_f64 = order_details::quantity
+            .nullable()
+            .cast::<diesel::sql_types::Nullable<Double>>();
+
+        let unit_price = order_details::unit_price.nullable();
+
+        sum(qty_f64 * unit_price).assume_not_null()
+    };
+
+    orders::table
+        .inner_join(customers::table)
+        .inner_join(order_details::table)
+        .group_by(customers::country)
+        .select((
+            customers::country,
+            sql::<BigInt>("count(DISTINCT orders.id)"),
+            revenue(),
+        ))
+        .order_by((revenue().desc(), customers::country.asc()))
+}
+
+pub async fn sales_by_country(conn: &mut AsyncPgConnection) -> QueryResult<Vec<CountrySales>> {
+    sales_by_country_query().load(conn).await
+}
+
+// An employee's sales: the orders they took and the revenue of those orders' lines
+#[derive(Queryable, Debug, Serialize)]
+pub struct EmployeeSales {
+    pub employee_id: i32,
+    pub first_name: Option<String>,
+    pub last_name: String,
+    pub orders: i64,
+    #[serde(serialize_with = "crate::output::money")]
+    pub revenue: f64,
+}
+
+// Employees joined to their orders and those to their lines, summed per employee, highest
+// revenue first (ties by id). Employees without sales aren't ranked.
+pub(crate) fn employee_leaderboard_query(count_: i64) -> impl BenchQuery<'static, EmployeeSales> {
+    // Built for the select and again for the ORDER BY
+    let revenue = || {
+        let qty_f64 = order_details::quantity
+            .nullable()
+            .cast::<diesel::sql_types::Nullable<Double>>();
+
+        let unit_price = order_details::unit_price.nullable();
+
+        sum(qty_f64 * unit_price).assume_not_null()
+    };
+
+    employees::table
+        .inner_join(orders::table.inner_join(order_details::table))
+        .group_by(employees::id)
+        .select((
+            employees::id,
+            employees::first_name,
+            employees::last_name,
+            sql::<BigInt>("count(DISTINCT orders.id)"),
+            revenue(),
+        ))
+        .order_by((revenue().desc(), employees::id.asc()))
+        .limit(count_)
+}
+
+pub async fn employee_leaderboard(
+    conn: &mut AsyncPgConnection,
+    count_: i64,
+) -> QueryResult<Vec<EmployeeSales>> {
+    employee_leaderboard_query(count_).load(conn).await
+}
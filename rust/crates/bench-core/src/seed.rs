@@ -0,0 +1,710 @@
+// The `seed` binary (also `rust seed`): fills the database with the same shape of data as
+// `pnpm start:seed` (src/seed.ts), in its sizes or any row counts, without Node. Fake values
+// come from small word lists and a seeded RNG instead of faker, so one --seed always produces
+// the same database and runs on different machines query identical rows. Each table is
+// loaded with one binary COPY, which keeps the larger sizes to seconds. The tables have to
+// exist already (drizzle migrations) and be empty, unless --truncate is given. What was
+// loaded is recorded in `dataset_meta` (see dataset_meta.rs).
+
+use std::{
+    pin::Pin,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use chrono::{Days, NaiveDate};
+use clap::{Args, ValueEnum};
+use fastrand::Rng;
+use serde::Serialize;
+use tokio_postgres::{
+    Client, NoTls,
+    binary_copy::BinaryCopyInWriter,
+    types::{ToSql, Type},
+};
+
+use crate::{
+    BenchResult,
+    dataset_meta::{self, GENERATOR_VERSION},
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SeedSize {
+    Nano,
+    Micro,
+}
+
+struct Counts {
+    employees: i32,
+    customers: i32,
+    orders: i32,
+    products: i32,
+    suppliers: i32,
+}
+
+impl SeedSize {
+    fn counts(self) -> Counts {
+        match self {
+            SeedSize::Nano => Counts {
+                employees: 50,
+                customers: 1000,
+                orders: 5000,
+                products: 500,
+                suppliers: 100,
+            },
+            SeedSize::Micro => Counts {
+                employees: 200,
+                customers: 10000,
+                orders: 50000,
+                products: 5000,
+                suppliers: 1000,
+            },
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct SeedArgs {
+    /// Row counts of seed.ts' sizes of the same name, before the overrides below
+    #[arg(long, value_enum, default_value_t = SeedSize::Micro)]
+    pub size: SeedSize,
+    #[arg(long)]
+    pub customers: Option<i32>,
+    #[arg(long)]
+    pub employees: Option<i32>,
+    /// Order details are generated per order, 1 to 25 each
+    #[arg(long)]
+    pub orders: Option<i32>,
+    #[arg(long)]
+    pub products: Option<i32>,
+    #[arg(long)]
+    pub suppliers: Option<i32>,
+    /// Seed of the generated values
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Empty the tables (and restart their ids) first instead of refusing to seed
+    #[arg(long)]
+    pub truncate: bool,
+}
+
+impl SeedArgs {
+    // The row counts of `size`, seeded from 0
+    pub fn sized(size: SeedSize) -> Self {
+        SeedArgs {
+            size,
+            customers: None,
+            employees: None,
+            orders: None,
+            products: None,
+            suppliers: None,
+            seed: 0,
+            truncate: false,
+        }
+    }
+
+    // For dataset_meta: the preset, then the counts that override it
+    fn scale(&self) -> String {
+        let mut scale = self
+            .size
+            .to_possible_value()
+            .map_or(String::new(), |value| value.get_name().to_string());
+        let overrides = [
+            ("customers", self.customers),
+            ("employees", self.employees),
+            ("orders", self.orders),
+            ("products", self.products),
+            ("suppliers", self.suppliers),
+        ];
+        for (table, count) in overrides {
+            if let Some(count) = count {
+                scale.push_str(&format!(", {}={}", table, count));
+            }
+        }
+        scale
+    }
+
+    fn counts(&self) -> BenchResult<Counts> {
+        let preset = self.size.counts();
+        let counts = Counts {
+            employees: self.employees.unwrap_or(preset.employees),
+            customers: self.customers.unwrap_or(preset.customers),
+            orders: self.orders.unwrap_or(preset.orders),
+            products: self.products.unwrap_or(preset.products),
+            suppliers: self.suppliers.unwrap_or(preset.suppliers),
+        };
+        // Orders and products reference rows of these
+        if counts.employees < 1
+            || counts.customers < 1
+            || counts.products < 1
+            || counts.suppliers < 1
+            || counts.orders < 0
+        {
+            return Err("row counts must be positive".into());
+        }
+        Ok(counts)
+    }
+}
+
+const FIRST_NAMES: &[&str] = &[
+    "Anna", "Ben", "Carla", "David", "Elena", "Felix", "Grace", "Hugo", "Iris", "Jonas", "Karen",
+    "Liam", "Maria", "Noah", "Olivia", "Peter", "Rosa", "Samuel", "Tara", "Victor",
+];
+const LAST_NAMES: &[&str] = &[
+    "Adams", "Becker", "Carter", "Dietrich", "Evans", "Fischer", "Garcia", "Hahn", "Ivanov",
+    "Jensen", "Kovacs", "Larsen", "Moreau", "Nguyen", "Olsen", "Petrov", "Quinn", "Rossi",
+    "Schultz", "Vogel",
+];
+const COMPANY_SUFFIXES: &[&str] = &["Inc", "LLC", "Group", "and Sons", "Traders", "Foods"];
+const JOB_LEVELS: &[&str] = &["Senior", "Junior", "Lead", "Principal", "Regional", "Chief"];
+const JOB_AREAS: &[&str] = &[
+    "Sales",
+    "Marketing",
+    "Accounts",
+    "Logistics",
+    "Operations",
+    "Purchasing",
+];
+const JOB_TYPES: &[&str] = &[
+    "Manager",
+    "Agent",
+    "Coordinator",
+    "Representative",
+    "Analyst",
+];
+const STREETS: &[&str] = &[
+    "Maple", "Oak", "Harbor", "Mill", "Church", "Station", "Park", "Lake", "Forest", "Bridge",
+];
+const STREET_SUFFIXES: &[&str] = &["Street", "Avenue", "Road", "Lane", "Way"];
+const CITIES: &[&str] = &[
+    "Berlin", "Lyon", "Porto", "Seattle", "Osaka", "Toronto", "Graz", "Bergen", "Cork", "Valencia",
+    "Austin", "Gdansk",
+];
+const REGIONS: &[&str] = &[
+    "Bavaria", "Ontario", "Texas", "Kansai", "Munster", "Styria", "Oregon", "Valais",
+];
+const COUNTRIES: &[&str] = &[
+    "Germany", "France", "Portugal", "USA", "Japan", "Canada", "Austria", "Norway", "Ireland",
+    "Spain", "Poland",
+];
+const TITLES_OF_COURTESY: &[&str] = &["Ms.", "Mrs.", "Dr."];
+const UNITS_ON_ORDERS: &[i32] = &[0, 10, 20, 30, 50, 60, 70, 80, 100];
+const REORDER_LEVELS: &[i32] = &[0, 5, 10, 15, 20, 25, 30];
+const QUANTITY_PER_UNIT: &[&str] = &[
+    "100 - 100 g pieces",
+    "100 - 250 g bags",
+    "10 - 200 g glasses",
+    "10 - 4 oz boxes",
+    "10 - 500 g pkgs.",
+    "10 boxes x 12 pieces",
+    "10 boxes x 20 bags",
+    "10 kg pkg.",
+    "12 - 355 ml cans",
+    "24 - 12 oz bottles",
+    "48 pieces",
+    "500 g",
+    "750 cc per bottle",
+];
+const DISCOUNTS: &[f64] = &[0.05, 0.15, 0.2, 0.25];
+
+fn pick<T: Copy>(rng: &mut Rng, values: &[T]) -> T {
+    values[rng.usize(..values.len())]
+}
+
+fn person(rng: &mut Rng) -> String {
+    format!("{} {}", pick(rng, FIRST_NAMES), pick(rng, LAST_NAMES))
+}
+
+fn company(rng: &mut Rng) -> String {
+    format!("{} {}", pick(rng, LAST_NAMES), pick(rng, COMPANY_SUFFIXES))
+}
+
+fn job_title(rng: &mut Rng) -> String {
+    format!(
+        "{} {} {}",
+        pick(rng, JOB_LEVELS),
+        pick(rng, JOB_AREAS),
+        pick(rng, JOB_TYPES)
+    )
+}
+
+fn street_address(rng: &mut Rng) -> String {
+    format!(
+        "{} {} {}",
+        rng.u32(1..10000),
+        pick(rng, STREETS),
+        pick(rng, STREET_SUFFIXES)
+    )
+}
+
+fn zip_code(rng: &mut Rng) -> String {
+    format!("{:05}", rng.u32(..100000))
+}
+
+fn phone(rng: &mut Rng) -> String {
+    format!(
+        "({:03}) {:03}-{:04}",
+        rng.u32(200..1000),
+        rng.u32(..1000),
+        rng.u32(..10000)
+    )
+}
+
+// A price with 0 to 2 decimals, like seed.ts' `${int}.${int}` prices
+fn price(rng: &mut Rng, whole: std::ops::RangeInclusive<i32>, cents: bool) -> f64 {
+    let whole = rng.i32(whole) as f64;
+    if cents {
+        whole + rng.i32(5..=99) as f64 / 100.0
+    } else {
+        whole
+    }
+}
+
+fn date(year: i32, days: u64) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_default() + Days::new(days)
+}
+
+// Products per order: mostly a few, occasionally up to 25
+fn product_count(rng: &mut Rng) -> i32 {
+    match rng.f64() {
+        r if r < 0.6 => rng.i32(1..=4),
+        r if r < 0.8 => rng.i32(5..=10),
+        r if r < 0.95 => rng.i32(11..=17),
+        _ => rng.i32(18..=25),
+    }
+}
+
+// The tables a seed fills, in the order it fills them
+pub const SEEDED_TABLES: &[&str] = &[
+    "customers",
+    "employees",
+    "orders",
+    "suppliers",
+    "products",
+    "order_details",
+];
+
+// How far a running seed has got, updated as it writes rows
+#[derive(Default)]
+pub struct SeedProgress {
+    table: Mutex<Option<&'static str>>,
+    rows: AtomicU64,
+    tables_done: AtomicUsize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedProgressSnapshot {
+    // Being filled, None before the first and after the last
+    pub table: Option<&'static str>,
+    // Written to `table` so far
+    pub rows: u64,
+    pub tables_done: usize,
+    pub tables: usize,
+}
+
+impl SeedProgress {
+    pub fn snapshot(&self) -> SeedProgressSnapshot {
+        SeedProgressSnapshot {
+            table: *self.table.lock().unwrap(),
+            rows: self.rows.load(Ordering::Relaxed),
+            tables_done: self.tables_done.load(Ordering::Relaxed),
+            tables: SEEDED_TABLES.len(),
+        }
+    }
+}
+
+struct CopyWriter<'a> {
+    writer: Pin<Box<BinaryCopyInWriter>>,
+    progress: &'a SeedProgress,
+}
+
+// Starts a binary COPY into `table` with `columns`; the caller writes rows of `types` and
+// finishes it
+async fn copy_in<'a>(
+    client: &Client,
+    progress: &'a SeedProgress,
+    table: &'static str,
+    columns: &str,
+    types: &[Type],
+) -> BenchResult<CopyWriter<'a>> {
+    println!("seeding {}...", table.replace('_', " "));
+    let sink = client
+        .copy_in(&format!(
+            "COPY {} ({}) FROM STDIN (FORMAT binary)",
+            table, columns
+        ))
+        .await?;
+    *progress.table.lock().unwrap() = Some(table);
+    progress.rows.store(0, Ordering::Relaxed);
+    Ok(CopyWriter {
+        writer: Box::pin(BinaryCopyInWriter::new(sink, types)),
+        progress,
+    })
+}
+
+async fn write(writer: &mut CopyWriter<'_>, row: &[&(dyn ToSql + Sync)]) -> BenchResult<()> {
+    writer.writer.as_mut().write(row).await?;
+    writer.progress.rows.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+async fn finish(mut writer: CopyWriter<'_>) -> BenchResult<()> {
+    writer.writer.as_mut().finish().await?;
+    *writer.progress.table.lock().unwrap() = None;
+    writer.progress.tables_done.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+async fn connect(database_url: &str) -> BenchResult<Client> {
+    let (client, connection) = tokio_postgres::connect(database_url, NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            eprintln!("Seed connection failed: {:?}", err);
+        }
+    });
+    Ok(client)
+}
+
+// Rows in all the seeded tables together
+async fn row_count(client: &Client) -> BenchResult<i64> {
+    Ok(client
+        .query_one(
+            "SELECT (SELECT count(*) FROM customers) + (SELECT count(*) FROM employees)
+                + (SELECT count(*) FROM orders) + (SELECT count(*) FROM order_details)
+                + (SELECT count(*) FROM products) + (SELECT count(*) FROM suppliers)",
+            &[],
+        )
+        .await?
+        .get(0))
+}
+
+pub async fn seed(database_url: &str, args: &SeedArgs) -> BenchResult<()> {
+    let counts = args.counts()?;
+    let client = connect(database_url).await?;
+
+    if args.truncate {
+        client
+            .batch_execute(
+                "TRUNCATE customers, employees, orders, order_details, products, suppliers RESTART IDENTITY",
+            )
+            .await?;
+    } else if row_count(&client).await? > 0 {
+        return Err("the database already has data, pass --truncate to replace it".into());
+    }
+
+    load(&client, args, &counts, &SeedProgress::default()).await
+}
+
+// Seeds the database if its tables are all empty, reporting to `progress`, and otherwise
+// leaves it as it is. Whether it seeded.
+pub async fn seed_if_empty(
+    database_url: &str,
+    args: &SeedArgs,
+    progress: &SeedProgress,
+) -> BenchResult<bool> {
+    let counts = args.counts()?;
+    let client = connect(database_url).await?;
+
+    if row_count(&client).await? > 0 {
+        return Ok(false);
+    }
+    load(&client, args, &counts, progress).await?;
+    Ok(true)
+}
+
+async fn load(
+    client: &Client,
+    args: &SeedArgs,
+    counts: &Counts,
+    progress: &SeedProgress,
+) -> BenchResult<()> {
+    let mut rng = Rng::with_seed(args.seed);
+
+    // Ids are written explicitly so references between the tables hold whatever state the
+    // sequences are in; the sequences are moved past them at the end
+    let mut writer = copy_in(
+        client,
+        progress,
+        "customers",
+        "id, company_name, contact_name, contact_title, address, city, postal_code, region, country, phone, fax",
+        &[
+            Type::INT4,
+            Type::TEXT,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+        ],
+    )
+    .await?;
+    for id in 1..=counts.customers {
+        write(
+            &mut writer,
+            &[
+                &id,
+                &company(&mut rng),
+                &person(&mut rng),
+                &job_title(&mut rng),
+                &street_address(&mut rng),
+                &pick(&mut rng, CITIES),
+                &rng.bool().then(|| zip_code(&mut rng)),
+                &Some(pick(&mut rng, REGIONS)),
+                &pick(&mut rng, COUNTRIES),
+                &phone(&mut rng),
+                &Some(phone(&mut rng)),
+            ],
+        )
+        .await?;
+    }
+    finish(writer).await?;
+
+    let mut writer = copy_in(
+        client,
+        progress,
+        "employees",
+        "id, last_name, first_name, title, title_of_courtesy, birth_date, hire_date, address, city, postal_code, country, home_phone, extension, notes, recipient_id",
+        &[
+            Type::INT4,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::DATE,
+            Type::DATE,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::INT4,
+            Type::TEXT,
+            Type::INT4,
+        ],
+    )
+    .await?;
+    for id in 1..=counts.employees {
+        write(
+            &mut writer,
+            &[
+                &id,
+                &pick(&mut rng, LAST_NAMES),
+                &Some(pick(&mut rng, FIRST_NAMES)),
+                &job_title(&mut rng),
+                &pick(&mut rng, TITLES_OF_COURTESY),
+                &date(1950, rng.u64(..18000)),
+                &date(2020, rng.u64(..1500)),
+                &street_address(&mut rng),
+                &pick(&mut rng, CITIES),
+                &zip_code(&mut rng),
+                &pick(&mut rng, COUNTRIES),
+                &phone(&mut rng),
+                &rng.i32(428..=5467),
+                &format!(
+                    "{} and {} enthusiast",
+                    job_title(&mut rng),
+                    pick(&mut rng, JOB_AREAS).to_lowercase()
+                ),
+                // Reports to an earlier employee, so the first one to nobody
+                &(id > 1).then(|| rng.i32(1..id)),
+            ],
+        )
+        .await?;
+    }
+    finish(writer).await?;
+
+    let mut writer = copy_in(
+        client,
+        progress,
+        "orders",
+        "id, order_date, required_date, shipped_date, ship_via, freight, ship_name, ship_city, ship_region, ship_postal_code, ship_country, customer_id, employee_id",
+        &[
+            Type::INT4,
+            Type::DATE,
+            Type::DATE,
+            Type::DATE,
+            Type::INT4,
+            Type::FLOAT8,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::INT4,
+            Type::INT4,
+        ],
+    )
+    .await?;
+    for id in 1..=counts.orders {
+        // One order a minute from 2016 on
+        let order_date = date(2016, id as u64 / (24 * 60));
+        write(
+            &mut writer,
+            &[
+                &id,
+                &order_date,
+                &(order_date + Days::new(30)),
+                &Some(order_date + Days::new(10)),
+                &rng.i32(1..=3),
+                &price(&mut rng, 0..=1000, true),
+                &street_address(&mut rng),
+                &pick(&mut rng, CITIES),
+                &Some(pick(&mut rng, REGIONS)),
+                &Some(zip_code(&mut rng)),
+                &pick(&mut rng, COUNTRIES),
+                &rng.i32(1..=counts.customers),
+                &rng.i32(1..=counts.employees),
+            ],
+        )
+        .await?;
+    }
+    finish(writer).await?;
+
+    let mut writer = copy_in(
+        client,
+        progress,
+        "suppliers",
+        "id, company_name, contact_name, contact_title, address, city, region, postal_code, country, phone",
+        &[
+            Type::INT4,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+            Type::VARCHAR,
+        ],
+    )
+    .await?;
+    for id in 1..=counts.suppliers {
+        write(
+            &mut writer,
+            &[
+                &id,
+                &company(&mut rng),
+                &person(&mut rng),
+                &job_title(&mut rng),
+                &street_address(&mut rng),
+                &pick(&mut rng, CITIES),
+                &Some(pick(&mut rng, REGIONS)),
+                &zip_code(&mut rng),
+                &pick(&mut rng, COUNTRIES),
+                &phone(&mut rng),
+            ],
+        )
+        .await?;
+    }
+    finish(writer).await?;
+
+    let prices: Vec<f64> = (0..counts.products)
+        .map(|_| {
+            let cents = rng.bool();
+            price(&mut rng, 3..=300, cents)
+        })
+        .collect();
+    let mut writer = copy_in(
+        client,
+        progress,
+        "products",
+        "id, name, qt_per_unit, unit_price, units_in_stock, units_on_order, reorder_level, discontinued, supplier_id",
+        &[
+            Type::INT4,
+            Type::TEXT,
+            Type::VARCHAR,
+            Type::FLOAT8,
+            Type::INT4,
+            Type::INT4,
+            Type::INT4,
+            Type::INT4,
+            Type::INT4,
+        ],
+    )
+    .await?;
+    for id in 1..=counts.products {
+        write(
+            &mut writer,
+            &[
+                &id,
+                &company(&mut rng),
+                &pick(&mut rng, QUANTITY_PER_UNIT),
+                &prices[id as usize - 1],
+                &rng.i32(0..=125),
+                &pick(&mut rng, UNITS_ON_ORDERS),
+                &pick(&mut rng, REORDER_LEVELS),
+                &rng.i32(0..=1),
+                &rng.i32(1..=counts.suppliers),
+            ],
+        )
+        .await?;
+    }
+    finish(writer).await?;
+
+    let mut writer = copy_in(
+        client,
+        progress,
+        "order_details",
+        "unit_price, quantity, discount, order_id, product_id",
+        &[
+            Type::FLOAT8,
+            Type::INT4,
+            Type::FLOAT8,
+            Type::INT4,
+            Type::INT4,
+        ],
+    )
+    .await?;
+    for order_id in 1..=counts.orders {
+        for _ in 0..product_count(&mut rng) {
+            let product_id = rng.i32(1..=counts.products);
+            let quantity = rng.i32(1..=130);
+            let discount = if rng.bool() {
+                0.0
+            } else {
+                pick(&mut rng, DISCOUNTS)
+            };
+            write(
+                &mut writer,
+                &[
+                    &prices[product_id as usize - 1],
+                    &quantity,
+                    &discount,
+                    &order_id,
+                    &product_id,
+                ],
+            )
+            .await?;
+        }
+    }
+    finish(writer).await?;
+
+    for table in ["customers", "employees", "orders", "suppliers", "products"] {
+        client
+            .batch_execute(&format!(
+                "SELECT setval(pg_get_serial_sequence('{0}', 'id'), max(id)) FROM {0}",
+                table
+            ))
+            .await?;
+    }
+
+    println!("recording dataset_meta...");
+    let checksum: String = client.query_one(dataset_meta::CHECKSUM, &[]).await?.get(0);
+    client.batch_execute(dataset_meta::CREATE_TABLE).await?;
+    client.batch_execute("DELETE FROM dataset_meta").await?;
+    client
+        .execute(
+            "INSERT INTO dataset_meta (generator_version, seed, scale, checksum) VALUES ($1, $2, $3, $4)",
+            &[&GENERATOR_VERSION, &(args.seed as i64), &args.scale(), &checksum],
+        )
+        .await?;
+
+    println!("done!");
+    Ok(())
+}
@@ -0,0 +1,638 @@
+// `QueryBackend` on an embedded SQLite file (`QUERY_BACKEND=sqlite`, `backend-sqlite`
+// feature): p1–p13 in the Diesel DSL of `queries` on blocking SqliteConnections from an r2d2
+// pool, each call moved to the blocking thread pool as in `sync_backend`. No network is
+// involved, so next to `diesel-sync` it shows what the Postgres round trips cost, and what
+// remains is Diesel's and the server's own overhead.
+//
+//   SQLITE_DATABASE  the database file (default northwind.sqlite3)
+//
+// A file without the tables gets them at startup, filled with a copy of the tables at
+// DATABASE_URL, so both databases serve the same rows and DIFF_BACKEND=sqlite can compare
+// them; delete the file to copy again. The searches run on FTS5 indexes of
+// customers.company_name and products.name, one per dictionary: porter-stemmed for `english`
+// (which unlike Postgres keeps stop words), tokenized only for `simple`. All words of a term
+// have to match, as with plainto_tsquery. The builders in `queries` are typed for Postgres,
+// so the queries are written out again here.
+
+use async_trait::async_trait;
+use diesel::{
+    Connection as _, OptionalExtension, QueryableByName, RunQueryDsl, SqliteConnection,
+    connection::SimpleConnection,
+    dsl::{avg, count, sql, sum},
+    expression::SqlLiteral,
+    prelude::*,
+    query_builder::QueryFragment,
+    r2d2::{ConnectionManager, Pool, PooledConnection},
+    sql_types::{BigInt, Bool, Double, Nullable, Text},
+    sqlite::Sqlite,
+};
+
+use crate::{
+    BenchResult,
+    backend::QueryBackend,
+    config::PoolConfig,
+    models::{Customer, Employee, Order, Product, Supplier},
+    queries::*,
+    schema::{customers, employees, order_details, orders, products, suppliers},
+};
+
+type Connection = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+const DEFAULT_DATABASE: &str = "northwind.sqlite3";
+
+// The tables of `schema`, with the full-text indexes in place of the GIN ones
+const SCHEMA: &str = "
+CREATE TABLE customers (
+    id INTEGER PRIMARY KEY,
+    company_name TEXT NOT NULL,
+    contact_name TEXT NOT NULL,
+    contact_title TEXT NOT NULL,
+    address TEXT NOT NULL,
+    city TEXT NOT NULL,
+    postal_code TEXT,
+    region TEXT,
+    country TEXT NOT NULL,
+    phone TEXT NOT NULL,
+    fax TEXT
+);
+CREATE TABLE employees (
+    id INTEGER PRIMARY KEY,
+    last_name TEXT NOT NULL,
+    first_name TEXT,
+    title TEXT NOT NULL,
+    title_of_courtesy TEXT NOT NULL,
+    birth_date TEXT NOT NULL,
+    hire_date TEXT NOT NULL,
+    address TEXT NOT NULL,
+    city TEXT NOT NULL,
+    postal_code TEXT NOT NULL,
+    country TEXT NOT NULL,
+    home_phone TEXT NOT NULL,
+    extension INTEGER NOT NULL,
+    notes TEXT NOT NULL,
+    recipient_id INTEGER REFERENCES employees (id)
+);
+CREATE INDEX recepient_idx ON employees (recipient_id);
+CREATE TABLE suppliers (
+    id INTEGER PRIMARY KEY,
+    company_name TEXT NOT NULL,
+    contact_name TEXT NOT NULL,
+    contact_title TEXT NOT NULL,
+    address TEXT NOT NULL,
+    city TEXT NOT NULL,
+    region TEXT,
+    postal_code TEXT NOT NULL,
+    country TEXT NOT NULL,
+    phone TEXT NOT NULL
+);
+CREATE TABLE products (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    qt_per_unit TEXT NOT NULL,
+    unit_price REAL NOT NULL,
+    units_in_stock INTEGER NOT NULL,
+    units_on_order INTEGER NOT NULL,
+    reorder_level INTEGER NOT NULL,
+    discontinued INTEGER NOT NULL,
+    supplier_id INTEGER NOT NULL REFERENCES suppliers (id) ON DELETE CASCADE
+);
+CREATE INDEX supplier_idx ON products (supplier_id);
+CREATE TABLE orders (
+    id INTEGER PRIMARY KEY,
+    order_date TEXT NOT NULL,
+    required_date TEXT NOT NULL,
+    shipped_date TEXT,
+    ship_via INTEGER NOT NULL,
+    freight REAL NOT NULL,
+    ship_name TEXT NOT NULL,
+    ship_city TEXT NOT NULL,
+    ship_region TEXT,
+    ship_postal_code TEXT,
+    ship_country TEXT NOT NULL,
+    customer_id INTEGER NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+    employee_id INTEGER NOT NULL REFERENCES employees (id) ON DELETE CASCADE
+);
+CREATE TABLE order_details (
+    unit_price REAL NOT NULL,
+    quantity INTEGER NOT NULL,
+    discount REAL NOT NULL,
+    order_id INTEGER NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    product_id INTEGER NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+    id INTEGER PRIMARY KEY
+);
+CREATE INDEX order_id_idx ON order_details (order_id);
+CREATE INDEX product_id_idx ON order_details (product_id);
+CREATE VIRTUAL TABLE customers_english USING fts5(
+    company_name, content = 'customers', content_rowid = 'id', tokenize = 'porter unicode61'
+);
+CREATE VIRTUAL TABLE customers_simple USING fts5(
+    company_name, content = 'customers', content_rowid = 'id', tokenize = 'unicode61'
+);
+CREATE VIRTUAL TABLE products_english USING fts5(
+    name, content = 'products', content_rowid = 'id', tokenize = 'porter unicode61'
+);
+CREATE VIRTUAL TABLE products_simple USING fts5(
+    name, content = 'products', content_rowid = 'id', tokenize = 'unicode61'
+);
+";
+
+// Filled by `SCHEMA`'s external-content tables once the rows are in
+const BUILD_SEARCH_INDEXES: &str = "
+INSERT INTO customers_english (customers_english) VALUES ('rebuild');
+INSERT INTO customers_simple (customers_simple) VALUES ('rebuild');
+INSERT INTO products_english (products_english) VALUES ('rebuild');
+INSERT INTO products_simple (products_simple) VALUES ('rebuild');
+";
+
+// The copied tables and their columns, referenced tables first
+const TABLES: &[(&str, &[&str])] = &[
+    (
+        "customers",
+        &[
+            "id",
+            "company_name",
+            "contact_name",
+            "contact_title",
+            "address",
+            "city",
+            "postal_code",
+            "region",
+            "country",
+            "phone",
+            "fax",
+        ],
+    ),
+    (
+        "employees",
+        &[
+            "id",
+            "last_name",
+            "first_name",
+            "title",
+            "title_of_courtesy",
+            "birth_date",
+            "hire_date",
+            "address",
+            "city",
+            "postal_code",
+            "country",
+            "home_phone",
+            "extension",
+            "notes",
+            "recipient_id",
+        ],
+    ),
+    (
+        "suppliers",
+        &[
+            "id",
+            "company_name",
+            "contact_name",
+            "contact_title",
+            "address",
+            "city",
+            "region",
+            "postal_code",
+            "country",
+            "phone",
+        ],
+    ),
+    (
+        "products",
+        &[
+            "id",
+            "name",
+            "qt_per_unit",
+            "unit_price",
+            "units_in_stock",
+            "units_on_order",
+            "reorder_level",
+            "discontinued",
+            "supplier_id",
+        ],
+    ),
+    (
+        "orders",
+        &[
+            "id",
+            "order_date",
+            "required_date",
+            "shipped_date",
+            "ship_via",
+            "freight",
+            "ship_name",
+            "ship_city",
+            "ship_region",
+            "ship_postal_code",
+            "ship_country",
+            "customer_id",
+            "employee_id",
+        ],
+    ),
+    (
+        "order_details",
+        &[
+            "unit_price",
+            "quantity",
+            "discount",
+            "order_id",
+            "product_id",
+            "id",
+        ],
+    ),
+];
+
+// A whole table as a JSON array of row objects
+#[derive(QueryableByName)]
+struct JsonRows {
+    #[diesel(sql_type = Text)]
+    rows: String,
+}
+
+// Creates the tables in `sqlite` and copies the Postgres rows into them, in one transaction.
+// Each table travels as one JSON array, which SQLite's json_each unpacks into rows.
+fn copy_from_postgres(sqlite: &mut SqliteConnection, database_url: &str) -> BenchResult<()> {
+    let mut pg = PgConnection::establish(database_url)?;
+
+    sqlite.immediate_transaction(|sqlite| {
+        sqlite.batch_execute(SCHEMA)?;
+        for (table, columns) in TABLES {
+            let json: JsonRows = diesel::sql_query(format!(
+                "SELECT coalesce(json_agg(t), '[]')::text AS rows FROM (SELECT {} FROM {} ORDER BY id) t",
+                columns.join(", "),
+                table
+            ))
+            .get_result(&mut pg)?;
+
+            let values: Vec<String> = columns
+                .iter()
+                .map(|column| format!("json_extract(value, '$.{}')", column))
+                .collect();
+            diesel::sql_query(format!(
+                "INSERT INTO {} ({}) SELECT {} FROM json_each(?)",
+                table,
+                columns.join(", "),
+                values.join(", ")
+            ))
+            .bind::<Text, _>(&json.rows)
+            .execute(sqlite)?;
+        }
+        sqlite.batch_execute(BUILD_SEARCH_INDEXES)?;
+        Ok(())
+    })
+}
+
+// `term` as an FTS5 query of every word in it, quoted, so that as with plainto_tsquery all
+// have to match and no input is a syntax error; without words it's `""`, matching nothing
+fn fts_query(term: &str) -> String {
+    let words: Vec<String> = term
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"", word))
+        .collect();
+    if words.is_empty() {
+        "\"\"".to_string()
+    } else {
+        words.join(" ")
+    }
+}
+
+// The full-text match of `term` in `table`'s index for `dictionary`
+fn matches(
+    table: &str,
+    dictionary: SearchDictionary,
+    term: &str,
+) -> SqlLiteral<Bool, impl QueryFragment<Sqlite>> {
+    let index = format!("{}_{}", table, dictionary.as_str());
+    sql::<Bool>(&format!(
+        "id IN (SELECT rowid FROM {0} WHERE {0} MATCH ",
+        index
+    ))
+    .bind::<Text, _>(fts_query(term))
+    .sql(")")
+}
+
+// quantity * unit_price of an order line, NULL without one; Diesel has no CAST to a double
+// for SQLite, which multiplies an INTEGER by a REAL as a REAL anyway
+fn line_total() -> SqlLiteral<Nullable<Double>> {
+    sql::<Nullable<Double>>("order_details.quantity * order_details.unit_price")
+}
+
+pub struct SqliteBackend {
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+impl SqliteBackend {
+    // Copies the Postgres tables first when the file has none; connections open on first use
+    pub fn from_env(pool_config: &PoolConfig) -> BenchResult<Self> {
+        let path =
+            std::env::var("SQLITE_DATABASE").unwrap_or_else(|_| DEFAULT_DATABASE.to_string());
+
+        let mut conn = SqliteConnection::establish(&path)?;
+        let tables: i64 = diesel::select(sql::<BigInt>(
+            "(SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'customers')",
+        ))
+        .get_result(&mut conn)?;
+        if tables == 0 {
+            println!("Copying the Postgres tables into {}", path);
+            copy_from_postgres(&mut conn, &std::env::var("DATABASE_URL")?)?;
+        }
+
+        let pool = Pool::builder()
+            .max_size(pool_config.max_size)
+            .min_idle(Some(0))
+            .connection_timeout(pool_config.connection_timeout())
+            .build_unchecked(ConnectionManager::new(path));
+        Ok(SqliteBackend { pool })
+    }
+
+    // Runs `query` on a pooled connection on the blocking thread pool
+    async fn run<T, F>(&self, query: F) -> BenchResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> diesel::QueryResult<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(query(&mut conn)?)
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl QueryBackend for SqliteBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        self.run(move |conn| {
+            customers::table
+                .order_by(customers::id.asc())
+                .limit(limit)
+                .offset(offset)
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        self.run(move |conn| {
+            customers::table
+                .filter(customers::id.eq(id))
+                .limit(1)
+                .get_result(conn)
+                .optional()
+        })
+        .await
+    }
+
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        let term = term.to_owned();
+        self.run(move |conn| {
+            customers::table
+                .filter(matches("customers", dictionary, &term))
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        self.run(move |conn| {
+            employees::table
+                .order_by(employees::id.asc())
+                .limit(limit)
+                .offset(offset)
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        self.run(move |conn| {
+            let recipient = diesel::alias!(employees as recipient);
+
+            employees::table
+                .left_join(
+                    recipient
+                        .on(employees::recipient_id.eq(recipient.field(employees::id).nullable())),
+                )
+                .filter(employees::id.eq(id))
+                .select((
+                    employees::id,
+                    employees::last_name,
+                    employees::first_name,
+                    employees::title,
+                    employees::title_of_courtesy,
+                    employees::birth_date,
+                    employees::hire_date,
+                    employees::address,
+                    employees::city,
+                    employees::postal_code,
+                    employees::country,
+                    employees::home_phone,
+                    employees::extension,
+                    employees::notes,
+                    employees::recipient_id,
+                    recipient.field(employees::id).nullable(),
+                    recipient.field(employees::last_name).nullable(),
+                    recipient.field(employees::first_name).nullable(),
+                    recipient.field(employees::title).nullable(),
+                    recipient.field(employees::title_of_courtesy).nullable(),
+                    recipient.field(employees::birth_date).nullable(),
+                    recipient.field(employees::hire_date).nullable(),
+                    recipient.field(employees::address).nullable(),
+                    recipient.field(employees::city).nullable(),
+                    recipient.field(employees::postal_code).nullable(),
+                    recipient.field(employees::country).nullable(),
+                    recipient.field(employees::home_phone).nullable(),
+                    recipient.field(employees::extension).nullable(),
+                    recipient.field(employees::notes).nullable(),
+                    recipient.field(employees::recipient_id).nullable(),
+                ))
+                .limit(1)
+                .get_result(conn)
+                .optional()
+        })
+        .await
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        self.run(move |conn| {
+            suppliers::table
+                .order_by(suppliers::id.asc())
+                .limit(limit)
+                .offset(offset)
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        self.run(move |conn| {
+            suppliers::table
+                .filter(suppliers::id.eq(id))
+                .limit(1)
+                .get_result(conn)
+                .optional()
+        })
+        .await
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        self.run(move |conn| {
+            products::table
+                .order_by(products::id.asc())
+                .limit(limit)
+                .offset(offset)
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        self.run(move |conn| {
+            products::table
+                .inner_join(suppliers::table)
+                .filter(products::id.eq(id))
+                .select((
+                    products::id,
+                    products::name,
+                    products::qt_per_unit,
+                    products::unit_price,
+                    products::units_in_stock,
+                    products::units_on_order,
+                    products::reorder_level,
+                    products::discontinued,
+                    products::supplier_id,
+                    suppliers::id,
+                    suppliers::company_name,
+                    suppliers::contact_name,
+                    suppliers::contact_title,
+                    suppliers::address,
+                    suppliers::city,
+                    suppliers::region,
+                    suppliers::postal_code,
+                    suppliers::country,
+                    suppliers::phone,
+                ))
+                .limit(1)
+                .get_result(conn)
+                .optional()
+        })
+        .await
+    }
+
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        let term = term.to_owned();
+        self.run(move |conn| {
+            products::table
+                .filter(matches("products", dictionary, &term))
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        self.run(move |conn| {
+            orders::table
+                .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+                .group_by(orders::id)
+                .select((
+                    orders::id,
+                    orders::shipped_date,
+                    orders::ship_name,
+                    orders::ship_city,
+                    orders::ship_country,
+                    count(order_details::product_id.nullable()),
+                    sum(order_details::quantity.nullable()),
+                    sum(line_total()),
+                ))
+                .order_by(orders::id.asc())
+                .limit(limit)
+                .offset(offset)
+                .load(conn)
+        })
+        .await
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        self.run(move |conn| {
+            orders::table
+                .left_join(order_details::table.on(order_details::order_id.eq(orders::id)))
+                .filter(orders::id.eq(id))
+                .group_by(orders::id)
+                .select((
+                    orders::id,
+                    orders::shipped_date,
+                    orders::ship_name,
+                    orders::ship_city,
+                    orders::ship_country,
+                    count(order_details::product_id.nullable()),
+                    sum(order_details::quantity.nullable()),
+                    sum(line_total()),
+                ))
+                .limit(1)
+                .get_result(conn)
+                .optional()
+        })
+        .await
+    }
+
+    // Both statements in one blocking call, on one connection, as `queries::p13` runs them
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        self.run(move |conn| {
+            let order: Option<Order> = orders::table
+                .filter(orders::id.eq(id))
+                .limit(1)
+                .get_result(conn)
+                .optional()?;
+            let Some(order) = order else {
+                return Ok(None);
+            };
+            let details: Vec<OrderDetail> = order_details::table
+                .inner_join(products::table)
+                .filter(order_details::order_id.eq(id))
+                .select((
+                    order_details::unit_price,
+                    order_details::quantity,
+                    order_details::discount,
+                    order_details::order_id,
+                    order_details::product_id,
+                    order_details::id,
+                    products::id,
+                    products::name,
+                    products::qt_per_unit,
+                    products::unit_price,
+                    products::units_in_stock,
+                    products::units_on_order,
+                    products::reorder_level,
+                    products::discontinued,
+                    products::supplier_id,
+                ))
+                .load(conn)?;
+            Ok(Some(OrderWithDetailsAndProducts::new(order, details)))
+        })
+        .await
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        self.run(move |conn| {
+            let peers = diesel::alias!(products as peers);
+            let supplier_average = peers
+                .filter(peers.field(products::supplier_id).eq(products::supplier_id))
+                .select(avg(peers.field(products::unit_price)))
+                .single_value();
+
+            products::table
+                .filter(products::unit_price.nullable().gt(supplier_average))
+                .order_by(products::id.asc())
+                .limit(limit)
+                .offset(offset)
+                .load(conn)
+        })
+        .await
+    }
+}
@@ -0,0 +1,285 @@
+// `QueryBackend` on sqlx (`QUERY_BACKEND=sqlx`, `backend-sqlx` feature): p1–p13 as
+// `sqlx::query_as!` into the same row types as the Diesel queries, with SQL equivalent to
+// what Diesel generates, on a sqlx pool sized by the same `PoolConfig` as the Diesel one and
+// connected to DATABASE_URL.
+//
+// The macros check the queries against a database at build time: DATABASE_URL (also read
+// from .env) when reachable, otherwise the query data in `.sqlx/`, which builds with
+// SQLX_OFFLINE=true use. Regenerate it with `cargo sqlx prepare -- --features backend-sqlx`
+// after changing a query.
+
+use async_trait::async_trait;
+use sqlx::{PgPool, postgres::PgPoolOptions};
+
+use crate::{
+    BenchResult,
+    backend::QueryBackend,
+    config::PoolConfig,
+    models::{Customer, Employee, Order, Product, Supplier},
+    queries::*,
+};
+
+pub struct SqlxBackend {
+    pool: PgPool,
+}
+
+impl SqlxBackend {
+    // Connections open on first use, like the Diesel pool's
+    pub fn from_env(pool_config: &PoolConfig) -> BenchResult<Self> {
+        let database_url = std::env::var("DATABASE_URL")?;
+        let pool = PgPoolOptions::new()
+            .max_connections(pool_config.max_size)
+            .min_connections(pool_config.min_idle)
+            .acquire_timeout(pool_config.connection_timeout())
+            .connect_lazy(&database_url)?;
+        Ok(SqlxBackend { pool })
+    }
+}
+
+#[async_trait]
+impl QueryBackend for SqlxBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        Ok(sqlx::query_as!(
+            Customer,
+            "SELECT * FROM customers ORDER BY id ASC LIMIT $1 OFFSET $2",
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        Ok(sqlx::query_as!(
+            Customer,
+            "SELECT * FROM customers WHERE id = $1 LIMIT 1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    // The query macros need each dictionary's SQL spelled out
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        Ok(match dictionary {
+            SearchDictionary::English => sqlx::query_as!(
+                Customer,
+                "SELECT * FROM customers WHERE to_tsvector('english', company_name) @@ plainto_tsquery('english', $1)",
+                term
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            SearchDictionary::Simple => sqlx::query_as!(
+                Customer,
+                "SELECT * FROM customers WHERE to_tsvector('simple', company_name) @@ plainto_tsquery('simple', $1)",
+                term
+            )
+            .fetch_all(&self.pool)
+            .await?,
+        })
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        Ok(sqlx::query_as!(
+            Employee,
+            "SELECT * FROM employees ORDER BY id ASC LIMIT $1 OFFSET $2",
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        // `?`: the recipient columns are NULL without a recipient
+        Ok(sqlx::query_as!(
+            EmployeeWithRecipient,
+            r#"SELECT e.*,
+                r.id AS "recipient_employee_id?",
+                r.last_name AS "recipient_last_name?",
+                r.first_name AS "recipient_first_name?",
+                r.title AS "recipient_title?",
+                r.title_of_courtesy AS "recipient_title_of_courtesy?",
+                r.birth_date AS "recipient_birth_date?",
+                r.hire_date AS "recipient_hire_date?",
+                r.address AS "recipient_address?",
+                r.city AS "recipient_city?",
+                r.postal_code AS "recipient_postal_code?",
+                r.country AS "recipient_country?",
+                r.home_phone AS "recipient_home_phone?",
+                r.extension AS "recipient_extension?",
+                r.notes AS "recipient_notes?",
+                r.recipient_id AS "recipient_recipient_id?"
+            FROM employees e
+            LEFT OUTER JOIN employees r ON e.recipient_id = r.id
+            WHERE e.id = $1
+            LIMIT 1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        Ok(sqlx::query_as!(
+            Supplier,
+            "SELECT * FROM suppliers ORDER BY id ASC LIMIT $1 OFFSET $2",
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        Ok(sqlx::query_as!(
+            Supplier,
+            "SELECT * FROM suppliers WHERE id = $1 LIMIT 1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        Ok(sqlx::query_as!(
+            Product,
+            "SELECT * FROM products ORDER BY id ASC LIMIT $1 OFFSET $2",
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        Ok(sqlx::query_as!(
+            ProductWithSupplier,
+            "SELECT p.*,
+                s.id AS supplier_supplier_id,
+                s.company_name AS supplier_company_name,
+                s.contact_name AS supplier_contact_name,
+                s.contact_title AS supplier_contact_title,
+                s.address AS supplier_address,
+                s.city AS supplier_city,
+                s.region AS supplier_region,
+                s.postal_code AS supplier_postal_code,
+                s.country AS supplier_country,
+                s.phone AS supplier_phone
+            FROM products p
+            INNER JOIN suppliers s ON p.supplier_id = s.id
+            WHERE p.id = $1
+            LIMIT 1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        Ok(match dictionary {
+            SearchDictionary::English => sqlx::query_as!(
+                Product,
+                "SELECT * FROM products WHERE to_tsvector('english', name) @@ plainto_tsquery('english', $1)",
+                term
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            SearchDictionary::Simple => sqlx::query_as!(
+                Product,
+                "SELECT * FROM products WHERE to_tsvector('simple', name) @@ plainto_tsquery('simple', $1)",
+                term
+            )
+            .fetch_all(&self.pool)
+            .await?,
+        })
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        Ok(sqlx::query_as!(
+            P11Row,
+            r#"SELECT o.id, o.shipped_date, o.ship_name, o.ship_city, o.ship_country,
+                count(od.product_id) AS "products_count!",
+                sum(od.quantity) AS quantity_sum,
+                sum(CAST(od.quantity AS double precision) * od.unit_price) AS total_price
+            FROM orders o
+            LEFT OUTER JOIN order_details od ON od.order_id = o.id
+            GROUP BY o.id
+            ORDER BY o.id ASC
+            LIMIT $1 OFFSET $2"#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        Ok(sqlx::query_as!(
+            P11Row,
+            r#"SELECT o.id, o.shipped_date, o.ship_name, o.ship_city, o.ship_country,
+                count(od.product_id) AS "products_count!",
+                sum(od.quantity) AS quantity_sum,
+                sum(CAST(od.quantity AS double precision) * od.unit_price) AS total_price
+            FROM orders o
+            LEFT OUTER JOIN order_details od ON od.order_id = o.id
+            WHERE o.id = $1
+            GROUP BY o.id
+            LIMIT 1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        let order = sqlx::query_as!(Order, "SELECT * FROM orders WHERE id = $1 LIMIT 1", id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(order) = order else {
+            return Ok(None);
+        };
+
+        let details = sqlx::query_as!(
+            OrderDetail,
+            "SELECT od.*,
+                p.id AS product_product_id,
+                p.name AS product_name,
+                p.qt_per_unit AS product_qt_per_unit,
+                p.unit_price AS product_unit_price,
+                p.units_in_stock AS product_units_in_stock,
+                p.units_on_order AS product_units_on_order,
+                p.reorder_level AS product_reorder_level,
+                p.discontinued AS product_discontinued,
+                p.supplier_id AS product_supplier_id
+            FROM order_details od
+            INNER JOIN products p ON od.product_id = p.id
+            WHERE od.order_id = $1",
+            id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(OrderWithDetailsAndProducts::new(order, details)))
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        Ok(sqlx::query_as!(
+            Product,
+            "SELECT * FROM products
+            WHERE unit_price > (
+                SELECT avg(peers.unit_price) FROM products AS peers
+                WHERE peers.supplier_id = products.supplier_id
+            )
+            ORDER BY id ASC LIMIT $1 OFFSET $2",
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
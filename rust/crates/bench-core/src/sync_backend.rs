@@ -0,0 +1,138 @@
+// `QueryBackend` on synchronous Diesel (`QUERY_BACKEND=diesel-sync`, `backend-diesel-sync`
+// feature): the same query builders as the async handlers, loaded over a blocking
+// PgConnection from an r2d2 pool, each call moved to tokio's blocking thread pool with
+// spawn_blocking. This is the pre-diesel-async way of serving Diesel from an async server,
+// kept as a deliberate comparison point for what the blocking pool hand-off costs.
+
+use async_trait::async_trait;
+use diesel::{
+    OptionalExtension, PgConnection, RunQueryDsl,
+    r2d2::{ConnectionManager, Pool, PooledConnection},
+};
+
+use crate::{
+    BenchResult,
+    backend::QueryBackend,
+    config::PoolConfig,
+    models::{Customer, Employee, Order, Product, Supplier},
+    queries::*,
+};
+
+type Connection = PooledConnection<ConnectionManager<PgConnection>>;
+
+pub struct SyncDieselBackend {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl SyncDieselBackend {
+    // Sized like the async pool; connections open on first use
+    pub fn from_env(pool_config: &PoolConfig) -> BenchResult<Self> {
+        let pool = Pool::builder()
+            .max_size(pool_config.max_size)
+            .min_idle(Some(0))
+            .connection_timeout(pool_config.connection_timeout())
+            .build_unchecked(ConnectionManager::new(std::env::var("DATABASE_URL")?));
+        Ok(SyncDieselBackend { pool })
+    }
+
+    // Runs `query` on a pooled connection on the blocking thread pool
+    async fn run<T, F>(&self, query: F) -> BenchResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> diesel::QueryResult<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(query(&mut conn)?)
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl QueryBackend for SyncDieselBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        self.run(move |conn| p1_query(limit, offset).load(conn))
+            .await
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        self.run(move |conn| p2_query(id).get_result(conn).optional())
+            .await
+    }
+
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        let term = term.to_owned();
+        self.run(move |conn| p3_query(&term, dictionary).load(conn))
+            .await
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        self.run(move |conn| p4_query(limit, offset).load(conn))
+            .await
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        self.run(move |conn| p5_query(id).get_result(conn).optional())
+            .await
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        self.run(move |conn| p6_query(limit, offset).load(conn))
+            .await
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        self.run(move |conn| p7_query(id).get_result(conn).optional())
+            .await
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        self.run(move |conn| p8_query(limit, offset).load(conn))
+            .await
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        self.run(move |conn| p9_query(id).get_result(conn).optional())
+            .await
+    }
+
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        let term = term.to_owned();
+        self.run(move |conn| p10_query(&term, dictionary).load(conn))
+            .await
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        self.run(move |conn| p11_query(limit, offset).load(conn))
+            .await
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        self.run(move |conn| p12_query(id).get_result(conn).optional())
+            .await
+    }
+
+    // Both statements in one blocking call, on one connection, as `queries::p13` runs them
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        self.run(move |conn| {
+            let order: Option<Order> = p13_order_query(id).get_result(conn).optional()?;
+            let Some(order) = order else {
+                return Ok(None);
+            };
+            let details: Vec<OrderDetail> = p13_details_query(id).load(conn)?;
+            Ok(Some(OrderWithDetailsAndProducts::new(order, details)))
+        })
+        .await
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        self.run(move |conn| products_above_average_price_query(limit, offset).load(conn))
+            .await
+    }
+}
@@ -0,0 +1,110 @@
+// Flags seconds of a run that don't look like steady state: throughput collapses,
+// latency cliffs and error bursts. Runs with anomalies shouldn't be averaged into
+// published numbers without a look.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::result::TimelinePoint;
+
+// A second is a collapse below this fraction of the median throughput
+const COLLAPSE_FRACTION: f64 = 0.5;
+// A second is a cliff above this multiple of the median mean latency
+const CLIFF_FACTOR: f64 = 3.0;
+// A second is an error burst above this error rate (with at least MIN_BURST_ERRORS errors)
+const BURST_ERROR_RATE: f64 = 0.05;
+const MIN_BURST_ERRORS: u64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    ThroughputCollapse,
+    LatencyCliff,
+    ErrorBurst,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub second: u64,
+    pub at: DateTime<Utc>,
+    // The offending value (req/s, mean latency in us, or error rate) and what it was
+    // compared against (the run's median, or the error rate threshold)
+    pub value: f64,
+    pub baseline: f64,
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+// Checks every complete second except the first, which includes connection ramp-up
+pub fn detect(
+    timeline: &[TimelinePoint],
+    started_at: DateTime<Utc>,
+    duration_secs: f64,
+) -> Vec<Anomaly> {
+    let complete = duration_secs.floor() as u64;
+    let steady: Vec<&TimelinePoint> = timeline
+        .iter()
+        .filter(|point| point.second > 0 && point.second < complete)
+        .collect();
+
+    if steady.len() < 3 {
+        return Vec::new();
+    }
+
+    let median_requests = median(steady.iter().map(|p| p.requests as f64).collect());
+    let median_latency = median(
+        steady
+            .iter()
+            .filter(|p| p.requests > 0)
+            .map(|p| p.mean_us)
+            .collect(),
+    );
+
+    let mut anomalies = Vec::new();
+    let mut flag = |kind, point: &TimelinePoint, value, baseline| {
+        anomalies.push(Anomaly {
+            kind,
+            second: point.second,
+            at: started_at + chrono::Duration::seconds(point.second as i64),
+            value,
+            baseline,
+        });
+    };
+
+    for point in steady {
+        let requests = point.requests as f64;
+        if requests < median_requests * COLLAPSE_FRACTION {
+            flag(
+                AnomalyKind::ThroughputCollapse,
+                point,
+                requests,
+                median_requests,
+            );
+        }
+
+        if point.requests > 0 && point.mean_us > median_latency * CLIFF_FACTOR {
+            flag(
+                AnomalyKind::LatencyCliff,
+                point,
+                point.mean_us,
+                median_latency,
+            );
+        }
+
+        if point.requests > 0 {
+            let error_rate = point.errors as f64 / requests;
+            if point.errors >= MIN_BURST_ERRORS && error_rate > BURST_ERROR_RATE {
+                flag(AnomalyKind::ErrorBurst, point, error_rate, BURST_ERROR_RATE);
+            }
+        }
+    }
+
+    anomalies
+}
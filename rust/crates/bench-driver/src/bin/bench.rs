@@ -0,0 +1,16 @@
+use bench_core::config;
+use bench_driver::cli::{self, Command};
+use clap::Parser;
+
+#[derive(Parser)]
+#[command(name = "bench", about = "Benchmark tooling for the Rust server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[tokio::main]
+async fn main() {
+    config::load_env();
+    cli::run(Cli::parse().command).await;
+}
@@ -0,0 +1,24 @@
+use bench_core::{
+    config, database_url,
+    seed::{self, SeedArgs},
+};
+use clap::Parser;
+
+#[derive(Parser)]
+#[command(
+    name = "seed",
+    about = "Fill an empty database with generated data, like `pnpm start:seed`"
+)]
+struct Cli {
+    #[command(flatten)]
+    args: SeedArgs,
+}
+
+#[tokio::main]
+async fn main() {
+    config::load_env();
+    if let Err(err) = seed::seed(&database_url(), &Cli::parse().args).await {
+        eprintln!("Seeding failed: {:?}", err);
+        std::process::exit(1);
+    }
+}
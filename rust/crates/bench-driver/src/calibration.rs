@@ -0,0 +1,65 @@
+// Estimates the load generator's own overhead (scheduling, request building, parsing,
+// loopback TCP) by running the same load against an in-process server that does nothing.
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{Router, http::StatusCode};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BenchResult,
+    loadgen::{self, LoadConfig},
+    result::LatencySummary,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Calibration {
+    pub duration_secs: f64,
+    pub requests: u64,
+    pub rps: f64,
+    // Latency of the no-op server as seen by the generator
+    pub latency: LatencySummary,
+    // Amount subtracted from every measured latency (the no-op median)
+    pub overhead_us: u64,
+}
+
+async fn spawn_noop_server() -> BenchResult<(SocketAddr, tokio::task::JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let app = Router::new().fallback(|| async { StatusCode::OK });
+
+    let server = tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app).await {
+            eprintln!("Calibration server failed: {:?}", err);
+        }
+    });
+
+    Ok((addr, server))
+}
+
+// Runs `config` (same scenario, concurrency and mode) against a localhost no-op server
+pub async fn calibrate(config: &LoadConfig, duration: Duration) -> BenchResult<Calibration> {
+    let (addr, server) = spawn_noop_server().await?;
+
+    let noop = LoadConfig {
+        name: format!("{}-calibration", config.name),
+        target: format!("http://{}", addr),
+        scenario: config.scenario.clone(),
+        concurrency: config.concurrency,
+        duration,
+        rate: config.rate,
+        keep_alive: config.keep_alive,
+    };
+
+    let result = loadgen::run(&noop).await;
+    server.abort();
+    let result = result?;
+
+    Ok(Calibration {
+        duration_secs: result.duration_secs,
+        requests: result.requests,
+        rps: result.rps,
+        overhead_us: result.latency.p50_us,
+        latency: result.latency,
+    })
+}
@@ -0,0 +1,521 @@
+// The `bench` binary's commands, in the library so the server binary can offer them as
+// well (`rust bench ...`, `rust compare`, `rust report`).
+
+use std::{path::PathBuf, time::Duration};
+
+use bench_core::dataset_meta::DatasetMeta;
+use clap::{Args, Subcommand};
+
+use super::{
+    BenchResult, calibration,
+    client::{self, http_client},
+    coordinator, fixtures,
+    loadgen::{self, LoadConfig},
+    report, requests,
+    result::RunResult,
+    scenario::Scenario,
+    significance, summary,
+    verify::{self, VerifyConfig},
+};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Hit every endpoint with canonical parameters and write normalized JSON fixtures
+    GenFixtures {
+        #[arg(long, default_value = "http://localhost:3003")]
+        target: String,
+        #[arg(long, default_value = "fixtures")]
+        out: PathBuf,
+    },
+    /// Generate the request list from the database (DATABASE_URL), like `pnpm start:generate`
+    GenRequests {
+        #[arg(long, default_value = "../data/requests.json")]
+        out: PathBuf,
+        /// Seed for the shuffle and the random pages
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Check that the list endpoints return the same rows in the same order on every request
+    Verify {
+        #[arg(long, default_value = "http://localhost:3003")]
+        target: String,
+        /// Page size of the paginated endpoints
+        #[arg(long, default_value_t = verify::DEFAULT_LIMIT)]
+        limit: i64,
+        /// Consecutive pages checked per paginated endpoint
+        #[arg(long, default_value_t = verify::DEFAULT_PAGES)]
+        pages: usize,
+        /// Requests per page (at least 2)
+        #[arg(long, default_value_t = verify::DEFAULT_RUNS)]
+        runs: usize,
+    },
+    /// Replay a request list against a server and write a result file
+    Run(RunArgs),
+    /// Wait for runs assigned by a coordinator
+    Worker {
+        #[arg(long, default_value = "0.0.0.0:7700")]
+        listen: String,
+    },
+    /// Split a run across workers and write their merged result file
+    Coordinate {
+        /// Worker addresses, e.g. 10.0.0.2:7700,10.0.0.3:7700
+        #[arg(long, value_delimiter = ',', required = true)]
+        workers: Vec<String>,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// Serve an HTML page plotting one or more result files
+    Report(ReportArgs),
+    /// Test whether two runs' latency distributions differ significantly
+    Compare(CompareArgs),
+    /// Print Markdown comparison tables for several result files
+    Summarize {
+        /// Name of the run speedups are relative to (defaults to the first file)
+        #[arg(long)]
+        baseline: Option<String>,
+        /// Write the tables to a file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Leave out runs with detected anomalies instead of listing them
+        #[arg(long)]
+        exclude_anomalous: bool,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct CompareArgs {
+    baseline: PathBuf,
+    candidate: PathBuf,
+    /// Print the comparison as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    #[arg(long, default_value = "http://localhost:3003")]
+    target: String,
+    #[arg(long, default_value = "../data/requests.json")]
+    requests: PathBuf,
+    /// Scenario file with a weighted endpoint mix and think time; without one the request
+    /// list is replayed in order
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+    /// Total concurrency, at least 1; split evenly between workers when coordinating
+    #[arg(
+        long,
+        default_value_t = 256,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    concurrency: usize,
+    /// Run length in seconds
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+    /// Open-loop mode: send at a fixed rate (e.g. 50000rps), capped by --concurrency in flight
+    #[arg(long, value_parser = loadgen::parse_rate)]
+    rate: Option<f64>,
+    /// Connection storm: open (and close) a new connection for every request instead of
+    /// keeping connections alive; with --rate this is the connections/s to sustain
+    #[arg(long)]
+    no_keepalive: bool,
+    /// Seconds to run the same load against a localhost no-op server afterwards and subtract
+    /// the measured client overhead from latencies (0 disables calibration). When
+    /// coordinating, calibration runs on the coordinator host only
+    #[arg(long, default_value_t = 0)]
+    calibrate: u64,
+    /// Run the load this many times and report the medians across repetitions
+    #[arg(long, default_value_t = 1)]
+    repeat: usize,
+    /// Seconds of unmeasured load before the first repetition
+    #[arg(long, default_value_t = 0)]
+    warmup: u64,
+    /// Leading repetitions to throw away as warmup (counted in --repeat)
+    #[arg(long, default_value_t = 0)]
+    discard_warmup: usize,
+    /// Run the `verify` checks with their defaults first and don't start on findings
+    #[arg(long)]
+    verify: bool,
+    #[arg(long, default_value = "rust")]
+    name: String,
+    #[arg(long, default_value = "results")]
+    folder: PathBuf,
+}
+
+impl RunArgs {
+    fn load_config(&self) -> BenchResult<LoadConfig> {
+        if self.discard_warmup >= self.repeat {
+            return Err("--discard-warmup must be less than --repeat".into());
+        }
+
+        let paths = loadgen::load_paths(&self.requests)?;
+        let scenario = match &self.scenario {
+            Some(path) => Scenario::load(path, &paths)?,
+            None => Scenario::sequential(paths),
+        };
+
+        Ok(LoadConfig {
+            name: self.name.clone(),
+            target: self.target.clone(),
+            scenario,
+            concurrency: self.concurrency,
+            duration: Duration::from_secs(self.duration),
+            rate: self.rate,
+            keep_alive: !self.no_keepalive,
+        })
+    }
+
+    fn result_path(&self) -> PathBuf {
+        self.folder.join(format!("{}.json", self.name))
+    }
+}
+
+fn or_exit<T>(result: BenchResult<T>, context: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}: {:?}", context, err);
+        std::process::exit(1);
+    })
+}
+
+fn print_result(result: &RunResult) {
+    println!(
+        "{}: {} requests ({} errors) in {:.1}s, {:.0} req/s",
+        result.name, result.requests, result.errors, result.duration_secs, result.rps
+    );
+    println!(
+        "latency: p50 {}us, p95 {}us, p99 {}us, max {}us",
+        result.latency.p50_us, result.latency.p95_us, result.latency.p99_us, result.latency.max_us
+    );
+    if let Some(first_byte) = &result.first_byte {
+        println!(
+            "first byte: p50 {}us, p95 {}us, p99 {}us, max {}us",
+            first_byte.p50_us, first_byte.p95_us, first_byte.p99_us, first_byte.max_us
+        );
+    }
+    if let (Some(calibration), Some(corrected)) = (&result.calibration, &result.corrected_latency) {
+        println!(
+            "corrected latency (-{}us client overhead): p50 {}us, p95 {}us, p99 {}us",
+            calibration.overhead_us, corrected.p50_us, corrected.p95_us, corrected.p99_us
+        );
+    }
+    if let Some(queue) = &result.queue_delay {
+        println!(
+            "queue delay: p50 {}us, p99 {}us, max {}us",
+            queue.p50_us, queue.p99_us, queue.max_us
+        );
+    }
+    if !result.repetitions.is_empty() {
+        println!(
+            "medians of {} repetitions ({} warmup discarded)",
+            result.repetitions.len(),
+            result.discarded_warmup
+        );
+    }
+    if !result.keep_alive {
+        println!(
+            "connection storm: {:.0} connections/s",
+            result.connections.connections_opened as f64 / result.duration_secs
+        );
+    }
+    println!(
+        "connections: {} opened, {:.2}% reused, {} TLS handshakes, {} DNS lookups",
+        result.connections.connections_opened,
+        result.connections.reuse_rate * 100.0,
+        result.connections.tls_handshakes,
+        result.connections.dns_lookups
+    );
+    for anomaly in &result.anomalies {
+        println!(
+            "anomaly: {:?} at {} (second {}): {:.2} vs {:.2}",
+            anomaly.kind, anomaly.at, anomaly.second, anomaly.value, anomaly.baseline
+        );
+    }
+}
+
+// Runs the load `--repeat` times, locally or on `workers`, and aggregates the repetitions
+// left after warmup
+async fn run_repeated(
+    run: &RunArgs,
+    config: &LoadConfig,
+    workers: &[String],
+) -> BenchResult<RunResult> {
+    if run.warmup > 0 {
+        let warmup = LoadConfig {
+            name: format!("{}-warmup", config.name),
+            target: config.target.clone(),
+            scenario: config.scenario.clone(),
+            concurrency: config.concurrency,
+            duration: Duration::from_secs(run.warmup),
+            rate: config.rate,
+            keep_alive: config.keep_alive,
+        };
+        let result = if workers.is_empty() {
+            loadgen::run(&warmup).await?
+        } else {
+            coordinator::coordinate(&warmup, workers).await?
+        };
+        println!(
+            "warmup: {} requests in {:.1}s, {:.0} req/s",
+            result.requests, result.duration_secs, result.rps
+        );
+    }
+
+    let mut kept = Vec::with_capacity(run.repeat);
+    for iteration in 1..=run.repeat {
+        let result = if workers.is_empty() {
+            loadgen::run(config).await?
+        } else {
+            coordinator::coordinate(config, workers).await?
+        };
+        if run.repeat == 1 {
+            return Ok(result);
+        }
+
+        let warmup = iteration <= run.discard_warmup;
+        println!(
+            "repetition {}/{}{}: {:.0} req/s, p50 {}us, p99 {}us",
+            iteration,
+            run.repeat,
+            if warmup { " (warmup, discarded)" } else { "" },
+            result.rps,
+            result.latency.p50_us,
+            result.latency.p99_us
+        );
+        if !warmup {
+            kept.push(result);
+        }
+    }
+
+    RunResult::aggregate(kept, run.discard_warmup)
+}
+
+// Prints the findings of the stability checks; false if there were any
+async fn stable(config: &VerifyConfig) -> bool {
+    let findings = or_exit(
+        verify::verify(&http_client(), config).await,
+        "Stability checks failed",
+    );
+    for finding in &findings {
+        println!("unstable: {} ({:?})", finding.path, finding.problem);
+    }
+    if findings.is_empty() {
+        println!("list endpoints are stable");
+    }
+    findings.is_empty()
+}
+
+async fn finish(run: &RunArgs, config: &LoadConfig, workers: &[String]) {
+    if run.verify
+        && !stable(&VerifyConfig {
+            target: config.target.clone(),
+            limit: verify::DEFAULT_LIMIT,
+            pages: verify::DEFAULT_PAGES,
+            runs: verify::DEFAULT_RUNS,
+        })
+        .await
+    {
+        eprintln!("Not starting the benchmark: list endpoints are unstable");
+        std::process::exit(1);
+    }
+
+    let mut result = or_exit(
+        run_repeated(run, config, workers).await,
+        "Load generator failed",
+    );
+
+    if run.calibrate > 0 {
+        let calibration = or_exit(
+            calibration::calibrate(config, Duration::from_secs(run.calibrate)).await,
+            "Calibration failed",
+        );
+        result.apply_calibration(calibration);
+    }
+    result.dataset = dataset(&config.target).await;
+
+    print_result(&result);
+
+    let path = run.result_path();
+    or_exit(
+        result.write(&path),
+        &format!("Failed to write {}", path.display()),
+    );
+}
+
+// The target's /debug/dataset, None (with a warning) for a target without one
+async fn dataset(target: &str) -> Option<DatasetMeta> {
+    let fetch = async {
+        let uri = client::request_uri(target, "/debug/dataset")?;
+        let (status, body) = client::get(&http_client(), uri).await?;
+        if !status.is_success() {
+            return Err(format!("/debug/dataset answered {}", status).into());
+        }
+        BenchResult::Ok(serde_json::from_slice(&body)?)
+    };
+
+    match fetch.await {
+        Ok(dataset) => Some(dataset),
+        Err(err) => {
+            eprintln!("Not recording the dataset: {}", err);
+            None
+        }
+    }
+}
+
+pub async fn report(args: ReportArgs) {
+    or_exit(
+        report::serve(&args.listen, args.files).await,
+        "Report server failed",
+    );
+}
+
+pub fn compare(args: CompareArgs) {
+    let baseline = or_exit(RunResult::read(&args.baseline), "Failed to read baseline");
+    let candidate = or_exit(RunResult::read(&args.candidate), "Failed to read candidate");
+    let comparison = significance::compare(&baseline, &candidate);
+
+    if args.json {
+        println!(
+            "{}",
+            or_exit(
+                serde_json::to_string_pretty(&comparison).map_err(Into::into),
+                "Failed to encode comparison",
+            )
+        );
+    } else {
+        let diff = &comparison.median_difference_us;
+        println!(
+            "{} vs {}: median {:+.0}us (95% CI {:+.0}..{:+.0}us), Mann-Whitney p={:.4}, P(slower)={:.3}",
+            comparison.candidate,
+            comparison.baseline,
+            diff.estimate,
+            diff.low,
+            diff.high,
+            comparison.mann_whitney.p_value,
+            comparison.mann_whitney.effect_size
+        );
+        println!(
+            "{}",
+            if comparison.significant {
+                "difference is significant"
+            } else {
+                "difference is NOT significant, treat as noise"
+            }
+        );
+    }
+}
+
+pub async fn run(command: Command) {
+    match command {
+        Command::GenFixtures { target, out } => {
+            let client = http_client();
+            let written = or_exit(
+                fixtures::generate(&client, &target, &out).await,
+                "Failed to generate fixtures",
+            );
+
+            for path in written {
+                println!("Wrote {}", path.display());
+            }
+        }
+        Command::GenRequests { out, seed } => {
+            let pool = bench_core::establish_connection_pool().await;
+            let ids = or_exit(
+                async {
+                    let mut conn = pool.get().await?;
+                    requests::id_ranges(&mut conn).await
+                }
+                .await,
+                "Failed to read id ranges",
+            );
+
+            let paths = requests::generate(&ids, &mut fastrand::Rng::with_seed(seed));
+            or_exit(
+                std::fs::write(&out, serde_json::to_vec(&paths).unwrap_or_default())
+                    .map_err(Into::into),
+                &format!("Failed to write {}", out.display()),
+            );
+            println!("Wrote {} requests to {}", paths.len(), out.display());
+        }
+        Command::Verify {
+            target,
+            limit,
+            pages,
+            runs,
+        } => {
+            let config = VerifyConfig {
+                target,
+                limit,
+                pages,
+                runs,
+            };
+            if !stable(&config).await {
+                std::process::exit(1);
+            }
+        }
+        Command::Run(run) => {
+            let config = or_exit(run.load_config(), "Invalid run configuration");
+            finish(&run, &config, &[]).await;
+        }
+        Command::Worker { listen } => {
+            or_exit(coordinator::serve_worker(&listen).await, "Worker failed");
+        }
+        Command::Coordinate { workers, run } => {
+            let config = or_exit(run.load_config(), "Invalid run configuration");
+            finish(&run, &config, &workers).await;
+        }
+        Command::Report(args) => report(args).await,
+        Command::Compare(args) => compare(args),
+        Command::Summarize {
+            baseline,
+            out,
+            exclude_anomalous,
+            files,
+        } => {
+            let mut results: Vec<RunResult> = or_exit(
+                files.iter().map(|path| RunResult::read(path)).collect(),
+                "Failed to read results",
+            );
+            if exclude_anomalous {
+                results.retain(|result| {
+                    if !result.anomalies.is_empty() {
+                        eprintln!(
+                            "Excluding {}: {} anomalies, rerun it",
+                            result.name,
+                            result.anomalies.len()
+                        );
+                    }
+                    result.anomalies.is_empty()
+                });
+            }
+
+            let baseline = match baseline {
+                Some(name) => results
+                    .iter()
+                    .position(|r| r.name == name)
+                    .unwrap_or_else(|| {
+                        eprintln!("No result named {}", name);
+                        std::process::exit(1);
+                    }),
+                None => 0,
+            };
+
+            let tables = summary::markdown(&results, baseline);
+            match out {
+                Some(path) => or_exit(
+                    std::fs::write(&path, tables).map_err(Into::into),
+                    &format!("Failed to write {}", path.display()),
+                ),
+                None => print!("{}", tables),
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::{StatusCode, Uri, http::uri::Scheme};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{
+        Client,
+        connect::{
+            HttpConnector,
+            dns::{GaiFuture, GaiResolver, Name},
+        },
+    },
+    rt::TokioExecutor,
+};
+use serde::{Deserialize, Serialize};
+use tower::Service;
+
+use super::BenchResult;
+
+pub type HttpClient =
+    Client<CountingConnector<HttpsConnector<HttpConnector<CountingResolver>>>, Empty<Bytes>>;
+
+// Connection-level events seen by a client; a request that triggers none of them
+// went over a reused keep-alive connection
+#[derive(Default)]
+pub struct ConnectionCounters {
+    pub dns_lookups: AtomicU64,
+    pub connections: AtomicU64,
+    pub tls_handshakes: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ConnectionStats {
+    pub connections_opened: u64,
+    pub tls_handshakes: u64,
+    pub dns_lookups: u64,
+    // Share of requests that did not open a new connection
+    pub reuse_rate: f64,
+}
+
+fn reuse_rate(connections_opened: u64, requests: u64) -> f64 {
+    if requests == 0 {
+        0.0
+    } else {
+        1.0 - (connections_opened.min(requests) as f64 / requests as f64)
+    }
+}
+
+impl ConnectionCounters {
+    pub fn stats(&self, requests: u64) -> ConnectionStats {
+        let connections_opened = self.connections.load(Ordering::Relaxed);
+
+        ConnectionStats {
+            connections_opened,
+            tls_handshakes: self.tls_handshakes.load(Ordering::Relaxed),
+            dns_lookups: self.dns_lookups.load(Ordering::Relaxed),
+            reuse_rate: reuse_rate(connections_opened, requests),
+        }
+    }
+}
+
+impl ConnectionStats {
+    // Sums the counters of several clients; `requests` is their combined request count
+    pub fn combine<'a>(
+        stats: impl IntoIterator<Item = &'a ConnectionStats>,
+        requests: u64,
+    ) -> Self {
+        let mut combined = ConnectionStats::default();
+        for s in stats {
+            combined.connections_opened += s.connections_opened;
+            combined.tls_handshakes += s.tls_handshakes;
+            combined.dns_lookups += s.dns_lookups;
+        }
+        combined.reuse_rate = reuse_rate(combined.connections_opened, requests);
+        combined
+    }
+}
+
+// getaddrinfo resolver counting lookups (IP literal hosts never reach the resolver)
+#[derive(Clone)]
+pub struct CountingResolver {
+    inner: GaiResolver,
+    counters: Arc<ConnectionCounters>,
+}
+
+impl Service<Name> for CountingResolver {
+    type Response = <GaiResolver as Service<Name>>::Response;
+    type Error = <GaiResolver as Service<Name>>::Error;
+    type Future = GaiFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        self.counters.dns_lookups.fetch_add(1, Ordering::Relaxed);
+        self.inner.call(name)
+    }
+}
+
+// Outermost connector layer: every call is a new connection, https ones also a handshake
+#[derive(Clone)]
+pub struct CountingConnector<C> {
+    inner: C,
+    counters: Arc<ConnectionCounters>,
+}
+
+impl<C> Service<Uri> for CountingConnector<C>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<C::Response, C::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        self.counters.connections.fetch_add(1, Ordering::Relaxed);
+        if uri.scheme() == Some(&Scheme::HTTPS) {
+            self.counters.tls_handshakes.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Box::pin(self.inner.call(uri))
+    }
+}
+
+pub fn http_client() -> HttpClient {
+    http_client_with_counters(Arc::default(), true)
+}
+
+// Without keep-alive no connection is pooled: each request connects (and handshakes)
+// anew and the connection is closed once its response has been read
+pub fn http_client_with_counters(
+    counters: Arc<ConnectionCounters>,
+    keep_alive: bool,
+) -> HttpClient {
+    let resolver = CountingResolver {
+        inner: GaiResolver::new(),
+        counters: counters.clone(),
+    };
+
+    let mut http = HttpConnector::new_with_resolver(resolver);
+    http.set_nodelay(true);
+    http.enforce_http(false);
+
+    let https = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http);
+
+    let mut builder = Client::builder(TokioExecutor::new());
+    if !keep_alive {
+        builder.pool_max_idle_per_host(0);
+    }
+
+    builder.build(CountingConnector {
+        inner: https,
+        counters,
+    })
+}
+
+// Joins `target` (e.g. http://localhost:3003) with a request path such as `/customers?limit=1`
+pub fn request_uri(target: &str, path: &str) -> BenchResult<Uri> {
+    Ok(format!("{}{}", target.trim_end_matches('/'), path).parse()?)
+}
+
+// GET a path and buffer the whole response body
+pub async fn get(client: &HttpClient, uri: Uri) -> BenchResult<(StatusCode, Bytes)> {
+    let response = client.get(uri).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+
+    Ok((status, body))
+}
+
+// GET a path and read the response body through without keeping it, returning when its first
+// byte arrived (the headers', for an empty body). The caller's own clock gives the full time.
+pub async fn get_first_byte(client: &HttpClient, uri: Uri) -> BenchResult<(StatusCode, Instant)> {
+    let response = client.get(uri).await?;
+    let status = response.status();
+    let headers_at = Instant::now();
+    let mut body = response.into_body();
+
+    let mut first_byte = None;
+    while let Some(frame) = body.frame().await {
+        if first_byte.is_none() && frame?.data_ref().is_some_and(|data| !data.is_empty()) {
+            first_byte = Some(Instant::now());
+        }
+    }
+
+    Ok((status, first_byte.unwrap_or(headers_at)))
+}
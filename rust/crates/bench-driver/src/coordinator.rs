@@ -0,0 +1,154 @@
+// Coordinator/worker mode: one `bench coordinate` process fans a run out to several
+// `bench worker` processes (possibly on other hosts) and merges their histograms.
+//
+// Protocol: one TCP connection per run, one JSON line each way. The coordinator sends a
+// `WorkerTask`, the worker waits until `start_at`, runs the load and answers with a
+// `WorkerReply`.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{
+    BenchResult,
+    loadgen::{self, LoadConfig},
+    result::RunResult,
+    scenario::Scenario,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkerTask {
+    pub name: String,
+    pub target: String,
+    pub scenario: Scenario,
+    pub concurrency: usize,
+    pub duration_secs: u64,
+    pub rate: Option<f64>,
+    #[serde(default = "keep_alive_default")]
+    pub keep_alive: bool,
+    // Common start time so all workers load the server simultaneously
+    pub start_at: DateTime<Utc>,
+}
+
+fn keep_alive_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum WorkerReply {
+    Done(Box<RunResult>),
+    Failed(String),
+}
+
+// Delay between sending tasks and the shared start, long enough to reach every worker
+const START_DELAY: Duration = Duration::from_secs(2);
+
+async fn write_line<T: Serialize>(stream: &mut TcpStream, message: &T) -> BenchResult<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line).await?;
+    Ok(())
+}
+
+async fn read_line<T: for<'de> Deserialize<'de>>(stream: &mut TcpStream) -> BenchResult<T> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    if line.is_empty() {
+        return Err("connection closed before a message was received".into());
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+// Serves runs one at a time, forever
+pub async fn serve_worker(listen: &str) -> BenchResult<()> {
+    let listener = TcpListener::bind(listen).await?;
+    println!("Worker listening on {}", listen);
+
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+
+        let task: WorkerTask = match read_line(&mut stream).await {
+            Ok(task) => task,
+            Err(err) => {
+                eprintln!("Invalid task from {}: {:?}", peer, err);
+                continue;
+            }
+        };
+
+        println!(
+            "Running {} against {} for coordinator {}",
+            task.name, task.target, peer
+        );
+
+        let wait = (task.start_at - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let config = LoadConfig {
+            name: format!("{}@{}", task.name, listen),
+            target: task.target,
+            scenario: task.scenario,
+            concurrency: task.concurrency,
+            duration: Duration::from_secs(task.duration_secs),
+            rate: task.rate,
+            keep_alive: task.keep_alive,
+        };
+
+        let reply = match loadgen::run(&config).await {
+            Ok(result) => WorkerReply::Done(Box::new(result)),
+            Err(err) => WorkerReply::Failed(err.to_string()),
+        };
+
+        if let Err(err) = write_line(&mut stream, &reply).await {
+            eprintln!("Failed to report to {}: {:?}", peer, err);
+        }
+    }
+}
+
+async fn run_on_worker(worker: String, task: &WorkerTask) -> BenchResult<RunResult> {
+    let mut stream = TcpStream::connect(&worker).await?;
+    write_line(&mut stream, task).await?;
+
+    match read_line(&mut stream).await? {
+        WorkerReply::Done(result) => Ok(*result),
+        WorkerReply::Failed(err) => Err(format!("worker {} failed: {}", worker, err).into()),
+    }
+}
+
+// Splits `config.concurrency` (and the open-loop rate) across `workers` and merges
+// what they report
+pub async fn coordinate(config: &LoadConfig, workers: &[String]) -> BenchResult<RunResult> {
+    if workers.is_empty() {
+        return Err("at least one worker is required".into());
+    }
+
+    let start_at = Utc::now() + START_DELAY;
+    let per_worker = config.concurrency.div_ceil(workers.len());
+
+    let runs = workers.iter().map(|worker| {
+        let task = WorkerTask {
+            name: config.name.clone(),
+            target: config.target.clone(),
+            scenario: config.scenario.clone(),
+            concurrency: per_worker,
+            duration_secs: config.duration.as_secs(),
+            rate: config.rate.map(|rate| rate / workers.len() as f64),
+            keep_alive: config.keep_alive,
+            start_at,
+        };
+        let worker = worker.clone();
+        tokio::spawn(async move { run_on_worker(worker, &task).await })
+    });
+    let runs: Vec<_> = runs.collect();
+
+    let mut results = Vec::with_capacity(runs.len());
+    for run in runs {
+        results.push(run.await??);
+    }
+
+    RunResult::merge(&config.name, &results)
+}
@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use super::{
+    BenchResult,
+    client::{HttpClient, get, request_uri},
+};
+
+pub struct Fixture {
+    pub name: &'static str,
+    pub path: &'static str,
+    // Endpoints without ORDER BY (full-text search) are sorted by id before writing
+    pub unordered: bool,
+}
+
+// One canonical request per endpoint, valid against any seeded database (ids start at 1,
+// search terms come from src/generate.ts)
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "customers",
+        path: "/customers?limit=50&offset=0",
+        unordered: false,
+    },
+    Fixture {
+        name: "customer-by-id",
+        path: "/customer-by-id?id=1",
+        unordered: false,
+    },
+    Fixture {
+        name: "search-customer",
+        path: "/search-customer?term=ve",
+        unordered: true,
+    },
+    Fixture {
+        name: "employees",
+        path: "/employees?limit=50&offset=0",
+        unordered: false,
+    },
+    Fixture {
+        name: "employee-with-recipient",
+        path: "/employee-with-recipient?id=1",
+        unordered: false,
+    },
+    Fixture {
+        name: "suppliers",
+        path: "/suppliers?limit=50&offset=0",
+        unordered: false,
+    },
+    Fixture {
+        name: "supplier-by-id",
+        path: "/supplier-by-id?id=1",
+        unordered: false,
+    },
+    Fixture {
+        name: "products",
+        path: "/products?limit=50&offset=0",
+        unordered: false,
+    },
+    Fixture {
+        name: "product-with-supplier",
+        path: "/product-with-supplier?id=1",
+        unordered: false,
+    },
+    Fixture {
+        name: "search-product",
+        path: "/search-product?term=ha",
+        unordered: true,
+    },
+    Fixture {
+        name: "orders-with-details",
+        path: "/orders-with-details?limit=50&offset=0",
+        unordered: false,
+    },
+    Fixture {
+        name: "order-with-details",
+        path: "/order-with-details?id=1",
+        unordered: false,
+    },
+    Fixture {
+        name: "order-with-details-and-products",
+        path: "/order-with-details-and-products?id=1",
+        unordered: false,
+    },
+];
+
+// Decimal places kept for floats, so sums computed in different orders compare equal
+const FLOAT_PRECISION: i32 = 6;
+
+// Sorts object keys, rounds floats and (for unordered endpoints) sorts arrays by `id`,
+// so the same data always produces byte-identical output regardless of server
+pub fn normalize(value: Value, unordered: bool) -> Value {
+    let mut value = normalize_value(value);
+
+    if unordered && let Value::Array(items) = &mut value {
+        items.sort_by_key(|item| item.get("id").and_then(Value::as_i64));
+    }
+
+    value
+}
+
+fn normalize_value(value: Value) -> Value {
+    match value {
+        Value::Number(n) if n.is_f64() => {
+            let scale = 10f64.powi(FLOAT_PRECISION);
+            let rounded = (n.as_f64().unwrap_or_default() * scale).round() / scale;
+            serde_json::Number::from_f64(rounded).map_or(Value::Null, Value::Number)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(normalize_value).collect()),
+        // serde_json::Map is a BTreeMap, so rebuilding it sorts the keys
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, normalize_value(value)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+pub fn fixture_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.json", name))
+}
+
+// Fetches every fixture from `target` and writes `<out_dir>/<name>.json`
+pub async fn generate(
+    client: &HttpClient,
+    target: &str,
+    out_dir: &Path,
+) -> BenchResult<Vec<PathBuf>> {
+    std::fs::create_dir_all(out_dir)?;
+
+    let mut written = Vec::with_capacity(FIXTURES.len());
+
+    for fixture in FIXTURES {
+        let (status, body) = get(client, request_uri(target, fixture.path)?).await?;
+        if !status.is_success() {
+            return Err(format!("{} returned {}", fixture.path, status).into());
+        }
+
+        let value = normalize(serde_json::from_slice(&body)?, fixture.unordered);
+
+        let path = fixture_path(out_dir, fixture.name);
+        let mut json = serde_json::to_string_pretty(&value)?;
+        json.push('\n');
+        std::fs::write(&path, json)?;
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
@@ -0,0 +1,20 @@
+// Client-side tooling of the `bench` binary (also `rust bench`): HTTP client, fixtures, load
+// generation.
+
+pub mod anomaly;
+pub mod calibration;
+pub mod cli;
+pub mod client;
+pub mod coordinator;
+pub mod fixtures;
+pub mod loadgen;
+pub mod report;
+pub mod requests;
+pub mod result;
+pub mod scenario;
+pub mod significance;
+pub mod summary;
+pub mod trace;
+pub mod verify;
+
+pub use bench_core::{BenchError, BenchResult};
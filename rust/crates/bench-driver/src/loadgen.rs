@@ -0,0 +1,292 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use hdrhistogram::Histogram;
+use hyper::Uri;
+use parking_lot::Mutex;
+use tokio::sync::Semaphore;
+
+use super::{
+    BenchResult, anomaly,
+    client::{ConnectionCounters, HttpClient, get_first_byte, http_client_with_counters},
+    result::{
+        LatencySummary, RunResult, TimelinePoint, add_timelines, histogram_to_buckets,
+        new_histogram,
+    },
+    scenario::{RequestMix, Scenario, ThinkTime},
+    trace,
+};
+
+pub struct LoadConfig {
+    pub name: String,
+    pub target: String,
+    pub scenario: Scenario,
+    // Closed loop: number of workers. Open loop: cap on requests in flight
+    pub concurrency: usize,
+    pub duration: Duration,
+    // Requests per second for the open-loop scheduler; None runs closed loop
+    pub rate: Option<f64>,
+    // false opens a new connection for every request (connection storm)
+    pub keep_alive: bool,
+}
+
+// Reads a request list in the data/requests.json format: a JSON array of paths, or the GET
+// requests of a trace recorded by `rust proxy`
+pub fn load_paths(path: &Path) -> BenchResult<Vec<String>> {
+    let contents = std::fs::read(path)?;
+    let paths: Vec<String> = if trace::is_trace(&contents) {
+        trace::trace_paths(&trace::parse_trace(&contents)?)
+    } else {
+        serde_json::from_slice(&contents)?
+    };
+    if paths.is_empty() {
+        return Err(format!("{} contains no requests", path.display()).into());
+    }
+    Ok(paths)
+}
+
+// Parses `--rate` values such as `50000rps` or `50000`
+pub fn parse_rate(value: &str) -> Result<f64, String> {
+    let rate: f64 = value
+        .trim()
+        .trim_end_matches("rps")
+        .parse()
+        .map_err(|_| format!("invalid rate `{}`, expected e.g. 50000rps", value))?;
+
+    if rate <= 0.0 || !rate.is_finite() {
+        return Err(format!("rate must be positive, got `{}`", value));
+    }
+    Ok(rate)
+}
+
+struct LoadStats {
+    start: Instant,
+    histogram: Histogram<u64>,
+    // Up to the first byte of the response body; `histogram` is up to its last
+    first_byte_histogram: Histogram<u64>,
+    timeline: Vec<TimelinePoint>,
+    // Open loop only: time between the scheduled send and the actual send
+    queue_histogram: Histogram<u64>,
+    requests: u64,
+    errors: u64,
+}
+
+impl LoadStats {
+    fn new(start: Instant) -> Self {
+        LoadStats {
+            start,
+            histogram: new_histogram(),
+            first_byte_histogram: new_histogram(),
+            timeline: Vec::new(),
+            queue_histogram: new_histogram(),
+            requests: 0,
+            errors: 0,
+        }
+    }
+
+    // `first_byte` is None for a request that failed before its response
+    fn record(&mut self, latency: Duration, first_byte: Option<Duration>, ok: bool) {
+        let latency_us = latency.as_micros() as u64;
+        self.histogram.saturating_record(latency_us);
+        if let Some(first_byte) = first_byte {
+            self.first_byte_histogram
+                .saturating_record(first_byte.as_micros() as u64);
+        }
+
+        let second = self.start.elapsed().as_secs() as usize;
+        while self.timeline.len() <= second {
+            self.timeline.push(TimelinePoint {
+                second: self.timeline.len() as u64,
+                ..Default::default()
+            });
+        }
+        self.timeline[second].record(latency_us, ok);
+
+        self.requests += 1;
+        if !ok {
+            self.errors += 1;
+        }
+    }
+
+    fn add(&mut self, other: &LoadStats) -> BenchResult<()> {
+        self.histogram.add(&other.histogram)?;
+        self.first_byte_histogram.add(&other.first_byte_histogram)?;
+        self.queue_histogram.add(&other.queue_histogram)?;
+        add_timelines(&mut self.timeline, &other.timeline);
+        self.requests += other.requests;
+        self.errors += other.errors;
+        Ok(())
+    }
+}
+
+// Whether the request succeeded, and when its response body started arriving
+async fn send(client: &HttpClient, uri: Uri) -> (bool, Option<Instant>) {
+    match get_first_byte(client, uri).await {
+        Ok((status, first_byte)) => (status.is_success(), Some(first_byte)),
+        Err(_) => (false, None),
+    }
+}
+
+pub async fn run(config: &LoadConfig) -> BenchResult<RunResult> {
+    let counters = Arc::new(ConnectionCounters::default());
+    let client = http_client_with_counters(counters.clone(), config.keep_alive);
+
+    let mix = Arc::new(RequestMix::new(&config.scenario, &config.target)?);
+
+    let started_at = Utc::now();
+    let start = Instant::now();
+    let deadline = start + config.duration;
+
+    let stats = match config.rate {
+        None => run_closed_loop(config, &client, mix, start, deadline).await?,
+        Some(rate) => run_open_loop(config, rate, &client, mix, start, deadline).await?,
+    };
+
+    let elapsed = start.elapsed().as_secs_f64();
+
+    Ok(RunResult {
+        name: config.name.clone(),
+        target: config.target.clone(),
+        started_at,
+        duration_secs: elapsed,
+        concurrency: config.concurrency,
+        rate: config.rate,
+        keep_alive: config.keep_alive,
+        requests: stats.requests,
+        errors: stats.errors,
+        rps: stats.requests as f64 / elapsed,
+        latency: LatencySummary::from_histogram(&stats.histogram),
+        histogram: histogram_to_buckets(&stats.histogram),
+        first_byte: Some(LatencySummary::from_histogram(&stats.first_byte_histogram)),
+        first_byte_histogram: histogram_to_buckets(&stats.first_byte_histogram),
+        anomalies: anomaly::detect(&stats.timeline, started_at, elapsed),
+        repetitions: Vec::new(),
+        discarded_warmup: 0,
+        timeline: stats.timeline,
+        queue_delay: config
+            .rate
+            .map(|_| LatencySummary::from_histogram(&stats.queue_histogram)),
+        queue_histogram: histogram_to_buckets(&stats.queue_histogram),
+        connections: counters.stats(stats.requests),
+        calibration: None,
+        corrected_latency: None,
+        dataset: None,
+    })
+}
+
+// Closed loop: each of `concurrency` workers sends its next request as soon as the
+// previous one completes (plus think time, if the scenario has one)
+async fn run_closed_loop(
+    config: &LoadConfig,
+    client: &HttpClient,
+    mix: Arc<RequestMix>,
+    start: Instant,
+    deadline: Instant,
+) -> BenchResult<LoadStats> {
+    let workers: Vec<_> = (0..config.concurrency)
+        .map(|index| {
+            tokio::spawn(closed_loop_worker(
+                client.clone(),
+                mix.clone(),
+                config.scenario.think_time.clone(),
+                fastrand::Rng::with_seed(index as u64),
+                start,
+                deadline,
+            ))
+        })
+        .collect();
+
+    let mut stats = LoadStats::new(start);
+    for worker in workers {
+        stats.add(&worker.await?)?;
+    }
+
+    Ok(stats)
+}
+
+async fn closed_loop_worker(
+    client: HttpClient,
+    mix: Arc<RequestMix>,
+    think_time: Option<ThinkTime>,
+    mut rng: fastrand::Rng,
+    start: Instant,
+    deadline: Instant,
+) -> LoadStats {
+    let mut stats = LoadStats::new(start);
+
+    while Instant::now() < deadline {
+        let uri = mix.next(&mut rng);
+
+        let sent = Instant::now();
+        let (ok, first_byte) = send(&client, uri).await;
+        stats.record(
+            sent.elapsed(),
+            first_byte.map(|at| at.duration_since(sent)),
+            ok,
+        );
+
+        if let Some(think_time) = &think_time {
+            tokio::time::sleep(think_time.sample(&mut rng)).await;
+        }
+    }
+
+    stats
+}
+
+// Open loop: request i is due at `start + i / rate` whether or not earlier ones have
+// completed. Latency is measured from the due time, so when the concurrency cap or the
+// server falls behind, the wait shows up in the numbers instead of being omitted.
+// Think time doesn't apply: the schedule alone decides when requests go out
+async fn run_open_loop(
+    config: &LoadConfig,
+    rate: f64,
+    client: &HttpClient,
+    mix: Arc<RequestMix>,
+    start: Instant,
+    deadline: Instant,
+) -> BenchResult<LoadStats> {
+    let in_flight = Arc::new(Semaphore::new(config.concurrency));
+    let stats = Arc::new(Mutex::new(LoadStats::new(start)));
+    let mut rng = fastrand::Rng::with_seed(0);
+
+    for i in 0u64.. {
+        let due = start + Duration::from_secs_f64(i as f64 / rate);
+        if due >= deadline {
+            break;
+        }
+
+        tokio::time::sleep_until(due.into()).await;
+        let permit = in_flight.clone().acquire_owned().await?;
+
+        let client = client.clone();
+        let uri = mix.next(&mut rng);
+        let stats = stats.clone();
+
+        tokio::spawn(async move {
+            let queued = due.elapsed();
+            let (ok, first_byte) = send(&client, uri).await;
+            drop(permit);
+
+            let mut stats = stats.lock();
+            stats.record(
+                due.elapsed(),
+                first_byte.map(|at| at.duration_since(due)),
+                ok,
+            );
+            stats
+                .queue_histogram
+                .saturating_record(queued.as_micros() as u64);
+        });
+    }
+
+    // Wait for the requests still in flight
+    let _ = in_flight.acquire_many(config.concurrency as u32).await?;
+
+    let mut total = LoadStats::new(start);
+    total.add(&stats.lock())?;
+    Ok(total)
+}
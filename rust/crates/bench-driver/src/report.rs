@@ -0,0 +1,42 @@
+// `bench report`: serves a static HTML page that plots result files in the browser.
+// The page fetches /results.json, which re-reads the files on every request so new
+// runs show up on reload.
+
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{Json, Router, extract::State, http::StatusCode, response::Html, routing::get};
+
+use super::{BenchResult, result::RunResult};
+
+const REPORT_HTML: &str = include_str!("report.html");
+
+async fn index() -> Html<&'static str> {
+    Html(REPORT_HTML)
+}
+
+async fn results(
+    State(files): State<Arc<Vec<PathBuf>>>,
+) -> Result<Json<Vec<RunResult>>, StatusCode> {
+    files
+        .iter()
+        .map(|path| RunResult::read(path))
+        .collect::<BenchResult<Vec<_>>>()
+        .map(Json)
+        .map_err(|err| {
+            eprintln!("Failed to read results: {:?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn serve(listen: &str, files: Vec<PathBuf>) -> BenchResult<()> {
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/results.json", get(results))
+        .with_state(Arc::new(files));
+
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!("Serving report on http://{}", listen);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
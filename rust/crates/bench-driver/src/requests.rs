@@ -0,0 +1,142 @@
+// Rust port of src/generate.ts: builds the request list k6 replays (data/requests.json)
+// from the id ranges in the database, so runs don't need Node to prepare their input.
+
+use bench_core::schema::{customers, employees, orders, products, suppliers};
+use diesel::{
+    QueryDsl,
+    dsl::{max, min},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use super::BenchResult;
+
+// Same terms, in the same order, as generate.ts
+const CUSTOMER_SEARCHES: [&str; 50] = [
+    "ve", "ey", "or", "bb", "te", "ab", "ca", "ki", "ap", "be", "ct", "hi", "er", "pr", "pi", "en",
+    "au", "ra", "ti", "ke", "ou", "ur", "me", "ea", "op", "at", "ne", "na", "os", "ri", "on", "ha",
+    "il", "to", "as", "io", "di", "zy", "az", "la", "ko", "st", "gh", "ug", "ac", "cc", "ch", "hu",
+    "re", "an",
+];
+
+const PRODUCT_SEARCHES: [&str; 50] = [
+    "ha", "ey", "or", "po", "te", "ab", "er", "ke", "ap", "be", "en", "au", "ra", "ti", "su", "sa",
+    "hi", "nu", "ge", "pi", "ou", "ur", "me", "ea", "tu", "at", "ne", "na", "os", "ri", "on", "ka",
+    "il", "to", "as", "io", "di", "za", "fa", "la", "ko", "st", "gh", "ug", "ac", "cc", "ch", "pa",
+    "re", "an",
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct IdRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl IdRange {
+    // The i-th id, cycling through the range like `ids[i % ids.length]`
+    fn nth(&self, i: usize) -> i32 {
+        let len = (self.max - self.min + 1) as usize;
+        self.min + (i % len) as i32
+    }
+}
+
+pub struct IdRanges {
+    pub customers: IdRange,
+    pub employees: IdRange,
+    pub suppliers: IdRange,
+    pub products: IdRange,
+    pub orders: IdRange,
+}
+
+fn range(table: &str, bounds: (Option<i32>, Option<i32>)) -> BenchResult<IdRange> {
+    match bounds {
+        (Some(min), Some(max)) => Ok(IdRange { min, max }),
+        _ => Err(format!("{} is empty, seed the database first", table).into()),
+    }
+}
+
+pub async fn id_ranges(conn: &mut AsyncPgConnection) -> BenchResult<IdRanges> {
+    Ok(IdRanges {
+        customers: range(
+            "customers",
+            customers::table
+                .select((min(customers::id), max(customers::id)))
+                .first(conn)
+                .await?,
+        )?,
+        employees: range(
+            "employees",
+            employees::table
+                .select((min(employees::id), max(employees::id)))
+                .first(conn)
+                .await?,
+        )?,
+        suppliers: range(
+            "suppliers",
+            suppliers::table
+                .select((min(suppliers::id), max(suppliers::id)))
+                .first(conn)
+                .await?,
+        )?,
+        products: range(
+            "products",
+            products::table
+                .select((min(products::id), max(products::id)))
+                .first(conn)
+                .await?,
+        )?,
+        orders: range(
+            "orders",
+            orders::table
+                .select((min(orders::id), max(orders::id)))
+                .first(conn)
+                .await?,
+        )?,
+    })
+}
+
+// `count` pages of `limit` rows out of roughly `total` rows, picked uniformly
+fn paginated(
+    requests: &mut Vec<String>,
+    rng: &mut fastrand::Rng,
+    path: &str,
+    count: usize,
+    limit: usize,
+    total: usize,
+) {
+    let pages = total as f64 / limit as f64;
+    for _ in 0..count {
+        let page = 1 + (pages * rng.f64()).floor() as usize;
+        let offset = page * limit - limit;
+        requests.push(format!("{}?limit={}&offset={}", path, limit, offset));
+    }
+}
+
+// Same endpoints and counts as generate.ts, shuffled
+pub fn generate(ids: &IdRanges, rng: &mut fastrand::Rng) -> Vec<String> {
+    let mut requests = Vec::with_capacity(427_000);
+
+    requests.extend((1..20_000).map(|i| format!("/customer-by-id?id={}", ids.customers.nth(i))));
+    requests
+        .extend((0..5_000).map(|i| format!("/search-customer?term={}", CUSTOMER_SEARCHES[i % 50])));
+    requests
+        .extend((0..50_000).map(|i| format!("/search-product?term={}", PRODUCT_SEARCHES[i % 50])));
+    requests.extend(
+        (0..5_000).map(|i| format!("/employee-with-recipient?id={}", ids.employees.nth(i))),
+    );
+    requests.extend((0..30_000).map(|i| format!("/supplier-by-id?id={}", ids.suppliers.nth(i))));
+    requests
+        .extend((0..100_000).map(|i| format!("/product-with-supplier?id={}", ids.products.nth(i))));
+    requests.extend((0..100_000).map(|i| format!("/order-with-details?id={}", ids.orders.nth(i))));
+    requests.extend(
+        (0..100_000).map(|i| format!("/order-with-details-and-products?id={}", ids.orders.nth(i))),
+    );
+
+    paginated(&mut requests, rng, "/customers", 2_000, 50, 10_000);
+    paginated(&mut requests, rng, "/employees", 1_000, 20, 100);
+    paginated(&mut requests, rng, "/suppliers", 1_000, 50, 10_000);
+    paginated(&mut requests, rng, "/products", 3_000, 50, 1_000);
+    paginated(&mut requests, rng, "/orders-with-details", 10_000, 50, 830);
+
+    rng.shuffle(&mut requests);
+    requests
+}
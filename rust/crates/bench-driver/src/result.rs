@@ -0,0 +1,339 @@
+use std::path::Path;
+
+use bench_core::dataset_meta::DatasetMeta;
+use chrono::{DateTime, Utc};
+use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BenchResult,
+    anomaly::{self, Anomaly},
+    calibration::Calibration,
+    client::ConnectionStats,
+};
+
+// Latencies are recorded in microseconds, up to one minute
+pub fn new_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, 60_000_000, 3).expect("valid histogram bounds")
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LatencySummary {
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p95_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+impl LatencySummary {
+    pub fn from_histogram(histogram: &Histogram<u64>) -> Self {
+        LatencySummary {
+            mean_us: histogram.mean(),
+            p50_us: histogram.value_at_quantile(0.50),
+            p90_us: histogram.value_at_quantile(0.90),
+            p95_us: histogram.value_at_quantile(0.95),
+            p99_us: histogram.value_at_quantile(0.99),
+            max_us: histogram.max(),
+        }
+    }
+}
+
+// Requests completed during one second of the run
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TimelinePoint {
+    pub second: u64,
+    pub requests: u64,
+    pub errors: u64,
+    pub mean_us: f64,
+    pub max_us: u64,
+}
+
+impl TimelinePoint {
+    pub fn record(&mut self, latency_us: u64, ok: bool) {
+        self.requests += 1;
+        if !ok {
+            self.errors += 1;
+        }
+        self.mean_us += (latency_us as f64 - self.mean_us) / self.requests as f64;
+        self.max_us = self.max_us.max(latency_us);
+    }
+
+    pub fn add(&mut self, other: &TimelinePoint) {
+        let requests = self.requests + other.requests;
+        if requests > 0 {
+            self.mean_us = (self.mean_us * self.requests as f64
+                + other.mean_us * other.requests as f64)
+                / requests as f64;
+        }
+        self.requests = requests;
+        self.errors += other.errors;
+        self.max_us = self.max_us.max(other.max_us);
+    }
+}
+
+// Adds `other` into `timeline` second by second
+pub fn add_timelines(timeline: &mut Vec<TimelinePoint>, other: &[TimelinePoint]) {
+    for point in other {
+        let index = point.second as usize;
+        if timeline.len() <= index {
+            timeline.extend((timeline.len()..=index).map(|second| TimelinePoint {
+                second: second as u64,
+                ..Default::default()
+            }));
+        }
+        timeline[index].add(point);
+    }
+}
+
+// One kept repetition of a repeated run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Repetition {
+    pub started_at: DateTime<Utc>,
+    pub requests: u64,
+    pub errors: u64,
+    pub rps: f64,
+    pub latency: LatencySummary,
+}
+
+fn keep_alive_default() -> bool {
+    true
+}
+
+// Upper median for even counts, so the value is always one that was measured
+fn median<T: Copy>(values: &mut [T], cmp: impl Fn(&T, &T) -> std::cmp::Ordering) -> T {
+    values.sort_by(cmp);
+    values[values.len() / 2]
+}
+
+// Everything one load generator run produced, written as `<folder>/<name>.json`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RunResult {
+    pub name: String,
+    pub target: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub concurrency: usize,
+    // Open-loop target rate in requests per second; absent for closed-loop runs
+    #[serde(default)]
+    pub rate: Option<f64>,
+    // false for connection storm runs, where every request used a new connection
+    #[serde(default = "keep_alive_default")]
+    pub keep_alive: bool,
+    pub requests: u64,
+    pub errors: u64,
+    pub rps: f64,
+    pub latency: LatencySummary,
+    // (latency_us, count) pairs, enough to rebuild the full histogram
+    pub histogram: Vec<(u64, u64)>,
+    // Latency to the first byte of the response body, where `latency` runs to its last; the
+    // two differ for streamed responses. Absent from results of older versions
+    #[serde(default)]
+    pub first_byte: Option<LatencySummary>,
+    #[serde(default)]
+    pub first_byte_histogram: Vec<(u64, u64)>,
+    #[serde(default)]
+    pub timeline: Vec<TimelinePoint>,
+    // Open loop only: delay between a request's scheduled and actual send
+    #[serde(default)]
+    pub queue_delay: Option<LatencySummary>,
+    #[serde(default)]
+    pub queue_histogram: Vec<(u64, u64)>,
+    pub connections: ConnectionStats,
+    // No-op server calibration and the latency with its overhead subtracted
+    #[serde(default)]
+    pub calibration: Option<Calibration>,
+    #[serde(default)]
+    pub corrected_latency: Option<LatencySummary>,
+    #[serde(default)]
+    pub anomalies: Vec<Anomaly>,
+    // `--repeat` runs: the repetitions aggregated into this result, after warmup
+    #[serde(default)]
+    pub repetitions: Vec<Repetition>,
+    #[serde(default)]
+    pub discarded_warmup: usize,
+    // The target's /debug/dataset, when it has one
+    #[serde(default)]
+    pub dataset: Option<DatasetMeta>,
+}
+
+pub fn histogram_to_buckets(histogram: &Histogram<u64>) -> Vec<(u64, u64)> {
+    histogram
+        .iter_recorded()
+        .map(|v| (v.value_iterated_to(), v.count_at_value()))
+        .collect()
+}
+
+// None when none of the results measured it
+fn first_byte_summary(histogram: &Histogram<u64>) -> Option<LatencySummary> {
+    (!histogram.is_empty()).then(|| LatencySummary::from_histogram(histogram))
+}
+
+pub fn buckets_to_histogram(buckets: &[(u64, u64)]) -> Histogram<u64> {
+    let mut histogram = new_histogram();
+    for &(value, count) in buckets {
+        histogram.saturating_record_n(value, count);
+    }
+    histogram
+}
+
+impl RunResult {
+    // Combines results of workers that ran concurrently against the same target
+    pub fn merge(name: &str, results: &[RunResult]) -> BenchResult<Self> {
+        let first = results.first().ok_or("no results to merge")?;
+
+        let mut histogram = new_histogram();
+        let mut first_byte_histogram = new_histogram();
+        let mut queue_histogram = new_histogram();
+        let mut timeline = Vec::new();
+        for result in results {
+            histogram.add(result.histogram())?;
+            first_byte_histogram.add(buckets_to_histogram(&result.first_byte_histogram))?;
+            queue_histogram.add(buckets_to_histogram(&result.queue_histogram))?;
+            add_timelines(&mut timeline, &result.timeline);
+        }
+        let rate = results.iter().map(|r| r.rate).sum::<Option<f64>>();
+
+        let requests = results.iter().map(|r| r.requests).sum();
+        let duration_secs = results.iter().map(|r| r.duration_secs).fold(0.0, f64::max);
+        let started_at = results
+            .iter()
+            .map(|r| r.started_at)
+            .min()
+            .unwrap_or(first.started_at);
+
+        Ok(RunResult {
+            name: name.to_string(),
+            target: first.target.clone(),
+            started_at,
+            duration_secs,
+            concurrency: results.iter().map(|r| r.concurrency).sum(),
+            rate,
+            keep_alive: first.keep_alive,
+            requests,
+            errors: results.iter().map(|r| r.errors).sum(),
+            rps: results.iter().map(|r| r.rps).sum(),
+            latency: LatencySummary::from_histogram(&histogram),
+            histogram: histogram_to_buckets(&histogram),
+            first_byte: first_byte_summary(&first_byte_histogram),
+            first_byte_histogram: histogram_to_buckets(&first_byte_histogram),
+            anomalies: anomaly::detect(&timeline, started_at, duration_secs),
+            timeline,
+            queue_delay: rate.map(|_| LatencySummary::from_histogram(&queue_histogram)),
+            queue_histogram: histogram_to_buckets(&queue_histogram),
+            connections: ConnectionStats::combine(results.iter().map(|r| &r.connections), requests),
+            // Workers don't calibrate; the coordinator calibrates on its own host
+            calibration: None,
+            dataset: first.dataset.clone(),
+            corrected_latency: None,
+            repetitions: Vec::new(),
+            discarded_warmup: 0,
+        })
+    }
+
+    // Combines repetitions of the same run: throughput and latency percentiles are the
+    // medians across repetitions, counts and duration are totals, the histogram holds
+    // every kept request, and the timeline is the one of the median-throughput repetition
+    pub fn aggregate(results: Vec<RunResult>, discarded_warmup: usize) -> BenchResult<Self> {
+        if results.is_empty() {
+            return Err("no repetitions to aggregate".into());
+        }
+
+        let mut histogram = new_histogram();
+        let mut first_byte_histogram = new_histogram();
+        let mut queue_histogram = new_histogram();
+        for result in &results {
+            histogram.add(result.histogram())?;
+            first_byte_histogram.add(buckets_to_histogram(&result.first_byte_histogram))?;
+            queue_histogram.add(buckets_to_histogram(&result.queue_histogram))?;
+        }
+
+        let repetitions: Vec<Repetition> = results
+            .iter()
+            .map(|r| Repetition {
+                started_at: r.started_at,
+                requests: r.requests,
+                errors: r.errors,
+                rps: r.rps,
+                latency: r.latency.clone(),
+            })
+            .collect();
+        let median_of = |metric: fn(&Repetition) -> u64| {
+            median(
+                &mut repetitions.iter().map(metric).collect::<Vec<_>>(),
+                u64::cmp,
+            )
+        };
+        let latency = LatencySummary {
+            mean_us: median(
+                &mut repetitions
+                    .iter()
+                    .map(|r| r.latency.mean_us)
+                    .collect::<Vec<_>>(),
+                f64::total_cmp,
+            ),
+            p50_us: median_of(|r| r.latency.p50_us),
+            p90_us: median_of(|r| r.latency.p90_us),
+            p95_us: median_of(|r| r.latency.p95_us),
+            p99_us: median_of(|r| r.latency.p99_us),
+            max_us: median_of(|r| r.latency.max_us),
+        };
+        let rps = median(
+            &mut repetitions.iter().map(|r| r.rps).collect::<Vec<_>>(),
+            f64::total_cmp,
+        );
+
+        let duration_secs = results.iter().map(|r| r.duration_secs).sum();
+        let anomalies = results.iter().flat_map(|r| r.anomalies.clone()).collect();
+        let typical = results
+            .into_iter()
+            .min_by(|a, b| (a.rps - rps).abs().total_cmp(&(b.rps - rps).abs()))
+            .expect("at least one repetition");
+
+        Ok(RunResult {
+            duration_secs,
+            requests: repetitions.iter().map(|r| r.requests).sum(),
+            errors: repetitions.iter().map(|r| r.errors).sum(),
+            rps,
+            latency,
+            histogram: histogram_to_buckets(&histogram),
+            first_byte: first_byte_summary(&first_byte_histogram),
+            first_byte_histogram: histogram_to_buckets(&first_byte_histogram),
+            queue_delay: typical
+                .rate
+                .map(|_| LatencySummary::from_histogram(&queue_histogram)),
+            queue_histogram: histogram_to_buckets(&queue_histogram),
+            anomalies,
+            repetitions,
+            discarded_warmup,
+            ..typical
+        })
+    }
+
+    pub fn apply_calibration(&mut self, calibration: Calibration) {
+        let mut corrected = new_histogram();
+        for &(value, count) in &self.histogram {
+            corrected.saturating_record_n(value.saturating_sub(calibration.overhead_us), count);
+        }
+
+        self.corrected_latency = Some(LatencySummary::from_histogram(&corrected));
+        self.calibration = Some(calibration);
+    }
+
+    pub fn histogram(&self) -> Histogram<u64> {
+        buckets_to_histogram(&self.histogram)
+    }
+
+    pub fn read(path: &Path) -> BenchResult<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> BenchResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
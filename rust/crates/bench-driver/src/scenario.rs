@@ -0,0 +1,160 @@
+// Workload description for the load generator: a weighted mix of request groups plus an
+// optional think time between a worker's requests.
+//
+// Scenario files are JSON, e.g.
+//
+//     {
+//       "mix": [
+//         { "name": "by-id", "weight": 70, "prefixes": ["/customer-by-id", "/product-with-supplier"] },
+//         { "name": "lists", "weight": 20, "prefixes": ["/customers", "/products"] },
+//         { "name": "search", "weight": 10, "paths": ["/search-product?term=ha"] }
+//       ],
+//       "think_time": { "distribution": "exponential", "mean_ms": 100 }
+//     }
+//
+// `prefixes` select paths from the request list (--requests), `paths` lists them directly.
+
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use hyper::Uri;
+use serde::{Deserialize, Serialize};
+
+use super::{BenchResult, client::request_uri};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scenario {
+    pub mix: Vec<MixEntry>,
+    #[serde(default)]
+    pub think_time: Option<ThinkTime>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MixEntry {
+    pub name: String,
+    pub weight: f64,
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum ThinkTime {
+    Constant { ms: f64 },
+    Uniform { min_ms: f64, max_ms: f64 },
+    Exponential { mean_ms: f64 },
+}
+
+impl ThinkTime {
+    pub fn sample(&self, rng: &mut fastrand::Rng) -> Duration {
+        let ms = match *self {
+            ThinkTime::Constant { ms } => ms,
+            ThinkTime::Uniform { min_ms, max_ms } => min_ms + rng.f64() * (max_ms - min_ms),
+            ThinkTime::Exponential { mean_ms } => -mean_ms * (1.0 - rng.f64()).ln(),
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+impl Scenario {
+    // The default workload: walk the whole request list in order, no think time
+    pub fn sequential(paths: Vec<String>) -> Self {
+        Scenario {
+            mix: vec![MixEntry {
+                name: "all".to_string(),
+                weight: 1.0,
+                prefixes: Vec::new(),
+                paths,
+            }],
+            think_time: None,
+        }
+    }
+
+    // Loads a scenario file and resolves its `prefixes` against `requests`
+    pub fn load(path: &Path, requests: &[String]) -> BenchResult<Self> {
+        let mut scenario: Scenario = serde_json::from_slice(&std::fs::read(path)?)?;
+
+        if scenario.mix.is_empty() {
+            return Err(format!("{} has an empty mix", path.display()).into());
+        }
+
+        for entry in &mut scenario.mix {
+            let matching = requests.iter().filter(|request| {
+                entry
+                    .prefixes
+                    .iter()
+                    .any(|p| request.starts_with(p.as_str()))
+            });
+            entry.paths.extend(matching.cloned());
+            entry.prefixes.clear();
+
+            if entry.paths.is_empty() || entry.weight <= 0.0 {
+                return Err(format!(
+                    "mix entry `{}` needs a positive weight and at least one path",
+                    entry.name
+                )
+                .into());
+            }
+        }
+
+        Ok(scenario)
+    }
+}
+
+struct MixGroup {
+    uris: Vec<Uri>,
+    next: AtomicUsize,
+}
+
+// A scenario resolved against a target: picks groups by weight and walks each group's
+// paths in order
+pub struct RequestMix {
+    groups: Vec<MixGroup>,
+    cumulative_weights: Vec<f64>,
+}
+
+impl RequestMix {
+    pub fn new(scenario: &Scenario, target: &str) -> BenchResult<Self> {
+        let mut groups = Vec::with_capacity(scenario.mix.len());
+        let mut cumulative_weights = Vec::with_capacity(scenario.mix.len());
+        let mut total = 0.0;
+
+        for entry in &scenario.mix {
+            let uris = entry
+                .paths
+                .iter()
+                .map(|path| request_uri(target, path))
+                .collect::<BenchResult<Vec<_>>>()?;
+
+            total += entry.weight;
+            cumulative_weights.push(total);
+            groups.push(MixGroup {
+                uris,
+                next: AtomicUsize::new(0),
+            });
+        }
+
+        Ok(RequestMix {
+            groups,
+            cumulative_weights,
+        })
+    }
+
+    pub fn next(&self, rng: &mut fastrand::Rng) -> Uri {
+        let group = if self.groups.len() == 1 {
+            &self.groups[0]
+        } else {
+            let total = self.cumulative_weights[self.cumulative_weights.len() - 1];
+            let point = rng.f64() * total;
+            let index = self.cumulative_weights.partition_point(|&w| w <= point);
+            &self.groups[index.min(self.groups.len() - 1)]
+        };
+
+        group.uris[group.next.fetch_add(1, Ordering::Relaxed) % group.uris.len()].clone()
+    }
+}
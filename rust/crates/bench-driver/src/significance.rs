@@ -0,0 +1,212 @@
+// Statistical comparison of two runs' latency distributions, so a difference is only
+// reported as a win when it is unlikely to be noise. Samples come from the recorded
+// histograms, i.e. latencies at histogram precision (3 significant digits).
+
+use serde::Serialize;
+
+use super::result::RunResult;
+
+const ALPHA: f64 = 0.05;
+const BOOTSTRAP_RESAMPLES: usize = 500;
+// Resamples larger runs down to this many samples, which widens the interval
+// (conservative) but keeps the bootstrap fast on million-request runs
+const BOOTSTRAP_SAMPLE_CAP: u64 = 20_000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MannWhitney {
+    pub u: f64,
+    pub z: f64,
+    pub p_value: f64,
+    // P(candidate latency > baseline latency), ties counted half; 0.5 means no difference
+    pub effect_size: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BootstrapInterval {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Comparison {
+    pub baseline: String,
+    pub candidate: String,
+    pub mann_whitney: MannWhitney,
+    // Candidate median minus baseline median, in microseconds, with a 95% interval
+    pub median_difference_us: BootstrapInterval,
+    pub significant: bool,
+}
+
+// Complementary error function (Abramowitz & Stegun 7.1.26, |error| < 1.5e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let result = poly * (-z * z).exp();
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+fn total(buckets: &[(u64, u64)]) -> u64 {
+    buckets.iter().map(|&(_, count)| count).sum()
+}
+
+// Mann-Whitney U test (normal approximation with tie correction) on two histograms,
+// `candidate` against `baseline`
+pub fn mann_whitney(baseline: &[(u64, u64)], candidate: &[(u64, u64)]) -> MannWhitney {
+    let n_base = total(baseline) as f64;
+    let n_cand = total(candidate) as f64;
+    let n = n_base + n_cand;
+
+    // Walk both (value-sorted) histograms together, assigning average ranks to ties
+    let (mut i, mut j) = (0, 0);
+    let mut rank = 0.0;
+    let mut rank_sum_cand = 0.0;
+    let mut tie_term = 0.0;
+
+    while i < baseline.len() || j < candidate.len() {
+        let value = match (baseline.get(i), candidate.get(j)) {
+            (Some(&(a, _)), Some(&(b, _))) => a.min(b),
+            (Some(&(a, _)), None) => a,
+            (None, Some(&(b, _))) => b,
+            (None, None) => unreachable!(),
+        };
+
+        let mut base_count = 0.0;
+        if let Some(&(v, count)) = baseline.get(i)
+            && v == value
+        {
+            base_count = count as f64;
+            i += 1;
+        }
+        let mut cand_count = 0.0;
+        if let Some(&(v, count)) = candidate.get(j)
+            && v == value
+        {
+            cand_count = count as f64;
+            j += 1;
+        }
+
+        let ties = base_count + cand_count;
+        let average_rank = rank + (ties + 1.0) / 2.0;
+        rank_sum_cand += cand_count * average_rank;
+        tie_term += ties.powi(3) - ties;
+        rank += ties;
+    }
+
+    let u = rank_sum_cand - n_cand * (n_cand + 1.0) / 2.0;
+    let mean = n_base * n_cand / 2.0;
+    let variance = n_base * n_cand / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)).max(1.0));
+
+    let z = if variance > 0.0 {
+        (u - mean) / variance.sqrt()
+    } else {
+        0.0
+    };
+
+    MannWhitney {
+        u,
+        z,
+        p_value: erfc(z.abs() / std::f64::consts::SQRT_2),
+        effect_size: if n_base * n_cand > 0.0 {
+            u / (n_base * n_cand)
+        } else {
+            0.5
+        },
+    }
+}
+
+// Draws `size` samples from a histogram (inverse CDF) and returns their median
+fn resampled_median(values: &[u64], cumulative: &[u64], size: u64, rng: &mut fastrand::Rng) -> f64 {
+    let total = cumulative[cumulative.len() - 1];
+    let mut samples: Vec<u64> = (0..size)
+        .map(|_| {
+            let draw = rng.u64(0..total);
+            values[cumulative.partition_point(|&c| c <= draw)]
+        })
+        .collect();
+    samples.sort_unstable();
+    samples[samples.len() / 2] as f64
+}
+
+fn median(buckets: &[(u64, u64)]) -> f64 {
+    let half = total(buckets) / 2;
+    let mut seen = 0;
+    for &(value, count) in buckets {
+        seen += count;
+        if seen > half {
+            return value as f64;
+        }
+    }
+    0.0
+}
+
+// Percentile bootstrap interval for `median(candidate) - median(baseline)`
+pub fn bootstrap_median_difference(
+    baseline: &[(u64, u64)],
+    candidate: &[(u64, u64)],
+    rng: &mut fastrand::Rng,
+) -> BootstrapInterval {
+    let estimate = median(candidate) - median(baseline);
+    if baseline.is_empty() || candidate.is_empty() {
+        return BootstrapInterval {
+            estimate,
+            low: estimate,
+            high: estimate,
+        };
+    }
+
+    let prepare = |buckets: &[(u64, u64)]| {
+        let values: Vec<u64> = buckets.iter().map(|&(value, _)| value).collect();
+        let cumulative: Vec<u64> = buckets
+            .iter()
+            .scan(0, |sum, &(_, count)| {
+                *sum += count;
+                Some(*sum)
+            })
+            .collect();
+        let size = total(buckets).min(BOOTSTRAP_SAMPLE_CAP);
+        (values, cumulative, size)
+    };
+    let (base_values, base_cumulative, base_size) = prepare(baseline);
+    let (cand_values, cand_cumulative, cand_size) = prepare(candidate);
+
+    let mut differences: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            resampled_median(&cand_values, &cand_cumulative, cand_size, rng)
+                - resampled_median(&base_values, &base_cumulative, base_size, rng)
+        })
+        .collect();
+    differences.sort_by(f64::total_cmp);
+
+    let at = |q: f64| differences[((differences.len() - 1) as f64 * q).round() as usize];
+
+    BootstrapInterval {
+        estimate,
+        low: at(ALPHA / 2.0),
+        high: at(1.0 - ALPHA / 2.0),
+    }
+}
+
+// A difference is significant when the U test rejects at ALPHA and the bootstrap
+// interval for the median difference excludes zero
+pub fn compare(baseline: &RunResult, candidate: &RunResult) -> Comparison {
+    let mut rng = fastrand::Rng::with_seed(0);
+
+    let mann_whitney = mann_whitney(&baseline.histogram, &candidate.histogram);
+    let median_difference_us =
+        bootstrap_median_difference(&baseline.histogram, &candidate.histogram, &mut rng);
+
+    let significant = mann_whitney.p_value < ALPHA
+        && (median_difference_us.low > 0.0 || median_difference_us.high < 0.0);
+
+    Comparison {
+        baseline: baseline.name.clone(),
+        candidate: candidate.name.clone(),
+        mann_whitney,
+        median_difference_us,
+        significant,
+    }
+}
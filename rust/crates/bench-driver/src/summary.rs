@@ -0,0 +1,165 @@
+// `bench summarize`: Markdown comparison tables in the format of the README results.
+
+use std::fmt::Write;
+
+use super::{result::RunResult, significance};
+
+// z for a two-sided 95% interval
+const Z_95: f64 = 1.96;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    // The interval is mean ± half_width
+    pub half_width: f64,
+}
+
+impl ConfidenceInterval {
+    fn from_moments(mean: f64, stdev: f64, samples: f64) -> Self {
+        let half_width = if samples > 1.0 {
+            Z_95 * stdev / samples.sqrt()
+        } else {
+            0.0
+        };
+        ConfidenceInterval { mean, half_width }
+    }
+}
+
+// Throughput CI over the run's complete one-second intervals
+pub fn throughput_ci(result: &RunResult) -> ConfidenceInterval {
+    let complete = result.duration_secs.floor() as u64;
+    let per_second: Vec<f64> = result
+        .timeline
+        .iter()
+        .filter(|point| point.second < complete)
+        .map(|point| point.requests as f64)
+        .collect();
+
+    if per_second.is_empty() {
+        return ConfidenceInterval {
+            mean: result.rps,
+            half_width: 0.0,
+        };
+    }
+
+    let n = per_second.len() as f64;
+    let mean = per_second.iter().sum::<f64>() / n;
+    let variance = per_second.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+
+    ConfidenceInterval::from_moments(mean, variance.sqrt(), n)
+}
+
+// CI of the mean latency in microseconds, from the recorded histogram
+pub fn mean_latency_ci(result: &RunResult) -> ConfidenceInterval {
+    let histogram = result.histogram();
+    ConfidenceInterval::from_moments(histogram.mean(), histogram.stdev(), histogram.len() as f64)
+}
+
+fn ms(us: f64) -> String {
+    format!("{:.2}", us / 1000.0)
+}
+
+fn ratio(value: f64, baseline: f64) -> String {
+    if baseline == 0.0 {
+        "-".to_string()
+    } else {
+        format!("{:.2}x", value / baseline)
+    }
+}
+
+// Renders throughput and latency tables; speedups are relative to `results[baseline]`
+pub fn markdown(results: &[RunResult], baseline: usize) -> String {
+    let mut out = String::new();
+    let Some(base) = results.get(baseline) else {
+        return out;
+    };
+    let base_rps = throughput_ci(base).mean;
+
+    writeln!(out, "### Throughput\n").unwrap();
+    writeln!(
+        out,
+        "| Server | req/s | 95% CI | errors | vs {} |",
+        base.name
+    )
+    .unwrap();
+    writeln!(out, "|---|---:|---:|---:|---:|").unwrap();
+    for result in results {
+        let ci = throughput_ci(result);
+        writeln!(
+            out,
+            "| {}{} | {:.0} | ±{:.0} | {} | {} |",
+            result.name,
+            if result.anomalies.is_empty() {
+                ""
+            } else {
+                " ⚠"
+            },
+            ci.mean,
+            ci.half_width,
+            result.errors,
+            ratio(ci.mean, base_rps)
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\n### Latency (ms)\n").unwrap();
+    writeln!(
+        out,
+        "| Server | p50 | p95 | p99 | max | mean | 95% CI | p50 speedup vs {} | significant |",
+        base.name
+    )
+    .unwrap();
+    writeln!(out, "|---|---:|---:|---:|---:|---:|---:|---:|---|").unwrap();
+    for (index, result) in results.iter().enumerate() {
+        let ci = mean_latency_ci(result);
+        let significant = if index == baseline {
+            "-".to_string()
+        } else {
+            let comparison = significance::compare(base, result);
+            format!(
+                "{} (p={:.3})",
+                if comparison.significant { "yes" } else { "no" },
+                comparison.mann_whitney.p_value
+            )
+        };
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | ±{} | {} | {} |",
+            result.name,
+            ms(result.latency.p50_us as f64),
+            ms(result.latency.p95_us as f64),
+            ms(result.latency.p99_us as f64),
+            ms(result.latency.max_us as f64),
+            ms(ci.mean),
+            ms(ci.half_width),
+            ratio(base.latency.p50_us as f64, result.latency.p50_us as f64),
+            significant
+        )
+        .unwrap();
+    }
+
+    // Anomalous runs are listed rather than silently averaged in
+    let anomalous: Vec<&RunResult> = results.iter().filter(|r| !r.anomalies.is_empty()).collect();
+    if !anomalous.is_empty() {
+        writeln!(out, "\n### Anomalies\n").unwrap();
+        writeln!(out, "| Server | kind | at | second | value | baseline |").unwrap();
+        writeln!(out, "|---|---|---|---:|---:|---:|").unwrap();
+        for result in anomalous {
+            for anomaly in &result.anomalies {
+                writeln!(
+                    out,
+                    "| {} | {:?} | {} | {} | {:.2} | {:.2} |",
+                    result.name,
+                    anomaly.kind,
+                    anomaly.at.format("%H:%M:%S"),
+                    anomaly.second,
+                    anomaly.value,
+                    anomaly.baseline
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    out
+}
@@ -0,0 +1,54 @@
+// Traces recorded by `rust proxy`: one JSON line per request that passed through it, with
+// the full request and response and when they happened. `run --requests` accepts a trace in
+// place of a request list and replays its GET requests in recorded order.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::BenchResult;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceRecord {
+    // When the request arrived, since the proxy started
+    pub start_ms: f64,
+    pub method: String,
+    // Path and query string
+    pub path: String,
+    pub request_headers: BTreeMap<String, String>,
+    // Bodies are recorded as text, lossily for any that aren't UTF-8; None when empty
+    pub request_body: Option<String>,
+    // 502 when the upstream couldn't be reached, with `error` set
+    pub status: u16,
+    pub response_headers: BTreeMap<String, String>,
+    pub response_body: Option<String>,
+    // Until the upstream's response headers, and until the end of its body
+    pub first_byte_ms: f64,
+    pub duration_ms: f64,
+    pub error: Option<String>,
+}
+
+// Tells a trace from a request list by its first character
+pub fn is_trace(contents: &[u8]) -> bool {
+    contents.iter().find(|c| !c.is_ascii_whitespace()) == Some(&b'{')
+}
+
+pub fn parse_trace(contents: &[u8]) -> BenchResult<Vec<TraceRecord>> {
+    let mut records = Vec::new();
+    for line in contents.split(|c| *c == b'\n') {
+        if !line.trim_ascii().is_empty() {
+            records.push(serde_json::from_slice(line)?);
+        }
+    }
+    Ok(records)
+}
+
+// The paths of a trace's GET requests, as a request list
+pub fn trace_paths(records: &[TraceRecord]) -> Vec<String> {
+    records
+        .iter()
+        .filter(|record| record.method == "GET")
+        .map(|record| record.path.clone())
+        .collect()
+}
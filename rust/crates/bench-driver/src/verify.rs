@@ -0,0 +1,188 @@
+// Stability checks of the list endpoints before a benchmark: every page is requested
+// several times and has to come back identical, ordered by id, without rows repeating
+// across pages. Catches queries whose ORDER BY doesn't determine the order, which would
+// make servers' responses (and cache hit rates) differ between runs. The full-text
+// searches have no ORDER BY by design; only their rows are compared.
+
+use serde_json::Value;
+
+use super::{
+    BenchResult,
+    client::{HttpClient, get, request_uri},
+    fixtures::normalize,
+};
+
+// Defaults of `bench verify`, also used by `bench run --verify`
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const DEFAULT_PAGES: usize = 3;
+pub const DEFAULT_RUNS: usize = 2;
+
+struct ListEndpoint {
+    path: &'static str,
+    // Takes limit/offset and orders by id
+    paginated: bool,
+}
+
+// Search terms as in the fixtures
+const LIST_ENDPOINTS: &[ListEndpoint] = &[
+    ListEndpoint {
+        path: "/customers",
+        paginated: true,
+    },
+    ListEndpoint {
+        path: "/employees",
+        paginated: true,
+    },
+    ListEndpoint {
+        path: "/suppliers",
+        paginated: true,
+    },
+    ListEndpoint {
+        path: "/products",
+        paginated: true,
+    },
+    ListEndpoint {
+        path: "/orders-with-details",
+        paginated: true,
+    },
+    ListEndpoint {
+        path: "/search-customer?term=ve",
+        paginated: false,
+    },
+    ListEndpoint {
+        path: "/search-product?term=ha",
+        paginated: false,
+    },
+];
+
+pub struct VerifyConfig {
+    pub target: String,
+    pub limit: i64,
+    // Consecutive pages checked from offset 0
+    pub pages: usize,
+    // Requests per page, at least 2
+    pub runs: usize,
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    Status(u16),
+    NotAnArray,
+    // Same rows, different order
+    OrderChanged,
+    ContentChanged,
+    NotAscending,
+    // A page's first id isn't after the previous page's last
+    PagesOverlap,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub path: String,
+    pub problem: Problem,
+}
+
+fn ids(rows: &[Value]) -> Vec<Option<i64>> {
+    rows.iter()
+        .map(|row| row.get("id").and_then(Value::as_i64))
+        .collect()
+}
+
+// Fetches `path` `runs` times; the first response, or the problem with them
+async fn fetch_stable(
+    client: &HttpClient,
+    config: &VerifyConfig,
+    path: &str,
+    ordered: bool,
+) -> BenchResult<Result<Vec<Value>, Problem>> {
+    let mut first: Option<Value> = None;
+
+    for _ in 0..config.runs.max(2) {
+        let (status, body) = get(client, request_uri(&config.target, path)?).await?;
+        if !status.is_success() {
+            return Ok(Err(Problem::Status(status.as_u16())));
+        }
+        let value = normalize(serde_json::from_slice(&body)?, false);
+
+        match &first {
+            None => first = Some(value),
+            Some(first) if *first == value => {}
+            Some(first) => {
+                if normalize(first.clone(), true) != normalize(value, true) {
+                    return Ok(Err(Problem::ContentChanged));
+                }
+                if ordered {
+                    return Ok(Err(Problem::OrderChanged));
+                }
+            }
+        }
+    }
+
+    match first {
+        Some(Value::Array(rows)) => Ok(Ok(rows)),
+        _ => Ok(Err(Problem::NotAnArray)),
+    }
+}
+
+// Runs the checks against `config.target`; no findings means the endpoints are stable
+pub async fn verify(client: &HttpClient, config: &VerifyConfig) -> BenchResult<Vec<Finding>> {
+    let mut findings = Vec::new();
+
+    for endpoint in LIST_ENDPOINTS {
+        if !endpoint.paginated {
+            if let Err(problem) = fetch_stable(client, config, endpoint.path, false).await? {
+                findings.push(Finding {
+                    path: endpoint.path.to_string(),
+                    problem,
+                });
+            }
+            continue;
+        }
+
+        let mut previous_last: Option<i64> = None;
+        for page in 0..config.pages {
+            let path = format!(
+                "{}?limit={}&offset={}",
+                endpoint.path,
+                config.limit,
+                page as i64 * config.limit
+            );
+            let rows = match fetch_stable(client, config, &path, true).await? {
+                Ok(rows) => rows,
+                Err(problem) => {
+                    findings.push(Finding { path, problem });
+                    break;
+                }
+            };
+
+            let ids = ids(&rows);
+            let ascending = ids
+                .windows(2)
+                .all(|pair| matches!(pair, [Some(a), Some(b)] if a < b));
+            if !ascending || ids.iter().any(Option::is_none) {
+                findings.push(Finding {
+                    path,
+                    problem: Problem::NotAscending,
+                });
+                break;
+            }
+            if let (Some(last), Some(Some(first))) = (previous_last, ids.first())
+                && *first <= last
+            {
+                findings.push(Finding {
+                    path,
+                    problem: Problem::PagesOverlap,
+                });
+                break;
+            }
+
+            match ids.last() {
+                Some(last) => previous_last = *last,
+                // Past the end of the table
+                None => break,
+            }
+        }
+    }
+
+    Ok(findings)
+}
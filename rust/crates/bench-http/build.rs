@@ -0,0 +1,15 @@
+// Generates the gRPC service of the `grpc` feature from proto/bench.proto, with the vendored
+// protoc so building it needs no system install
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc");
+        // SAFETY: the build script is single-threaded
+        unsafe { std::env::set_var("PROTOC", protoc) };
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/bench.proto"], &["proto"])
+            .expect("failed to compile proto/bench.proto");
+    }
+}
@@ -0,0 +1,231 @@
+// Experimental adaptive concurrency limit in front of the query routes, in the spirit of
+// Netflix's concurrency-limits: the limit is tuned from observed latency instead of being
+// fixed, and every change is kept as a time series for the saturation studies.
+//
+//   ADAPTIVE_LIMIT          `aimd` or `vegas` (unset disables the limiter)
+//   ADAPTIVE_LIMIT_INITIAL  starting limit (default 20)
+//   ADAPTIVE_LIMIT_MAX      upper bound (default 128, the pool size)
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+const MIN_LIMIT: f64 = 1.0;
+// AIMD: a request slower than this multiple of the minimum latency counts as a drop
+const AIMD_TOLERANCE: f64 = 2.0;
+const AIMD_BACKOFF: f64 = 0.9;
+// The minimum latency is re-measured every this many samples, so a faster period early
+// on (e.g. an empty cache) doesn't pin it forever
+const MIN_RTT_RESET_SAMPLES: u64 = 10_000;
+// Decisions kept for `/stats/adaptive-limit`; the oldest are discarded beyond this
+const MAX_DECISIONS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    Aimd,
+    Vegas,
+}
+
+// One change of the (integer) limit
+#[derive(Clone, Serialize)]
+pub struct LimitDecision {
+    pub at_ms: u64,
+    pub limit: usize,
+    pub in_flight: usize,
+    pub rtt_us: u64,
+    pub min_rtt_us: u64,
+}
+
+#[derive(Serialize)]
+pub struct LimiterStats {
+    pub algorithm: Algorithm,
+    pub limit: usize,
+    pub in_flight: usize,
+    pub max_limit: usize,
+    pub decisions: Vec<LimitDecision>,
+}
+
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+    min_rtt: Option<Duration>,
+    samples: u64,
+    decisions: VecDeque<LimitDecision>,
+}
+
+pub struct AdaptiveLimiter {
+    algorithm: Algorithm,
+    max_limit: f64,
+    started: Instant,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(algorithm: Algorithm, initial: usize, max: usize) -> Self {
+        AdaptiveLimiter {
+            algorithm,
+            max_limit: max as f64,
+            started: Instant::now(),
+            state: Mutex::new(LimiterState {
+                limit: initial.clamp(1, max) as f64,
+                in_flight: 0,
+                min_rtt: None,
+                samples: 0,
+                decisions: VecDeque::new(),
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let algorithm = match std::env::var("ADAPTIVE_LIMIT").ok()?.as_str() {
+            "aimd" => Algorithm::Aimd,
+            "vegas" => Algorithm::Vegas,
+            other => {
+                eprintln!("Unknown ADAPTIVE_LIMIT {}, limiter disabled", other);
+                return None;
+            }
+        };
+        let number = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+
+        Some(AdaptiveLimiter::new(
+            algorithm,
+            number("ADAPTIVE_LIMIT_INITIAL", 20),
+            number("ADAPTIVE_LIMIT_MAX", 128),
+        ))
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let state = self.state.lock();
+        LimiterStats {
+            algorithm: self.algorithm,
+            limit: state.limit as usize,
+            in_flight: state.in_flight,
+            max_limit: self.max_limit as usize,
+            decisions: state.decisions.iter().cloned().collect(),
+        }
+    }
+
+    async fn acquire(&self) -> Permit<'_> {
+        loop {
+            // Registered before the check so a release in between isn't missed
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock();
+                if (state.in_flight as f64) < state.limit.floor() {
+                    state.in_flight += 1;
+                    return Permit {
+                        limiter: self,
+                        started: Instant::now(),
+                        completed: false,
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+
+    // `rtt` is None for requests cancelled before completing, which aren't a sample
+    fn release(&self, rtt: Option<Duration>) {
+        let mut state = self.state.lock();
+        state.in_flight -= 1;
+        let Some(rtt) = rtt else {
+            drop(state);
+            self.released.notify_waiters();
+            return;
+        };
+
+        state.samples += 1;
+        if state.samples.is_multiple_of(MIN_RTT_RESET_SAMPLES) {
+            state.min_rtt = None;
+        }
+        let min_rtt = state.min_rtt.map_or(rtt, |min| min.min(rtt));
+        state.min_rtt = Some(min_rtt);
+
+        let limit = state.limit;
+        let next = match self.algorithm {
+            Algorithm::Aimd => {
+                if rtt.as_secs_f64() > min_rtt.as_secs_f64() * AIMD_TOLERANCE {
+                    limit * AIMD_BACKOFF
+                } else if state.in_flight as f64 * 2.0 >= limit {
+                    // Only grow while the limit is actually being used
+                    limit + 1.0 / limit
+                } else {
+                    limit
+                }
+            }
+            Algorithm::Vegas => {
+                // Estimated requests queued beyond what the no-load latency allows
+                let queue = limit * (1.0 - min_rtt.as_secs_f64() / rtt.as_secs_f64().max(1e-9));
+                let log = limit.log10().max(1.0);
+                if queue <= 3.0 * log {
+                    limit + log / limit
+                } else if queue >= 6.0 * log {
+                    limit - log / limit
+                } else {
+                    limit
+                }
+            }
+        };
+        state.limit = next.clamp(MIN_LIMIT, self.max_limit);
+
+        if state.limit as usize != limit as usize {
+            let decision = LimitDecision {
+                at_ms: self.started.elapsed().as_millis() as u64,
+                limit: state.limit as usize,
+                in_flight: state.in_flight,
+                rtt_us: rtt.as_micros() as u64,
+                min_rtt_us: min_rtt.as_micros() as u64,
+            };
+            if state.decisions.len() == MAX_DECISIONS {
+                state.decisions.pop_front();
+            }
+            state.decisions.push_back(decision);
+        }
+        drop(state);
+
+        self.released.notify_waiters();
+    }
+}
+
+struct Permit<'a> {
+    limiter: &'a AdaptiveLimiter,
+    started: Instant,
+    completed: bool,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limiter
+            .release(self.completed.then(|| self.started.elapsed()));
+    }
+}
+
+pub async fn limit(
+    State(limiter): State<Arc<AdaptiveLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut permit = limiter.acquire().await;
+    let response = next.run(request).await;
+    permit.completed = true;
+    response
+}
@@ -0,0 +1,49 @@
+// The server's global allocator, picked at build time so allocator comparisons can be part
+// of the benchmark matrix:
+//
+//   alloc-mimalloc  mimalloc (the default feature)
+//   alloc-jemalloc  jemalloc, through tikv-jemallocator
+//   alloc-system    the platform's malloc
+//
+// The default gives way to the other two, so `--features alloc-jemalloc` needs no
+// `--no-default-features`; jemalloc and the system allocator together are an error. The
+// binary wraps it in `heap::CountingAlloc`, and `/stats/system` names it.
+
+#[cfg(all(feature = "alloc-jemalloc", feature = "alloc-system"))]
+compile_error!("the `alloc-jemalloc` and `alloc-system` features are mutually exclusive");
+
+#[cfg(not(any(
+    feature = "alloc-mimalloc",
+    feature = "alloc-jemalloc",
+    feature = "alloc-system"
+)))]
+compile_error!(
+    "one of the `alloc-mimalloc`, `alloc-jemalloc` and `alloc-system` features is needed"
+);
+
+#[cfg(feature = "alloc-jemalloc")]
+mod selected {
+    pub type Allocator = tikv_jemallocator::Jemalloc;
+    pub const ALLOCATOR: Allocator = tikv_jemallocator::Jemalloc;
+    pub const NAME: &str = "jemalloc";
+}
+
+#[cfg(all(feature = "alloc-system", not(feature = "alloc-jemalloc")))]
+mod selected {
+    pub type Allocator = std::alloc::System;
+    pub const ALLOCATOR: Allocator = std::alloc::System;
+    pub const NAME: &str = "system";
+}
+
+#[cfg(all(
+    feature = "alloc-mimalloc",
+    not(feature = "alloc-jemalloc"),
+    not(feature = "alloc-system")
+))]
+mod selected {
+    pub type Allocator = mimalloc::MiMalloc;
+    pub const ALLOCATOR: Allocator = mimalloc::MiMalloc;
+    pub const NAME: &str = "mimalloc";
+}
+
+pub use selected::{ALLOCATOR, Allocator, NAME};
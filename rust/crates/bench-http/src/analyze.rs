@@ -0,0 +1,103 @@
+// `POST /admin/analyze`: VACUUM (ANALYZE) of the request's dataset, so orchestration can put
+// the planner statistics and visibility maps of compared servers on an equal footing before
+// each run. It runs in the background on a connection of its own rather than one of the
+// pool's, one at a time: a POST while one runs gets a 409, and the caller polls
+// `GET /admin/analyze` until `running` is false.
+//
+// `?cost_delay_ms=N` throttles it with Postgres' cost-based vacuum delay, for running it
+// next to live traffic.
+
+use std::{sync::Arc, time::Instant};
+
+use bench_core::BenchResult;
+use chrono::{DateTime, Utc};
+use diesel_async::{AsyncConnection, AsyncPgConnection, SimpleAsyncConnection};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Default)]
+pub struct AnalyzeParams {
+    cost_delay_ms: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeRun {
+    pub dataset: Option<String>,
+    pub started_at: DateTime<Utc>,
+    // None while running
+    pub duration_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AnalyzeStatus {
+    pub running: bool,
+    // The running one, or else the last to finish
+    pub last: Option<AnalyzeRun>,
+}
+
+#[derive(Default)]
+pub struct Analyzer {
+    state: Mutex<AnalyzeStatus>,
+}
+
+impl Analyzer {
+    pub fn status(&self) -> AnalyzeStatus {
+        let state = self.state.lock();
+        AnalyzeStatus {
+            running: state.running,
+            last: state.last.clone(),
+        }
+    }
+
+    // Starts a run against `database_url` unless one is running; false if one is
+    pub fn start(
+        self: &Arc<Self>,
+        database_url: String,
+        dataset: Option<String>,
+        params: AnalyzeParams,
+    ) -> bool {
+        {
+            let mut state = self.state.lock();
+            if state.running {
+                return false;
+            }
+            state.running = true;
+            state.last = Some(AnalyzeRun {
+                dataset,
+                started_at: Utc::now(),
+                duration_ms: None,
+                error: None,
+            });
+        }
+
+        let analyzer = self.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = vacuum_analyze(&database_url, params.cost_delay_ms).await;
+            if let Err(err) = &result {
+                eprintln!("VACUUM (ANALYZE) failed: {:?}", err);
+            }
+
+            let mut state = analyzer.state.lock();
+            state.running = false;
+            if let Some(last) = &mut state.last {
+                last.duration_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+                last.error = result.err().map(|err| err.to_string());
+            }
+        });
+        true
+    }
+}
+
+async fn vacuum_analyze(database_url: &str, cost_delay_ms: Option<u32>) -> BenchResult<()> {
+    let mut conn = AsyncPgConnection::establish(database_url).await?;
+    if let Some(delay) = cost_delay_ms {
+        conn.batch_execute(&format!("SET vacuum_cost_delay = {}", delay))
+            .await?;
+    }
+    // Through the simple query protocol on its own, as VACUUM can't run in a transaction
+    conn.batch_execute("VACUUM (ANALYZE)").await?;
+    Ok(())
+}
@@ -0,0 +1,1771 @@
+// The axum application: shared state, the handlers and the router with the optional layers
+// the state enables. Used by the HTTP server in main.rs and the Lambda adapter, and usable
+// with `tower::ServiceExt::oneshot` to call handlers without a listener.
+
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use axum::{
+    Json, Router, async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{HeaderMap, StatusCode, Uri, header, request::Parts},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+};
+use bench_core::{
+    BenchResult, DbPool,
+    backend::{self, QueryBackend},
+    config::PoolConfig,
+    database_url,
+    dataset_meta::{DatasetMeta, dataset_meta},
+    models::*,
+    queries::*,
+    query_catalog::{QueryDefinition, query_definitions},
+    reports::*,
+};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use sysinfo::System;
+
+use crate::{
+    adaptive::{self, AdaptiveLimiter, LimiterStats},
+    analyze::{AnalyzeParams, AnalyzeStatus, Analyzer},
+    backend_diff, backend_routes, batch,
+    build_info::{BuildInfo, build_info},
+    cache::{self, CacheStats, ResponseCache, WarmReport, WarmRequest},
+    capture::{CapturedQuery, QueryCapture},
+    cpu_time::{self, CpuAccounting, RouteCpu},
+    datasets::{DATASET_HEADER, Datasets},
+    encoding::Format,
+    error::{self, failed},
+    etag::{self, Etags},
+    heap::{self, HeapDump, HeapProfiler},
+    hot_set::{self, HotSet, HotSetStats},
+    id_filter::{self, IdFilterStats, IdFilters},
+    indexes::{self, IndexState},
+    inflight::{self, InFlightBytes, InFlightStats},
+    metrics::{self, RequestMetrics, SizeSummary},
+    mirror::{self, Mirror, MirrorStats},
+    ndjson::{self, RowFormat},
+    orders_stream,
+    pagination::{self, CursorPage, PaginationLinks},
+    params::{
+        self, Autocomplete, BatchQuery, Cursor, CustomersWithoutOrders, Id, OrderBody,
+        OrdersStream, Pagination, ParamError, Search, SearchBody, SupplierProducts, Top,
+    },
+    pg_stats::{
+        PgLocks, PgSystemStats, StatementCacheStats, connection_statements, pg_locks,
+        pg_system_stats,
+    },
+    readiness, request_log,
+    route_limits::{self, RouteLimitStats, RouteLimits},
+    snapshots::{self, SNAPSHOT_HEADER, SnapshotToken, Snapshots},
+    stats::{IoCounters, SystemStats, system_stats},
+    telemetry,
+};
+
+pub struct AppState {
+    pool: DbPool,
+    pool_config: PoolConfig,
+    sys: Mutex<System>,
+    cpu_warmed_up: Mutex<bool>,
+    io: Mutex<IoCounters>,
+    capture: Option<QueryCapture>,
+    inflight: Option<Arc<InFlightBytes>>,
+    adaptive_limiter: Option<Arc<AdaptiveLimiter>>,
+    route_limits: Option<Arc<RouteLimits>>,
+    cpu_accounting: Option<Arc<CpuAccounting>>,
+    heap_profiler: Option<Arc<HeapProfiler>>,
+    datasets: Option<Datasets>,
+    response_cache: Option<Arc<ResponseCache>>,
+    id_filters: Option<Arc<IdFilters>>,
+    hot_set: Option<Arc<HotSet>>,
+    request_metrics: Option<Arc<RequestMetrics>>,
+    // REQUEST_LOG
+    request_log: bool,
+    // OTEL_EXPORTER_OTLP_ENDPOINT, with the `otel` feature
+    telemetry: bool,
+    etags: Option<Etags>,
+    mirror: Option<Arc<Mirror>>,
+    snapshots: Option<Arc<Snapshots>>,
+    pagination_links: Option<PaginationLinks>,
+    // Of the search routes, unless a request names another
+    search_dictionary: SearchDictionary,
+    analyzer: Arc<Analyzer>,
+    // QUERY_BACKEND's, diffed against DIFF_BACKEND's when that's set; None serves the
+    // regular handlers
+    query_backend: Option<Arc<dyn QueryBackend>>,
+}
+
+impl AppState {
+    // State for `pool` with every optional feature configured from the environment; the
+    // other pools opened here are sized by `pool_config` as well. An error for a query
+    // backend that's unknown or fails to set up.
+    pub async fn from_env(pool: DbPool, pool_config: PoolConfig) -> BenchResult<Self> {
+        let query_backend = match backend::from_env(&pool, &pool_config)? {
+            Some(primary) => Some(backend_diff::from_env(primary, &pool, &pool_config)?),
+            None => None,
+        };
+        let id_filters = match IdFilters::from_env(&pool).await {
+            Some(Ok(filters)) => Some(Arc::new(filters)),
+            Some(Err(err)) => {
+                eprintln!(
+                    "Failed to build the id filters, continuing without: {:?}",
+                    err
+                );
+                None
+            }
+            None => None,
+        };
+        let snapshots = Snapshots::from_env(pool.clone()).map(Arc::new);
+
+        Ok(AppState {
+            pool,
+            pool_config,
+            sys: Mutex::new(System::new_all()),
+            cpu_warmed_up: Mutex::new(false),
+            io: Mutex::new(IoCounters::new()),
+            capture: QueryCapture::from_env(),
+            inflight: InFlightBytes::from_env().map(Arc::new),
+            adaptive_limiter: AdaptiveLimiter::from_env().map(Arc::new),
+            route_limits: RouteLimits::from_env().map(Arc::new),
+            cpu_accounting: CpuAccounting::from_env().map(Arc::new),
+            heap_profiler: HeapProfiler::from_env().map(Arc::new),
+            datasets: Datasets::from_env(&pool_config).await,
+            response_cache: ResponseCache::from_env().map(Arc::new),
+            id_filters,
+            hot_set: HotSet::from_env().map(Arc::new),
+            request_metrics: RequestMetrics::from_env().map(Arc::new),
+            request_log: request_log::init(),
+            telemetry: telemetry::init(),
+            etags: Etags::from_env(),
+            mirror: Mirror::from_env().map(Arc::new),
+            snapshots,
+            pagination_links: PaginationLinks::from_env(),
+            search_dictionary: SearchDictionary::from_env(),
+            analyzer: Arc::default(),
+            query_backend,
+        })
+    }
+
+    // Hot set refreshes and snapshot expiry, for the life of the process
+    pub fn spawn_background_tasks(&self) {
+        if let Some(hot_set) = self.hot_set.clone() {
+            hot_set.spawn_refresh(self.pool.clone());
+        }
+        if let Some(snapshots) = self.snapshots.clone() {
+            snapshots.spawn_expiry();
+        }
+    }
+
+    fn capture(&self, query: impl FnOnce() -> CapturedQuery) {
+        if let Some(capture) = &self.capture {
+            capture.record(query());
+        }
+    }
+}
+
+// Pool of the database named by the X-Dataset header, DATABASE_URL's without one; 400 for
+// a dataset missing from DATASETS
+struct Dataset(DbPool);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Dataset {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(name) = parts.headers.get(DATASET_HEADER) else {
+            return Ok(Dataset(state.pool.clone()));
+        };
+
+        name.to_str()
+            .ok()
+            .and_then(|name| state.datasets.as_ref()?.get(name))
+            .map(|pool| Dataset(pool.clone()))
+            .ok_or(StatusCode::BAD_REQUEST)
+    }
+}
+
+// Token of the X-Snapshot header; 400 for a token that isn't held (unknown, expired or
+// snapshots disabled) and together with X-Dataset, as snapshots are of the default database
+struct Snapshot(Option<String>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Snapshot {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(token) = parts.headers.get(SNAPSHOT_HEADER) else {
+            return Ok(Snapshot(None));
+        };
+        if parts.headers.contains_key(DATASET_HEADER) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        token
+            .to_str()
+            .ok()
+            .filter(|token| {
+                state
+                    .snapshots
+                    .as_ref()
+                    .is_some_and(|snapshots| snapshots.is_held(token))
+            })
+            .map(|token| Snapshot(Some(token.to_string())))
+            .ok_or(StatusCode::BAD_REQUEST)
+    }
+}
+
+// Refreshes CPU readings; the first call primes sysinfo, which needs two samples
+fn refresh_cpu(state: &AppState) -> parking_lot::MutexGuard<'_, System> {
+    let needs_warmup = {
+        let mut warmed = state.cpu_warmed_up.lock();
+        if !*warmed {
+            *warmed = true;
+            true
+        } else {
+            false
+        }
+    };
+
+    if needs_warmup {
+        {
+            let mut sys = state.sys.lock();
+            sys.refresh_cpu_all();
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    let mut sys = state.sys.lock();
+    sys.refresh_cpu_all();
+    sys
+}
+
+async fn stats_handler(State(state): State<Arc<AppState>>) -> Result<Json<Vec<i32>>, StatusCode> {
+    let state = state.clone();
+
+    let res = tokio::task::spawn_blocking(move || {
+        let sys = refresh_cpu(&state);
+
+        sys.cpus()
+            .iter()
+            .map(|cpu| cpu.cpu_usage().round() as i32)
+            .collect()
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(res))
+}
+
+// `/stats` stays a plain array for bench/cpu-usage.ts; this adds memory, container limits,
+// hardware state and I/O since the previous call
+async fn system_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SystemStats>, StatusCode> {
+    let state = state.clone();
+
+    let res = tokio::task::spawn_blocking(move || {
+        let mut sys = refresh_cpu(&state);
+        sys.refresh_memory();
+        let io = state.io.lock().sample();
+        system_stats(&sys, io)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(res))
+}
+
+// 404 unless MAX_INFLIGHT_RESPONSE_BYTES is set
+async fn inflight_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<InFlightStats>, StatusCode> {
+    let inflight = state.inflight.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(inflight.stats()))
+}
+
+// 404 unless ADAPTIVE_LIMIT is set
+async fn adaptive_limit_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<LimiterStats>, StatusCode> {
+    let limiter = state
+        .adaptive_limiter
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(limiter.stats()))
+}
+
+// 404 unless ROUTE_CONCURRENCY is set
+async fn route_limits_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<RouteLimitStats>>, StatusCode> {
+    let limits = state.route_limits.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(limits.stats()))
+}
+
+async fn build_info_handler() -> Json<BuildInfo> {
+    Json(build_info())
+}
+
+// 404 unless CPU_ACCOUNTING is set
+async fn cpu_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<RouteCpu>>, StatusCode> {
+    let accounting = state.cpu_accounting.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(accounting.stats()))
+}
+
+#[derive(Deserialize)]
+struct HeapProfiling {
+    enabled: bool,
+}
+
+// Starts (resetting the counters) or stops heap profiling; 404 unless HEAP_PROFILER is set
+async fn heap_profiling_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<HeapProfiling>,
+) -> Result<Json<HeapDump>, StatusCode> {
+    let profiler = state.heap_profiler.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    profiler.set_enabled(body.enabled);
+    Ok(Json(profiler.dump()))
+}
+
+async fn heap_dump_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let profiler = state.heap_profiler.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"heap.json\"",
+        )],
+        Json(profiler.dump()),
+    ))
+}
+
+// 404 unless RESPONSE_CACHE_MAX_BYTES is set
+async fn cache_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<CacheStats>, StatusCode> {
+    let cache = state.response_cache.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(cache.stats()))
+}
+
+// 404 unless ID_BLOOM_FILTER is set
+async fn id_filter_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<IdFilterStats>, StatusCode> {
+    let filters = state.id_filters.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(filters.stats()))
+}
+
+// 404 unless HOT_SET is set
+async fn hot_set_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<HotSetStats>, StatusCode> {
+    let hot_set = state.hot_set.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(hot_set.stats()))
+}
+
+// 404 unless REQUEST_METRICS is set
+async fn response_sizes_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BTreeMap<String, SizeSummary>>, StatusCode> {
+    let metrics = state
+        .request_metrics
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(metrics.response_sizes()))
+}
+
+async fn mirror_stats_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<MirrorStats>, StatusCode> {
+    let mirror = state.mirror.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(mirror.stats()))
+}
+
+// Request (REQUEST_METRICS), pool and response cache metrics in Prometheus text format
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut out = String::new();
+    if let Some(requests) = &state.request_metrics {
+        requests.write_prometheus(&mut out);
+    }
+    let datasets = state.datasets.iter().flat_map(Datasets::iter);
+    metrics::write_pool_prometheus(
+        std::iter::once(("default", &state.pool)).chain(datasets),
+        &mut out,
+    );
+    if let Some(cache) = &state.response_cache {
+        cache.stats().write_prometheus(&mut out);
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+// Exports a snapshot for X-Snapshot; 404 unless SNAPSHOTS is set, 429 when SNAPSHOT_MAX
+// snapshots are held
+async fn create_snapshot_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<SnapshotToken>), StatusCode> {
+    let snapshots = state.snapshots.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let token = snapshots.create().await.map_err(|e| {
+        eprintln!("Failed to export a snapshot: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    token
+        .map(|token| (StatusCode::CREATED, Json(token)))
+        .ok_or(StatusCode::TOO_MANY_REQUESTS)
+}
+
+async fn release_snapshot_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> StatusCode {
+    match &state.snapshots {
+        Some(snapshots) if snapshots.release(&token) => StatusCode::NO_CONTENT,
+        _ => StatusCode::NOT_FOUND,
+    }
+}
+
+// Pre-populates the response cache; 404 unless RESPONSE_CACHE_MAX_BYTES is set
+async fn warm_cache_handler(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<WarmRequest>,
+) -> Result<Json<WarmReport>, StatusCode> {
+    let cache = state.response_cache.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let paths = body.paths().map_err(|e| {
+        eprintln!("Invalid warm-cache request: {:?}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(cache.warm(paths, body.dataset).await))
+}
+
+// Starts a VACUUM (ANALYZE) of the X-Dataset database (DATABASE_URL's without one): 202, or
+// 409 while one is running
+async fn analyze_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> StatusCode {
+    let Ok(params) = serde_urlencoded::from_str::<AnalyzeParams>(uri.query().unwrap_or("")) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let dataset = match headers.get(DATASET_HEADER).map(|name| name.to_str()) {
+        None => None,
+        Some(Ok(name)) => Some(name.to_string()),
+        Some(Err(_)) => return StatusCode::BAD_REQUEST,
+    };
+    let database_url = match &dataset {
+        None => database_url(),
+        Some(name) => match state.datasets.as_ref().and_then(|d| d.url(name)) {
+            Some(url) => url.to_string(),
+            None => return StatusCode::BAD_REQUEST,
+        },
+    };
+
+    if state.analyzer.start(database_url, dataset, params) {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::CONFLICT
+    }
+}
+
+async fn analyze_status_handler(State(state): State<Arc<AppState>>) -> Json<AnalyzeStatus> {
+    Json(state.analyzer.status())
+}
+
+async fn indexes_handler(Dataset(pool): Dataset) -> Result<Json<Vec<IndexState>>, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        indexes::index_states(&mut conn).await.map_err(|e| {
+            eprintln!("Error in index_states: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    Ok(Json(result))
+}
+
+// 404 for an index that can't be toggled
+async fn drop_index_handler(
+    Dataset(pool): Dataset,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let index = indexes::find(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+    indexes::drop_index(&mut conn, index).await.map_err(|e| {
+        eprintln!("Error in drop_index: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn create_index_handler(
+    Dataset(pool): Dataset,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let index = indexes::find(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+    indexes::create_index(&mut conn, index).await.map_err(|e| {
+        eprintln!("Error in create_index: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn pg_system_handler(Dataset(pool): Dataset) -> Result<Json<PgSystemStats>, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        pg_system_stats(&mut conn).await.map_err(|e| {
+            eprintln!("Error in pg_system_stats: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    Ok(Json(result))
+}
+
+// Statement cache sizes of the pool's idle connections, all checked out together so each is
+// counted once; connections busy with requests are left out
+async fn statements_handler(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+) -> Result<Json<StatementCacheStats>, StatusCode> {
+    let mut conns = Vec::new();
+    for _ in 0..pool.state().idle_connections {
+        conns.push(telemetry::checkout(&pool).await.map_err(failed)?);
+    }
+
+    let mut connections = Vec::with_capacity(conns.len());
+    for conn in &mut conns {
+        connections.push(connection_statements(conn).await.map_err(|e| {
+            eprintln!("Error in connection_statements: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?);
+    }
+
+    Ok(Json(StatementCacheStats {
+        cache: state.pool_config.statement_cache,
+        total: connections.iter().map(|c| c.statements).sum(),
+        connections,
+    }))
+}
+
+async fn queries_handler() -> Json<Vec<QueryDefinition>> {
+    Json(query_definitions())
+}
+
+// What the seeder recorded about the request's dataset, 404 for data it didn't load
+async fn dataset_handler(Dataset(pool): Dataset) -> Result<Json<DatasetMeta>, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        dataset_meta(&mut conn).await.map_err(|e| {
+            eprintln!("Error in dataset_meta: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    result.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn pg_locks_handler(Dataset(pool): Dataset) -> Result<Json<PgLocks>, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        pg_locks(&mut conn).await.map_err(|e| {
+            eprintln!("Error in pg_locks: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    Ok(Json(result))
+}
+
+async fn get_customers(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    headers: HeaderMap,
+    format: Format,
+    params: Pagination,
+) -> Result<Response, StatusCode> {
+    let Pagination { limit, offset, .. } = params;
+
+    state.capture(|| CapturedQuery::P1 { limit, offset });
+
+    // Snapshot reads stay buffered: the stream would have to hold their transaction open
+    let rows_format = ndjson::requested(&headers, params.format.as_deref());
+    if let (None, Some(rows_format)) = (&snapshot, rows_format) {
+        return ndjson::stream(pool, rows_format, move |conn| {
+            p1_stream(conn, limit, offset).scope_boxed()
+        })
+        .await;
+    }
+
+    let links = state.pagination_links;
+    let (result, total) = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            async move {
+                let rows = telemetry::query("p1", p1(conn, limit, offset)).await?;
+                let total = pagination::count_if(links, count_customers(conn)).await?;
+                Ok((rows, total))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    let response = match rows_format {
+        Some(RowFormat::Csv) => ndjson::csv_response(&result),
+        _ => format.respond(&result),
+    };
+    Ok(pagination::with_links(
+        response,
+        links,
+        "/customers",
+        limit,
+        offset,
+        total,
+    ))
+}
+
+async fn get_customers_without_orders(
+    Dataset(pool): Dataset,
+    format: Format,
+    params: CustomersWithoutOrders,
+) -> Result<Response, StatusCode> {
+    let CustomersWithoutOrders {
+        strategy,
+        limit,
+        offset,
+    } = params;
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        customers_without_orders(&mut conn, strategy, limit, offset)
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_customer_by_id(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    state.capture(|| CapturedQuery::P2 { id });
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        telemetry::query("p2", p2(&mut conn, id))
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_customer_orders(
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        customer_orders(&mut conn, id).await.map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+// Drops the cached by-id response of `path` for `id` in the request's dataset
+fn invalidate_cached(state: &AppState, headers: &HeaderMap, path: &str, id: i32) {
+    if let Some(cache) = &state.response_cache {
+        let dataset = headers
+            .get(DATASET_HEADER)
+            .and_then(|value| value.to_str().ok());
+        cache.invalidate(dataset, path, id);
+    }
+}
+
+async fn create_customer(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    headers: HeaderMap,
+    Json(customer): Json<NewCustomer>,
+) -> Result<(StatusCode, Json<Customer>), StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        insert_customer(&mut conn, &customer)
+            .await
+            .map_err(failed)?
+    };
+
+    if let Some(filters) = &state.id_filters {
+        filters.customers.insert(result.id);
+    }
+    invalidate_cached(&state, &headers, "/customer-by-id", result.id);
+
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+async fn update_customer_by_id(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    headers: HeaderMap,
+    Id(id): Id,
+    Json(customer): Json<NewCustomer>,
+) -> Result<Json<Customer>, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        update_customer(&mut conn, id, &customer)
+            .await
+            .map_err(failed)?
+    };
+    invalidate_cached(&state, &headers, "/customer-by-id", id);
+
+    result.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+// 204, 404 for an unknown id, and 409 for a customer who still has orders
+async fn delete_customer_by_id(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    headers: HeaderMap,
+    Id(id): Id,
+) -> Result<StatusCode, StatusCode> {
+    let deleted = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        match delete_customer(&mut conn, id).await {
+            Ok(deleted) => deleted,
+            Err(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+                return Err(StatusCode::CONFLICT);
+            }
+            Err(err) => return Err(failed(err)),
+        }
+    };
+    invalidate_cached(&state, &headers, "/customer-by-id", id);
+
+    Ok(if deleted {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    })
+}
+
+async fn search_customer(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    search: Search,
+) -> Result<Response, StatusCode> {
+    let Search {
+        term,
+        dictionary,
+        highlight,
+        rank,
+    } = search;
+    let dictionary = dictionary.unwrap_or(state.search_dictionary);
+
+    state.capture(|| CapturedQuery::P3 {
+        term: term.clone(),
+        dictionary,
+        highlight,
+        rank,
+    });
+
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+    let response = if highlight {
+        let result = p3_highlighted(&mut conn, &term, dictionary, rank)
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    } else if rank {
+        let result = telemetry::query("p3_ranked", p3_ranked(&mut conn, &term, dictionary))
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    } else {
+        let result = telemetry::query("p3", p3(&mut conn, &term, dictionary))
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    };
+
+    Ok(params::with_dictionary(response, dictionary))
+}
+
+async fn get_employees(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    format: Format,
+    params: Pagination,
+) -> Result<Response, StatusCode> {
+    let Pagination { limit, offset, .. } = params;
+
+    state.capture(|| CapturedQuery::P4 { limit, offset });
+
+    let links = state.pagination_links;
+    let (result, total) = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            async move {
+                let rows = telemetry::query("p4", p4(conn, limit, offset)).await?;
+                let total = pagination::count_if(links, count_employees(conn)).await?;
+                Ok((rows, total))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    Ok(pagination::with_links(
+        format.respond(&result),
+        links,
+        "/employees",
+        limit,
+        offset,
+        total,
+    ))
+}
+
+async fn get_employee_with_recipient(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    state.capture(|| CapturedQuery::P5 { id });
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        telemetry::query("p5", p5(&mut conn, id))
+            .await
+            .map_err(|e| {
+                eprintln!("Error in p5: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_suppliers(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    format: Format,
+    params: Pagination,
+) -> Result<Response, StatusCode> {
+    let Pagination { limit, offset, .. } = params;
+
+    state.capture(|| CapturedQuery::P6 { limit, offset });
+
+    let links = state.pagination_links;
+    let (result, total) = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            async move {
+                let rows = telemetry::query("p6", p6(conn, limit, offset)).await?;
+                let total = pagination::count_if(links, count_suppliers(conn)).await?;
+                Ok((rows, total))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    Ok(pagination::with_links(
+        format.respond(&result),
+        links,
+        "/suppliers",
+        limit,
+        offset,
+        total,
+    ))
+}
+
+async fn get_supplier_by_id(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    state.capture(|| CapturedQuery::P7 { id });
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        telemetry::query("p7", p7(&mut conn, id))
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+// Empty for a supplier without products, or with none at all
+async fn get_supplier_products(
+    Dataset(pool): Dataset,
+    format: Format,
+    Path(supplier_id): Path<i32>,
+    params: SupplierProducts,
+) -> Result<Response, StatusCode> {
+    let SupplierProducts {
+        sort,
+        limit,
+        offset,
+    } = params;
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        supplier_products(&mut conn, supplier_id, &sort, limit, offset)
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_products(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    headers: HeaderMap,
+    format: Format,
+    params: Pagination,
+) -> Result<Response, StatusCode> {
+    let Pagination { limit, offset, .. } = params;
+
+    state.capture(|| CapturedQuery::P8 { limit, offset });
+
+    // Snapshot reads stay buffered: the stream would have to hold their transaction open
+    let rows_format = ndjson::requested(&headers, params.format.as_deref());
+    if let (None, Some(rows_format)) = (&snapshot, rows_format) {
+        return ndjson::stream(pool, rows_format, move |conn| {
+            p8_stream(conn, limit, offset).scope_boxed()
+        })
+        .await;
+    }
+
+    let links = state.pagination_links;
+    let (result, total) = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            async move {
+                let rows = telemetry::query("p8", p8(conn, limit, offset)).await?;
+                let total = pagination::count_if(links, count_products(conn)).await?;
+                Ok((rows, total))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    let response = match rows_format {
+        Some(RowFormat::Csv) => ndjson::csv_response(&result),
+        _ => format.respond(&result),
+    };
+    Ok(pagination::with_links(
+        response,
+        links,
+        "/products",
+        limit,
+        offset,
+        total,
+    ))
+}
+
+async fn get_products_above_average_price(
+    Dataset(pool): Dataset,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        products_above_average_price(&mut conn, limit, offset)
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_product_with_supplier(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    state.capture(|| CapturedQuery::P9 { id });
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        telemetry::query("p9", p9(&mut conn, id))
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn search_product(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    search: Search,
+) -> Result<Response, StatusCode> {
+    let Search {
+        term,
+        dictionary,
+        highlight,
+        rank,
+    } = search;
+    let dictionary = dictionary.unwrap_or(state.search_dictionary);
+
+    state.capture(|| CapturedQuery::P10 {
+        term: term.clone(),
+        dictionary,
+        highlight,
+        rank,
+    });
+
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+    let response = if highlight {
+        let result = p10_highlighted(&mut conn, &term, dictionary, rank)
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    } else if rank {
+        let result = telemetry::query("p10_ranked", p10_ranked(&mut conn, &term, dictionary))
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    } else {
+        let result = telemetry::query("p10", p10(&mut conn, &term, dictionary))
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    };
+
+    Ok(params::with_dictionary(response, dictionary))
+}
+
+// p10's matches and their facets, both on one connection
+async fn search_product_faceted(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    search: Search,
+) -> Result<Response, StatusCode> {
+    let Search {
+        term,
+        dictionary,
+        highlight,
+        rank,
+    } = search;
+    let dictionary = dictionary.unwrap_or(state.search_dictionary);
+
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+    let facets = p10_facets(&mut conn, &term, dictionary)
+        .await
+        .map_err(failed)?;
+    let response = if highlight {
+        let matches = p10_highlighted(&mut conn, &term, dictionary, rank)
+            .await
+            .map_err(failed)?;
+        format.respond(&FacetedSearch { matches, facets })
+    } else if rank {
+        let matches = telemetry::query("p10_ranked", p10_ranked(&mut conn, &term, dictionary))
+            .await
+            .map_err(failed)?;
+        format.respond(&FacetedSearch { matches, facets })
+    } else {
+        let matches = telemetry::query("p10", p10(&mut conn, &term, dictionary))
+            .await
+            .map_err(failed)?;
+        format.respond(&FacetedSearch { matches, facets })
+    };
+
+    Ok(params::with_dictionary(response, dictionary))
+}
+
+async fn autocomplete_products(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Autocomplete { q, limit }: Autocomplete,
+) -> Result<Response, StatusCode> {
+    let dictionary = state.search_dictionary;
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        product_suggestions(&mut conn, &q, dictionary, limit)
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(params::with_dictionary(format.respond(&result), dictionary))
+}
+
+async fn search_customer_body(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Json(body): Json<SearchBody<CustomerFilters>>,
+) -> Result<Response, StatusCode> {
+    let limit = body.limit().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let dictionary = body.dictionary.unwrap_or(state.search_dictionary);
+
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+    let response = if body.highlight {
+        let result =
+            p3_filtered_highlighted(&mut conn, &body.term, dictionary, &body.filters, limit)
+                .await
+                .map_err(failed)?;
+        format.respond(&result)
+    } else {
+        let result = p3_filtered(&mut conn, &body.term, dictionary, &body.filters, limit)
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    };
+
+    Ok(params::with_dictionary(response, dictionary))
+}
+
+async fn search_product_body(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Json(body): Json<SearchBody<ProductFilters>>,
+) -> Result<Response, StatusCode> {
+    let limit = body.limit().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let dictionary = body.dictionary.unwrap_or(state.search_dictionary);
+
+    let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+    let response = if body.highlight {
+        let result =
+            p10_filtered_highlighted(&mut conn, &body.term, dictionary, &body.filters, limit)
+                .await
+                .map_err(failed)?;
+        format.respond(&result)
+    } else {
+        let result = p10_filtered(&mut conn, &body.term, dictionary, &body.filters, limit)
+            .await
+            .map_err(failed)?;
+        format.respond(&result)
+    };
+
+    Ok(params::with_dictionary(response, dictionary))
+}
+
+async fn shipping_status_report(
+    Dataset(pool): Dataset,
+    format: Format,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        shipping_status(&mut conn).await.map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+// 201 with the order and its details; 409 when a line's product lacks the stock and 422 for
+// an unknown customer or employee, both without writing anything
+async fn create_order(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    headers: HeaderMap,
+    Json(body): Json<OrderBody>,
+) -> Result<(StatusCode, Json<PlacedOrder>), Response> {
+    body.check().map_err(IntoResponse::into_response)?;
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool)
+            .await
+            .map_err(|err| failed(err).into_response())?;
+
+        p14_create_order(&mut conn, &body.order, &body.details).await
+    };
+
+    match result {
+        Ok(placed) => {
+            if let Some(filters) = &state.id_filters {
+                filters.orders.insert(placed.order.id);
+            }
+            // The products' stock went down, and the order may have been cached as `null`
+            for line in &body.details {
+                invalidate_cached(&state, &headers, "/product-with-supplier", line.product_id);
+            }
+            for path in ["/order-with-details", "/order-with-details-and-products"] {
+                invalidate_cached(&state, &headers, path, placed.order.id);
+            }
+            Ok((StatusCode::CREATED, Json(placed)))
+        }
+        Err(PlaceOrderError::OutOfStock {
+            product_id,
+            quantity,
+        }) => Err((
+            StatusCode::CONFLICT,
+            format!("product {} doesn't have {} in stock", product_id, quantity),
+        )
+            .into_response()),
+        Err(PlaceOrderError::Query(DieselError::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation,
+            _,
+        ))) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unknown customer or employee",
+        )
+            .into_response()),
+        Err(PlaceOrderError::Query(err)) => {
+            eprintln!("Failed to place order: {:?}", err);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+async fn order_value_percentiles_report(
+    Dataset(pool): Dataset,
+    format: Format,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        order_value_percentiles(&mut conn).await.map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn repeat_customers_report(
+    Dataset(pool): Dataset,
+    format: Format,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        repeat_customers(&mut conn).await.map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_top_products(
+    Dataset(pool): Dataset,
+    format: Format,
+    Top { count }: Top,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        top_products(&mut conn, count).await.map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_employee_leaderboard(
+    Dataset(pool): Dataset,
+    format: Format,
+    Top { count }: Top,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        employee_leaderboard(&mut conn, count)
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_sales_by_country(
+    Dataset(pool): Dataset,
+    format: Format,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        sales_by_country(&mut conn).await.map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_orders_with_details(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    headers: HeaderMap,
+    format: Format,
+    params: Pagination,
+) -> Result<Response, StatusCode> {
+    let Pagination { limit, offset, .. } = params;
+
+    state.capture(|| CapturedQuery::P11 { limit, offset });
+
+    // Snapshot reads stay buffered: the stream would have to hold their transaction open
+    let rows_format = ndjson::requested(&headers, params.format.as_deref());
+    if let (None, Some(rows_format)) = (&snapshot, rows_format) {
+        return ndjson::stream(pool, rows_format, move |conn| {
+            p11_stream(conn, limit, offset).scope_boxed()
+        })
+        .await;
+    }
+
+    let links = state.pagination_links;
+    let (result, total) = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            async move {
+                let rows = telemetry::query("p11", p11(conn, limit, offset)).await?;
+                let total = pagination::count_if(links, count_orders(conn)).await?;
+                Ok((rows, total))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    let response = match rows_format {
+        Some(RowFormat::Csv) => ndjson::csv_response(&result),
+        _ => format.respond(&result),
+    };
+    Ok(pagination::with_links(
+        response,
+        links,
+        "/orders-with-details",
+        limit,
+        offset,
+        total,
+    ))
+}
+
+async fn get_order_with_details(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    state.capture(|| CapturedQuery::P12 { id });
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        telemetry::query("p12", p12(&mut conn, id))
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_order_with_details_and_products(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    state.capture(|| CapturedQuery::P13 { id });
+
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        telemetry::query("p13", p13(&mut conn, id))
+            .await
+            .map_err(failed)?
+    };
+
+    Ok(format.respond(&result))
+}
+
+async fn get_customers_cursor(
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    format: Format,
+    Cursor { cursor, limit }: Cursor,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            p1_cursor(conn, cursor, limit).scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    Ok(format.respond(&CursorPage::new(result, limit, |row| row.id)))
+}
+
+async fn get_products_cursor(
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    format: Format,
+    Cursor { cursor, limit }: Cursor,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            p8_cursor(conn, cursor, limit).scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    Ok(format.respond(&CursorPage::new(result, limit, |row| row.id)))
+}
+
+async fn get_orders_cursor(
+    Dataset(pool): Dataset,
+    Snapshot(snapshot): Snapshot,
+    format: Format,
+    Cursor { cursor, limit }: Cursor,
+) -> Result<Response, StatusCode> {
+    let result = {
+        let mut conn = telemetry::checkout(&pool).await.map_err(failed)?;
+
+        snapshots::run_in(&mut conn, snapshot.as_deref(), |conn| {
+            p11_cursor(conn, cursor, limit).scope_boxed()
+        })
+        .await
+        .map_err(failed)?
+    };
+
+    Ok(format.respond(&CursorPage::new(result, limit, |row| row.id)))
+}
+
+async fn batch_handler(
+    State(state): State<Arc<AppState>>,
+    Dataset(pool): Dataset,
+    format: Format,
+    Json(queries): Json<Vec<BatchQuery>>,
+) -> Result<Response, ParamError> {
+    params::check_batch(&queries)?;
+    let results = batch::run(&pool, state.search_dictionary, queries).await;
+    Ok(format.respond(&results))
+}
+
+async fn get_orders_stream(Dataset(pool): Dataset, params: OrdersStream) -> Response {
+    orders_stream::stream(pool, params)
+}
+
+// Shared by the HTTP server and the Lambda adapter, with the optional layers the state
+// enables
+pub fn build_router(state: Arc<AppState>) -> Router {
+    let mut queries = Router::new()
+        .route("/customers", get(get_customers).post(create_customer))
+        .route("/customers-cursor", get(get_customers_cursor))
+        .route(
+            "/customers/without-orders",
+            get(get_customers_without_orders),
+        )
+        .route("/customer-orders", get(get_customer_orders))
+        .route(
+            "/customer-by-id",
+            get(get_customer_by_id)
+                .put(update_customer_by_id)
+                .delete(delete_customer_by_id),
+        )
+        .route(
+            "/search-customer",
+            get(search_customer).post(search_customer_body),
+        )
+        .route("/employees", get(get_employees))
+        .route("/employee-with-recipient", get(get_employee_with_recipient))
+        .route("/suppliers", get(get_suppliers))
+        .route("/supplier-by-id", get(get_supplier_by_id))
+        .route("/suppliers/:id/products", get(get_supplier_products))
+        .route("/products", get(get_products))
+        .route("/products-cursor", get(get_products_cursor))
+        .route(
+            "/products/above-average-price",
+            get(get_products_above_average_price),
+        )
+        .route("/product-with-supplier", get(get_product_with_supplier))
+        .route("/top-products", get(get_top_products))
+        .route("/employee-leaderboard", get(get_employee_leaderboard))
+        .route("/sales-by-country", get(get_sales_by_country))
+        .route(
+            "/search-product",
+            get(search_product).post(search_product_body),
+        )
+        .route("/search-product-faceted", get(search_product_faceted))
+        .route("/autocomplete/products", get(autocomplete_products))
+        .route("/orders", post(create_order))
+        .route("/batch", post(batch_handler))
+        .route("/orders-with-details", get(get_orders_with_details))
+        .route("/orders-cursor", get(get_orders_cursor))
+        .route("/order-with-details", get(get_order_with_details))
+        .route(
+            "/order-with-details-and-products",
+            get(get_order_with_details_and_products),
+        )
+        .route("/reports/shipping-status", get(shipping_status_report))
+        .route(
+            "/reports/order-value-percentiles",
+            get(order_value_percentiles_report),
+        )
+        .route("/reports/repeat-customers", get(repeat_customers_report));
+
+    #[cfg(feature = "neon-http")]
+    if let Some(neon) = crate::neon_http::NeonHttp::from_env() {
+        queries = crate::neon_http::router(Arc::new(neon));
+    }
+    if let Some(backend) = state.query_backend.clone() {
+        queries = backend_routes::router(backend, state.search_dictionary);
+    }
+
+    if let Some(limiter) = state.adaptive_limiter.clone() {
+        queries = queries.route_layer(middleware::from_fn_with_state(limiter, adaptive::limit));
+    }
+    // Around the adaptive limiter, so requests queued for their route don't hold its permits
+    if let Some(limits) = state.route_limits.clone() {
+        queries = queries.route_layer(middleware::from_fn_with_state(limits, route_limits::limit));
+    }
+    // Outside the limiter, so hits don't take a permit
+    if let Some(cache) = state.response_cache.clone() {
+        queries = queries.route_layer(middleware::from_fn_with_state(cache.clone(), cache::cached));
+        cache.set_routes(queries.clone().with_state(state.clone()));
+    }
+    // Outermost: definite misses skip the cache as well
+    if let Some(filters) = state.id_filters.clone() {
+        queries = queries.route_layer(middleware::from_fn_with_state(filters, id_filter::precheck));
+    }
+    if let Some(hot_set) = state.hot_set.clone() {
+        queries = queries.route_layer(middleware::from_fn_with_state(hot_set, hot_set::serve));
+    }
+    // Around everything above, so cache and hot set hits are tagged too
+    if state.etags.is_some() {
+        queries = queries.route_layer(middleware::from_fn(etag::tag));
+    }
+    // Outermost, to compare with the mirror what clients get from this server
+    if let Some(mirror) = state.mirror.clone() {
+        queries = queries.route_layer(middleware::from_fn_with_state(mirror, mirror::mirror));
+    }
+
+    let mut app = Router::new()
+        .route("/build-info", get(build_info_handler))
+        .route("/readyz", get(readiness::ready))
+        .route("/stats", get(stats_handler))
+        .route("/stats/system", get(system_stats_handler))
+        .route("/stats/inflight", get(inflight_stats_handler))
+        .route("/stats/adaptive-limit", get(adaptive_limit_stats_handler))
+        .route("/stats/route-limits", get(route_limits_stats_handler))
+        .route("/stats/cpu", get(cpu_stats_handler))
+        .route("/stats/cache", get(cache_stats_handler))
+        .route("/stats/id-filter", get(id_filter_stats_handler))
+        .route("/stats/hot-set", get(hot_set_stats_handler))
+        .route("/stats/mirror", get(mirror_stats_handler))
+        .route("/stats/response-sizes", get(response_sizes_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/debug/pg-system", get(pg_system_handler))
+        .route("/debug/pg-locks", get(pg_locks_handler))
+        .route("/debug/queries", get(queries_handler))
+        .route("/debug/dataset", get(dataset_handler))
+        .route("/debug/statements", get(statements_handler))
+        .route("/debug/heap", get(heap_dump_handler))
+        .route("/admin/heap-profiling", post(heap_profiling_handler))
+        .route("/admin/warm-cache", post(warm_cache_handler))
+        .route("/admin/indexes", get(indexes_handler))
+        .route(
+            "/admin/indexes/:name",
+            put(create_index_handler).delete(drop_index_handler),
+        )
+        .route(
+            "/admin/analyze",
+            get(analyze_status_handler).post(analyze_handler),
+        )
+        .route("/snapshots", post(create_snapshot_handler))
+        .route("/snapshots/:token", delete(release_snapshot_handler))
+        // Not with the queries: their cache and limiter would hold on to the whole stream
+        .route("/orders-stream", get(get_orders_stream))
+        .merge(queries)
+        .with_state(state.clone());
+
+    #[cfg(feature = "graphql")]
+    {
+        app = app.merge(crate::graphql::router(state.pool.clone()));
+    }
+    #[cfg(feature = "pprof")]
+    {
+        app = app.merge(crate::cpu_profile::router());
+    }
+
+    if let Some(profiler) = state.heap_profiler.clone() {
+        app = app.layer(middleware::from_fn_with_state(profiler, heap::track));
+    }
+    if let Some(accounting) = state.cpu_accounting.clone() {
+        app = app.layer(middleware::from_fn_with_state(
+            accounting,
+            cpu_time::account,
+        ));
+    }
+    if let Some(inflight) = state.inflight.clone() {
+        app = app.layer(middleware::from_fn_with_state(inflight, inflight::limit));
+    }
+    // Around the in-flight limit for its rejections, inside the metrics to label them
+    app = app.layer(middleware::from_fn(error::error_codes));
+    // Outermost, so latencies include the time spent in the other layers (the request log's
+    // as well)
+    if let Some(metrics) = state.request_metrics.clone() {
+        app = app.layer(middleware::from_fn_with_state(metrics, metrics::record));
+    }
+    if state.request_log {
+        app = app.layer(middleware::from_fn(request_log::log));
+    }
+    // Around everything, for the spans to cover the whole request
+    if state.telemetry {
+        app = app.layer(middleware::from_fn(telemetry::trace));
+    }
+
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, Bytes, to_bytes};
+    use axum::http::Request;
+    use diesel_async::AsyncPgConnection;
+    use diesel_async::pooled_connection::{AsyncDieselConnectionManager, bb8::Pool};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    // A pool that never connects: the routes under test don't check out a connection
+    async fn state() -> Arc<AppState> {
+        let manager =
+            AsyncDieselConnectionManager::<AsyncPgConnection>::new("postgres://localhost/unused");
+        let pool = Pool::builder().build_unchecked(manager);
+        Arc::new(
+            AppState::from_env(pool, PoolConfig::default())
+                .await
+                .unwrap(),
+        )
+    }
+
+    async fn get(uri: &str) -> (StatusCode, Bytes) {
+        get_with(Request::get(uri)).await
+    }
+
+    async fn get_with(request: axum::http::request::Builder) -> (StatusCode, Bytes) {
+        let request = request.body(Body::empty()).unwrap();
+        let response = build_router(state().await).oneshot(request).await.unwrap();
+        let status = response.status();
+        (
+            status,
+            to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn build_info_needs_no_database() {
+        let (status, body) = get("/build-info").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(info["target_os"], std::env::consts::OS);
+    }
+
+    #[tokio::test]
+    async fn invalid_pagination_is_rejected_before_the_query() {
+        for uri in [
+            "/customers?limit=-1",
+            "/customers?offset=-1",
+            "/customers?limit=ten",
+            "/products?limit=-5&offset=0",
+            "/orders-with-details?offset=-1",
+        ] {
+            assert_eq!(get(uri).await.0, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_or_malformed_ids_are_rejected_before_the_query() {
+        for uri in [
+            "/customer-by-id",
+            "/customer-by-id?id=",
+            "/customer-by-id?id=abc",
+            "/supplier-by-id?id=1.5",
+            "/product-with-supplier?id=99999999999",
+            "/order-with-details?name=1",
+        ] {
+            assert_eq!(get(uri).await.0, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_dataset_is_rejected() {
+        let request = Request::get("/customer-by-id?id=1").header(DATASET_HEADER, "nope");
+        assert_eq!(get_with(request).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn disabled_features_answer_404() {
+        for uri in [
+            "/stats/id-filter",
+            "/stats/cache",
+            "/stats/hot-set",
+            "/stats/adaptive-limit",
+            "/stats/route-limits",
+            "/stats/cpu",
+        ] {
+            assert_eq!(get(uri).await.0, StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_answer_404() {
+        assert_eq!(get("/no-such-route").await.0, StatusCode::NOT_FOUND);
+    }
+}
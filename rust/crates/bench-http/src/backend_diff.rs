@@ -0,0 +1,328 @@
+// Dual-backend mode, to catch behavioral drift between implementations under continuous
+// load: with QUERY_BACKEND set and DIFF_BACKEND naming a second backend (say `diesel` and
+// `sqlx`), sampled queries run on both at once. The QUERY_BACKEND result is served, and the
+// other is compared with it as JSON; any difference is logged with the route and its
+// parameters, as is a query that fails on only one side.
+//
+//   DIFF_BACKEND      the second backend, named as in QUERY_BACKEND
+//   DIFF_SAMPLE_RATE  fraction of queries run on both (0..1, default 1)
+//
+// Latencies include waiting for the slower of the two.
+
+use std::{future::Future, sync::Arc};
+
+use axum::async_trait;
+use bench_core::{
+    BenchResult, DbPool,
+    backend::{self, QueryBackend},
+    config::PoolConfig,
+    models::{Customer, Employee, Product, Supplier},
+    queries::*,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+// Differences listed per logged query; the rest are only counted
+const MAX_LISTED: usize = 5;
+
+type Backend = Arc<dyn QueryBackend>;
+
+pub struct DiffingBackend {
+    primary: Backend,
+    primary_name: String,
+    secondary: Backend,
+    secondary_name: String,
+    sample_rate: f64,
+}
+
+// `primary` running next to DIFF_BACKEND's backend, or just `primary` when that's unset
+pub fn from_env(primary: Backend, pool: &DbPool, pool_config: &PoolConfig) -> BenchResult<Backend> {
+    let Ok(secondary_name) = std::env::var("DIFF_BACKEND") else {
+        return Ok(primary);
+    };
+    let secondary = backend::named(&secondary_name, pool, pool_config)
+        .map_err(|err| format!("DIFF_BACKEND: {}", err))?;
+    let sample_rate = std::env::var("DIFF_SAMPLE_RATE")
+        .ok()
+        .and_then(|rate| rate.parse().ok())
+        .filter(|rate: &f64| (0.0..=1.0).contains(rate))
+        .unwrap_or(1.0);
+    let primary_name = std::env::var("QUERY_BACKEND").unwrap_or_default();
+
+    println!(
+        "Diffing {:.2}% of {} queries against {}",
+        sample_rate * 100.0,
+        primary_name,
+        secondary_name
+    );
+    Ok(Arc::new(DiffingBackend {
+        primary,
+        primary_name,
+        secondary,
+        secondary_name,
+        sample_rate,
+    }))
+}
+
+impl DiffingBackend {
+    // Awaits `primary`, and when sampled `secondary` alongside it, logging where they differ
+    async fn run<T: Serialize>(
+        &self,
+        route: &str,
+        params: impl FnOnce() -> String,
+        primary: impl Future<Output = BenchResult<T>>,
+        secondary: impl Future<Output = BenchResult<T>>,
+    ) -> BenchResult<T> {
+        if fastrand::f64() >= self.sample_rate {
+            return primary.await;
+        }
+        let (primary, secondary) = tokio::join!(primary, secondary);
+
+        match (&primary, &secondary) {
+            (Ok(expected), Ok(actual)) => {
+                let mut found = Differences::default();
+                found.compare(
+                    &mut String::from("$"),
+                    &to_value(expected),
+                    &to_value(actual),
+                );
+                if found.count > 0 {
+                    eprintln!(
+                        "{} {} differs between {} and {} in {} place{}: {}{}",
+                        route,
+                        params(),
+                        self.primary_name,
+                        self.secondary_name,
+                        found.count,
+                        if found.count == 1 { "" } else { "s" },
+                        found.listed.join("; "),
+                        if found.count > found.listed.len() {
+                            "; ..."
+                        } else {
+                            ""
+                        }
+                    );
+                }
+            }
+            (Ok(_), Err(err)) => eprintln!(
+                "{} {} failed on {} only: {:?}",
+                route,
+                params(),
+                self.secondary_name,
+                err
+            ),
+            (Err(err), Ok(_)) => eprintln!(
+                "{} {} failed on {} only: {:?}",
+                route,
+                params(),
+                self.primary_name,
+                err
+            ),
+            (Err(_), Err(_)) => {}
+        }
+        primary
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|err| Value::String(err.to_string()))
+}
+
+#[derive(Default)]
+struct Differences {
+    count: usize,
+    listed: Vec<String>,
+}
+
+impl Differences {
+    fn found(&mut self, difference: impl FnOnce() -> String) {
+        self.count += 1;
+        if self.listed.len() < MAX_LISTED {
+            self.listed.push(difference());
+        }
+    }
+
+    // Compares the values at `path`, e.g. `$[3].total_price`
+    fn compare(&mut self, path: &mut String, expected: &Value, actual: &Value) {
+        let len = path.len();
+        match (expected, actual) {
+            (Value::Object(expected), Value::Object(actual)) => {
+                for (key, value) in expected {
+                    path.push('.');
+                    path.push_str(key);
+                    match actual.get(key) {
+                        Some(other) => self.compare(path, value, other),
+                        None => self.found(|| format!("{} missing", path)),
+                    }
+                    path.truncate(len);
+                }
+                for key in actual.keys().filter(|key| !expected.contains_key(*key)) {
+                    self.found(|| format!("{}.{} unexpected", path, key));
+                }
+            }
+            (Value::Array(expected), Value::Array(actual)) => {
+                if expected.len() != actual.len() {
+                    self.found(|| {
+                        format!("{}: {} items != {}", path, expected.len(), actual.len())
+                    });
+                }
+                for (i, (value, other)) in expected.iter().zip(actual).enumerate() {
+                    path.push_str(&format!("[{}]", i));
+                    self.compare(path, value, other);
+                    path.truncate(len);
+                }
+            }
+            _ if expected != actual => {
+                self.found(|| format!("{}: {} != {}", path, expected, actual))
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl QueryBackend for DiffingBackend {
+    async fn p1(&self, limit: i64, offset: i64) -> BenchResult<Vec<Customer>> {
+        self.run(
+            "/customers",
+            || format!("limit={} offset={}", limit, offset),
+            self.primary.p1(limit, offset),
+            self.secondary.p1(limit, offset),
+        )
+        .await
+    }
+
+    async fn p2(&self, id: i32) -> BenchResult<Option<Customer>> {
+        self.run(
+            "/customer-by-id",
+            || format!("id={}", id),
+            self.primary.p2(id),
+            self.secondary.p2(id),
+        )
+        .await
+    }
+
+    async fn p3(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Customer>> {
+        self.run(
+            "/search-customer",
+            || format!("term={:?} dictionary={:?}", term, dictionary),
+            self.primary.p3(term, dictionary),
+            self.secondary.p3(term, dictionary),
+        )
+        .await
+    }
+
+    async fn p4(&self, limit: i64, offset: i64) -> BenchResult<Vec<Employee>> {
+        self.run(
+            "/employees",
+            || format!("limit={} offset={}", limit, offset),
+            self.primary.p4(limit, offset),
+            self.secondary.p4(limit, offset),
+        )
+        .await
+    }
+
+    async fn p5(&self, id: i32) -> BenchResult<Option<EmployeeWithRecipient>> {
+        self.run(
+            "/employee-with-recipient",
+            || format!("id={}", id),
+            self.primary.p5(id),
+            self.secondary.p5(id),
+        )
+        .await
+    }
+
+    async fn p6(&self, limit: i64, offset: i64) -> BenchResult<Vec<Supplier>> {
+        self.run(
+            "/suppliers",
+            || format!("limit={} offset={}", limit, offset),
+            self.primary.p6(limit, offset),
+            self.secondary.p6(limit, offset),
+        )
+        .await
+    }
+
+    async fn p7(&self, id: i32) -> BenchResult<Option<Supplier>> {
+        self.run(
+            "/supplier-by-id",
+            || format!("id={}", id),
+            self.primary.p7(id),
+            self.secondary.p7(id),
+        )
+        .await
+    }
+
+    async fn p8(&self, limit: i64, offset: i64) -> BenchResult<Vec<Product>> {
+        self.run(
+            "/products",
+            || format!("limit={} offset={}", limit, offset),
+            self.primary.p8(limit, offset),
+            self.secondary.p8(limit, offset),
+        )
+        .await
+    }
+
+    async fn p9(&self, id: i32) -> BenchResult<Option<ProductWithSupplier>> {
+        self.run(
+            "/product-with-supplier",
+            || format!("id={}", id),
+            self.primary.p9(id),
+            self.secondary.p9(id),
+        )
+        .await
+    }
+
+    async fn p10(&self, term: &str, dictionary: SearchDictionary) -> BenchResult<Vec<Product>> {
+        self.run(
+            "/search-product",
+            || format!("term={:?} dictionary={:?}", term, dictionary),
+            self.primary.p10(term, dictionary),
+            self.secondary.p10(term, dictionary),
+        )
+        .await
+    }
+
+    async fn p11(&self, limit: i64, offset: i64) -> BenchResult<Vec<P11Row>> {
+        self.run(
+            "/orders-with-details",
+            || format!("limit={} offset={}", limit, offset),
+            self.primary.p11(limit, offset),
+            self.secondary.p11(limit, offset),
+        )
+        .await
+    }
+
+    async fn p12(&self, id: i32) -> BenchResult<Option<P11Row>> {
+        self.run(
+            "/order-with-details",
+            || format!("id={}", id),
+            self.primary.p12(id),
+            self.secondary.p12(id),
+        )
+        .await
+    }
+
+    async fn p13(&self, id: i32) -> BenchResult<Option<OrderWithDetailsAndProducts>> {
+        self.run(
+            "/order-with-details-and-products",
+            || format!("id={}", id),
+            self.primary.p13(id),
+            self.secondary.p13(id),
+        )
+        .await
+    }
+
+    async fn products_above_average_price(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> BenchResult<Vec<Product>> {
+        self.run(
+            "/products/above-average-price",
+            || format!("limit={} offset={}", limit, offset),
+            self.primary.products_above_average_price(limit, offset),
+            self.secondary.products_above_average_price(limit, offset),
+        )
+        .await
+    }
+}
@@ -0,0 +1,225 @@
+// Routes for a `bench_core::backend::QueryBackend`, served in place of the regular query
+// handlers when QUERY_BACKEND picks one (see `bench_core::backend`).
+//
+// Through `QueryBackend` the routes answer JSON or MessagePack and nothing else; the
+// customer write routes, the keyset pagination routes, faceted search, autocomplete, the
+// supplier product listing, the reports and the POST searches aren't served, and
+// highlighted and ranked searches are a 400.
+// The layers in front of the query routes (limiter, cache, id filters, hot set) apply
+// either way.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{FromRef, State},
+    http::StatusCode,
+    response::Response,
+    routing::get,
+};
+use bench_core::{BenchError, backend::QueryBackend, queries::SearchDictionary};
+
+use crate::{
+    encoding::Format,
+    params::{self, Id, Pagination, Search},
+};
+
+type Backend = Arc<dyn QueryBackend>;
+
+#[derive(Clone)]
+struct RouteState {
+    backend: Backend,
+    // Of the search routes, unless a request names another
+    search_dictionary: SearchDictionary,
+}
+
+impl FromRef<RouteState> for Backend {
+    fn from_ref(state: &RouteState) -> Self {
+        state.backend.clone()
+    }
+}
+
+impl FromRef<RouteState> for SearchDictionary {
+    fn from_ref(state: &RouteState) -> Self {
+        state.search_dictionary
+    }
+}
+
+fn failed(err: BenchError) -> StatusCode {
+    eprintln!("Query backend error: {:?}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+async fn get_customers(
+    State(backend): State<Backend>,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = backend.p1(limit, offset).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_customer_by_id(
+    State(backend): State<Backend>,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = backend.p2(id).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn search_customer(
+    State(backend): State<Backend>,
+    State(default): State<SearchDictionary>,
+    format: Format,
+    search: Search,
+) -> Result<Response, StatusCode> {
+    if search.highlight || search.rank {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let dictionary = search.dictionary.unwrap_or(default);
+    let result = backend.p3(&search.term, dictionary).await.map_err(failed)?;
+    Ok(params::with_dictionary(format.respond(&result), dictionary))
+}
+
+async fn get_employees(
+    State(backend): State<Backend>,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = backend.p4(limit, offset).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_employee_with_recipient(
+    State(backend): State<Backend>,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = backend.p5(id).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_suppliers(
+    State(backend): State<Backend>,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = backend.p6(limit, offset).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_supplier_by_id(
+    State(backend): State<Backend>,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = backend.p7(id).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_products(
+    State(backend): State<Backend>,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = backend.p8(limit, offset).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_products_above_average_price(
+    State(backend): State<Backend>,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = backend
+        .products_above_average_price(limit, offset)
+        .await
+        .map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_product_with_supplier(
+    State(backend): State<Backend>,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = backend.p9(id).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn search_product(
+    State(backend): State<Backend>,
+    State(default): State<SearchDictionary>,
+    format: Format,
+    search: Search,
+) -> Result<Response, StatusCode> {
+    if search.highlight || search.rank {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let dictionary = search.dictionary.unwrap_or(default);
+    let result = backend
+        .p10(&search.term, dictionary)
+        .await
+        .map_err(failed)?;
+    Ok(params::with_dictionary(format.respond(&result), dictionary))
+}
+
+async fn get_orders_with_details(
+    State(backend): State<Backend>,
+    format: Format,
+    Pagination { limit, offset, .. }: Pagination,
+) -> Result<Response, StatusCode> {
+    let result = backend.p11(limit, offset).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_order_with_details(
+    State(backend): State<Backend>,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = backend.p12(id).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+async fn get_order_with_details_and_products(
+    State(backend): State<Backend>,
+    format: Format,
+    Id(id): Id,
+) -> Result<Response, StatusCode> {
+    let result = backend.p13(id).await.map_err(failed)?;
+    Ok(format.respond(&result))
+}
+
+// The 13 query routes and the above-average-price filter, served by `backend`
+pub fn router<S>(backend: Backend, search_dictionary: SearchDictionary) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/customers", get(get_customers))
+        .route("/customer-by-id", get(get_customer_by_id))
+        .route("/search-customer", get(search_customer))
+        .route("/employees", get(get_employees))
+        .route("/employee-with-recipient", get(get_employee_with_recipient))
+        .route("/suppliers", get(get_suppliers))
+        .route("/supplier-by-id", get(get_supplier_by_id))
+        .route("/products", get(get_products))
+        .route(
+            "/products/above-average-price",
+            get(get_products_above_average_price),
+        )
+        .route("/product-with-supplier", get(get_product_with_supplier))
+        .route("/search-product", get(search_product))
+        .route("/orders-with-details", get(get_orders_with_details))
+        .route("/order-with-details", get(get_order_with_details))
+        .route(
+            "/order-with-details-and-products",
+            get(get_order_with_details_and_products),
+        )
+        .with_state(RouteState {
+            backend,
+            search_dictionary,
+        })
+}
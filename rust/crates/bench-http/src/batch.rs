@@ -0,0 +1,111 @@
+// `POST /batch`: several of the query routes in one request, to measure batching against
+// the same queries as separate HTTP calls. The body is an array of `params::BatchQuery`,
+//
+//   [{"q":"customer-by-id","id":1},{"q":"product-with-supplier","id":7}]
+//
+// run concurrently, each on a pool connection of its own, and answered as one array of what
+// the routes would have answered, in the same order. A query that fails is an
+// `{"error":...}` in its place rather than failing the batch. X-Snapshot doesn't apply.
+
+use bench_core::{
+    BenchResult, DbPool,
+    models::{Customer, Employee, Product, Supplier},
+    queries::*,
+};
+use futures_util::future::join_all;
+use serde::Serialize;
+
+use crate::params::{self, BatchQuery};
+
+// The single-row results much larger than the rest are boxed
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BatchResult {
+    Customers(Vec<Customer>),
+    Customer(Option<Customer>),
+    CustomerSearch(Vec<Customer>),
+    Employees(Vec<Employee>),
+    EmployeeWithRecipient(Option<Box<EmployeeWithRecipient>>),
+    Suppliers(Vec<Supplier>),
+    Supplier(Option<Supplier>),
+    Products(Vec<Product>),
+    ProductWithSupplier(Option<Box<ProductWithSupplier>>),
+    ProductSearch(Vec<Product>),
+    Orders(Vec<P11Row>),
+    Order(Option<P11Row>),
+    OrderWithProducts(Option<OrderWithDetailsAndProducts>),
+    Error { error: &'static str },
+}
+
+// `queries`, checked with `params::check_batch`
+pub async fn run(
+    pool: &DbPool,
+    dictionary: SearchDictionary,
+    queries: Vec<BatchQuery>,
+) -> Vec<BatchResult> {
+    join_all(queries.into_iter().map(|query| async move {
+        match execute(pool, dictionary, query).await {
+            Ok(result) => result,
+            Err(err) => {
+                eprintln!("Batch query failed: {:?}", err);
+                BatchResult::Error {
+                    error: "query failed",
+                }
+            }
+        }
+    }))
+    .await
+}
+
+async fn execute(
+    pool: &DbPool,
+    dictionary: SearchDictionary,
+    query: BatchQuery,
+) -> BenchResult<BatchResult> {
+    let mut conn = pool.get().await?;
+    let conn = &mut *conn;
+    let page = |limit, offset| params::page(limit, offset).map_err(|err| err.0);
+
+    Ok(match query {
+        BatchQuery::Customers { limit, offset } => {
+            let (limit, offset) = page(limit, offset)?;
+            BatchResult::Customers(p1(conn, limit, offset).await?)
+        }
+        BatchQuery::CustomerById { id } => BatchResult::Customer(p2(conn, id).await?),
+        BatchQuery::SearchCustomer {
+            term,
+            dictionary: requested,
+        } => BatchResult::CustomerSearch(p3(conn, &term, requested.unwrap_or(dictionary)).await?),
+        BatchQuery::Employees { limit, offset } => {
+            let (limit, offset) = page(limit, offset)?;
+            BatchResult::Employees(p4(conn, limit, offset).await?)
+        }
+        BatchQuery::EmployeeWithRecipient { id } => {
+            BatchResult::EmployeeWithRecipient(p5(conn, id).await?.map(Box::new))
+        }
+        BatchQuery::Suppliers { limit, offset } => {
+            let (limit, offset) = page(limit, offset)?;
+            BatchResult::Suppliers(p6(conn, limit, offset).await?)
+        }
+        BatchQuery::SupplierById { id } => BatchResult::Supplier(p7(conn, id).await?),
+        BatchQuery::Products { limit, offset } => {
+            let (limit, offset) = page(limit, offset)?;
+            BatchResult::Products(p8(conn, limit, offset).await?)
+        }
+        BatchQuery::ProductWithSupplier { id } => {
+            BatchResult::ProductWithSupplier(p9(conn, id).await?.map(Box::new))
+        }
+        BatchQuery::SearchProduct {
+            term,
+            dictionary: requested,
+        } => BatchResult::ProductSearch(p10(conn, &term, requested.unwrap_or(dictionary)).await?),
+        BatchQuery::OrdersWithDetails { limit, offset } => {
+            let (limit, offset) = page(limit, offset)?;
+            BatchResult::Orders(p11(conn, limit, offset).await?)
+        }
+        BatchQuery::OrderWithDetails { id } => BatchResult::Order(p12(conn, id).await?),
+        BatchQuery::OrderWithDetailsAndProducts { id } => {
+            BatchResult::OrderWithProducts(p13(conn, id).await?)
+        }
+    })
+}
@@ -0,0 +1,83 @@
+use std::{path::PathBuf, sync::Arc};
+
+use bench_core::{BenchResult, establish_connection_pool};
+use bench_http::capture::{self, ReplayReport};
+use clap::Parser;
+
+/// Replay a server query capture (CAPTURE_QUERIES) straight against DATABASE_URL, without
+/// HTTP, and report query latencies
+#[derive(Parser)]
+#[command(name = "sqlreplay")]
+struct Cli {
+    /// JSON lines file written by the server's query capture
+    file: PathBuf,
+    /// Queries in flight, one pooled connection each (the pool holds at most 128)
+    #[arg(long, default_value_t = 32)]
+    concurrency: usize,
+    /// Play the capture this many times back to back
+    #[arg(long, default_value_t = 1)]
+    loops: usize,
+    /// Also write the report as JSON
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+fn or_exit<T>(result: BenchResult<T>, context: &str) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}: {:?}", context, err);
+        std::process::exit(1);
+    })
+}
+
+fn print_report(report: &ReplayReport) {
+    println!(
+        "{} queries ({} errors, {} rows) in {:.2}s at concurrency {}, {:.0} queries/s",
+        report.queries,
+        report.errors,
+        report.rows,
+        report.elapsed_secs,
+        report.concurrency,
+        report.queries_per_sec
+    );
+    println!(
+        "latency: p50 {}us, p95 {}us, p99 {}us, max {}us",
+        report.latency.p50_us, report.latency.p95_us, report.latency.p99_us, report.latency.max_us
+    );
+    for (name, latency) in &report.per_query {
+        println!(
+            "  {:<4} p50 {}us, p95 {}us, p99 {}us, max {}us",
+            name, latency.p50_us, latency.p95_us, latency.p99_us, latency.max_us
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let captured = or_exit(
+        capture::read_captured(&cli.file).map_err(Into::into),
+        "Failed to read captured queries",
+    );
+    let queries: Vec<_> = (0..cli.loops)
+        .flat_map(|_| captured.iter().cloned())
+        .collect();
+    let queries = Arc::new(queries);
+
+    let pool = establish_connection_pool().await;
+    let report = or_exit(
+        capture::replay(&pool, queries, cli.concurrency).await,
+        "Replay failed",
+    );
+
+    print_report(&report);
+
+    if let Some(path) = cli.out {
+        or_exit(
+            serde_json::to_string_pretty(&report)
+                .map_err(Into::into)
+                .and_then(|json| std::fs::write(&path, json).map_err(Into::into)),
+            &format!("Failed to write {}", path.display()),
+        );
+    }
+}
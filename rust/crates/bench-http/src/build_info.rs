@@ -0,0 +1,152 @@
+// Served at `/build-info`: what was built (profile, target, binary size) and, when the
+// server runs with `--measure-startup`, how long it took to come up. Cold start is one of
+// the dimensions published for the serverless comparisons.
+
+use std::{sync::OnceLock, time::Instant};
+
+use serde::Serialize;
+
+use bench_driver::client::{self, http_client};
+
+// Probed once the listener is bound; answers 200 as soon as the pool hands out connections,
+// even on an empty database
+const STARTUP_PROBE_PATH: &str = "/customers?limit=1";
+const STARTUP_PROBE_ATTEMPTS: u32 = 100;
+
+#[derive(Clone, Serialize)]
+pub struct StartupTimes {
+    // Process creation until `main` ran, from /proc (clock tick resolution, so ~10ms)
+    pub pre_main_ms: Option<f64>,
+    // The rest are relative to `main`
+    pub pool_ready_ms: f64,
+    pub listening_ms: f64,
+    pub first_response_ms: f64,
+    // Process creation until the first successful response
+    pub total_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub profile: &'static str,
+    pub target_arch: &'static str,
+    pub target_os: &'static str,
+    pub binary_size_bytes: Option<u64>,
+    pub startup: Option<StartupTimes>,
+}
+
+static STARTUP: OnceLock<StartupTimes> = OnceLock::new();
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        profile: if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        },
+        target_arch: std::env::consts::ARCH,
+        target_os: std::env::consts::OS,
+        binary_size_bytes: std::env::current_exe()
+            .and_then(std::fs::metadata)
+            .map(|metadata| metadata.len())
+            .ok(),
+        startup: STARTUP.get().cloned(),
+    }
+}
+
+// Seconds since this process was created: its start time in /proc/self/stat (field 22,
+// clock ticks since boot) against the system uptime
+fn process_age_secs() -> Option<f64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name in field 2 may contain spaces; fields after it are plain
+    let after_name = &stat[stat.rfind(')')? + 2..];
+    let start_ticks: f64 = after_name.split_whitespace().nth(19)?.parse().ok()?;
+
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    let uptime: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+
+    // SAFETY: sysconf has no preconditions
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+    Some((uptime - start_ticks / ticks_per_sec as f64).max(0.0))
+}
+
+// Milestones of one startup, recorded from `main`
+pub struct StartupClock {
+    main_started: Instant,
+    pre_main_ms: Option<f64>,
+    pool_ready_ms: f64,
+    listening_ms: f64,
+}
+
+impl StartupClock {
+    // Call first thing in `main`
+    pub fn start() -> Self {
+        StartupClock {
+            main_started: Instant::now(),
+            pre_main_ms: process_age_secs().map(|secs| secs * 1000.0),
+            pool_ready_ms: 0.0,
+            listening_ms: 0.0,
+        }
+    }
+
+    fn elapsed_ms(&self) -> f64 {
+        self.main_started.elapsed().as_secs_f64() * 1000.0
+    }
+
+    pub fn pool_ready(&mut self) {
+        self.pool_ready_ms = self.elapsed_ms();
+    }
+
+    pub fn listening(&mut self) {
+        self.listening_ms = self.elapsed_ms();
+    }
+
+    // Requests `STARTUP_PROBE_PATH` on `port` until it succeeds, then prints and publishes
+    // the startup times. Run in the background once the server is listening
+    pub async fn measure_first_response(self, port: u16) {
+        let uri =
+            match client::request_uri(&format!("http://127.0.0.1:{}", port), STARTUP_PROBE_PATH) {
+                Ok(uri) => uri,
+                Err(err) => {
+                    eprintln!("Startup measurement failed: {}", err);
+                    return;
+                }
+            };
+        let client = http_client();
+
+        for _ in 0..STARTUP_PROBE_ATTEMPTS {
+            match client::get(&client, uri.clone()).await {
+                Ok((status, _)) if status.is_success() => {
+                    let first_response_ms = self.elapsed_ms();
+                    let times = StartupTimes {
+                        pre_main_ms: self.pre_main_ms,
+                        pool_ready_ms: self.pool_ready_ms,
+                        listening_ms: self.listening_ms,
+                        first_response_ms,
+                        total_ms: self.pre_main_ms.unwrap_or(0.0) + first_response_ms,
+                    };
+                    println!(
+                        "Startup: {:.1}ms to first successful request (pre-main {}, pool ready {:.1}ms, listening {:.1}ms)",
+                        times.total_ms,
+                        times
+                            .pre_main_ms
+                            .map_or("n/a".to_string(), |ms| format!("{:.1}ms", ms)),
+                        times.pool_ready_ms,
+                        times.listening_ms,
+                    );
+                    let _ = STARTUP.set(times);
+                    return;
+                }
+                Ok(_) | Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        }
+        eprintln!(
+            "Startup measurement failed: {} never succeeded",
+            STARTUP_PROBE_PATH
+        );
+    }
+}
@@ -0,0 +1,573 @@
+// Read-through cache of query route responses, for the cached-mode benchmarks. Only the
+// by-id routes are cached unless RESPONSE_CACHE_ROUTES says otherwise: the list, search
+// and report routes are what the uncached runs measure. Responses are keyed by dataset,
+// path and query string exactly as requested, except by-id lookups (`/customer-by-id?id=1`,
+// ...), keyed by route and id; only 200s are kept. Every response the cache looked up says
+// so in `X-Cache: hit` or `miss`. Configured with:
+//
+//   RESPONSE_CACHE_MAX_BYTES  capacity in bytes of bodies and keys (unset or 0 disables it)
+//   RESPONSE_CACHE_TTL_MS     time an entry lives after being stored (default 60000)
+//   RESPONSE_CACHE_NEGATIVE_TTL_MS
+//                             the same for by-id lookups that found nothing (`null`),
+//                             short so new rows show up soon (default 5000, 0 doesn't
+//                             cache them at all)
+//   RESPONSE_CACHE_ROUTES     comma-separated routes to cache, e.g.
+//                             `/customer-by-id,/products` (default the by-id routes)
+//
+// `POST /admin/warm-cache` fills it ahead of a run through the same routes, so cached runs
+// start from a defined state instead of whatever the previous run left. The scenario and
+// request list files it can read are those under SCENARIOS_DIR (default `scenarios`).
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::{MatchedPath, Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bench_driver::{BenchResult, loadgen::load_paths, scenario::Scenario};
+use moka::{Expiry, notification::RemovalCause, sync::Cache};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tower::ServiceExt;
+
+use crate::{
+    datasets::DATASET_HEADER,
+    encoding::Format,
+    ndjson,
+    params::{DICTIONARY_HEADER, Id},
+    snapshots::SNAPSHOT_HEADER,
+};
+
+pub const CACHE_HEADER: &str = "x-cache";
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(5);
+// Warm-up requests in flight at once
+const WARM_CONCURRENCY: usize = 32;
+// Page size of the list routes in data/requests.json
+const DEFAULT_WARM_LIMIT: i64 = 50;
+const DEFAULT_SCENARIOS_DIR: &str = "scenarios";
+const DEFAULT_CACHED_ROUTES: [&str; 3] = [
+    "/customer-by-id",
+    "/supplier-by-id",
+    "/product-with-supplier",
+];
+
+#[derive(Clone)]
+struct CachedResponse {
+    body: Bytes,
+    content_type: Option<HeaderValue>,
+    // Pagination links of a list route
+    link: Option<HeaderValue>,
+    // Dictionary of a search route
+    dictionary: Option<HeaderValue>,
+    // A by-id lookup that found nothing
+    negative: bool,
+}
+
+struct Ttls {
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl Expiry<String, CachedResponse> for Ttls {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &CachedResponse,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(if value.negative {
+            self.negative_ttl
+        } else {
+            self.ttl
+        })
+    }
+}
+
+impl IntoResponse for CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Body::from(self.body).into_response();
+        let headers = response.headers_mut();
+        if let Some(content_type) = self.content_type {
+            headers.insert(header::CONTENT_TYPE, content_type);
+        }
+        if let Some(link) = self.link {
+            headers.insert(header::LINK, link);
+        }
+        if let Some(dictionary) = self.dictionary {
+            headers.insert(DICTIONARY_HEADER, dictionary);
+        }
+        response
+    }
+}
+
+#[derive(Default)]
+struct Removals {
+    // Pushed out by the capacity limit
+    evicted: AtomicU64,
+    expired: AtomicU64,
+}
+
+enum Lookup {
+    Hit,
+    NegativeHit,
+    Miss,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Lookups {
+    hits: u64,
+    negative_hits: u64,
+    misses: u64,
+}
+
+#[derive(Serialize)]
+pub struct RouteCacheStats {
+    pub route: String,
+    pub hits: u64,
+    // Hits on a cached `null`, not counted in `hits`
+    pub negative_hits: u64,
+    pub misses: u64,
+    // Both kinds of hits over all lookups
+    pub hit_ratio: f64,
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub entries: u64,
+    // Bodies and keys, as weighed against the capacity
+    pub bytes: u64,
+    pub max_bytes: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+    pub routes: Vec<RouteCacheStats>,
+}
+
+pub struct ResponseCache {
+    entries: Cache<String, CachedResponse>,
+    max_bytes: u64,
+    cache_negative: bool,
+    removals: Arc<Removals>,
+    // Lookups by route; warming requests count as misses too
+    lookups: Mutex<HashMap<String, Lookups>>,
+    // The query routes behind the cache, for warming; set once the router is built
+    routes: OnceLock<Router>,
+    // Matched routes whose responses are cached; the others pass through
+    cached_routes: HashSet<String>,
+}
+
+fn cache_key(dataset: Option<&str>, path_and_query: &str) -> String {
+    let dataset = dataset.unwrap_or("");
+    // A query of just the id is a by-id lookup: `?id=01` shares the entry of `?id=1`
+    if let Some((path, query)) = path_and_query.split_once('?')
+        && !query.contains('&')
+        && let Ok(Id(id)) = Id::from_query(Some(query))
+    {
+        return format!("{}|{}?id={}", dataset, path, id);
+    }
+    format!("{}|{}", dataset, path_and_query)
+}
+
+impl ResponseCache {
+    // A zero `negative_ttl` leaves `null` results uncached
+    pub fn new(
+        max_bytes: u64,
+        ttl: Duration,
+        negative_ttl: Duration,
+        cached_routes: HashSet<String>,
+    ) -> Self {
+        let removals = Arc::new(Removals::default());
+        let counted = removals.clone();
+
+        ResponseCache {
+            entries: Cache::builder()
+                .max_capacity(max_bytes)
+                .weigher(|key: &String, value: &CachedResponse| {
+                    (key.len() + value.body.len())
+                        .try_into()
+                        .unwrap_or(u32::MAX)
+                })
+                .expire_after(Ttls { ttl, negative_ttl })
+                .eviction_listener(move |_, _, cause| {
+                    let counter = match cause {
+                        RemovalCause::Size => &counted.evicted,
+                        RemovalCause::Expired => &counted.expired,
+                        RemovalCause::Explicit | RemovalCause::Replaced => return,
+                    };
+                    counter.fetch_add(1, Ordering::Relaxed);
+                })
+                .build(),
+            max_bytes,
+            cache_negative: !negative_ttl.is_zero(),
+            removals,
+            lookups: Mutex::new(HashMap::new()),
+            routes: OnceLock::new(),
+            cached_routes,
+        }
+    }
+
+    // None when RESPONSE_CACHE_MAX_BYTES is unset or 0
+    pub fn from_env() -> Option<Self> {
+        let max_bytes: u64 = std::env::var("RESPONSE_CACHE_MAX_BYTES")
+            .ok()?
+            .parse()
+            .ok()
+            .filter(|&max| max > 0)?;
+        let millis = |name: &str, default: Duration| {
+            std::env::var(name)
+                .ok()
+                .and_then(|ms| ms.parse().ok())
+                .map_or(default, Duration::from_millis)
+        };
+
+        let cached_routes = match std::env::var("RESPONSE_CACHE_ROUTES") {
+            Ok(routes) => routes
+                .split(',')
+                .map(str::trim)
+                .filter(|route| !route.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(_) => DEFAULT_CACHED_ROUTES.map(String::from).into(),
+        };
+
+        Some(ResponseCache::new(
+            max_bytes,
+            millis("RESPONSE_CACHE_TTL_MS", DEFAULT_TTL),
+            millis("RESPONSE_CACHE_NEGATIVE_TTL_MS", DEFAULT_NEGATIVE_TTL),
+            cached_routes,
+        ))
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        // Applies pending evictions and updates the sizes below
+        self.entries.run_pending_tasks();
+
+        let mut routes: Vec<RouteCacheStats> = self
+            .lookups
+            .lock()
+            .iter()
+            .map(|(route, lookups)| {
+                let hits = lookups.hits + lookups.negative_hits;
+                let total = hits + lookups.misses;
+                RouteCacheStats {
+                    route: route.clone(),
+                    hits: lookups.hits,
+                    negative_hits: lookups.negative_hits,
+                    misses: lookups.misses,
+                    hit_ratio: if total > 0 {
+                        hits as f64 / total as f64
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        routes.sort_by(|a, b| a.route.cmp(&b.route));
+
+        CacheStats {
+            entries: self.entries.entry_count(),
+            bytes: self.entries.weighted_size(),
+            max_bytes: self.max_bytes,
+            evictions: self.removals.evicted.load(Ordering::Relaxed),
+            expirations: self.removals.expired.load(Ordering::Relaxed),
+            routes,
+        }
+    }
+
+    fn count_lookup(&self, route: &str, lookup: Lookup) {
+        let mut lookups = self.lookups.lock();
+        let lookups = match lookups.get_mut(route) {
+            Some(lookups) => lookups,
+            None => lookups.entry(route.to_string()).or_default(),
+        };
+        match lookup {
+            Lookup::Hit => lookups.hits += 1,
+            Lookup::NegativeHit => lookups.negative_hits += 1,
+            Lookup::Miss => lookups.misses += 1,
+        }
+    }
+
+    // `routes` must be the cached query routes with their state, so warming stores exactly
+    // what a request would
+    pub fn set_routes(&self, routes: Router) {
+        let _ = self.routes.set(routes);
+    }
+
+    // Drops the by-id entry a write made stale, a cached `null` included
+    pub fn invalidate(&self, dataset: Option<&str>, path: &str, id: i32) {
+        self.entries
+            .invalidate(&cache_key(dataset, &format!("{}?id={}", path, id)));
+    }
+
+    // Requests every path not cached yet through the query routes
+    pub async fn warm(&self, paths: Vec<String>, dataset: Option<String>) -> WarmReport {
+        let mut report = WarmReport::default();
+        let Some(routes) = self.routes.get() else {
+            return report;
+        };
+
+        let mut seen = HashSet::new();
+        let pending: Vec<String> = paths
+            .into_iter()
+            .filter(|path| seen.insert(path.clone()))
+            .filter(|path| {
+                let cached = self
+                    .entries
+                    .contains_key(&cache_key(dataset.as_deref(), path));
+                if cached {
+                    report.already_cached += 1;
+                }
+                !cached
+            })
+            .collect();
+
+        for chunk in pending.chunks(WARM_CONCURRENCY) {
+            let mut requests = JoinSet::new();
+            for path in chunk {
+                let mut request = Request::get(path.as_str());
+                if let Some(dataset) = &dataset {
+                    request = request.header(DATASET_HEADER, dataset.as_str());
+                }
+                let request = match request.body(Body::empty()) {
+                    Ok(request) => request,
+                    Err(_) => {
+                        report.failed.push(path.clone());
+                        continue;
+                    }
+                };
+                let routes = routes.clone();
+                let path = path.clone();
+                requests.spawn(async move {
+                    let ok = matches!(
+                        routes.oneshot(request).await,
+                        Ok(response) if response.status() == StatusCode::OK
+                    );
+                    (path, ok)
+                });
+            }
+
+            while let Some(result) = requests.join_next().await {
+                match result {
+                    Ok((_, true)) => report.warmed += 1,
+                    Ok((path, false)) => report.failed.push(path),
+                    Err(err) => eprintln!("Cache warming request panicked: {:?}", err),
+                }
+            }
+        }
+
+        report
+    }
+}
+
+pub async fn cached(
+    State(cache): State<Arc<ResponseCache>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str());
+    // Snapshot reads answer as of their snapshot, not the current data; NDJSON, CSV or
+    // MessagePack negotiated by Accept would share the key of the JSON response
+    if !cache.cached_routes.contains(route)
+        || request.method() != Method::GET
+        || request.headers().contains_key(SNAPSHOT_HEADER)
+        || ndjson::requested(request.headers(), None).is_some()
+        || Format::from_headers(request.headers()) != Format::Json
+    {
+        return next.run(request).await;
+    }
+
+    let dataset = request
+        .headers()
+        .get(DATASET_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map_or("", |path| path.as_str());
+    let key = cache_key(dataset, path_and_query);
+
+    if let Some(hit) = cache.entries.get(&key) {
+        let lookup = if hit.negative {
+            Lookup::NegativeHit
+        } else {
+            Lookup::Hit
+        };
+        cache.count_lookup(route, lookup);
+        let mut response = hit.into_response();
+        response
+            .headers_mut()
+            .insert(CACHE_HEADER, HeaderValue::from_static("hit"));
+        return response;
+    }
+    cache.count_lookup(route, Lookup::Miss);
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(CACHE_HEADER, HeaderValue::from_static("miss"));
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => {
+            eprintln!("Failed to buffer response for the cache: {:?}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    // Only the by-id routes answer a bare `null`
+    let negative = body.as_ref() == b"null";
+    if !negative || cache.cache_negative {
+        cache.entries.insert(
+            key,
+            CachedResponse {
+                body: body.clone(),
+                content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+                link: parts.headers.get(header::LINK).cloned(),
+                dictionary: parts.headers.get(DICTIONARY_HEADER).cloned(),
+                negative,
+            },
+        );
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+impl CacheStats {
+    // Appends the stats in the Prometheus text format
+    pub fn write_prometheus(&self, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "# HELP response_cache_lookups_total Response cache lookups by route and result\n\
+             # TYPE response_cache_lookups_total counter"
+        );
+        for route in &self.routes {
+            for (result, count) in [
+                ("hit", route.hits),
+                ("negative_hit", route.negative_hits),
+                ("miss", route.misses),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "response_cache_lookups_total{{route=\"{}\",result=\"{}\"}} {}",
+                    route.route, result, count
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP response_cache_removals_total Entries removed by the capacity limit or TTL\n\
+             # TYPE response_cache_removals_total counter\n\
+             response_cache_removals_total{{cause=\"evicted\"}} {}\n\
+             response_cache_removals_total{{cause=\"expired\"}} {}",
+            self.evictions, self.expirations
+        );
+        let _ = writeln!(
+            out,
+            "# HELP response_cache_entries Entries in the response cache\n\
+             # TYPE response_cache_entries gauge\n\
+             response_cache_entries {}\n\
+             # HELP response_cache_bytes Bytes of bodies and keys in the response cache\n\
+             # TYPE response_cache_bytes gauge\n\
+             response_cache_bytes {}\n\
+             # HELP response_cache_max_bytes Capacity of the response cache\n\
+             # TYPE response_cache_max_bytes gauge\n\
+             response_cache_max_bytes {}",
+            self.entries, self.bytes, self.max_bytes
+        );
+    }
+}
+
+// Body of `POST /admin/warm-cache`; every field is optional and they combine
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct WarmRequest {
+    // Paths as requested, e.g. "/customer-by-id?id=1"
+    pub paths: Vec<String>,
+    // By-id routes and the ids to fetch, e.g. {"/customer-by-id": [1, 2]}
+    pub ids: BTreeMap<String, Vec<i32>>,
+    // List routes and the offsets to fetch, e.g. {"/customers": [0, 50]}
+    pub offsets: BTreeMap<String, Vec<i64>>,
+    // Page size for `offsets` (default 50, as in data/requests.json)
+    pub limit: Option<i64>,
+    // The hot set of a load generator scenario: every path of its mix. Prefix entries are
+    // resolved against `requests`, a request list in the data/requests.json format. Both
+    // are file names relative to SCENARIOS_DIR, e.g. "autocomplete.json"
+    pub scenario: Option<String>,
+    pub requests: Option<String>,
+    // Dataset to warm (X-Dataset), the default database without one
+    pub dataset: Option<String>,
+}
+
+impl WarmRequest {
+    pub fn paths(&self) -> BenchResult<Vec<String>> {
+        let mut paths = self.paths.clone();
+
+        for (route, ids) in &self.ids {
+            paths.extend(ids.iter().map(|id| format!("{}?id={}", route, id)));
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_WARM_LIMIT);
+        for (route, offsets) in &self.offsets {
+            paths.extend(
+                offsets
+                    .iter()
+                    .map(|offset| format!("{}?limit={}&offset={}", route, limit, offset)),
+            );
+        }
+
+        if let Some(scenario) = &self.scenario {
+            let requests = match &self.requests {
+                Some(requests) => load_paths(&scenario_file(requests)?)?,
+                None => Vec::new(),
+            };
+            let scenario = Scenario::load(&scenario_file(scenario)?, &requests)?;
+            paths.extend(scenario.mix.into_iter().flat_map(|entry| entry.paths));
+        }
+
+        Ok(paths)
+    }
+}
+
+// `name` under SCENARIOS_DIR; a request body names files there and nowhere else, so
+// absolute paths and `..` are refused
+fn scenario_file(name: &str) -> BenchResult<PathBuf> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(format!("{:?} isn't a file name under the scenarios directory", name).into());
+    }
+    let dir = std::env::var("SCENARIOS_DIR").unwrap_or_else(|_| DEFAULT_SCENARIOS_DIR.to_string());
+    Ok(Path::new(&dir).join(relative))
+}
+
+#[derive(Serialize, Default)]
+pub struct WarmReport {
+    pub warmed: usize,
+    pub already_cached: usize,
+    // Paths that didn't answer 200 (or weren't valid request paths)
+    pub failed: Vec<String>,
+}
@@ -0,0 +1,360 @@
+// Sampled capture of the queries the server runs, with their exact parameters, as JSON
+// lines that `sqlreplay` replays against Postgres without HTTP, isolating the DB layer.
+//
+// Enabled with CAPTURE_QUERIES=<file>; CAPTURE_SAMPLE_RATE (0..1, default 0.01) sets the
+// fraction of requests recorded.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{SyncSender, TrySendError, sync_channel},
+    },
+    time::Instant,
+};
+
+use bench_core::{DbPool, queries::*};
+use bench_driver::{
+    BenchResult,
+    result::{LatencySummary, new_histogram},
+};
+use diesel::QueryResult;
+use diesel_async::AsyncPgConnection;
+use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
+
+// Lines buffered for the writer thread; beyond this, samples are dropped rather than
+// slowing requests down
+const CHANNEL_CAPACITY: usize = 8192;
+const DEFAULT_SAMPLE_RATE: f64 = 0.01;
+
+// One query invocation, named after the function in `queries`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub enum CapturedQuery {
+    P1 {
+        limit: i64,
+        offset: i64,
+    },
+    P2 {
+        id: i32,
+    },
+    // Captures from before dictionaries were configurable searched in english
+    P3 {
+        term: String,
+        #[serde(default)]
+        dictionary: SearchDictionary,
+        #[serde(default)]
+        highlight: bool,
+        #[serde(default)]
+        rank: bool,
+    },
+    P4 {
+        limit: i64,
+        offset: i64,
+    },
+    P5 {
+        id: i32,
+    },
+    P6 {
+        limit: i64,
+        offset: i64,
+    },
+    P7 {
+        id: i32,
+    },
+    P8 {
+        limit: i64,
+        offset: i64,
+    },
+    P9 {
+        id: i32,
+    },
+    P10 {
+        term: String,
+        #[serde(default)]
+        dictionary: SearchDictionary,
+        #[serde(default)]
+        highlight: bool,
+        #[serde(default)]
+        rank: bool,
+    },
+    P11 {
+        limit: i64,
+        offset: i64,
+    },
+    P12 {
+        id: i32,
+    },
+    P13 {
+        id: i32,
+    },
+}
+
+impl CapturedQuery {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CapturedQuery::P1 { .. } => "p1",
+            CapturedQuery::P2 { .. } => "p2",
+            CapturedQuery::P3 {
+                highlight: true, ..
+            } => "p3_highlighted",
+            CapturedQuery::P3 { rank: true, .. } => "p3_ranked",
+            CapturedQuery::P3 { .. } => "p3",
+            CapturedQuery::P4 { .. } => "p4",
+            CapturedQuery::P5 { .. } => "p5",
+            CapturedQuery::P6 { .. } => "p6",
+            CapturedQuery::P7 { .. } => "p7",
+            CapturedQuery::P8 { .. } => "p8",
+            CapturedQuery::P9 { .. } => "p9",
+            CapturedQuery::P10 {
+                highlight: true, ..
+            } => "p10_highlighted",
+            CapturedQuery::P10 { rank: true, .. } => "p10_ranked",
+            CapturedQuery::P10 { .. } => "p10",
+            CapturedQuery::P11 { .. } => "p11",
+            CapturedQuery::P12 { .. } => "p12",
+            CapturedQuery::P13 { .. } => "p13",
+        }
+    }
+
+    // Runs the query and returns the number of rows it produced
+    pub async fn execute(&self, conn: &mut AsyncPgConnection) -> QueryResult<usize> {
+        Ok(match self {
+            CapturedQuery::P1 { limit, offset } => p1(conn, *limit, *offset).await?.len(),
+            CapturedQuery::P2 { id } => p2(conn, *id).await?.into_iter().count(),
+            CapturedQuery::P3 {
+                term,
+                dictionary,
+                highlight: true,
+                rank,
+            } => p3_highlighted(conn, term, *dictionary, *rank).await?.len(),
+            CapturedQuery::P3 {
+                term,
+                dictionary,
+                rank: true,
+                ..
+            } => p3_ranked(conn, term, *dictionary).await?.len(),
+            CapturedQuery::P3 {
+                term, dictionary, ..
+            } => p3(conn, term, *dictionary).await?.len(),
+            CapturedQuery::P4 { limit, offset } => p4(conn, *limit, *offset).await?.len(),
+            CapturedQuery::P5 { id } => p5(conn, *id).await?.into_iter().count(),
+            CapturedQuery::P6 { limit, offset } => p6(conn, *limit, *offset).await?.len(),
+            CapturedQuery::P7 { id } => p7(conn, *id).await?.into_iter().count(),
+            CapturedQuery::P8 { limit, offset } => p8(conn, *limit, *offset).await?.len(),
+            CapturedQuery::P9 { id } => p9(conn, *id).await?.into_iter().count(),
+            CapturedQuery::P10 {
+                term,
+                dictionary,
+                highlight: true,
+                rank,
+            } => p10_highlighted(conn, term, *dictionary, *rank).await?.len(),
+            CapturedQuery::P10 {
+                term,
+                dictionary,
+                rank: true,
+                ..
+            } => p10_ranked(conn, term, *dictionary).await?.len(),
+            CapturedQuery::P10 {
+                term, dictionary, ..
+            } => p10(conn, term, *dictionary).await?.len(),
+            CapturedQuery::P11 { limit, offset } => p11(conn, *limit, *offset).await?.len(),
+            CapturedQuery::P12 { id } => p12(conn, *id).await?.into_iter().count(),
+            CapturedQuery::P13 { id } => p13(conn, *id).await?.into_iter().count(),
+        })
+    }
+}
+
+pub struct QueryCapture {
+    sender: SyncSender<CapturedQuery>,
+    sample_rate: f64,
+    dropped: AtomicU64,
+}
+
+impl QueryCapture {
+    // Starts the writer thread appending to `path`
+    pub fn start(path: &Path, sample_rate: f64) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::options().create(true).append(true).open(path)?);
+        let (sender, receiver) = sync_channel::<CapturedQuery>(CHANNEL_CAPACITY);
+
+        std::thread::spawn(move || {
+            while let Ok(query) = receiver.recv() {
+                let mut next = Some(query);
+                while let Some(query) = next {
+                    let written = serde_json::to_writer(&mut writer, &query)
+                        .map_err(std::io::Error::from)
+                        .and_then(|_| writer.write_all(b"\n"));
+                    if let Err(err) = written {
+                        eprintln!("Failed to write captured query: {:?}", err);
+                        return;
+                    }
+                    next = receiver.try_recv().ok();
+                }
+                // Flush whenever the channel drains so the file is usable while running
+                if let Err(err) = writer.flush() {
+                    eprintln!("Failed to flush captured queries: {:?}", err);
+                    return;
+                }
+            }
+        });
+
+        Ok(QueryCapture {
+            sender,
+            sample_rate,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    // Reads CAPTURE_QUERIES / CAPTURE_SAMPLE_RATE; None when capture is off
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("CAPTURE_QUERIES").ok()?;
+        let sample_rate = std::env::var("CAPTURE_SAMPLE_RATE")
+            .ok()
+            .and_then(|rate| rate.parse().ok())
+            .unwrap_or(DEFAULT_SAMPLE_RATE);
+
+        match QueryCapture::start(Path::new(&path), sample_rate) {
+            Ok(capture) => {
+                println!(
+                    "Capturing {:.2}% of queries to {}",
+                    sample_rate * 100.0,
+                    path
+                );
+                Some(capture)
+            }
+            Err(err) => {
+                eprintln!("Failed to open query capture file {}: {:?}", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn record(&self, query: CapturedQuery) {
+        if fastrand::f64() >= self.sample_rate {
+            return;
+        }
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(query) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                eprintln!("Query capture can't keep up, {} samples dropped", dropped);
+            }
+        }
+    }
+}
+
+pub fn read_captured(path: &Path) -> std::io::Result<Vec<CapturedQuery>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReplayReport {
+    pub concurrency: usize,
+    pub queries: u64,
+    pub errors: u64,
+    pub rows: u64,
+    pub elapsed_secs: f64,
+    pub queries_per_sec: f64,
+    pub latency: LatencySummary,
+    pub per_query: BTreeMap<&'static str, LatencySummary>,
+}
+
+struct ReplayWorker {
+    queries: u64,
+    errors: u64,
+    rows: u64,
+    histogram: Histogram<u64>,
+    per_query: HashMap<&'static str, Histogram<u64>>,
+}
+
+// Replays `queries` once on `concurrency` pooled connections, each taking the next
+// unplayed query, and times every query on the client side of the connection
+pub async fn replay(
+    pool: &DbPool,
+    queries: Arc<Vec<CapturedQuery>>,
+    concurrency: usize,
+) -> BenchResult<ReplayReport> {
+    let next = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+
+    let workers: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let pool = pool.clone();
+            let queries = queries.clone();
+            let next = next.clone();
+            tokio::spawn(async move {
+                let mut conn = pool.get().await?;
+                let mut worker = ReplayWorker {
+                    queries: 0,
+                    errors: 0,
+                    rows: 0,
+                    histogram: new_histogram(),
+                    per_query: HashMap::new(),
+                };
+
+                while let Some(query) = queries.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let sent = Instant::now();
+                    let result = query.execute(&mut conn).await;
+                    let latency_us = sent.elapsed().as_micros() as u64;
+
+                    worker.queries += 1;
+                    match result {
+                        Ok(rows) => worker.rows += rows as u64,
+                        Err(err) => {
+                            worker.errors += 1;
+                            eprintln!("Replay of {:?} failed: {:?}", query, err);
+                        }
+                    }
+                    worker.histogram.saturating_record(latency_us);
+                    worker
+                        .per_query
+                        .entry(query.name())
+                        .or_insert_with(new_histogram)
+                        .saturating_record(latency_us);
+                }
+
+                BenchResult::Ok(worker)
+            })
+        })
+        .collect();
+
+    let mut histogram = new_histogram();
+    let mut per_query: HashMap<&'static str, Histogram<u64>> = HashMap::new();
+    let (mut total, mut errors, mut rows) = (0, 0, 0);
+    for worker in workers {
+        let worker = worker.await??;
+        total += worker.queries;
+        errors += worker.errors;
+        rows += worker.rows;
+        histogram.add(&worker.histogram)?;
+        for (name, query_histogram) in worker.per_query {
+            per_query
+                .entry(name)
+                .or_insert_with(new_histogram)
+                .add(&query_histogram)?;
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    Ok(ReplayReport {
+        concurrency,
+        queries: total,
+        errors,
+        rows,
+        elapsed_secs: elapsed,
+        queries_per_sec: total as f64 / elapsed,
+        latency: LatencySummary::from_histogram(&histogram),
+        per_query: per_query
+            .iter()
+            .map(|(name, histogram)| (*name, LatencySummary::from_histogram(histogram)))
+            .collect(),
+    })
+}
@@ -0,0 +1,227 @@
+// Slow-client protection (slowloris headers, trickled request bodies), so slow-client
+// scenarios can be run against this server as well as the others. Configured with:
+//
+//   HEADER_READ_TIMEOUT_MS  time allowed for a request's headers (default 30000, 0 disables)
+//   BODY_READ_TIMEOUT_MS    time allowed for a whole request body (default 0, disabled)
+//   MIN_BODY_RATE_BPS       minimum average body transfer rate in bytes/s, enforced after
+//                           a one second grace period (default 0, disabled)
+
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::{Request, State},
+    middleware::{self, Next},
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use hyper::server::conn::http1;
+use hyper_util::{
+    rt::{TokioIo, TokioTimer},
+    service::TowerToHyperService,
+};
+use tokio::{net::TcpListener, time::Sleep};
+
+const DEFAULT_HEADER_READ_TIMEOUT: Duration = Duration::from_secs(30);
+// Lets a body start slowly (e.g. TCP slow start) before the minimum rate applies
+const RATE_GRACE: Duration = Duration::from_secs(1);
+// Pause after an accept error other than a failed connection, e.g. EMFILE
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug)]
+pub struct ClientLimits {
+    pub header_read_timeout: Option<Duration>,
+    pub body_read_timeout: Option<Duration>,
+    pub min_body_rate: Option<u64>,
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    let value = std::env::var(name).ok()?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            eprintln!("Ignoring invalid {}: {}", name, value);
+            None
+        }
+    }
+}
+
+impl ClientLimits {
+    pub fn from_env() -> Self {
+        let millis = |name| env_u64(name).map(Duration::from_millis);
+
+        ClientLimits {
+            header_read_timeout: match millis("HEADER_READ_TIMEOUT_MS") {
+                Some(Duration::ZERO) => None,
+                Some(timeout) => Some(timeout),
+                None => Some(DEFAULT_HEADER_READ_TIMEOUT),
+            },
+            body_read_timeout: millis("BODY_READ_TIMEOUT_MS").filter(|t| !t.is_zero()),
+            min_body_rate: env_u64("MIN_BODY_RATE_BPS").filter(|&rate| rate > 0),
+        }
+    }
+
+    fn limits_body(&self) -> bool {
+        self.body_read_timeout.is_some() || self.min_body_rate.is_some()
+    }
+}
+
+// Request body that fails once it exceeds the read timeout or falls below the minimum rate
+struct LimitedBody {
+    inner: Body,
+    started: Instant,
+    received: u64,
+    limits: Arc<ClientLimits>,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl LimitedBody {
+    fn new(inner: Body, limits: Arc<ClientLimits>) -> Self {
+        let started = Instant::now();
+        let mut body = LimitedBody {
+            inner,
+            started,
+            received: 0,
+            limits,
+            deadline: Box::pin(tokio::time::sleep_until(started.into())),
+        };
+        body.reset_deadline();
+        body
+    }
+
+    // The earlier of the read timeout and the moment the average rate would drop below
+    // the minimum if nothing more arrived
+    fn reset_deadline(&mut self) {
+        let timeout = self.limits.body_read_timeout.map(|t| self.started + t);
+        let rate = self.limits.min_body_rate.map(|rate| {
+            self.started
+                + RATE_GRACE.max(Duration::from_secs_f64(self.received as f64 / rate as f64))
+        });
+
+        if let Some(deadline) = [timeout, rate].into_iter().flatten().min() {
+            self.deadline.as_mut().reset(deadline.into());
+        }
+    }
+}
+
+impl http_body::Body for LimitedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    this.received += data.len() as u64;
+                    this.reset_deadline();
+                }
+                return Poll::Ready(Some(Ok(frame)));
+            }
+            Poll::Ready(other) => return Poll::Ready(other),
+            Poll::Pending => {}
+        }
+
+        if this.deadline.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Some(Err(axum::Error::new(format!(
+                "request body too slow: {} bytes in {:?}",
+                this.received,
+                this.started.elapsed()
+            )))));
+        }
+        Poll::Pending
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+async fn limit_body(
+    State(limits): State<Arc<ClientLimits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    next.run(request.map(|body| Body::new(LimitedBody::new(body, limits))))
+        .await
+}
+
+// Like `axum::serve`, but over HTTP/1 connections configured with `limits`, with an accept
+// loop per listener
+pub async fn serve(
+    listeners: Vec<TcpListener>,
+    app: Router,
+    limits: ClientLimits,
+    tcp_nodelay: bool,
+) {
+    let limits = Arc::new(limits);
+    let app = if limits.limits_body() {
+        app.layer(middleware::from_fn_with_state(limits.clone(), limit_body))
+    } else {
+        app
+    };
+
+    let acceptors: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| tokio::spawn(accept(listener, app.clone(), limits.clone(), tcp_nodelay)))
+        .collect();
+    for acceptor in acceptors {
+        let _ = acceptor.await;
+    }
+}
+
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+async fn accept(listener: TcpListener, app: Router, limits: Arc<ClientLimits>, tcp_nodelay: bool) {
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            // A connection reset or aborted before it was accepted is the client's business
+            Err(err) if is_connection_error(&err) => continue,
+            // Out of file descriptors and the like: retrying at once would only spin
+            Err(err) => {
+                eprintln!("Failed to accept connection: {:?}", err);
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(tcp_nodelay);
+
+        let service = TowerToHyperService::new(app.clone());
+        let header_read_timeout = limits.header_read_timeout;
+
+        tokio::spawn(async move {
+            let mut builder = http1::Builder::new();
+            builder
+                .timer(TokioTimer::new())
+                .header_read_timeout(header_read_timeout);
+
+            // Errors here are clients timing out or hanging up; nothing to report
+            let _ = builder
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
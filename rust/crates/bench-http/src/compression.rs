@@ -0,0 +1,62 @@
+// Response compression, to measure what compressing the larger JSON responses costs in
+// throughput and CPU under load. Off by default; `--compression gzip,br,zstd` (or any subset)
+// compresses responses of at least 32 bytes with whichever of the listed codecs the request's
+// Accept-Encoding prefers, and everything else passes through as before.
+
+use axum::Router;
+use clap::{Args, ValueEnum};
+use tower_http::{CompressionLevel, compression::CompressionLayer};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Codec {
+    Gzip,
+    Br,
+    Zstd,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Level {
+    Fastest,
+    Default,
+    Best,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CompressionConfig {
+    /// Codecs to compress responses with, comma-separated; none disables compression
+    #[arg(
+        long = "compression",
+        env = "COMPRESSION",
+        value_enum,
+        value_delimiter = ','
+    )]
+    pub codecs: Vec<Codec>,
+
+    /// Trade-off between compression speed and size, for every codec
+    #[arg(long, env = "COMPRESSION_LEVEL", value_enum, default_value_t = Level::Default)]
+    pub compression_level: Level,
+}
+
+impl CompressionConfig {
+    // `app` behind the compression layer, as the outermost layer, so the other layers see
+    // (and the metrics count) the uncompressed responses
+    pub fn apply(&self, app: Router) -> Router {
+        if self.codecs.is_empty() {
+            return app;
+        }
+
+        let level = match self.compression_level {
+            Level::Fastest => CompressionLevel::Fastest,
+            Level::Default => CompressionLevel::Default,
+            Level::Best => CompressionLevel::Best,
+        };
+        app.layer(
+            CompressionLayer::new()
+                .gzip(self.codecs.contains(&Codec::Gzip))
+                .br(self.codecs.contains(&Codec::Br))
+                .zstd(self.codecs.contains(&Codec::Zstd))
+                .no_deflate()
+                .quality(level),
+        )
+    }
+}
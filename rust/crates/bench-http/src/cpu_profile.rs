@@ -0,0 +1,101 @@
+// `GET /debug/pprof/profile`: a CPU profile of the running server, sampled with pprof-rs for
+// `seconds`, to profile it under load without restarting it under perf. It answers the
+// protobuf `go tool pprof` reads,
+//
+//   go tool pprof -http=: 'http://localhost:3003/debug/pprof/profile?seconds=20'
+//
+// or with `format=flamegraph` (or Accept: image/svg+xml) an SVG flame graph; see
+// `params::CpuProfile`. One profile is taken at a time, another request meanwhile is a 409.
+// Served by servers built with the `pprof` feature.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use axum::{
+    Router,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use bench_core::BenchResult;
+use pprof::{ProfilerGuardBuilder, protos::Message};
+
+use crate::params::{CpuProfile, ProfileFormat};
+
+// Frames of these libraries are left out of the samples, as pprof-rs recommends: unwinding
+// through them can crash
+const BLOCKLIST: &[&str] = &["libc", "libgcc", "pthread", "vdso"];
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+// Clears RUNNING when the profile is done, even if its request went away
+struct Running;
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::Release);
+    }
+}
+
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new().route("/debug/pprof/profile", get(profile))
+}
+
+async fn profile(params: CpuProfile) -> Response {
+    if RUNNING.swap(true, Ordering::AcqRel) {
+        return (StatusCode::CONFLICT, "A CPU profile is already being taken").into_response();
+    }
+    let running = Running;
+    // Sampling and symbolizing block, and the profile must go on if the client leaves
+    let profiled = tokio::task::spawn_blocking(move || {
+        let _running = running;
+        capture(params)
+    })
+    .await;
+
+    match profiled {
+        Ok(Ok(body)) => match params.format {
+            ProfileFormat::Flamegraph => {
+                ([(header::CONTENT_TYPE, "image/svg+xml")], body).into_response()
+            }
+            ProfileFormat::Pprof => (
+                [
+                    (header::CONTENT_TYPE, "application/octet-stream"),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"profile.pb\"",
+                    ),
+                ],
+                body,
+            )
+                .into_response(),
+        },
+        Ok(Err(err)) => {
+            eprintln!("CPU profile failed: {:?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(err) => {
+            eprintln!("CPU profile panicked: {:?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// Samples every thread for `params.seconds`, blocking meanwhile
+fn capture(params: CpuProfile) -> BenchResult<Vec<u8>> {
+    let guard = ProfilerGuardBuilder::default()
+        .frequency(params.frequency)
+        .blocklist(BLOCKLIST)
+        .build()?;
+    std::thread::sleep(Duration::from_secs(params.seconds));
+    let report = guard.report().build()?;
+
+    let mut body = Vec::new();
+    match params.format {
+        ProfileFormat::Flamegraph => report.flamegraph(&mut body)?,
+        ProfileFormat::Pprof => report.pprof()?.encode(&mut body)?,
+    }
+    Ok(body)
+}
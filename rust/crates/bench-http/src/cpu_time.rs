@@ -0,0 +1,126 @@
+// Per-route CPU accounting: the thread CPU time spent polling each request's handler
+// future (query building, row decoding, serialization), which wall-clock latency hides.
+// Enabled with CPU_ACCOUNTING=1; every poll costs two clock_gettime calls.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RouteCpu {
+    pub route: String,
+    pub requests: u64,
+    pub cpu_seconds: f64,
+    pub cpu_seconds_per_1k_requests: f64,
+}
+
+#[derive(Default)]
+struct Totals {
+    requests: u64,
+    cpu_ns: u64,
+}
+
+#[derive(Default)]
+pub struct CpuAccounting {
+    routes: Mutex<HashMap<String, Totals>>,
+}
+
+// CPU time consumed by the calling thread so far
+fn thread_cpu_ns() -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid timespec for clock_gettime to write into
+    unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+// Adds the CPU time of every poll of `inner` to `cpu_ns`. Measured per poll because tokio
+// may move the task between worker threads in between
+struct CpuTimed<F> {
+    inner: Pin<Box<F>>,
+    cpu_ns: u64,
+}
+
+impl<F: Future> Future for CpuTimed<F> {
+    type Output = (F::Output, u64);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let before = thread_cpu_ns();
+        let poll = this.inner.as_mut().poll(cx);
+        this.cpu_ns += thread_cpu_ns().saturating_sub(before);
+
+        poll.map(|output| (output, this.cpu_ns))
+    }
+}
+
+impl CpuAccounting {
+    pub fn from_env() -> Option<Self> {
+        matches!(
+            std::env::var("CPU_ACCOUNTING").as_deref(),
+            Ok("1") | Ok("true")
+        )
+        .then(CpuAccounting::default)
+    }
+
+    pub fn stats(&self) -> Vec<RouteCpu> {
+        let routes = self.routes.lock();
+        let mut stats: Vec<RouteCpu> = routes
+            .iter()
+            .map(|(route, totals)| {
+                let cpu_seconds = totals.cpu_ns as f64 / 1e9;
+                RouteCpu {
+                    route: route.clone(),
+                    requests: totals.requests,
+                    cpu_seconds,
+                    cpu_seconds_per_1k_requests: if totals.requests > 0 {
+                        cpu_seconds * 1000.0 / totals.requests as f64
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.route.cmp(&b.route));
+        stats
+    }
+}
+
+pub async fn account(
+    State(accounting): State<Arc<CpuAccounting>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    let (response, cpu_ns) = CpuTimed {
+        inner: Box::pin(next.run(request)),
+        cpu_ns: 0,
+    }
+    .await;
+
+    let mut routes = accounting.routes.lock();
+    let totals = routes.entry(route).or_default();
+    totals.requests += 1;
+    totals.cpu_ns += cpu_ns;
+
+    response
+}
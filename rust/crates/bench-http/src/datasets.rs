@@ -0,0 +1,54 @@
+// Extra databases selectable per request with the `X-Dataset` header, so small and large
+// dataset comparisons can be interleaved in one run without restarting the server:
+//
+//   DATASETS  comma separated name=url pairs, e.g. sf10=postgres://...,sf100=postgres://...
+//
+// Requests without the header keep using DATABASE_URL.
+
+use std::collections::HashMap;
+
+use bench_core::{DbPool, config::PoolConfig, establish_async_pool};
+
+pub const DATASET_HEADER: &str = "x-dataset";
+
+pub struct Datasets {
+    pools: HashMap<String, DbPool>,
+    // For connections outside the pools
+    urls: HashMap<String, String>,
+}
+
+impl Datasets {
+    // Connects one pool per dataset, sized like the main one; None when DATASETS is unset
+    // or empty
+    pub async fn from_env(pool_config: &PoolConfig) -> Option<Self> {
+        let config = std::env::var("DATASETS").ok()?;
+        let mut pools = HashMap::new();
+        let mut urls = HashMap::new();
+
+        for entry in config.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((name, url)) = entry.split_once('=') else {
+                eprintln!("Ignoring DATASETS entry without a name: {}", entry);
+                continue;
+            };
+            pools.insert(
+                name.trim().to_string(),
+                establish_async_pool(url.trim(), pool_config).await,
+            );
+            urls.insert(name.trim().to_string(), url.trim().to_string());
+        }
+
+        (!pools.is_empty()).then_some(Datasets { pools, urls })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DbPool> {
+        self.pools.get(name)
+    }
+
+    pub fn url(&self, name: &str) -> Option<&str> {
+        self.urls.get(name).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &DbPool)> {
+        self.pools.iter().map(|(name, pool)| (name.as_str(), pool))
+    }
+}
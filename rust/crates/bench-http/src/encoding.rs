@@ -0,0 +1,175 @@
+// Response encoding of the 13 query routes, negotiated with Accept, so serialization cost
+// can be benchmarked apart from the queries: `application/msgpack` encodes the same structs
+// with rmp-serde (field names kept, as in the JSON), anything else gets JSON. Servers built
+// without the `msgpack` feature answer MessagePack requests with 406.
+//
+// The JSON itself (and the NDJSON lines) can come from one of three encoders, to compare
+// them on identical payloads: serde_json, simd-json (`json-simd` feature) or sonic-rs
+// (`json-sonic` feature), chosen with `--json-encoder`. Without the flag a server built
+// with one of the features uses that encoder, sonic-rs if both, and serde_json otherwise.
+
+use std::{convert::Infallible, sync::OnceLock};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{HeaderMap, StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use bench_core::BenchResult;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::request_log;
+
+pub const MSGPACK: &str = "application/msgpack";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Json,
+    MsgPack,
+}
+
+impl Format {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let msgpack = headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains(MSGPACK));
+
+        if msgpack {
+            Format::MsgPack
+        } else {
+            Format::Json
+        }
+    }
+
+    pub fn respond<T: Serialize + ?Sized>(self, value: &T) -> Response {
+        request_log::time_serialization(|| self.encode(value))
+    }
+
+    fn encode<T: Serialize + ?Sized>(self, value: &T) -> Response {
+        match self {
+            Format::Json => match JsonEncoder::current().to_vec(value) {
+                Ok(body) => ([(header::CONTENT_TYPE, "application/json")], body).into_response(),
+                Err(err) => {
+                    eprintln!("Failed to encode JSON response: {:?}", err);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            },
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack => match rmp_serde::to_vec_named(value) {
+                Ok(body) => ([(header::CONTENT_TYPE, MSGPACK)], body).into_response(),
+                Err(err) => {
+                    eprintln!("Failed to encode MessagePack response: {:?}", err);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            },
+            #[cfg(not(feature = "msgpack"))]
+            Format::MsgPack => StatusCode::NOT_ACCEPTABLE.into_response(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Format::from_headers(&parts.headers))
+    }
+}
+
+pub trait Encoder {
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> BenchResult<Vec<u8>>;
+}
+
+pub struct SerdeJson;
+
+impl Encoder for SerdeJson {
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> BenchResult<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+}
+
+#[cfg(feature = "json-simd")]
+pub struct SimdJson;
+
+#[cfg(feature = "json-simd")]
+impl Encoder for SimdJson {
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> BenchResult<Vec<u8>> {
+        Ok(simd_json::to_vec(value)?)
+    }
+}
+
+#[cfg(feature = "json-sonic")]
+pub struct SonicRs;
+
+#[cfg(feature = "json-sonic")]
+impl Encoder for SonicRs {
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> BenchResult<Vec<u8>> {
+        Ok(sonic_rs::to_vec(value)?)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum JsonEncoder {
+    SerdeJson,
+    SimdJson,
+    SonicRs,
+}
+
+static JSON_ENCODER: OnceLock<JsonEncoder> = OnceLock::new();
+
+impl JsonEncoder {
+    fn built_default() -> Self {
+        if cfg!(feature = "json-sonic") {
+            JsonEncoder::SonicRs
+        } else if cfg!(feature = "json-simd") {
+            JsonEncoder::SimdJson
+        } else {
+            JsonEncoder::SerdeJson
+        }
+    }
+
+    // Sets the encoder of every response from now on, `encoder` or the built-in default;
+    // an error for an encoder this server was built without. Call before serving.
+    pub fn select(encoder: Option<JsonEncoder>) -> Result<(), String> {
+        let encoder = encoder.unwrap_or_else(Self::built_default);
+        let missing = match encoder {
+            JsonEncoder::SerdeJson => None,
+            JsonEncoder::SimdJson => {
+                (!cfg!(feature = "json-simd")).then_some(("simd-json", "json-simd"))
+            }
+            JsonEncoder::SonicRs => {
+                (!cfg!(feature = "json-sonic")).then_some(("sonic-rs", "json-sonic"))
+            }
+        };
+        if let Some((name, feature)) = missing {
+            return Err(format!(
+                "{} needs a server built with the `{}` feature",
+                name, feature
+            ));
+        }
+        let _ = JSON_ENCODER.set(encoder);
+        Ok(())
+    }
+
+    pub fn current() -> Self {
+        *JSON_ENCODER.get_or_init(Self::built_default)
+    }
+
+    pub fn to_vec<T: Serialize + ?Sized>(self, value: &T) -> BenchResult<Vec<u8>> {
+        match self {
+            JsonEncoder::SerdeJson => SerdeJson::to_vec(value),
+            #[cfg(feature = "json-simd")]
+            JsonEncoder::SimdJson => SimdJson::to_vec(value),
+            #[cfg(not(feature = "json-simd"))]
+            JsonEncoder::SimdJson => unreachable!("rejected by select"),
+            #[cfg(feature = "json-sonic")]
+            JsonEncoder::SonicRs => SonicRs::to_vec(value),
+            #[cfg(not(feature = "json-sonic"))]
+            JsonEncoder::SonicRs => unreachable!("rejected by select"),
+        }
+    }
+}
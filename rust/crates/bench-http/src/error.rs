@@ -0,0 +1,151 @@
+// Machine-readable error codes, so load-generator assertions and dashboards can tell
+// failures apart without parsing messages. Every error response of the server gets a JSON
+// body naming one,
+//
+//   {"code":"POOL_TIMEOUT","message":"Service Unavailable"}
+//
+// and /metrics counts them per route in `http_errors_total{route,code}`. The code comes
+// from the response's status, or from an `ErrorCode` extension where the status alone
+// doesn't tell (a 503 is a pool timeout unless marked OVERLOADED):
+//
+//   INVALID_PARAM  400, 415 and 422: a parameter or body the route doesn't take
+//   NOT_FOUND      404
+//   POOL_TIMEOUT   503: no connection became free within the pool's connection timeout
+//   OVERLOADED     503 when the in-flight limit sheds the request
+//   DB_ERROR       500: the query failed
+//   TIMEOUT        504 and 408: a statement timeout, or a request too slow to arrive
+//
+// Other statuses (405, 409, 429, ...) keep their bodies.
+
+use axum::{
+    Json,
+    body::to_bytes,
+    extract::Request,
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use diesel::result::Error as DieselError;
+use diesel_async::pooled_connection::bb8::RunError;
+use serde::Serialize;
+
+// Longest error body kept as the message; longer ones are replaced by the status' reason
+const MAX_MESSAGE_BYTES: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    PoolTimeout,
+    NotFound,
+    InvalidParam,
+    DbError,
+    Timeout,
+    Overloaded,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::PoolTimeout => "POOL_TIMEOUT",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::InvalidParam => "INVALID_PARAM",
+            ErrorCode::DbError => "DB_ERROR",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::Overloaded => "OVERLOADED",
+        }
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::PoolTimeout | ErrorCode::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::InvalidParam => StatusCode::BAD_REQUEST,
+            ErrorCode::DbError => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    fn for_status(status: StatusCode) -> Option<Self> {
+        match status {
+            StatusCode::BAD_REQUEST
+            | StatusCode::UNSUPPORTED_MEDIA_TYPE
+            | StatusCode::UNPROCESSABLE_ENTITY => Some(ErrorCode::InvalidParam),
+            StatusCode::NOT_FOUND => Some(ErrorCode::NotFound),
+            StatusCode::SERVICE_UNAVAILABLE => Some(ErrorCode::PoolTimeout),
+            StatusCode::INTERNAL_SERVER_ERROR => Some(ErrorCode::DbError),
+            StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => Some(ErrorCode::Timeout),
+            _ => None,
+        }
+    }
+}
+
+// Errors of the handlers' pool checkouts and queries, by the code they answer with
+pub trait Classify {
+    fn code(&self) -> ErrorCode;
+}
+
+impl Classify for DieselError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            DieselError::NotFound => ErrorCode::NotFound,
+            // Postgres' query_canceled, which has no DatabaseErrorKind of its own
+            DieselError::DatabaseError(_, info) if info.message().contains("statement timeout") => {
+                ErrorCode::Timeout
+            }
+            _ => ErrorCode::DbError,
+        }
+    }
+}
+
+impl Classify for RunError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            RunError::TimedOut => ErrorCode::PoolTimeout,
+            RunError::User(_) => ErrorCode::DbError,
+        }
+    }
+}
+
+// For `map_err`: the status of `err`'s code
+pub fn failed<E: Classify>(err: E) -> StatusCode {
+    err.code().status()
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: ErrorCode,
+    message: String,
+}
+
+// Middleware giving error responses their code, as a JSON body and as an `ErrorCode`
+// extension for the metrics
+pub async fn error_codes(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+    let Some(code) = response
+        .extensions()
+        .get::<ErrorCode>()
+        .copied()
+        .or_else(|| ErrorCode::for_status(status))
+    else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let reason = || status.canonical_reason().unwrap_or_default().to_owned();
+    let message = match to_bytes(body, MAX_MESSAGE_BYTES).await {
+        Ok(bytes) if !bytes.is_empty() => String::from_utf8_lossy(&bytes).into_owned(),
+        _ => reason(),
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    parts.extensions.insert(code);
+    let body = Json(ErrorBody { code, message }).into_response();
+    let (body_parts, body) = body.into_parts();
+    parts.headers.extend(body_parts.headers);
+    Response::from_parts(parts, body)
+}
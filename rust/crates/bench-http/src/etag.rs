@@ -0,0 +1,78 @@
+// ETags on the query routes, for revalidation-heavy client patterns. Enabled with ETAGS=1:
+// every 200 to a GET gets a strong ETag of its body (the XXH3 hash, so computing it costs
+// little next to the query), and a request whose If-None-Match names it gets a 304 without
+// the body instead. The query still runs either way; what a revalidation saves is the
+// transfer. Streamed responses (NDJSON) have no length up front and pass through untagged.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use xxhash_rust::xxh3::xxh3_64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Etags;
+
+impl Etags {
+    pub fn from_env() -> Option<Self> {
+        matches!(std::env::var("ETAGS").as_deref(), Ok("1") | Ok("true")).then_some(Etags)
+    }
+}
+
+pub async fn tag(request: Request, next: Next) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK
+        || http_body::Body::size_hint(response.body())
+            .exact()
+            .is_none()
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => {
+            eprintln!("Failed to buffer response for its ETag: {:?}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = format!("\"{:016x}\"", xxh3_64(&body));
+    let matched = if_none_match.is_some_and(|value| matches(&value, &etag));
+    parts
+        .headers
+        .insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+
+    if matched {
+        strip_content_headers(&mut parts.headers);
+        parts.status = StatusCode::NOT_MODIFIED;
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+// Whether an If-None-Match value lists `etag` or is `*`. The comparison is the weak one
+// RFC 9110 asks for, so a `W/` a proxy may have added doesn't matter.
+fn matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+// A 304 carries no body, so nothing describing one
+fn strip_content_headers(headers: &mut HeaderMap) {
+    headers.remove(header::CONTENT_LENGTH);
+    headers.remove(header::CONTENT_TYPE);
+    headers.remove(header::CONTENT_ENCODING);
+}
@@ -0,0 +1,388 @@
+// POST /graphql (the `graphql` feature): customers, suppliers, products and orders as an
+// async-graphql schema whose resolvers call the same queries.rs functions as the REST
+// handlers, so resolver overhead can be measured against plain REST on identical data:
+//
+//   { order(id: 1) { shipName details { quantity product { name supplier { city } } } } }
+//
+// Nested fields cost nothing unless selected. `product(id)` prefetches its supplier with
+// p9's join, while the supplier of a listed product or an order's product takes one more
+// query (p7) each, as a resolver without a data loader would. Lists take the same limit
+// defaults and cap as the REST routes.
+
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject,
+};
+use axum::{Json, Router, extract::State, routing::post};
+use bench_core::{
+    DbPool, models,
+    queries::{self, *},
+};
+use chrono::NaiveDate;
+
+use crate::params::{self, ParamError};
+
+type BenchSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// Deep enough for order -> details -> product -> supplier
+const MAX_DEPTH: usize = 8;
+
+// Logged and answered with a generic message, as the REST handlers answer a bare 500
+fn failed(err: impl std::fmt::Debug) -> async_graphql::Error {
+    eprintln!("GraphQL query failed: {:?}", err);
+    async_graphql::Error::new("internal error")
+}
+
+fn page(limit: Option<i64>, offset: Option<i64>) -> async_graphql::Result<(i64, i64)> {
+    params::page(limit, offset).map_err(|ParamError(message)| message.into())
+}
+
+#[derive(SimpleObject)]
+struct Customer {
+    id: i32,
+    company_name: String,
+    contact_name: String,
+    contact_title: String,
+    address: String,
+    city: String,
+    postal_code: Option<String>,
+    region: Option<String>,
+    country: String,
+    phone: String,
+    fax: Option<String>,
+}
+
+impl From<models::Customer> for Customer {
+    fn from(c: models::Customer) -> Self {
+        Customer {
+            id: c.id,
+            company_name: c.company_name,
+            contact_name: c.contact_name,
+            contact_title: c.contact_title,
+            address: c.address,
+            city: c.city,
+            postal_code: c.postal_code,
+            region: c.region,
+            country: c.country,
+            phone: c.phone,
+            fax: c.fax,
+        }
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+struct Supplier {
+    id: i32,
+    company_name: String,
+    contact_name: String,
+    contact_title: String,
+    address: String,
+    city: String,
+    region: Option<String>,
+    postal_code: String,
+    country: String,
+    phone: String,
+}
+
+impl From<models::Supplier> for Supplier {
+    fn from(s: models::Supplier) -> Self {
+        Supplier {
+            id: s.id,
+            company_name: s.company_name,
+            contact_name: s.contact_name,
+            contact_title: s.contact_title,
+            address: s.address,
+            city: s.city,
+            region: s.region,
+            postal_code: s.postal_code,
+            country: s.country,
+            phone: s.phone,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct Product {
+    id: i32,
+    name: String,
+    qt_per_unit: String,
+    unit_price: f64,
+    units_in_stock: i32,
+    units_on_order: i32,
+    reorder_level: i32,
+    discontinued: i32,
+    supplier_id: i32,
+    // Set when the query joined it in
+    #[graphql(skip)]
+    supplier: Option<Supplier>,
+}
+
+#[ComplexObject]
+impl Product {
+    async fn supplier(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Supplier>> {
+        if let Some(supplier) = &self.supplier {
+            return Ok(Some(supplier.clone()));
+        }
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let supplier = p7(&mut conn, self.supplier_id).await.map_err(failed)?;
+        Ok(supplier.map(Supplier::from))
+    }
+}
+
+impl From<models::Product> for Product {
+    fn from(p: models::Product) -> Self {
+        Product {
+            id: p.id,
+            name: p.name,
+            qt_per_unit: p.qt_per_unit,
+            unit_price: p.unit_price,
+            units_in_stock: p.units_in_stock,
+            units_on_order: p.units_on_order,
+            reorder_level: p.reorder_level,
+            discontinued: p.discontinued,
+            supplier_id: p.supplier_id,
+            supplier: None,
+        }
+    }
+}
+
+impl From<ProductWithSupplier> for Product {
+    fn from(p: ProductWithSupplier) -> Self {
+        Product {
+            id: p.id,
+            name: p.name,
+            qt_per_unit: p.qt_per_unit,
+            unit_price: p.unit_price,
+            units_in_stock: p.units_in_stock,
+            units_on_order: p.units_on_order,
+            reorder_level: p.reorder_level,
+            discontinued: p.discontinued,
+            supplier_id: p.supplier_id,
+            supplier: Some(Supplier {
+                id: p.supplier_supplier_id,
+                company_name: p.supplier_company_name,
+                contact_name: p.supplier_contact_name,
+                contact_title: p.supplier_contact_title,
+                address: p.supplier_address,
+                city: p.supplier_city,
+                region: p.supplier_region,
+                postal_code: p.supplier_postal_code,
+                country: p.supplier_country,
+                phone: p.supplier_phone,
+            }),
+        }
+    }
+}
+
+// A row of p11: an order with its details summed up
+#[derive(SimpleObject)]
+struct OrderSummary {
+    id: i32,
+    shipped_date: Option<NaiveDate>,
+    ship_name: String,
+    ship_city: String,
+    ship_country: String,
+    products_count: i64,
+    quantity_sum: Option<i64>,
+    total_price: Option<f64>,
+}
+
+impl From<P11Row> for OrderSummary {
+    fn from(row: P11Row) -> Self {
+        OrderSummary {
+            id: row.id,
+            shipped_date: row.shipped_date,
+            ship_name: row.ship_name,
+            ship_city: row.ship_city,
+            ship_country: row.ship_country,
+            products_count: row.products_count,
+            quantity_sum: row.quantity_sum,
+            total_price: row.total_price,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct OrderDetail {
+    unit_price: f64,
+    quantity: i32,
+    discount: f64,
+    order_id: i32,
+    product_id: i32,
+    product: Product,
+}
+
+impl From<queries::OrderDetail> for OrderDetail {
+    fn from(d: queries::OrderDetail) -> Self {
+        OrderDetail {
+            unit_price: d.unit_price,
+            quantity: d.quantity,
+            discount: d.discount,
+            order_id: d.order_id,
+            product_id: d.product_id,
+            product: Product {
+                id: d.product_product_id,
+                name: d.product_name,
+                qt_per_unit: d.product_qt_per_unit,
+                unit_price: d.product_unit_price,
+                units_in_stock: d.product_units_in_stock,
+                units_on_order: d.product_units_on_order,
+                reorder_level: d.product_reorder_level,
+                discontinued: d.product_discontinued,
+                supplier_id: d.product_supplier_id,
+                supplier: None,
+            },
+        }
+    }
+}
+
+// An order of p13, with its details and their products
+#[derive(SimpleObject)]
+struct Order {
+    id: i32,
+    order_date: NaiveDate,
+    required_date: NaiveDate,
+    shipped_date: Option<NaiveDate>,
+    ship_via: i32,
+    freight: f64,
+    ship_name: String,
+    ship_city: String,
+    ship_region: Option<String>,
+    ship_postal_code: Option<String>,
+    ship_country: String,
+    customer_id: i32,
+    employee_id: i32,
+    details: Vec<OrderDetail>,
+}
+
+impl From<OrderWithDetailsAndProducts> for Order {
+    fn from(o: OrderWithDetailsAndProducts) -> Self {
+        Order {
+            id: o.id,
+            order_date: o.order_date,
+            required_date: o.required_date,
+            shipped_date: o.shipped_date,
+            ship_via: o.ship_via,
+            freight: o.freight,
+            ship_name: o.ship_name,
+            ship_city: o.ship_city,
+            ship_region: o.ship_region,
+            ship_postal_code: o.ship_postal_code,
+            ship_country: o.ship_country,
+            customer_id: o.customer_id,
+            employee_id: o.employee_id,
+            details: o.details.into_iter().map(OrderDetail::from).collect(),
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // p1
+    async fn customers(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> async_graphql::Result<Vec<Customer>> {
+        let (limit, offset) = page(limit, offset)?;
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let rows = p1(&mut conn, limit, offset).await.map_err(failed)?;
+        Ok(rows.into_iter().map(Customer::from).collect())
+    }
+
+    // p2
+    async fn customer(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> async_graphql::Result<Option<Customer>> {
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let row = p2(&mut conn, id).await.map_err(failed)?;
+        Ok(row.map(Customer::from))
+    }
+
+    // p6
+    async fn suppliers(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> async_graphql::Result<Vec<Supplier>> {
+        let (limit, offset) = page(limit, offset)?;
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let rows = p6(&mut conn, limit, offset).await.map_err(failed)?;
+        Ok(rows.into_iter().map(Supplier::from).collect())
+    }
+
+    // p7
+    async fn supplier(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> async_graphql::Result<Option<Supplier>> {
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let row = p7(&mut conn, id).await.map_err(failed)?;
+        Ok(row.map(Supplier::from))
+    }
+
+    // p8
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> async_graphql::Result<Vec<Product>> {
+        let (limit, offset) = page(limit, offset)?;
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let rows = p8(&mut conn, limit, offset).await.map_err(failed)?;
+        Ok(rows.into_iter().map(Product::from).collect())
+    }
+
+    // p9, supplier included
+    async fn product(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<Product>> {
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let row = p9(&mut conn, id).await.map_err(failed)?;
+        Ok(row.map(Product::from))
+    }
+
+    // p11
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> async_graphql::Result<Vec<OrderSummary>> {
+        let (limit, offset) = page(limit, offset)?;
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let rows = p11(&mut conn, limit, offset).await.map_err(failed)?;
+        Ok(rows.into_iter().map(OrderSummary::from).collect())
+    }
+
+    // p13
+    async fn order(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<Order>> {
+        let mut conn = ctx.data::<DbPool>()?.get().await.map_err(failed)?;
+        let row = p13(&mut conn, id).await.map_err(failed)?;
+        Ok(row.map(Order::from))
+    }
+}
+
+async fn execute(
+    State(schema): State<BenchSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+pub fn router(pool: DbPool) -> Router {
+    let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .limit_depth(MAX_DEPTH)
+        .finish();
+
+    Router::new()
+        .route("/graphql", post(execute))
+        .with_state(schema)
+}
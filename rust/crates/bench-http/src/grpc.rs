@@ -0,0 +1,454 @@
+// The 13 queries as a tonic gRPC service (the `grpc` feature), served on GRPC_PORT next to
+// the HTTP server and from the same pool, so gRPC+protobuf can be compared with the HTTP
+// transports on identical queries. Messages and RPCs are in proto/bench.proto; the RPCs
+// take the HTTP routes' parameters with the same defaults, and reject what they reject with
+// INVALID_ARGUMENT. A by-id RPC answers a missing row with an empty reply, not NOT_FOUND.
+
+// tonic's `Status` is large, and every RPC returns it
+#![allow(clippy::result_large_err)]
+
+use std::net::SocketAddr;
+
+use bench_core::{
+    DbPool, models,
+    queries::{self, *},
+};
+use chrono::NaiveDate;
+use diesel_async::{AsyncPgConnection, pooled_connection::bb8::PooledConnection};
+use tonic::{Request, Response, Status, transport::Server};
+
+use crate::params::{self, ParamError};
+
+mod pb {
+    tonic::include_proto!("bench");
+}
+
+use pb::bench_server::{Bench, BenchServer};
+
+// Logged and answered with a generic status, as the REST handlers answer a bare 500
+fn failed(err: impl std::fmt::Debug) -> Status {
+    eprintln!("gRPC query failed: {:?}", err);
+    Status::internal("internal error")
+}
+
+fn invalid(ParamError(message): ParamError) -> Status {
+    Status::invalid_argument(message)
+}
+
+fn date(date: NaiveDate) -> String {
+    date.to_string()
+}
+
+impl From<models::Customer> for pb::Customer {
+    fn from(c: models::Customer) -> Self {
+        pb::Customer {
+            id: c.id,
+            company_name: c.company_name,
+            contact_name: c.contact_name,
+            contact_title: c.contact_title,
+            address: c.address,
+            city: c.city,
+            postal_code: c.postal_code,
+            region: c.region,
+            country: c.country,
+            phone: c.phone,
+            fax: c.fax,
+        }
+    }
+}
+
+impl From<models::Employee> for pb::Employee {
+    fn from(e: models::Employee) -> Self {
+        pb::Employee {
+            id: e.id,
+            last_name: e.last_name,
+            first_name: e.first_name,
+            title: e.title,
+            title_of_courtesy: e.title_of_courtesy,
+            birth_date: date(e.birth_date),
+            hire_date: date(e.hire_date),
+            address: e.address,
+            city: e.city,
+            postal_code: e.postal_code,
+            country: e.country,
+            home_phone: e.home_phone,
+            extension: e.extension,
+            notes: e.notes,
+            recipient_id: e.recipient_id,
+        }
+    }
+}
+
+impl From<EmployeeWithRecipient> for pb::EmployeeWithRecipient {
+    fn from(e: EmployeeWithRecipient) -> Self {
+        // The LEFT JOIN's columns are all set or all null
+        let recipient = match e.recipient_employee_id {
+            Some(id) => Some(pb::Employee {
+                id,
+                last_name: e.recipient_last_name.unwrap_or_default(),
+                first_name: e.recipient_first_name,
+                title: e.recipient_title.unwrap_or_default(),
+                title_of_courtesy: e.recipient_title_of_courtesy.unwrap_or_default(),
+                birth_date: e.recipient_birth_date.map(date).unwrap_or_default(),
+                hire_date: e.recipient_hire_date.map(date).unwrap_or_default(),
+                address: e.recipient_address.unwrap_or_default(),
+                city: e.recipient_city.unwrap_or_default(),
+                postal_code: e.recipient_postal_code.unwrap_or_default(),
+                country: e.recipient_country.unwrap_or_default(),
+                home_phone: e.recipient_home_phone.unwrap_or_default(),
+                extension: e.recipient_extension.unwrap_or_default(),
+                notes: e.recipient_notes.unwrap_or_default(),
+                recipient_id: e.recipient_recipient_id,
+            }),
+            None => None,
+        };
+        pb::EmployeeWithRecipient {
+            employee: Some(pb::Employee {
+                id: e.id,
+                last_name: e.last_name,
+                first_name: e.first_name,
+                title: e.title,
+                title_of_courtesy: e.title_of_courtesy,
+                birth_date: date(e.birth_date),
+                hire_date: date(e.hire_date),
+                address: e.address,
+                city: e.city,
+                postal_code: e.postal_code,
+                country: e.country,
+                home_phone: e.home_phone,
+                extension: e.extension,
+                notes: e.notes,
+                recipient_id: e.recipient_id,
+            }),
+            recipient,
+        }
+    }
+}
+
+impl From<models::Supplier> for pb::Supplier {
+    fn from(s: models::Supplier) -> Self {
+        pb::Supplier {
+            id: s.id,
+            company_name: s.company_name,
+            contact_name: s.contact_name,
+            contact_title: s.contact_title,
+            address: s.address,
+            city: s.city,
+            region: s.region,
+            postal_code: s.postal_code,
+            country: s.country,
+            phone: s.phone,
+        }
+    }
+}
+
+impl From<models::Product> for pb::Product {
+    fn from(p: models::Product) -> Self {
+        pb::Product {
+            id: p.id,
+            name: p.name,
+            qt_per_unit: p.qt_per_unit,
+            unit_price: p.unit_price,
+            units_in_stock: p.units_in_stock,
+            units_on_order: p.units_on_order,
+            reorder_level: p.reorder_level,
+            discontinued: p.discontinued,
+            supplier_id: p.supplier_id,
+        }
+    }
+}
+
+impl From<ProductWithSupplier> for pb::ProductWithSupplier {
+    fn from(p: ProductWithSupplier) -> Self {
+        pb::ProductWithSupplier {
+            product: Some(pb::Product {
+                id: p.id,
+                name: p.name,
+                qt_per_unit: p.qt_per_unit,
+                unit_price: p.unit_price,
+                units_in_stock: p.units_in_stock,
+                units_on_order: p.units_on_order,
+                reorder_level: p.reorder_level,
+                discontinued: p.discontinued,
+                supplier_id: p.supplier_id,
+            }),
+            supplier: Some(pb::Supplier {
+                id: p.supplier_supplier_id,
+                company_name: p.supplier_company_name,
+                contact_name: p.supplier_contact_name,
+                contact_title: p.supplier_contact_title,
+                address: p.supplier_address,
+                city: p.supplier_city,
+                region: p.supplier_region,
+                postal_code: p.supplier_postal_code,
+                country: p.supplier_country,
+                phone: p.supplier_phone,
+            }),
+        }
+    }
+}
+
+impl From<P11Row> for pb::OrderSummary {
+    fn from(row: P11Row) -> Self {
+        pb::OrderSummary {
+            id: row.id,
+            shipped_date: row.shipped_date.map(date),
+            ship_name: row.ship_name,
+            ship_city: row.ship_city,
+            ship_country: row.ship_country,
+            products_count: row.products_count,
+            quantity_sum: row.quantity_sum,
+            total_price: row.total_price,
+        }
+    }
+}
+
+impl From<queries::OrderDetail> for pb::OrderDetail {
+    fn from(d: queries::OrderDetail) -> Self {
+        pb::OrderDetail {
+            unit_price: d.unit_price,
+            quantity: d.quantity,
+            discount: d.discount,
+            order_id: d.order_id,
+            product_id: d.product_id,
+            id: d.id,
+            product: Some(pb::Product {
+                id: d.product_product_id,
+                name: d.product_name,
+                qt_per_unit: d.product_qt_per_unit,
+                unit_price: d.product_unit_price,
+                units_in_stock: d.product_units_in_stock,
+                units_on_order: d.product_units_on_order,
+                reorder_level: d.product_reorder_level,
+                discontinued: d.product_discontinued,
+                supplier_id: d.product_supplier_id,
+            }),
+        }
+    }
+}
+
+impl From<OrderWithDetailsAndProducts> for pb::OrderWithDetails {
+    fn from(o: OrderWithDetailsAndProducts) -> Self {
+        pb::OrderWithDetails {
+            order: Some(pb::Order {
+                id: o.id,
+                order_date: date(o.order_date),
+                required_date: date(o.required_date),
+                shipped_date: o.shipped_date.map(date),
+                ship_via: o.ship_via,
+                freight: o.freight,
+                ship_name: o.ship_name,
+                ship_city: o.ship_city,
+                ship_region: o.ship_region,
+                ship_postal_code: o.ship_postal_code,
+                ship_country: o.ship_country,
+                customer_id: o.customer_id,
+                employee_id: o.employee_id,
+            }),
+            details: o.details.into_iter().map(pb::OrderDetail::from).collect(),
+        }
+    }
+}
+
+pub struct BenchService {
+    pool: DbPool,
+    // For the searches that don't name a dictionary
+    search_dictionary: SearchDictionary,
+}
+
+impl BenchService {
+    async fn conn(&self) -> Result<PooledConnection<'_, AsyncPgConnection>, Status> {
+        self.pool.get().await.map_err(failed)
+    }
+
+    fn dictionary(&self, name: Option<&str>) -> Result<SearchDictionary, Status> {
+        match name {
+            Some(name) => name.parse().map_err(Status::invalid_argument),
+            None => Ok(self.search_dictionary),
+        }
+    }
+}
+
+fn page(request: &pb::PageRequest) -> Result<(i64, i64), Status> {
+    params::page(request.limit, request.offset).map_err(invalid)
+}
+
+#[tonic::async_trait]
+impl Bench for BenchService {
+    async fn get_customers(
+        &self,
+        request: Request<pb::PageRequest>,
+    ) -> Result<Response<pb::Customers>, Status> {
+        let (limit, offset) = page(request.get_ref())?;
+        let rows = p1(&mut *self.conn().await?, limit, offset)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::Customers {
+            customers: rows.into_iter().map(pb::Customer::from).collect(),
+        }))
+    }
+
+    async fn get_customer_by_id(
+        &self,
+        request: Request<pb::IdRequest>,
+    ) -> Result<Response<pb::CustomerReply>, Status> {
+        let row = p2(&mut *self.conn().await?, request.get_ref().id)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::CustomerReply {
+            customer: row.map(pb::Customer::from),
+        }))
+    }
+
+    async fn search_customer(
+        &self,
+        request: Request<pb::SearchRequest>,
+    ) -> Result<Response<pb::Customers>, Status> {
+        let request = request.into_inner();
+        let dictionary = self.dictionary(request.dictionary.as_deref())?;
+        let rows = p3(&mut *self.conn().await?, &request.term, dictionary)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::Customers {
+            customers: rows.into_iter().map(pb::Customer::from).collect(),
+        }))
+    }
+
+    async fn get_employees(
+        &self,
+        request: Request<pb::PageRequest>,
+    ) -> Result<Response<pb::Employees>, Status> {
+        let (limit, offset) = page(request.get_ref())?;
+        let rows = p4(&mut *self.conn().await?, limit, offset)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::Employees {
+            employees: rows.into_iter().map(pb::Employee::from).collect(),
+        }))
+    }
+
+    async fn get_employee_with_recipient(
+        &self,
+        request: Request<pb::IdRequest>,
+    ) -> Result<Response<pb::EmployeeWithRecipient>, Status> {
+        let row = p5(&mut *self.conn().await?, request.get_ref().id)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(row.map(Into::into).unwrap_or_default()))
+    }
+
+    async fn get_suppliers(
+        &self,
+        request: Request<pb::PageRequest>,
+    ) -> Result<Response<pb::Suppliers>, Status> {
+        let (limit, offset) = page(request.get_ref())?;
+        let rows = p6(&mut *self.conn().await?, limit, offset)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::Suppliers {
+            suppliers: rows.into_iter().map(pb::Supplier::from).collect(),
+        }))
+    }
+
+    async fn get_supplier_by_id(
+        &self,
+        request: Request<pb::IdRequest>,
+    ) -> Result<Response<pb::SupplierReply>, Status> {
+        let row = p7(&mut *self.conn().await?, request.get_ref().id)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::SupplierReply {
+            supplier: row.map(pb::Supplier::from),
+        }))
+    }
+
+    async fn get_products(
+        &self,
+        request: Request<pb::PageRequest>,
+    ) -> Result<Response<pb::Products>, Status> {
+        let (limit, offset) = page(request.get_ref())?;
+        let rows = p8(&mut *self.conn().await?, limit, offset)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::Products {
+            products: rows.into_iter().map(pb::Product::from).collect(),
+        }))
+    }
+
+    async fn get_product_with_supplier(
+        &self,
+        request: Request<pb::IdRequest>,
+    ) -> Result<Response<pb::ProductWithSupplier>, Status> {
+        let row = p9(&mut *self.conn().await?, request.get_ref().id)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(row.map(Into::into).unwrap_or_default()))
+    }
+
+    async fn search_product(
+        &self,
+        request: Request<pb::SearchRequest>,
+    ) -> Result<Response<pb::Products>, Status> {
+        let request = request.into_inner();
+        let dictionary = self.dictionary(request.dictionary.as_deref())?;
+        let rows = p10(&mut *self.conn().await?, &request.term, dictionary)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::Products {
+            products: rows.into_iter().map(pb::Product::from).collect(),
+        }))
+    }
+
+    async fn get_orders_with_details(
+        &self,
+        request: Request<pb::PageRequest>,
+    ) -> Result<Response<pb::OrderSummaries>, Status> {
+        let (limit, offset) = page(request.get_ref())?;
+        let rows = p11(&mut *self.conn().await?, limit, offset)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::OrderSummaries {
+            orders: rows.into_iter().map(pb::OrderSummary::from).collect(),
+        }))
+    }
+
+    async fn get_order_with_details(
+        &self,
+        request: Request<pb::IdRequest>,
+    ) -> Result<Response<pb::OrderSummaryReply>, Status> {
+        let row = p12(&mut *self.conn().await?, request.get_ref().id)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(pb::OrderSummaryReply {
+            order: row.map(pb::OrderSummary::from),
+        }))
+    }
+
+    async fn get_order_with_details_and_products(
+        &self,
+        request: Request<pb::IdRequest>,
+    ) -> Result<Response<pb::OrderWithDetails>, Status> {
+        let row = p13(&mut *self.conn().await?, request.get_ref().id)
+            .await
+            .map_err(failed)?;
+        Ok(Response::new(row.map(Into::into).unwrap_or_default()))
+    }
+}
+
+// Serves the service until the process exits; a failure to bind is logged like the HTTP
+// listener's and leaves the HTTP server running
+pub async fn serve(addr: SocketAddr, pool: DbPool) {
+    let service = BenchService {
+        pool,
+        search_dictionary: SearchDictionary::from_env(),
+    };
+    println!("Starting gRPC server on port {}", addr.port());
+    if let Err(err) = Server::builder()
+        .tcp_nodelay(true)
+        .add_service(BenchServer::new(service))
+        .serve(addr)
+        .await
+    {
+        eprintln!("gRPC server on {} failed: {:?}", addr, err);
+    }
+}
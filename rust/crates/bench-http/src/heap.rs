@@ -0,0 +1,354 @@
+// dhat-style heap profiling: a counting wrapper around the global allocator recording
+// allocations by size class and by the route whose handler made them, to chase allocation
+// regressions in new serialization paths. Installed with HEAP_PROFILER=1, then started and
+// stopped at runtime through `POST /admin/heap-profiling`; `/debug/heap` downloads the dump.
+//
+// While stopped the wrapper costs one relaxed load per allocation. Counters are reset on
+// every start, so `live_bytes` only covers memory allocated since then (and can go
+// negative when older memory is freed).
+
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+// Powers of two up to 2^(SIZE_CLASSES - 1) bytes; larger allocations share the last class
+const SIZE_CLASSES: usize = 32;
+// Routes beyond this many are counted as untracked
+const MAX_ROUTES: usize = 64;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct Counters {
+    allocations: AtomicU64,
+    allocated_bytes: AtomicU64,
+    frees: AtomicU64,
+    freed_bytes: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Counters {
+            allocations: AtomicU64::new(0),
+            allocated_bytes: AtomicU64::new(0),
+            frees: AtomicU64::new(0),
+            freed_bytes: AtomicU64::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.allocations.store(0, Ordering::Relaxed);
+        self.allocated_bytes.store(0, Ordering::Relaxed);
+        self.frees.store(0, Ordering::Relaxed);
+        self.freed_bytes.store(0, Ordering::Relaxed);
+    }
+}
+
+static TOTALS: Counters = Counters::new();
+static LIVE: AtomicI64 = AtomicI64::new(0);
+static PEAK: AtomicI64 = AtomicI64::new(0);
+static SIZE_CLASS_COUNTS: [AtomicU64; SIZE_CLASSES] = [const { AtomicU64::new(0) }; SIZE_CLASSES];
+// Slot 0 collects allocations made outside any tracked route (connection handling,
+// background tasks, ...)
+static ROUTES: [Counters; MAX_ROUTES + 1] = [const { Counters::new() }; MAX_ROUTES + 1];
+
+thread_local! {
+    // Route slot whose handler is being polled on this thread
+    static CURRENT_ROUTE: Cell<usize> = const { Cell::new(0) };
+}
+
+fn size_class(size: usize) -> usize {
+    (usize::BITS - size.saturating_sub(1).leading_zeros()).min(SIZE_CLASSES as u32 - 1) as usize
+}
+
+fn route_slot() -> usize {
+    CURRENT_ROUTE.try_with(Cell::get).unwrap_or(0)
+}
+
+fn record_alloc(size: usize) {
+    let route = &ROUTES[route_slot()];
+    route.allocations.fetch_add(1, Ordering::Relaxed);
+    route
+        .allocated_bytes
+        .fetch_add(size as u64, Ordering::Relaxed);
+    TOTALS.allocations.fetch_add(1, Ordering::Relaxed);
+    TOTALS
+        .allocated_bytes
+        .fetch_add(size as u64, Ordering::Relaxed);
+    SIZE_CLASS_COUNTS[size_class(size)].fetch_add(1, Ordering::Relaxed);
+
+    let live = LIVE.fetch_add(size as i64, Ordering::Relaxed) + size as i64;
+    PEAK.fetch_max(live, Ordering::Relaxed);
+}
+
+fn record_free(size: usize) {
+    let route = &ROUTES[route_slot()];
+    route.frees.fetch_add(1, Ordering::Relaxed);
+    route.freed_bytes.fetch_add(size as u64, Ordering::Relaxed);
+    TOTALS.frees.fetch_add(1, Ordering::Relaxed);
+    TOTALS.freed_bytes.fetch_add(size as u64, Ordering::Relaxed);
+    LIVE.fetch_sub(size as i64, Ordering::Relaxed);
+}
+
+// Global allocator wrapper, e.g. `CountingAlloc(mimalloc::MiMalloc)`; see `allocator`
+pub struct CountingAlloc<A>(pub A);
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.0.alloc(layout) };
+        if !ptr.is_null() && ENABLED.load(Ordering::Relaxed) {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { self.0.alloc_zeroed(layout) };
+        if !ptr.is_null() && ENABLED.load(Ordering::Relaxed) {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { self.0.dealloc(ptr, layout) };
+        if ENABLED.load(Ordering::Relaxed) {
+            record_free(layout.size());
+        }
+    }
+
+    // Counted as a free of the old block and an allocation of the new one
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { self.0.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() && ENABLED.load(Ordering::Relaxed) {
+            record_free(layout.size());
+            record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[derive(Serialize)]
+pub struct AllocationCounts {
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+    pub frees: u64,
+    pub freed_bytes: u64,
+}
+
+impl From<&Counters> for AllocationCounts {
+    fn from(counters: &Counters) -> Self {
+        AllocationCounts {
+            allocations: counters.allocations.load(Ordering::Relaxed),
+            allocated_bytes: counters.allocated_bytes.load(Ordering::Relaxed),
+            frees: counters.frees.load(Ordering::Relaxed),
+            freed_bytes: counters.freed_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SizeClass {
+    // Allocations of at most this many bytes (and more than half of it)
+    pub max_bytes: u64,
+    pub allocations: u64,
+}
+
+#[derive(Serialize)]
+pub struct RouteAllocations {
+    pub route: String,
+    pub requests: u64,
+    pub allocations_per_request: f64,
+    pub bytes_per_request: f64,
+    #[serde(flatten)]
+    pub counts: AllocationCounts,
+}
+
+#[derive(Serialize)]
+pub struct HeapDump {
+    pub enabled: bool,
+    pub profiled_secs: f64,
+    pub live_bytes: i64,
+    pub peak_live_bytes: i64,
+    pub totals: AllocationCounts,
+    pub size_classes: Vec<SizeClass>,
+    pub routes: Vec<RouteAllocations>,
+    pub untracked: AllocationCounts,
+}
+
+#[derive(Default)]
+struct Session {
+    started: Option<Instant>,
+    profiled_secs: f64,
+}
+
+#[derive(Default)]
+pub struct HeapProfiler {
+    // Route names by slot - 1, and the requests each served while profiling
+    routes: Mutex<Vec<(String, Arc<AtomicU64>)>>,
+    session: Mutex<Session>,
+}
+
+impl HeapProfiler {
+    // None unless HEAP_PROFILER is set; the profiler then starts stopped
+    pub fn from_env() -> Option<Self> {
+        matches!(
+            std::env::var("HEAP_PROFILER").as_deref(),
+            Ok("1") | Ok("true")
+        )
+        .then(HeapProfiler::default)
+    }
+
+    // Starting resets every counter; stopping keeps them for the dump
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut session = self.session.lock();
+        if enabled == ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if enabled {
+            TOTALS.reset();
+            ROUTES.iter().for_each(Counters::reset);
+            SIZE_CLASS_COUNTS
+                .iter()
+                .for_each(|count| count.store(0, Ordering::Relaxed));
+            LIVE.store(0, Ordering::Relaxed);
+            PEAK.store(0, Ordering::Relaxed);
+            for (_, requests) in self.routes.lock().iter() {
+                requests.store(0, Ordering::Relaxed);
+            }
+            *session = Session {
+                started: Some(Instant::now()),
+                profiled_secs: 0.0,
+            };
+        } else if let Some(started) = session.started.take() {
+            session.profiled_secs = started.elapsed().as_secs_f64();
+        }
+        ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn dump(&self) -> HeapDump {
+        let profiled_secs = {
+            let session = self.session.lock();
+            session.started.map_or(session.profiled_secs, |started| {
+                started.elapsed().as_secs_f64()
+            })
+        };
+
+        let routes = self
+            .routes
+            .lock()
+            .iter()
+            .enumerate()
+            .map(|(index, (route, requests))| {
+                let counts = AllocationCounts::from(&ROUTES[index + 1]);
+                let requests = requests.load(Ordering::Relaxed);
+                let per_request = |value: u64| {
+                    if requests > 0 {
+                        value as f64 / requests as f64
+                    } else {
+                        0.0
+                    }
+                };
+                RouteAllocations {
+                    route: route.clone(),
+                    requests,
+                    allocations_per_request: per_request(counts.allocations),
+                    bytes_per_request: per_request(counts.allocated_bytes),
+                    counts,
+                }
+            })
+            .collect();
+
+        HeapDump {
+            enabled: ENABLED.load(Ordering::Relaxed),
+            profiled_secs,
+            live_bytes: LIVE.load(Ordering::Relaxed),
+            peak_live_bytes: PEAK.load(Ordering::Relaxed),
+            totals: AllocationCounts::from(&TOTALS),
+            size_classes: SIZE_CLASS_COUNTS
+                .iter()
+                .enumerate()
+                .map(|(class, count)| SizeClass {
+                    max_bytes: 1 << class,
+                    allocations: count.load(Ordering::Relaxed),
+                })
+                .filter(|class| class.allocations > 0)
+                .collect(),
+            routes,
+            untracked: AllocationCounts::from(&ROUTES[0]),
+        }
+    }
+
+    // Slot and request counter of `route`, registering it on first use
+    fn route(&self, route: &str) -> Option<(usize, Arc<AtomicU64>)> {
+        let mut routes = self.routes.lock();
+        if let Some(index) = routes.iter().position(|(name, _)| name == route) {
+            return Some((index + 1, routes[index].1.clone()));
+        }
+        if routes.len() == MAX_ROUTES {
+            return None;
+        }
+        let requests = Arc::new(AtomicU64::new(0));
+        routes.push((route.to_string(), requests.clone()));
+        Some((routes.len(), requests))
+    }
+}
+
+// Attributes the allocations made while polling `inner` to a route slot
+struct Tagged<F> {
+    inner: Pin<Box<F>>,
+    slot: usize,
+}
+
+impl<F: Future> Future for Tagged<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let previous = CURRENT_ROUTE.replace(this.slot);
+        let poll = this.inner.as_mut().poll(cx);
+        CURRENT_ROUTE.set(previous);
+        poll
+    }
+}
+
+pub async fn track(
+    State(profiler): State<Arc<HeapProfiler>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return next.run(request).await;
+    }
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let Some((slot, requests)) = route.and_then(|route| profiler.route(&route)) else {
+        return next.run(request).await;
+    };
+
+    requests.fetch_add(1, Ordering::Relaxed);
+    Tagged {
+        inner: Box::pin(next.run(request)),
+        slot,
+    }
+    .await
+}
@@ -0,0 +1,338 @@
+// Optional in-memory copies of the small tables (employees, suppliers), reloaded in the
+// background and used to answer their four routes without a database round trip. Comparing
+// runs with and without it shows how much of the benchmark is small-table round trips.
+//
+//   HOT_SET             1 to load the tables and serve from memory
+//   HOT_SET_REFRESH_MS  reload interval (default 5000)
+//
+// Until the first load finishes, and for requests with X-Dataset or X-Snapshot, the handlers
+// run as usual.
+// Requests served from memory aren't recorded by the query capture.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use bench_core::{
+    BenchResult, DbPool,
+    models::{Employee, Supplier},
+    queries::EmployeeWithRecipient,
+    schema::{employees, suppliers},
+};
+use diesel::{ExpressionMethods, QueryDsl};
+use parking_lot::RwLock;
+use serde::Serialize;
+
+use crate::{
+    datasets::DATASET_HEADER,
+    encoding::Format,
+    pagination::{self, PaginationLinks},
+    params::{Id, Pagination},
+    snapshots::SNAPSHOT_HEADER,
+};
+
+const DEFAULT_REFRESH: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+enum HotRoute {
+    Employees,
+    EmployeeWithRecipient,
+    Suppliers,
+    SupplierById,
+}
+
+impl HotRoute {
+    const ALL: [HotRoute; 4] = [
+        HotRoute::Employees,
+        HotRoute::EmployeeWithRecipient,
+        HotRoute::Suppliers,
+        HotRoute::SupplierById,
+    ];
+
+    fn from_path(path: &str) -> Option<Self> {
+        HotRoute::ALL.into_iter().find(|route| route.path() == path)
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            HotRoute::Employees => "/employees",
+            HotRoute::EmployeeWithRecipient => "/employee-with-recipient",
+            HotRoute::Suppliers => "/suppliers",
+            HotRoute::SupplierById => "/supplier-by-id",
+        }
+    }
+}
+
+// One snapshot of both tables, ordered by id like the queries
+struct Tables {
+    employees: Vec<Employee>,
+    with_recipient: Vec<EmployeeWithRecipient>,
+    suppliers: Vec<Supplier>,
+    loaded_at: Instant,
+}
+
+// p5's row for `employee`, recipient columns filled from `recipient`
+fn with_recipient(employee: &Employee, recipient: Option<&Employee>) -> EmployeeWithRecipient {
+    EmployeeWithRecipient {
+        id: employee.id,
+        last_name: employee.last_name.clone(),
+        first_name: employee.first_name.clone(),
+        title: employee.title.clone(),
+        title_of_courtesy: employee.title_of_courtesy.clone(),
+        birth_date: employee.birth_date,
+        hire_date: employee.hire_date,
+        address: employee.address.clone(),
+        city: employee.city.clone(),
+        postal_code: employee.postal_code.clone(),
+        country: employee.country.clone(),
+        home_phone: employee.home_phone.clone(),
+        extension: employee.extension,
+        notes: employee.notes.clone(),
+        recipient_id: employee.recipient_id,
+        recipient_employee_id: recipient.map(|r| r.id),
+        recipient_last_name: recipient.map(|r| r.last_name.clone()),
+        recipient_first_name: recipient.and_then(|r| r.first_name.clone()),
+        recipient_title: recipient.map(|r| r.title.clone()),
+        recipient_title_of_courtesy: recipient.map(|r| r.title_of_courtesy.clone()),
+        recipient_birth_date: recipient.map(|r| r.birth_date),
+        recipient_hire_date: recipient.map(|r| r.hire_date),
+        recipient_address: recipient.map(|r| r.address.clone()),
+        recipient_city: recipient.map(|r| r.city.clone()),
+        recipient_postal_code: recipient.map(|r| r.postal_code.clone()),
+        recipient_country: recipient.map(|r| r.country.clone()),
+        recipient_home_phone: recipient.map(|r| r.home_phone.clone()),
+        recipient_extension: recipient.map(|r| r.extension),
+        recipient_notes: recipient.map(|r| r.notes.clone()),
+        recipient_recipient_id: recipient.and_then(|r| r.recipient_id),
+    }
+}
+
+// Binary search in a slice ordered by id
+fn by_id<T>(rows: &[T], id: i32, row_id: impl Fn(&T) -> i32) -> Option<&T> {
+    rows.binary_search_by_key(&id, row_id)
+        .ok()
+        .map(|index| &rows[index])
+}
+
+// LIMIT/OFFSET over an ordered slice
+fn page<T>(rows: &[T], limit: i64, offset: i64) -> &[T] {
+    let start = (offset as usize).min(rows.len());
+    let end = start.saturating_add(limit as usize).min(rows.len());
+    &rows[start..end]
+}
+
+impl Tables {
+    async fn load(pool: &DbPool) -> BenchResult<Self> {
+        // Scoped: its blanket `load` shadows AtomicU64::load elsewhere in this file
+        use diesel_async::RunQueryDsl;
+
+        let mut conn = pool.get().await?;
+
+        let employees: Vec<Employee> = employees::table
+            .order_by(employees::id.asc())
+            .load(&mut conn)
+            .await?;
+        let suppliers = suppliers::table
+            .order_by(suppliers::id.asc())
+            .load(&mut conn)
+            .await?;
+
+        let with_recipient = employees
+            .iter()
+            .map(|employee| {
+                let recipient = employee
+                    .recipient_id
+                    .and_then(|id| by_id(&employees, id, |e| e.id));
+                with_recipient(employee, recipient)
+            })
+            .collect();
+
+        Ok(Tables {
+            employees,
+            with_recipient,
+            suppliers,
+            loaded_at: Instant::now(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct RouteServed {
+    pub route: &'static str,
+    pub requests: u64,
+}
+
+#[derive(Serialize)]
+pub struct HotSetStats {
+    pub loaded: bool,
+    pub employees: usize,
+    pub suppliers: usize,
+    pub refresh_interval_ms: u64,
+    // Time since the snapshot being served was loaded
+    pub age_ms: Option<u64>,
+    pub refreshes: u64,
+    pub refresh_errors: u64,
+    pub last_refresh_ms: f64,
+    // Requests answered from memory, by route
+    pub served: Vec<RouteServed>,
+}
+
+pub struct HotSet {
+    refresh_interval: Duration,
+    tables: RwLock<Option<Arc<Tables>>>,
+    refreshes: AtomicU64,
+    refresh_errors: AtomicU64,
+    last_refresh_us: AtomicU64,
+    served: [AtomicU64; 4],
+    // Same setting as the handlers
+    links: Option<PaginationLinks>,
+}
+
+impl HotSet {
+    pub fn from_env() -> Option<Self> {
+        if !matches!(std::env::var("HOT_SET").as_deref(), Ok("1") | Ok("true")) {
+            return None;
+        }
+        let refresh_interval = std::env::var("HOT_SET_REFRESH_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_REFRESH);
+
+        Some(HotSet {
+            refresh_interval,
+            tables: RwLock::new(None),
+            refreshes: AtomicU64::new(0),
+            refresh_errors: AtomicU64::new(0),
+            last_refresh_us: AtomicU64::new(0),
+            served: Default::default(),
+            links: PaginationLinks::from_env(),
+        })
+    }
+
+    async fn refresh(&self, pool: &DbPool) {
+        let started = Instant::now();
+        match Tables::load(pool).await {
+            Ok(tables) => {
+                *self.tables.write() = Some(Arc::new(tables));
+                self.refreshes.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                // Keep serving the previous snapshot
+                eprintln!("Failed to refresh the hot set: {:?}", err);
+                self.refresh_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.last_refresh_us
+            .store(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    // Loads the tables now and then every refresh interval, for the life of the process
+    pub fn spawn_refresh(self: Arc<Self>, pool: DbPool) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.refresh_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                self.refresh(&pool).await;
+            }
+        });
+    }
+
+    fn snapshot(&self) -> Option<Arc<Tables>> {
+        self.tables.read().clone()
+    }
+
+    pub fn stats(&self) -> HotSetStats {
+        let tables = self.snapshot();
+        HotSetStats {
+            loaded: tables.is_some(),
+            employees: tables.as_ref().map_or(0, |t| t.employees.len()),
+            suppliers: tables.as_ref().map_or(0, |t| t.suppliers.len()),
+            refresh_interval_ms: self.refresh_interval.as_millis() as u64,
+            age_ms: tables
+                .as_ref()
+                .map(|t| t.loaded_at.elapsed().as_millis() as u64),
+            refreshes: self.refreshes.load(Ordering::Relaxed),
+            refresh_errors: self.refresh_errors.load(Ordering::Relaxed),
+            last_refresh_ms: self.last_refresh_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            served: HotRoute::ALL
+                .iter()
+                .zip(&self.served)
+                .map(|(route, requests)| RouteServed {
+                    route: route.path(),
+                    requests: requests.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+}
+
+// The response for `route`, None to leave the request to the handler (invalid parameters,
+// which the handler rejects the usual way)
+fn answer(
+    tables: &Tables,
+    route: HotRoute,
+    request: &Request,
+    links: Option<PaginationLinks>,
+) -> Option<Response> {
+    let uri = request.uri();
+    let format = Format::from_headers(request.headers());
+    let response = match route {
+        HotRoute::Employees | HotRoute::Suppliers => {
+            let Pagination { limit, offset, .. } = Pagination::from_query(uri.query()).ok()?;
+            let (response, total) = match route {
+                HotRoute::Employees => (
+                    format.respond(page(&tables.employees, limit, offset)),
+                    tables.employees.len(),
+                ),
+                _ => (
+                    format.respond(page(&tables.suppliers, limit, offset)),
+                    tables.suppliers.len(),
+                ),
+            };
+            let total = Some(total as i64);
+            pagination::with_links(response, links, route.path(), limit, offset, total)
+        }
+        HotRoute::EmployeeWithRecipient => {
+            let Id(id) = Id::from_query(uri.query()).ok()?;
+            format.respond(&by_id(&tables.with_recipient, id, |e| e.id))
+        }
+        HotRoute::SupplierById => {
+            let Id(id) = Id::from_query(uri.query()).ok()?;
+            format.respond(&by_id(&tables.suppliers, id, |s| s.id))
+        }
+    };
+    Some(response)
+}
+
+// Answers the small-table routes from the current snapshot
+pub async fn serve(State(hot): State<Arc<HotSet>>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .filter(|_| request.method() == Method::GET)
+        .filter(|_| !request.headers().contains_key(DATASET_HEADER))
+        .filter(|_| !request.headers().contains_key(SNAPSHOT_HEADER))
+        .and_then(|path| HotRoute::from_path(path.as_str()));
+
+    if let (Some(route), Some(tables)) = (route, hot.snapshot())
+        && let Some(response) = answer(&tables, route, &request, hot.links)
+    {
+        hot.served[route as usize].fetch_add(1, Ordering::Relaxed);
+        return response;
+    }
+
+    next.run(request).await
+}
@@ -0,0 +1,236 @@
+// Experimental bloom filters of the ids in each table, checked before the by-id routes touch
+// the pool: an id the filter has never seen definitely doesn't exist, so the request is
+// answered `null` right away. Meant for miss-heavy workloads; hits still pay for the query.
+//
+//   ID_BLOOM_FILTER       1 to build the filters at startup
+//   ID_BLOOM_FP_RATE      target false positive rate (default 0.01)
+//
+// Filters only grow: created customers are added, deleted ids stay (a false positive, which
+// just queries the database as before). Requests for another dataset (X-Dataset) skip them.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use bench_core::{
+    BenchResult, DbPool,
+    schema::{customers, employees, orders, products, suppliers},
+};
+use diesel::QueryDsl;
+use serde::Serialize;
+
+use crate::{datasets::DATASET_HEADER, encoding::Format, params::Id};
+
+const DEFAULT_FP_RATE: f64 = 0.01;
+// Room for ids created after startup before the false positive rate degrades
+const GROWTH_FACTOR: f64 = 1.5;
+
+fn mix(mut x: u64) -> u64 {
+    // splitmix64 finalizer
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+pub struct IdFilter {
+    bits: Vec<AtomicU64>,
+    hashes: u32,
+    ids: AtomicU64,
+}
+
+impl IdFilter {
+    // Sized for `expected` ids at false positive rate `fp_rate`
+    pub fn new(expected: usize, fp_rate: f64) -> Self {
+        let n = (expected as f64 * GROWTH_FACTOR).max(64.0);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-n * fp_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let hashes = ((bits as f64 / n) * ln2).round().clamp(1.0, 16.0) as u32;
+
+        IdFilter {
+            bits: (0..bits.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            hashes,
+            ids: AtomicU64::new(0),
+        }
+    }
+
+    // Double hashing: the i-th probe is h1 + i * h2
+    fn probes(&self, id: i32) -> impl Iterator<Item = usize> + '_ {
+        let h1 = mix(id as u64);
+        let h2 = mix(h1) | 1;
+        let bits = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    pub fn insert(&self, id: i32) {
+        for bit in self.probes(id) {
+            self.bits[bit / 64].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        }
+        self.ids.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // false means the id was never inserted
+    pub fn may_contain(&self, id: i32) -> bool {
+        self.probes(id)
+            .all(|bit| self.bits[bit / 64].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0)
+    }
+
+    fn stats(&self, table: &'static str) -> FilterStats {
+        let bits = self.bits.len() as u64 * 64;
+        let set: u64 = self
+            .bits
+            .iter()
+            .map(|word| word.load(Ordering::Relaxed).count_ones() as u64)
+            .sum();
+
+        FilterStats {
+            table,
+            ids: self.ids.load(Ordering::Relaxed),
+            bits,
+            hashes: self.hashes,
+            // Chance that every probe of an absent id lands on a set bit
+            estimated_fp_rate: (set as f64 / bits as f64).powi(self.hashes as i32),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FilterStats {
+    pub table: &'static str,
+    pub ids: u64,
+    pub bits: u64,
+    pub hashes: u32,
+    pub estimated_fp_rate: f64,
+}
+
+#[derive(Serialize)]
+pub struct IdFilterStats {
+    pub checked: u64,
+    // Requests answered without a query
+    pub definite_misses: u64,
+    pub filters: Vec<FilterStats>,
+}
+
+pub struct IdFilters {
+    pub customers: IdFilter,
+    pub employees: IdFilter,
+    pub suppliers: IdFilter,
+    pub products: IdFilter,
+    pub orders: IdFilter,
+    checked: AtomicU64,
+    definite_misses: AtomicU64,
+}
+
+fn filled(ids: Vec<i32>, fp_rate: f64) -> IdFilter {
+    let filter = IdFilter::new(ids.len(), fp_rate);
+    ids.into_iter().for_each(|id| filter.insert(id));
+    filter
+}
+
+impl IdFilters {
+    // Loads every table's ids; None unless ID_BLOOM_FILTER is set
+    pub async fn from_env(pool: &DbPool) -> Option<BenchResult<Self>> {
+        if !matches!(
+            std::env::var("ID_BLOOM_FILTER").as_deref(),
+            Ok("1") | Ok("true")
+        ) {
+            return None;
+        }
+        let fp_rate = std::env::var("ID_BLOOM_FP_RATE")
+            .ok()
+            .and_then(|rate| rate.parse().ok())
+            .filter(|rate: &f64| *rate > 0.0 && *rate < 1.0)
+            .unwrap_or(DEFAULT_FP_RATE);
+
+        Some(IdFilters::build(pool, fp_rate).await)
+    }
+
+    pub async fn build(pool: &DbPool, fp_rate: f64) -> BenchResult<Self> {
+        // Scoped: its blanket `load` shadows AtomicU64::load elsewhere in this file
+        use diesel_async::RunQueryDsl;
+
+        let mut conn = pool.get().await?;
+
+        let customers = customers::table
+            .select(customers::id)
+            .load(&mut conn)
+            .await?;
+        let employees = employees::table
+            .select(employees::id)
+            .load(&mut conn)
+            .await?;
+        let suppliers = suppliers::table
+            .select(suppliers::id)
+            .load(&mut conn)
+            .await?;
+        let products = products::table.select(products::id).load(&mut conn).await?;
+        let orders = orders::table.select(orders::id).load(&mut conn).await?;
+
+        Ok(IdFilters {
+            customers: filled(customers, fp_rate),
+            employees: filled(employees, fp_rate),
+            suppliers: filled(suppliers, fp_rate),
+            products: filled(products, fp_rate),
+            orders: filled(orders, fp_rate),
+            checked: AtomicU64::new(0),
+            definite_misses: AtomicU64::new(0),
+        })
+    }
+
+    // Filter of the table a by-id route looks up
+    fn for_route(&self, route: &str) -> Option<&IdFilter> {
+        match route {
+            "/customer-by-id" => Some(&self.customers),
+            "/employee-with-recipient" => Some(&self.employees),
+            "/supplier-by-id" => Some(&self.suppliers),
+            "/product-with-supplier" => Some(&self.products),
+            "/order-with-details" | "/order-with-details-and-products" => Some(&self.orders),
+            _ => None,
+        }
+    }
+
+    pub fn stats(&self) -> IdFilterStats {
+        IdFilterStats {
+            checked: self.checked.load(Ordering::Relaxed),
+            definite_misses: self.definite_misses.load(Ordering::Relaxed),
+            filters: vec![
+                self.customers.stats("customers"),
+                self.employees.stats("employees"),
+                self.suppliers.stats("suppliers"),
+                self.products.stats("products"),
+                self.orders.stats("orders"),
+            ],
+        }
+    }
+}
+
+// Answers `null`, as the handlers do for a missing row, when the id is definitely absent
+pub async fn precheck(
+    State(filters): State<Arc<IdFilters>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Only GETs of the default dataset; writes and invalid ids go on to the handler
+    let filter = request
+        .extensions()
+        .get::<MatchedPath>()
+        .filter(|_| request.method() == axum::http::Method::GET)
+        .filter(|_| !request.headers().contains_key(DATASET_HEADER))
+        .and_then(|route| filters.for_route(route.as_str()));
+    let id = Id::from_query(request.uri().query()).ok();
+
+    if let (Some(filter), Some(Id(id))) = (filter, id) {
+        filters.checked.fetch_add(1, Ordering::Relaxed);
+        if !filter.may_contain(id) {
+            filters.definite_misses.fetch_add(1, Ordering::Relaxed);
+            return Format::from_headers(request.headers()).respond(&None::<()>);
+        }
+    }
+
+    next.run(request).await
+}
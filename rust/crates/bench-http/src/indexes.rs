@@ -0,0 +1,126 @@
+// Dropping and recreating the schema's secondary indexes between scenarios, for ablation
+// runs measuring what each index contributes to each server's numbers:
+//
+//   GET    /admin/indexes        the indexes below and whether each currently exists
+//   DELETE /admin/indexes/:name  drops it (204, also when it was already gone)
+//   PUT    /admin/indexes/:name  recreates it as the drizzle migrations define it (204)
+//
+// Only these can be toggled; the primary keys stay. Both act on the X-Dataset database and
+// take as long as the DDL does, recreating being a full build of the index. Postgres
+// invalidates cached plans on the DDL, so prepared statements replan on their next run.
+
+use diesel::{
+    prelude::*,
+    sql_types::{Bool, Text},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IndexKind {
+    // GIN over to_tsvector, used by the search routes
+    FullText,
+    // btree over a foreign key column, used by the joins
+    ForeignKey,
+}
+
+pub struct ToggleableIndex {
+    pub name: &'static str,
+    pub table: &'static str,
+    pub kind: IndexKind,
+    create: &'static str,
+}
+
+// Definitions from drizzle/0000_flat_master_mold.sql and 0001_concerned_mother_askani.sql
+pub const INDEXES: &[ToggleableIndex] = &[
+    ToggleableIndex {
+        name: "customers_company_name_idx",
+        table: "customers",
+        kind: IndexKind::FullText,
+        create: "CREATE INDEX IF NOT EXISTS customers_company_name_idx ON customers \
+                 USING GIN (to_tsvector('english', company_name))",
+    },
+    ToggleableIndex {
+        name: "products_name_idx",
+        table: "products",
+        kind: IndexKind::FullText,
+        create: "CREATE INDEX IF NOT EXISTS products_name_idx ON products \
+                 USING GIN (to_tsvector('english', name))",
+    },
+    ToggleableIndex {
+        name: "order_id_idx",
+        table: "order_details",
+        kind: IndexKind::ForeignKey,
+        create: "CREATE INDEX IF NOT EXISTS order_id_idx ON order_details (order_id)",
+    },
+    ToggleableIndex {
+        name: "product_id_idx",
+        table: "order_details",
+        kind: IndexKind::ForeignKey,
+        create: "CREATE INDEX IF NOT EXISTS product_id_idx ON order_details (product_id)",
+    },
+    ToggleableIndex {
+        name: "recepient_idx",
+        table: "employees",
+        kind: IndexKind::ForeignKey,
+        create: "CREATE INDEX IF NOT EXISTS recepient_idx ON employees (recipient_id)",
+    },
+    ToggleableIndex {
+        name: "supplier_idx",
+        table: "products",
+        kind: IndexKind::ForeignKey,
+        create: "CREATE INDEX IF NOT EXISTS supplier_idx ON products (supplier_id)",
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static ToggleableIndex> {
+    INDEXES.iter().find(|index| index.name == name)
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexState {
+    pub name: &'static str,
+    pub table: &'static str,
+    pub kind: IndexKind,
+    pub present: bool,
+}
+
+#[derive(QueryableByName)]
+struct Present {
+    #[diesel(sql_type = Bool)]
+    present: bool,
+}
+
+pub async fn index_states(conn: &mut AsyncPgConnection) -> QueryResult<Vec<IndexState>> {
+    let mut states = Vec::with_capacity(INDEXES.len());
+    for index in INDEXES {
+        let present = diesel::sql_query("SELECT to_regclass($1) IS NOT NULL AS present")
+            .bind::<Text, _>(index.name)
+            .get_result::<Present>(conn)
+            .await?
+            .present;
+        states.push(IndexState {
+            name: index.name,
+            table: index.table,
+            kind: index.kind,
+            present,
+        });
+    }
+    Ok(states)
+}
+
+pub async fn drop_index(conn: &mut AsyncPgConnection, index: &ToggleableIndex) -> QueryResult<()> {
+    diesel::sql_query(format!("DROP INDEX IF EXISTS {}", index.name))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn create_index(
+    conn: &mut AsyncPgConnection,
+    index: &ToggleableIndex,
+) -> QueryResult<()> {
+    diesel::sql_query(index.create).execute(conn).await?;
+    Ok(())
+}
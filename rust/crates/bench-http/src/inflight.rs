@@ -0,0 +1,207 @@
+// Limit on the bytes of response bodies built but not yet handed to their connection, so
+// huge-payload scenarios can't exhaust memory on the benchmark host. Configured with:
+//
+//   MAX_INFLIGHT_RESPONSE_BYTES  the limit (unset or 0 disables tracking entirely)
+//   INFLIGHT_LIMIT_MODE          `reject` (503 while over the limit, the default) or
+//                                `wait` (hold new requests until bodies drain)
+//
+// Only bodies of known size count; streamed bodies are bounded by the connection's own
+// write buffer instead.
+
+use std::{
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body::{Frame, SizeHint};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::error::ErrorCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitMode {
+    Reject,
+    Wait,
+}
+
+#[derive(Serialize)]
+pub struct InFlightStats {
+    pub limit_bytes: u64,
+    pub mode: LimitMode,
+    pub in_flight_bytes: u64,
+    pub peak_bytes: u64,
+    pub rejected: u64,
+    pub waited: u64,
+}
+
+pub struct InFlightBytes {
+    limit: u64,
+    mode: LimitMode,
+    in_flight: AtomicU64,
+    peak: AtomicU64,
+    rejected: AtomicU64,
+    waited: AtomicU64,
+    released: Notify,
+}
+
+impl InFlightBytes {
+    pub fn new(limit: u64, mode: LimitMode) -> Self {
+        InFlightBytes {
+            limit,
+            mode,
+            in_flight: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    // None when MAX_INFLIGHT_RESPONSE_BYTES is unset or 0
+    pub fn from_env() -> Option<Self> {
+        let limit: u64 = std::env::var("MAX_INFLIGHT_RESPONSE_BYTES")
+            .ok()?
+            .parse()
+            .ok()
+            .filter(|&limit| limit > 0)?;
+        let mode = match std::env::var("INFLIGHT_LIMIT_MODE").as_deref() {
+            Ok("wait") => LimitMode::Wait,
+            Ok("reject") | Err(_) => LimitMode::Reject,
+            Ok(other) => {
+                eprintln!("Unknown INFLIGHT_LIMIT_MODE {}, rejecting", other);
+                LimitMode::Reject
+            }
+        };
+
+        Some(InFlightBytes::new(limit, mode))
+    }
+
+    pub fn stats(&self) -> InFlightStats {
+        InFlightStats {
+            limit_bytes: self.limit,
+            mode: self.mode,
+            in_flight_bytes: self.in_flight.load(Ordering::Relaxed),
+            peak_bytes: self.peak.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            waited: self.waited.load(Ordering::Relaxed),
+        }
+    }
+
+    fn over_limit(&self) -> bool {
+        self.in_flight.load(Ordering::Relaxed) >= self.limit
+    }
+
+    fn reserve(&self, bytes: u64) {
+        let in_flight = self.in_flight.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak.fetch_max(in_flight, Ordering::Relaxed);
+    }
+
+    fn release(&self, bytes: u64) {
+        if bytes > 0 {
+            self.in_flight.fetch_sub(bytes, Ordering::Relaxed);
+            self.released.notify_waiters();
+        }
+    }
+}
+
+// Response body releasing its reserved bytes as frames are handed to the connection, and
+// whatever is left when it is dropped (e.g. the client went away)
+struct CountedBody {
+    inner: Body,
+    reserved: u64,
+    tracker: Arc<InFlightBytes>,
+}
+
+impl http_body::Body for CountedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+
+        if let Poll::Ready(Some(Ok(frame))) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            let bytes = (data.len() as u64).min(this.reserved);
+            this.reserved -= bytes;
+            this.tracker.release(bytes);
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for CountedBody {
+    fn drop(&mut self) {
+        self.tracker.release(self.reserved);
+    }
+}
+
+pub async fn limit(
+    State(tracker): State<Arc<InFlightBytes>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tracker.over_limit() {
+        match tracker.mode {
+            LimitMode::Reject => {
+                tracker.rejected.fetch_add(1, Ordering::Relaxed);
+                let mut response = StatusCode::SERVICE_UNAVAILABLE.into_response();
+                response.extensions_mut().insert(ErrorCode::Overloaded);
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+                return response;
+            }
+            LimitMode::Wait => {
+                tracker.waited.fetch_add(1, Ordering::Relaxed);
+                loop {
+                    // Registered before the check so a release in between isn't missed
+                    let released = tracker.released.notified();
+                    if !tracker.over_limit() {
+                        break;
+                    }
+                    released.await;
+                }
+            }
+        }
+    }
+
+    let response = next.run(request).await;
+    let Some(size) = http_body::Body::size_hint(response.body()).exact() else {
+        return response;
+    };
+
+    tracker.reserve(size);
+    response.map(|body| {
+        Body::new(CountedBody {
+            inner: body,
+            reserved: size,
+            tracker,
+        })
+    })
+}
@@ -0,0 +1,47 @@
+pub mod adaptive;
+pub mod allocator;
+pub mod analyze;
+pub mod app;
+pub mod backend_diff;
+pub mod backend_routes;
+pub mod batch;
+pub mod build_info;
+pub mod cache;
+pub mod capture;
+pub mod client_limits;
+pub mod compression;
+#[cfg(feature = "pprof")]
+pub mod cpu_profile;
+pub mod cpu_time;
+pub mod datasets;
+pub mod encoding;
+pub mod error;
+pub mod etag;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod heap;
+pub mod hot_set;
+pub mod id_filter;
+pub mod indexes;
+pub mod inflight;
+pub mod listen;
+pub mod metrics;
+pub mod mirror;
+pub mod ndjson;
+#[cfg(feature = "neon-http")]
+pub mod neon_http;
+pub mod orders_stream;
+pub mod pagination;
+pub mod params;
+pub mod pg_stats;
+pub mod proxy;
+pub mod readiness;
+pub mod request_log;
+pub mod route_limits;
+pub mod snapshots;
+#[cfg(feature = "sql-over-http")]
+pub mod sql_http;
+pub mod stats;
+pub mod telemetry;
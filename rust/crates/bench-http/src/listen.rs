@@ -0,0 +1,90 @@
+// Listener settings of the server, as flags of the `rust` binary with environment fallbacks
+// like the pool's (`bench_core::config`). The defaults are what the server always used.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+use clap::{ArgAction, Args};
+use tokio::net::{TcpListener, TcpSocket};
+
+// Address and TCP options of the HTTP listener
+#[derive(Args, Clone, Debug)]
+pub struct ListenConfig {
+    /// Address to listen on
+    #[arg(long, env = "LISTEN_HOST", default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    pub host: IpAddr,
+
+    /// Port to listen on
+    #[arg(long, env = "PORT", default_value_t = 3003)]
+    pub port: u16,
+
+    /// Length of the queue of connections waiting to be accepted
+    #[arg(long, env = "LISTEN_BACKLOG", default_value_t = 1024)]
+    pub backlog: u32,
+
+    /// Set TCP_NODELAY on accepted connections
+    #[arg(long, env = "TCP_NODELAY", default_value_t = true, action = ArgAction::Set)]
+    pub tcp_nodelay: bool,
+
+    /// Set SO_REUSEPORT, so several servers can share the port
+    #[arg(long, env = "SO_REUSEPORT")]
+    pub reuse_port: bool,
+
+    /// Accept on one SO_REUSEPORT listener per core, each with its own accept loop, instead
+    /// of a single listener; the kernel spreads the incoming connections over them
+    #[arg(long = "reuseport", env = "REUSEPORT_ACCEPTORS")]
+    pub acceptor_per_core: bool,
+
+    /// SO_RCVBUF of the listening socket in bytes; the system default when unset
+    #[arg(long, env = "SO_RCVBUF")]
+    pub recv_buffer: Option<u32>,
+
+    /// SO_SNDBUF of the listening socket in bytes; the system default when unset
+    #[arg(long, env = "SO_SNDBUF")]
+    pub send_buffer: Option<u32>,
+}
+
+impl ListenConfig {
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    // The listeners to accept on: one per core with `--reuseport`, otherwise just `bind`'s
+    pub fn bind_all(&self) -> io::Result<Vec<TcpListener>> {
+        if !self.acceptor_per_core || cfg!(not(unix)) {
+            return Ok(vec![self.bind()?]);
+        }
+
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let config = ListenConfig {
+            reuse_port: true,
+            ..self.clone()
+        };
+        (0..cores).map(|_| config.bind()).collect()
+    }
+
+    // Like `TcpListener::bind`, with the configured backlog and socket options
+    pub fn bind(&self) -> io::Result<TcpListener> {
+        let addr = self.addr();
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+
+        socket.set_reuseaddr(true)?;
+        #[cfg(unix)]
+        socket.set_reuseport(self.reuse_port)?;
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        socket.bind(addr)?;
+        socket.listen(self.backlog)
+    }
+}
@@ -0,0 +1,231 @@
+use axum::Router;
+use bench_core::{
+    DbPool,
+    config::{self, PoolConfig},
+    database_url, establish_connection_pool_with,
+    output::OutputFormat,
+    seed::{self, SeedArgs, SeedSize},
+};
+use bench_driver::cli::{self as bench, CompareArgs, ReportArgs};
+use bench_http::{
+    allocator,
+    app::{AppState, build_router},
+    build_info::StartupClock,
+    client_limits::{self, ClientLimits},
+    compression::CompressionConfig,
+    encoding::JsonEncoder,
+    heap::CountingAlloc,
+    listen::ListenConfig,
+    proxy::{self, ProxyArgs},
+    readiness::Readiness,
+    request_log, telemetry,
+};
+use clap::{Args, Parser, Subcommand};
+use std::sync::Arc;
+
+#[global_allocator]
+static GLOBAL: CountingAlloc<allocator::Allocator> = CountingAlloc(allocator::ALLOCATOR);
+
+/// Benchmark server for the 13 Diesel queries, and the tools around it. Without a command
+/// it serves, taking the `serve` flags; all commands read .env first, starting with
+/// DATABASE_URL
+#[derive(Parser)]
+#[command(name = "rust", args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the benchmark server (the default). Pool and listener settings are flags (or
+    /// their environment variables); everything else is configured through environment
+    /// variables
+    Serve(ServeArgs),
+    /// Fill an empty database with generated data, like `pnpm start:seed`
+    Seed(SeedArgs),
+    /// Forward requests to another server, recording them to a trace `bench run` replays
+    Proxy(ProxyArgs),
+    /// Load generation and result tooling, as in the `bench` binary
+    Bench {
+        #[command(subcommand)]
+        command: bench::Command,
+    },
+    /// Test whether two runs' latency distributions differ significantly
+    Compare(CompareArgs),
+    /// Serve an HTML page plotting one or more result files
+    Report(ReportArgs),
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Request the server from itself once it listens and report the time from process
+    /// start to the first successful response (also in /build-info)
+    #[arg(long)]
+    measure_startup: bool,
+
+    /// Seed the database first if its tables are empty, listening meanwhile but answering
+    /// only `/readyz`, with the progress, until done
+    #[arg(long, env = "AUTO_SEED")]
+    auto_seed: bool,
+
+    /// Size seeded by --auto-seed, as in `seed --size`
+    #[arg(long, env = "AUTO_SEED_SIZE", value_enum, default_value_t = SeedSize::Micro)]
+    auto_seed_size: SeedSize,
+
+    #[command(flatten)]
+    listen: ListenConfig,
+
+    #[command(flatten)]
+    pool: PoolConfig,
+
+    #[command(flatten)]
+    compression: CompressionConfig,
+
+    #[command(flatten)]
+    output: OutputFormat,
+
+    /// Encoder of the JSON responses; by default the one the server was built for (see the
+    /// `json-simd` and `json-sonic` features), serde_json without either
+    #[arg(long, env = "JSON_ENCODER", value_enum)]
+    json_encoder: Option<JsonEncoder>,
+
+    /// Also serve the queries over gRPC on this port (proto/bench.proto), on the listen
+    /// address and the HTTP server's pool
+    #[cfg(feature = "grpc")]
+    #[arg(long, env = "GRPC_PORT")]
+    grpc_port: Option<u16>,
+}
+
+#[tokio::main]
+async fn main() {
+    let startup = StartupClock::start();
+    config::load_env();
+    let cli = Cli::parse();
+
+    match cli.command.unwrap_or(Command::Serve(cli.serve)) {
+        Command::Serve(args) => serve(args, startup).await,
+        Command::Seed(args) => {
+            if let Err(err) = seed::seed(&database_url(), &args).await {
+                eprintln!("Seeding failed: {:?}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Proxy(args) => proxy::run(args).await,
+        Command::Bench { command } => bench::run(command).await,
+        Command::Compare(args) => bench::compare(args),
+        Command::Report(args) => bench::report(args).await,
+    }
+}
+
+async fn serve(args: ServeArgs, mut startup: StartupClock) {
+    if let Err(err) = JsonEncoder::select(args.json_encoder) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    args.output.select();
+    // Before the pool connects, so its connections time their queries for the log
+    request_log::init();
+    telemetry::init();
+    let pool = establish_connection_pool_with(&args.pool).await;
+    startup.pool_ready();
+    #[cfg(feature = "grpc")]
+    if let Some(port) = args.grpc_port {
+        let addr = std::net::SocketAddr::new(args.listen.host, port);
+        tokio::spawn(bench_http::grpc::serve(addr, pool.clone()));
+    }
+    if args.auto_seed {
+        return serve_after_seed(args, pool, startup).await;
+    }
+    let state = app_state(pool, args.pool).await;
+    state.spawn_background_tasks();
+    let app = args.compression.apply(build_router(state));
+
+    #[cfg(feature = "lambda")]
+    if std::env::var_os("AWS_LAMBDA_RUNTIME_API").is_some() {
+        // Inside a Lambda function: the runtime API delivers the requests instead
+        if let Err(err) = lambda_http::run(app).await {
+            eprintln!("Lambda runtime failed: {:?}", err);
+        }
+        return;
+    }
+
+    let Some(server) = listen(&args, app) else {
+        return;
+    };
+    startup.listening();
+    if args.measure_startup {
+        tokio::spawn(startup.measure_first_response(args.listen.port));
+    }
+    server.await;
+}
+
+// --auto-seed: listens with only /readyz until the database is seeded (or found to have
+// data already), then serves as usual. Startup counts as listening once it serves.
+async fn serve_after_seed(args: ServeArgs, pool: DbPool, mut startup: StartupClock) {
+    let readiness = Readiness::new();
+    let Some(server) = listen(&args, readiness.clone().router()) else {
+        return;
+    };
+    let server = tokio::spawn(server);
+
+    let seed_args = SeedArgs::sized(args.auto_seed_size);
+    match seed::seed_if_empty(&database_url(), &seed_args, readiness.progress()).await {
+        Ok(true) => {}
+        Ok(false) => println!("Database already has data, not seeding"),
+        Err(err) => {
+            eprintln!("Seeding failed: {:?}", err);
+            std::process::exit(1);
+        }
+    }
+
+    let state = app_state(pool, args.pool).await;
+    state.spawn_background_tasks();
+    readiness.ready(args.compression.apply(build_router(state)));
+    startup.listening();
+    if args.measure_startup {
+        tokio::spawn(startup.measure_first_response(args.listen.port));
+    }
+    let _ = server.await;
+}
+
+// Exits on a misconfigured query backend rather than serving the regular handlers in its
+// place
+async fn app_state(pool: DbPool, pool_config: PoolConfig) -> Arc<AppState> {
+    match AppState::from_env(pool, pool_config).await {
+        Ok(state) => Arc::new(state),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Binds the listeners, returning the server for `app` on them
+fn listen(args: &ServeArgs, app: Router) -> Option<impl Future<Output = ()> + use<>> {
+    let listeners = match args.listen.bind_all() {
+        Ok(listeners) => listeners,
+        Err(err) => {
+            eprintln!("Failed to bind to {}: {:?}", args.listen.addr(), err);
+            return None;
+        }
+    };
+
+    println!(
+        "Starting server on port {} ({} acceptor{})",
+        args.listen.port,
+        listeners.len(),
+        if listeners.len() == 1 { "" } else { "s" }
+    );
+
+    // Start the server.
+    Some(client_limits::serve(
+        listeners,
+        app,
+        ClientLimits::from_env(),
+        args.listen.tcp_nodelay,
+    ))
+}
@@ -0,0 +1,407 @@
+// Server-side request and pool metrics for /metrics (Prometheus text format), to line up
+// benchmark results with what the server saw. Error responses are also counted by their
+// `ErrorCode`. Request metrics are enabled with
+// REQUEST_METRICS=1 (a lock per request); pool statistics are always reported.
+//
+// The request metrics also keep the distribution of response body sizes per route, served as
+// JSON at `/stats/response-sizes`, since bytes on the wire are part of the published
+// comparison. Sizes are before compression; a streamed body (NDJSON, CSV, SSE) counts what
+// was sent of it by the time it ended or the client left.
+//
+// Durations are kept twice: `http_request_duration_seconds` to the response headers, which is
+// the first byte of the response, and `http_response_duration_seconds` to the end of its body.
+// They're the same for a buffered body; a streamed one's runs until its last frame is sent
+// (or the client leaves), which a single latency number hides.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicI64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use bench_core::DbPool;
+use hdrhistogram::Histogram;
+use http_body::{Frame, SizeHint};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::error::ErrorCode;
+
+// Upper bounds in seconds; the last bucket is +Inf
+const BUCKETS: [f64; 14] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Durations {
+    // Non-cumulative counts per bucket, +Inf last
+    buckets: [u64; BUCKETS.len() + 1],
+    sum_seconds: f64,
+    count: u64,
+}
+
+impl Durations {
+    fn record(&mut self, seconds: f64) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum_seconds += seconds;
+        self.count += 1;
+    }
+
+    fn write_prometheus(&self, metric: &str, route: &str, out: &mut String) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{metric}_bucket{{route=\"{route}\",le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{metric}_bucket{{route=\"{route}\",le=\"+Inf\"}} {}\n\
+             {metric}_sum{{route=\"{route}\"}} {}\n\
+             {metric}_count{{route=\"{route}\"}} {}",
+            self.count, self.sum_seconds, self.count,
+        );
+    }
+}
+
+#[derive(Default)]
+struct RouteMetrics {
+    statuses: HashMap<u16, u64>,
+    errors: HashMap<ErrorCode, u64>,
+    // Up to the response headers
+    durations: Durations,
+    // Up to the end of the response body
+    body_durations: Durations,
+    // Of the response bodies, in bytes
+    sizes: Option<Histogram<u64>>,
+    total_bytes: u64,
+}
+
+impl RouteMetrics {
+    fn record(&mut self, status: u16, error: Option<ErrorCode>, seconds: f64) {
+        *self.statuses.entry(status).or_default() += 1;
+        if let Some(code) = error {
+            *self.errors.entry(code).or_default() += 1;
+        }
+        self.durations.record(seconds);
+    }
+
+    // A response body's size and the time until it ended
+    fn record_body(&mut self, bytes: u64, seconds: f64) {
+        let _ = self
+            .sizes
+            .get_or_insert_with(|| Histogram::new(3).expect("valid histogram precision"))
+            .record(bytes);
+        self.total_bytes += bytes;
+        self.body_durations.record(seconds);
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SizeSummary {
+    pub responses: u64,
+    pub total_bytes: u64,
+    pub mean_bytes: f64,
+    pub min_bytes: u64,
+    pub p50_bytes: u64,
+    pub p90_bytes: u64,
+    pub p99_bytes: u64,
+    pub max_bytes: u64,
+}
+
+impl SizeSummary {
+    fn from_histogram(histogram: &Histogram<u64>, total_bytes: u64) -> Self {
+        SizeSummary {
+            responses: histogram.len(),
+            total_bytes,
+            mean_bytes: histogram.mean(),
+            min_bytes: histogram.min(),
+            p50_bytes: histogram.value_at_quantile(0.50),
+            p90_bytes: histogram.value_at_quantile(0.90),
+            p99_bytes: histogram.value_at_quantile(0.99),
+            max_bytes: histogram.max(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RequestMetrics {
+    routes: Mutex<HashMap<String, RouteMetrics>>,
+    in_flight: AtomicI64,
+}
+
+impl RequestMetrics {
+    pub fn from_env() -> Option<Self> {
+        matches!(
+            std::env::var("REQUEST_METRICS").as_deref(),
+            Ok("1") | Ok("true")
+        )
+        .then(RequestMetrics::default)
+    }
+
+    // Response body sizes by route
+    pub fn response_sizes(&self) -> BTreeMap<String, SizeSummary> {
+        self.routes
+            .lock()
+            .iter()
+            .filter_map(|(name, route)| {
+                let sizes = route.sizes.as_ref()?;
+                Some((
+                    name.clone(),
+                    SizeSummary::from_histogram(sizes, route.total_bytes),
+                ))
+            })
+            .collect()
+    }
+
+    pub fn write_prometheus(&self, out: &mut String) {
+        let routes = self.routes.lock();
+        let mut names: Vec<&String> = routes.keys().collect();
+        names.sort();
+
+        let _ = writeln!(
+            out,
+            "# HELP http_requests_total Responses by route and status code\n\
+             # TYPE http_requests_total counter"
+        );
+        for name in &names {
+            let mut statuses: Vec<_> = routes[*name].statuses.iter().collect();
+            statuses.sort();
+            for (status, count) in statuses {
+                let _ = writeln!(
+                    out,
+                    "http_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                    name, status, count
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP http_errors_total Error responses by route and error code\n\
+             # TYPE http_errors_total counter"
+        );
+        for name in &names {
+            let mut errors: Vec<_> = routes[*name].errors.iter().collect();
+            errors.sort_by_key(|(code, _)| code.as_str());
+            for (code, count) in errors {
+                let _ = writeln!(
+                    out,
+                    "http_errors_total{{route=\"{}\",code=\"{}\"}} {}",
+                    name,
+                    code.as_str(),
+                    count
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP http_request_duration_seconds Time from request to response headers\n\
+             # TYPE http_request_duration_seconds histogram"
+        );
+        for name in &names {
+            routes[*name]
+                .durations
+                .write_prometheus("http_request_duration_seconds", name, out);
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP http_response_duration_seconds Time from request to the end of the response body\n\
+             # TYPE http_response_duration_seconds histogram"
+        );
+        for name in &names {
+            routes[*name].body_durations.write_prometheus(
+                "http_response_duration_seconds",
+                name,
+                out,
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP http_requests_in_flight Requests being handled\n\
+             # TYPE http_requests_in_flight gauge\n\
+             http_requests_in_flight {}",
+            self.in_flight.load(Ordering::Relaxed)
+        );
+    }
+}
+
+pub async fn record(
+    State(metrics): State<Arc<RequestMetrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let seconds = started.elapsed().as_secs_f64();
+    metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+    let status = response.status().as_u16();
+    let error = response.extensions().get::<ErrorCode>().copied();
+    let Some(bytes) = http_body::Body::size_hint(response.body()).exact() else {
+        // Streamed: sized once it's done
+        metrics
+            .routes
+            .lock()
+            .entry(route.clone())
+            .or_default()
+            .record(status, error, seconds);
+        return response.map(|body| {
+            Body::new(SizedBody {
+                inner: body,
+                bytes: 0,
+                started,
+                metrics,
+                route,
+            })
+        });
+    };
+
+    let mut routes = metrics.routes.lock();
+    let route = routes.entry(route).or_default();
+    route.record(status, error, seconds);
+    route.record_body(bytes, seconds);
+    drop(routes);
+    response
+}
+
+// A response body counting its bytes, recorded with the time since the request started as
+// the route's response size and duration when dropped
+struct SizedBody {
+    inner: Body,
+    bytes: u64,
+    started: Instant,
+    metrics: Arc<RequestMetrics>,
+    route: String,
+}
+
+impl http_body::Body for SizedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &frame
+            && let Some(data) = frame.data_ref()
+        {
+            this.bytes += data.len() as u64;
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for SizedBody {
+    fn drop(&mut self) {
+        if let Some(route) = self.metrics.routes.lock().get_mut(&self.route) {
+            route.record_body(self.bytes, self.started.elapsed().as_secs_f64());
+        }
+    }
+}
+
+// Connection counts and checkout statistics of each pool, labelled by `name`
+pub fn write_pool_prometheus<'a>(
+    pools: impl IntoIterator<Item = (&'a str, &'a DbPool)>,
+    out: &mut String,
+) {
+    let pools: Vec<_> = pools
+        .into_iter()
+        .map(|(name, pool)| (name, pool.state()))
+        .collect();
+
+    let _ = writeln!(
+        out,
+        "# HELP db_pool_connections Open pool connections, checked out or idle\n\
+         # TYPE db_pool_connections gauge"
+    );
+    for (name, state) in &pools {
+        let _ = writeln!(
+            out,
+            "db_pool_connections{{pool=\"{name}\",state=\"in_use\"}} {}\n\
+             db_pool_connections{{pool=\"{name}\",state=\"idle\"}} {}",
+            state.connections - state.idle_connections,
+            state.idle_connections,
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP db_pool_gets_total Connection checkouts: served immediately, after waiting, or timed out\n\
+         # TYPE db_pool_gets_total counter"
+    );
+    for (name, state) in &pools {
+        let stats = &state.statistics;
+        let _ = writeln!(
+            out,
+            "db_pool_gets_total{{pool=\"{name}\",result=\"direct\"}} {}\n\
+             db_pool_gets_total{{pool=\"{name}\",result=\"waited\"}} {}\n\
+             db_pool_gets_total{{pool=\"{name}\",result=\"timed_out\"}} {}",
+            stats.get_direct, stats.get_waited, stats.get_timed_out,
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP db_pool_get_wait_seconds_total Time checkouts spent waiting for a connection\n\
+         # TYPE db_pool_get_wait_seconds_total counter"
+    );
+    for (name, state) in &pools {
+        let _ = writeln!(
+            out,
+            "db_pool_get_wait_seconds_total{{pool=\"{name}\"}} {}",
+            state.statistics.get_wait_time.as_secs_f64()
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP db_pool_connections_created_total Connections the pool has opened\n\
+         # TYPE db_pool_connections_created_total counter"
+    );
+    for (name, state) in &pools {
+        let _ = writeln!(
+            out,
+            "db_pool_connections_created_total{{pool=\"{name}\"}} {}",
+            state.statistics.connections_created
+        );
+    }
+}
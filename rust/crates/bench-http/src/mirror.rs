@@ -0,0 +1,246 @@
+// Shadow traffic: a share of the GET requests to the query routes is also sent to a second
+// server, fire-and-forget, to validate a new backend implementation under the real load
+// before switching to it. Clients only ever get this server's response; the mirrored one is
+// compared by status and latency, both measured to the response headers, and the two
+// distributions are served at `GET /stats/mirror`.
+//
+//   MIRROR_URL           base URL of the second server, e.g. http://10.0.0.3:3003
+//   MIRROR_PERCENT       share of requests mirrored (default 10)
+//   MIRROR_MAX_INFLIGHT  mirrored requests in flight before more are dropped (default 256)
+//
+// Writes aren't mirrored, as the second server may well share the database.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Request, State},
+    http::{Method, StatusCode, Uri, header},
+    middleware::Next,
+    response::Response,
+};
+use bench_driver::result::{LatencySummary, new_histogram};
+use hdrhistogram::Histogram;
+use http_body_util::{BodyExt, Empty};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::{Semaphore, oneshot};
+
+const DEFAULT_PERCENT: f64 = 10.0;
+const DEFAULT_MAX_INFLIGHT: usize = 256;
+
+#[derive(Default)]
+struct Side {
+    statuses: BTreeMap<u16, u64>,
+    latency: Option<Histogram<u64>>,
+}
+
+impl Side {
+    fn record(&mut self, status: StatusCode, latency: Duration) {
+        *self.statuses.entry(status.as_u16()).or_default() += 1;
+        let _ = self
+            .latency
+            .get_or_insert_with(new_histogram)
+            .record(latency.as_micros().max(1) as u64);
+    }
+
+    fn stats(&self) -> SideStats {
+        SideStats {
+            statuses: self.statuses.clone(),
+            latency: self
+                .latency
+                .as_ref()
+                .map(LatencySummary::from_histogram)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sides {
+    primary: Side,
+    shadow: Side,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SideStats {
+    pub statuses: BTreeMap<u16, u64>,
+    pub latency: LatencySummary,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorStats {
+    pub target: String,
+    pub percent: f64,
+    pub mirrored: u64,
+    // Not mirrored for MIRROR_MAX_INFLIGHT
+    pub dropped: u64,
+    // Mirrored requests that got no response, left out of `shadow`
+    pub errors: u64,
+    pub status_mismatches: u64,
+    pub primary: SideStats,
+    pub shadow: SideStats,
+}
+
+pub struct Mirror {
+    client: Client<HttpConnector, Empty<Bytes>>,
+    target: Uri,
+    percent: f64,
+    slots: Arc<Semaphore>,
+    sides: Mutex<Sides>,
+    mirrored: AtomicU64,
+    dropped: AtomicU64,
+    errors: AtomicU64,
+    status_mismatches: AtomicU64,
+}
+
+impl Mirror {
+    // None unless MIRROR_URL is set to an http:// URL
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("MIRROR_URL").ok()?;
+        let target = match url.parse::<Uri>() {
+            Ok(target) if target.scheme_str() == Some("http") && target.host().is_some() => target,
+            _ => {
+                eprintln!("Ignoring MIRROR_URL, not an http:// URL: {}", url);
+                return None;
+            }
+        };
+        let percent = std::env::var("MIRROR_PERCENT")
+            .ok()
+            .and_then(|percent| percent.parse().ok())
+            .filter(|percent: &f64| (0.0..=100.0).contains(percent))
+            .unwrap_or(DEFAULT_PERCENT);
+        let max_inflight = std::env::var("MIRROR_MAX_INFLIGHT")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_MAX_INFLIGHT);
+
+        println!("Mirroring {}% of GET requests to {}", percent, target);
+        Some(Mirror {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            target,
+            percent,
+            slots: Arc::new(Semaphore::new(max_inflight)),
+            sides: Mutex::default(),
+            mirrored: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            status_mismatches: AtomicU64::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> MirrorStats {
+        let sides = self.sides.lock();
+        MirrorStats {
+            target: self.target.to_string(),
+            percent: self.percent,
+            mirrored: self.mirrored.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            status_mismatches: self.status_mismatches.load(Ordering::Relaxed),
+            primary: sides.primary.stats(),
+            shadow: sides.shadow.stats(),
+        }
+    }
+
+    // A copy of `request` for the second server, with its headers except Host
+    fn shadow_request(&self, request: &Request) -> Option<hyper::Request<Empty<Bytes>>> {
+        let mut target = self.target.clone().into_parts();
+        target.path_and_query = request.uri().path_and_query().cloned();
+
+        let mut shadow = hyper::Request::builder()
+            .method(Method::GET)
+            .uri(Uri::from_parts(target).ok()?)
+            .body(Empty::new())
+            .ok()?;
+        *shadow.headers_mut() = request.headers().clone();
+        shadow.headers_mut().remove(header::HOST);
+        Some(shadow)
+    }
+
+    // Sends `shadow` and records it next to the primary response once both are in
+    async fn send(
+        &self,
+        shadow: hyper::Request<Empty<Bytes>>,
+        primary: oneshot::Receiver<(StatusCode, Duration)>,
+    ) {
+        let path = shadow.uri().path().to_owned();
+        let started = Instant::now();
+        let outcome = match self.client.request(shadow).await {
+            Ok(response) => {
+                let latency = started.elapsed();
+                let status = response.status();
+                // Read to the end so the connection can be reused
+                let _ = response.into_body().collect().await;
+                Some((status, latency))
+            }
+            Err(err) => {
+                let errors = self.errors.fetch_add(1, Ordering::Relaxed) + 1;
+                if errors.is_power_of_two() {
+                    eprintln!("Mirrored request failed ({} so far): {:?}", errors, err);
+                }
+                None
+            }
+        };
+        // Gone when the primary handler panicked
+        let Ok(primary) = primary.await else {
+            return;
+        };
+
+        let mut sides = self.sides.lock();
+        sides.primary.record(primary.0, primary.1);
+        let Some(shadow) = outcome else {
+            return;
+        };
+        sides.shadow.record(shadow.0, shadow.1);
+        if shadow.0 != primary.0 {
+            let mismatches = self.status_mismatches.fetch_add(1, Ordering::Relaxed) + 1;
+            if mismatches.is_power_of_two() {
+                eprintln!(
+                    "Mirror answered {} where this server answered {} for {} ({} mismatches so far)",
+                    shadow.0, primary.0, path, mismatches
+                );
+            }
+        }
+    }
+}
+
+// Middleware mirroring the sampled GET requests, sent at the same time as they're handled
+pub async fn mirror(State(mirror): State<Arc<Mirror>>, request: Request, next: Next) -> Response {
+    if request.method() != Method::GET || fastrand::f64() * 100.0 >= mirror.percent {
+        return next.run(request).await;
+    }
+    let Ok(slot) = mirror.slots.clone().try_acquire_owned() else {
+        mirror.dropped.fetch_add(1, Ordering::Relaxed);
+        return next.run(request).await;
+    };
+    let Some(shadow) = mirror.shadow_request(&request) else {
+        return next.run(request).await;
+    };
+
+    mirror.mirrored.fetch_add(1, Ordering::Relaxed);
+    let (primary_sender, primary) = oneshot::channel();
+    let sender = mirror.clone();
+    tokio::spawn(async move {
+        sender.send(shadow, primary).await;
+        drop(slot);
+    });
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let _ = primary_sender.send((response.status(), started.elapsed()));
+    response
+}
@@ -0,0 +1,187 @@
+// Newline-delimited JSON and CSV for the large list routes (/customers, /products,
+// /orders-with-details): with `Accept: application/x-ndjson` or `?format=ndjson` rows are
+// written one per line as diesel-async decodes them, instead of collecting the Vec first.
+// A query error after the first row can only end the body early. Requests with X-Snapshot
+// get the buffered array.
+//
+// `Accept: text/csv` or `?format=csv` streams the rows the same way as CSV through the csv
+// crate, a header line of the field names first, for export workloads and to compare the
+// cost of encoding rows as text with JSON's. X-Snapshot requests get the same CSV buffered.
+
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use bench_core::{DbPool, queries::RowStream};
+use diesel::QueryResult;
+use diesel_async::{AsyncPgConnection, scoped_futures::ScopedBoxFuture};
+use futures_util::StreamExt;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::{encoding::JsonEncoder, error::Classify};
+
+pub const NDJSON: &str = "application/x-ndjson";
+pub const CSV: &str = "text/csv";
+
+// Lines buffered ahead of a slow client
+const BUFFERED_LINES: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RowFormat {
+    Ndjson,
+    Csv,
+}
+
+impl RowFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            RowFormat::Ndjson => NDJSON,
+            RowFormat::Csv => CSV,
+        }
+    }
+
+    // A fresh encoder for each response, as the CSV one tracks whether the header is out
+    fn encoder(self) -> RowEncoder {
+        match self {
+            RowFormat::Ndjson => RowEncoder::Ndjson,
+            RowFormat::Csv => RowEncoder::Csv { header: true },
+        }
+    }
+}
+
+enum RowEncoder {
+    Ndjson,
+    // Writes the header line with the first row
+    Csv { header: bool },
+}
+
+impl RowEncoder {
+    fn encode<T: Serialize>(&mut self, row: &T) -> std::io::Result<Vec<u8>> {
+        match self {
+            RowEncoder::Ndjson => {
+                let mut line = JsonEncoder::current()
+                    .to_vec(row)
+                    .map_err(std::io::Error::other)?;
+                line.push(b'\n');
+                Ok(line)
+            }
+            RowEncoder::Csv { header } => {
+                // A writer per row, as csv only hands its buffer back by value
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(std::mem::take(header))
+                    .from_writer(Vec::new());
+                writer.serialize(row)?;
+                writer.into_inner().map_err(|err| err.into_error())
+            }
+        }
+    }
+}
+
+// The streamed format the request asks for, if any
+pub fn requested(headers: &HeaderMap, format: Option<&str>) -> Option<RowFormat> {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or("");
+
+    if format == Some("ndjson") || accept.contains(NDJSON) {
+        Some(RowFormat::Ndjson)
+    } else if format == Some("csv") || accept.contains(CSV) {
+        Some(RowFormat::Csv)
+    } else {
+        None
+    }
+}
+
+// `rows` as one buffered CSV body, for the requests that can't be streamed
+pub fn csv_response<T: Serialize>(rows: &[T]) -> Response {
+    let mut encoder = RowFormat::Csv.encoder();
+    let mut body = Vec::new();
+    for row in rows {
+        match encoder.encode(row) {
+            Ok(line) => body.extend_from_slice(&line),
+            Err(err) => {
+                eprintln!("Failed to encode CSV response: {:?}", err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+    ([(header::CONTENT_TYPE, CSV)], body).into_response()
+}
+
+// Streams the rows of `query` as `format`. The query runs in its own task holding a pool
+// connection until the last row is sent or the client goes away. Errors up to the first
+// row are answered with a 500 as usual
+pub async fn stream<T, F>(pool: DbPool, format: RowFormat, query: F) -> Result<Response, StatusCode>
+where
+    T: Serialize + Send + 'static,
+    F: for<'r> FnOnce(
+            &'r mut AsyncPgConnection,
+        ) -> ScopedBoxFuture<'static, 'r, QueryResult<RowStream<'r, T>>>
+        + Send
+        + 'static,
+{
+    let (lines, mut received) = mpsc::channel::<Result<Bytes, std::io::Error>>(BUFFERED_LINES);
+    let (started, start) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        let mut conn = match pool.get().await {
+            Ok(conn) => conn,
+            Err(err) => {
+                let _ = started.send(Err((err.code(), format!("{:?}", err))));
+                return;
+            }
+        };
+        let mut rows = match query(&mut conn).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                let _ = started.send(Err((err.code(), format!("{:?}", err))));
+                return;
+            }
+        };
+        // Postgres reports most errors with the first row, still in time for a 500
+        let first = rows.next().await;
+        if let Some(Err(err)) = &first {
+            let _ = started.send(Err((err.code(), format!("{:?}", err))));
+            return;
+        }
+        let _ = started.send(Ok(()));
+        // Polling a finished diesel-async stream again doesn't just return None
+        let Some(first) = first else {
+            return;
+        };
+
+        let mut rows = futures_util::stream::iter([first]).chain(rows);
+        let mut encoder = format.encoder();
+
+        while let Some(row) = rows.next().await {
+            let line = row
+                .map_err(std::io::Error::other)
+                .and_then(|row| encoder.encode(&row))
+                .map(Bytes::from);
+            let failed = line.is_err();
+            if lines.send(line).await.is_err() || failed {
+                // Client gone, or the error ends the body
+                return;
+            }
+        }
+    });
+
+    match start.await {
+        Ok(Ok(())) => {}
+        Ok(Err((code, err))) => {
+            eprintln!("Failed to start NDJSON stream: {}", err);
+            return Err(code.status());
+        }
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+
+    let body = futures_util::stream::poll_fn(move |cx| received.poll_recv(cx));
+    Ok((
+        [(header::CONTENT_TYPE, format.content_type())],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
@@ -0,0 +1,92 @@
+// `GET /orders-stream`: the /orders-with-details rows (p11) as Server-Sent Events, to
+// benchmark long-lived streaming connections next to the request/response traffic. Each
+// event is a JSON array of `batch` rows, fetched by keyset on a pool connection that goes
+// back to the pool between events, so idle streams don't hold connections the other routes
+// need. Events go out at `rate` per second until the orders run out, then an `end` event
+// closes the stream:
+//
+//   id: 42
+//   data: [{"id":42,"shipped_date":...}]
+//
+// The id of an event is the last order id in it, so a client that reconnects with
+// Last-Event-ID resumes after it. A query error ends the stream with an `error` event.
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::response::{
+    IntoResponse, Response,
+    sse::{Event, KeepAlive, Sse},
+};
+use bench_core::{
+    DbPool,
+    queries::{P11Row, p11_cursor},
+};
+use futures_util::stream;
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::{encoding::JsonEncoder, params::OrdersStream};
+
+struct Position {
+    pool: DbPool,
+    cursor: i32,
+    ticks: Interval,
+}
+
+pub fn stream(pool: DbPool, params: OrdersStream) -> Response {
+    let mut ticks = tokio::time::interval(Duration::from_secs_f64(1.0 / params.rate));
+    // A slow client gets fewer events per second rather than a burst once it catches up
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let position = Position {
+        pool,
+        cursor: params.cursor,
+        ticks,
+    };
+
+    let events = stream::unfold(Some(position), move |position| async move {
+        let mut position = position?;
+        position.ticks.tick().await;
+
+        let event = match next_batch(&position.pool, position.cursor, params.batch).await {
+            Some(rows) if rows.is_empty() => Event::default().event("end").data(""),
+            Some(rows) => match JsonEncoder::current().to_vec(&rows) {
+                Ok(data) => {
+                    position.cursor = rows[rows.len() - 1].id;
+                    let event = Event::default()
+                        .id(position.cursor.to_string())
+                        .data(String::from_utf8_lossy(&data));
+                    return Some((Ok::<_, Infallible>(event), Some(position)));
+                }
+                Err(err) => {
+                    eprintln!("Failed to encode orders stream event: {:?}", err);
+                    Event::default().event("error").data("encoding failed")
+                }
+            },
+            None => Event::default().event("error").data("query failed"),
+        };
+        Some((Ok(event), None))
+    });
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn next_batch(pool: &DbPool, cursor: i32, batch: i64) -> Option<Vec<P11Row>> {
+    let mut conn = match pool.get().await {
+        Ok(conn) => conn,
+        Err(err) => {
+            eprintln!(
+                "Failed to get a connection for the orders stream: {:?}",
+                err
+            );
+            return None;
+        }
+    };
+    match p11_cursor(&mut conn, cursor, batch).await {
+        Ok(rows) => Some(rows),
+        Err(err) => {
+            eprintln!("Orders stream query failed: {:?}", err);
+            None
+        }
+    }
+}
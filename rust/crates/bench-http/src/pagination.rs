@@ -0,0 +1,108 @@
+// RFC 8288 `Link` headers on the limit/offset list routes (/customers, /employees,
+// /suppliers, /products, /orders-with-details), as some of the compared frameworks send them
+// out of the box:
+//
+//   Link: </customers?limit=100&offset=0>; rel="first", </customers?limit=100&offset=0>;
+//         rel="prev", </customers?limit=100&offset=200>; rel="next", ...; rel="last"
+//
+// Enabled with PAGINATION_LINKS=1. `next` and `last` need the row count, one more query per
+// request, run on the same connection (and snapshot) as the page. `prev` and `next` are left
+// out on the first and last page; NDJSON responses don't carry the header.
+//
+// The keyset routes (/customers-cursor, /products-cursor, /orders-cursor) page by
+// `WHERE id > cursor` instead, so deep pages cost the same as the first; their responses
+// carry the cursor of the next page in the body.
+
+use std::future::Future;
+
+use axum::{
+    http::{HeaderValue, header},
+    response::Response,
+};
+use diesel::QueryResult;
+use serde::Serialize;
+
+#[derive(Clone, Copy)]
+pub struct PaginationLinks;
+
+impl PaginationLinks {
+    pub fn from_env() -> Option<Self> {
+        matches!(
+            std::env::var("PAGINATION_LINKS").as_deref(),
+            Ok("1") | Ok("true")
+        )
+        .then_some(PaginationLinks)
+    }
+
+    // Header value for the page at `offset` of a `total`-row table, None for a limit or
+    // offset the query would have rejected
+    pub fn header(self, path: &str, limit: i64, offset: i64, total: i64) -> Option<HeaderValue> {
+        if limit <= 0 || offset < 0 {
+            return None;
+        }
+        let link = |offset: i64, rel: &str| {
+            format!(
+                "<{}?limit={}&offset={}>; rel=\"{}\"",
+                path, limit, offset, rel
+            )
+        };
+        let last = (total - 1).max(0) / limit * limit;
+
+        let mut links = vec![link(0, "first")];
+        if offset > 0 {
+            // From past the end, back to the last page
+            links.push(link((offset - limit).min(last).max(0), "prev"));
+        }
+        if offset + limit < total {
+            links.push(link(offset + limit, "next"));
+        }
+        links.push(link(last, "last"));
+
+        HeaderValue::from_str(&links.join(", ")).ok()
+    }
+}
+
+// Runs `count` only when the links are enabled
+pub async fn count_if(
+    links: Option<PaginationLinks>,
+    count: impl Future<Output = QueryResult<i64>>,
+) -> QueryResult<Option<i64>> {
+    match links {
+        Some(_) => count.await.map(Some),
+        None => Ok(None),
+    }
+}
+
+// Adds the header to `response` when the count is known
+pub fn with_links(
+    mut response: Response,
+    links: Option<PaginationLinks>,
+    path: &str,
+    limit: i64,
+    offset: i64,
+    total: Option<i64>,
+) -> Response {
+    if let (Some(links), Some(total)) = (links, total)
+        && let Some(value) = links.header(path, limit, offset, total)
+    {
+        response.headers_mut().insert(header::LINK, value);
+    }
+    response
+}
+
+#[derive(Serialize)]
+pub struct CursorPage<T> {
+    pub data: Vec<T>,
+    // None once a page comes back short, i.e. after the last row
+    pub next_cursor: Option<i32>,
+}
+
+impl<T> CursorPage<T> {
+    pub fn new(data: Vec<T>, limit: i64, id: impl Fn(&T) -> i32) -> Self {
+        let next_cursor = match data.last() {
+            Some(last) if data.len() as i64 >= limit => Some(id(last)),
+            _ => None,
+        };
+        CursorPage { data, next_cursor }
+    }
+}
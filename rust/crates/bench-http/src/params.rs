@@ -0,0 +1,793 @@
+// Query parameters of the benchmark routes, and the bodies of the POST searches, parsed and
+// checked in one place so the handlers, the `QueryBackend` routes and the layers answering
+// in front of them (hot set, id filters, SQL over HTTP) agree on defaults and on what they
+// reject. A missing or malformed parameter, a negative limit or offset, is a 400 instead of
+// a query Postgres refuses with a 500; limits past MAX_LIMIT are served as MAX_LIMIT.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{HeaderValue, StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use bench_core::{
+    models::NewOrder,
+    queries::{AntiJoin, OrderLine, ProductSortKey, SearchDictionary, parse_product_sort},
+};
+use serde::Deserialize;
+
+pub const DEFAULT_LIMIT: i64 = 100;
+pub const MAX_LIMIT: i64 = 1000;
+// Autocomplete answers a handful of suggestions per keystroke
+pub const AUTOCOMPLETE_DEFAULT_LIMIT: i64 = 10;
+pub const AUTOCOMPLETE_MAX_LIMIT: i64 = 50;
+pub const TOP_DEFAULT_COUNT: i64 = 10;
+// Queries in one POST /batch
+pub const MAX_BATCH: usize = 100;
+// Events per second of /orders-stream
+pub const STREAM_DEFAULT_RATE: f64 = 10.0;
+pub const STREAM_MAX_RATE: f64 = 10_000.0;
+// CPU profiles of /debug/pprof/profile: their length, as Go's default, and sampling rate
+pub const PROFILE_DEFAULT_SECONDS: u64 = 30;
+pub const PROFILE_MAX_SECONDS: u64 = 600;
+pub const PROFILE_DEFAULT_FREQUENCY: i32 = 99;
+pub const PROFILE_MAX_FREQUENCY: i32 = 1000;
+
+#[derive(Debug)]
+pub struct ParamError(pub String);
+
+impl IntoResponse for ParamError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, self.0).into_response()
+    }
+}
+
+fn parse<'a, T: Deserialize<'a>>(query: Option<&'a str>) -> Result<T, ParamError> {
+    serde_urlencoded::from_str(query.unwrap_or("")).map_err(|err| ParamError(err.to_string()))
+}
+
+fn checked_limit(limit: Option<i64>) -> Result<i64, ParamError> {
+    match limit {
+        None => Ok(DEFAULT_LIMIT),
+        Some(limit) if limit < 0 => Err(ParamError("limit must not be negative".into())),
+        Some(limit) => Ok(limit.min(MAX_LIMIT)),
+    }
+}
+
+// Checked limit and offset, for the transports that don't take them from a query string
+pub fn page(limit: Option<i64>, offset: Option<i64>) -> Result<(i64, i64), ParamError> {
+    let offset = offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(ParamError("offset must not be negative".into()));
+    }
+    Ok((checked_limit(limit)?, offset))
+}
+
+#[derive(Deserialize)]
+struct RawPagination {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    format: Option<String>,
+}
+
+// `limit` and `offset` of the limit/offset list routes
+#[derive(Clone, Debug)]
+pub struct Pagination {
+    pub limit: i64,
+    pub offset: i64,
+    // `ndjson` or `csv` streams the list routes that support it
+    pub format: Option<String>,
+}
+
+impl Pagination {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawPagination = parse(query)?;
+        let (limit, offset) = page(raw.limit, raw.offset)?;
+        Ok(Pagination {
+            limit,
+            offset,
+            format: raw.format,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Pagination {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Pagination::from_query(parts.uri.query())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawCursor {
+    cursor: Option<i32>,
+    limit: Option<i64>,
+}
+
+// `cursor` and `limit` of the keyset routes
+#[derive(Clone, Copy, Debug)]
+pub struct Cursor {
+    // Last id of the previous page, 0 for the first
+    pub cursor: i32,
+    pub limit: i64,
+}
+
+impl Cursor {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawCursor = parse(query)?;
+        Ok(Cursor {
+            cursor: raw.cursor.unwrap_or(0),
+            limit: checked_limit(raw.limit)?,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Cursor {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Cursor::from_query(parts.uri.query())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawOrdersStream {
+    rate: Option<f64>,
+    batch: Option<i64>,
+    cursor: Option<i32>,
+}
+
+// `rate` (events per second, capped at STREAM_MAX_RATE), `batch` (rows per event, capped
+// like a limit) and `cursor` of /orders-stream. A Last-Event-ID header, sent by a
+// reconnecting client, takes the place of `cursor`
+#[derive(Clone, Copy, Debug)]
+pub struct OrdersStream {
+    pub rate: f64,
+    pub batch: i64,
+    pub cursor: i32,
+}
+
+impl OrdersStream {
+    pub fn from_parts(parts: &Parts) -> Result<Self, ParamError> {
+        let raw: RawOrdersStream = parse(parts.uri.query())?;
+        let rate = match raw.rate {
+            None => STREAM_DEFAULT_RATE,
+            Some(rate) if rate > 0.0 && rate.is_finite() => rate.min(STREAM_MAX_RATE),
+            Some(_) => return Err(ParamError("rate must be positive".into())),
+        };
+        let batch = match raw.batch {
+            None => 1,
+            Some(batch) if batch < 1 => {
+                return Err(ParamError("batch must be at least 1".into()));
+            }
+            Some(batch) => batch.min(MAX_LIMIT),
+        };
+        let cursor = match parts.headers.get("last-event-id") {
+            Some(id) => id
+                .to_str()
+                .ok()
+                .and_then(|id| id.trim().parse().ok())
+                .ok_or_else(|| ParamError("Last-Event-ID must be an order id".into()))?,
+            None => raw.cursor.unwrap_or(0),
+        };
+        Ok(OrdersStream {
+            rate,
+            batch,
+            cursor,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for OrdersStream {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        OrdersStream::from_parts(parts)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    // Protobuf, for `go tool pprof`
+    Pprof,
+    Flamegraph,
+}
+
+#[derive(Deserialize)]
+struct RawCpuProfile {
+    seconds: Option<u64>,
+    frequency: Option<i32>,
+    format: Option<ProfileFormat>,
+}
+
+// `seconds` (up to PROFILE_MAX_SECONDS), `frequency` (samples per second) and `format` of
+// /debug/pprof/profile. Without `format` it answers a flame graph to Accept: image/svg+xml
+// and a pprof profile otherwise, which is what `go tool pprof` asks for
+#[derive(Clone, Copy, Debug)]
+pub struct CpuProfile {
+    pub seconds: u64,
+    pub frequency: i32,
+    pub format: ProfileFormat,
+}
+
+impl CpuProfile {
+    pub fn from_parts(parts: &Parts) -> Result<Self, ParamError> {
+        let raw: RawCpuProfile = parse(parts.uri.query())?;
+        let seconds = match raw.seconds {
+            None => PROFILE_DEFAULT_SECONDS,
+            Some(seconds) if (1..=PROFILE_MAX_SECONDS).contains(&seconds) => seconds,
+            Some(_) => {
+                return Err(ParamError(format!(
+                    "seconds must be between 1 and {}",
+                    PROFILE_MAX_SECONDS
+                )));
+            }
+        };
+        let frequency = match raw.frequency {
+            None => PROFILE_DEFAULT_FREQUENCY,
+            Some(frequency) if (1..=PROFILE_MAX_FREQUENCY).contains(&frequency) => frequency,
+            Some(_) => {
+                return Err(ParamError(format!(
+                    "frequency must be between 1 and {}",
+                    PROFILE_MAX_FREQUENCY
+                )));
+            }
+        };
+        let format = raw.format.unwrap_or_else(|| {
+            let svg = parts
+                .headers
+                .get(header::ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .is_some_and(|accept| accept.contains("image/svg+xml"));
+            if svg {
+                ProfileFormat::Flamegraph
+            } else {
+                ProfileFormat::Pprof
+            }
+        });
+        Ok(CpuProfile {
+            seconds,
+            frequency,
+            format,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CpuProfile {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        CpuProfile::from_parts(parts)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawCustomersWithoutOrders {
+    #[serde(default)]
+    strategy: AntiJoin,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+// `strategy` (`not-exists`, the default, or `left-join`), `limit` and `offset` of
+// /customers/without-orders
+#[derive(Clone, Copy, Debug)]
+pub struct CustomersWithoutOrders {
+    pub strategy: AntiJoin,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl CustomersWithoutOrders {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawCustomersWithoutOrders = parse(query)?;
+        let (limit, offset) = page(raw.limit, raw.offset)?;
+        Ok(CustomersWithoutOrders {
+            strategy: raw.strategy,
+            limit,
+            offset,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CustomersWithoutOrders {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        CustomersWithoutOrders::from_query(parts.uri.query())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawId {
+    id: i32,
+}
+
+// The required `id` of the by-id routes
+#[derive(Clone, Copy, Debug)]
+pub struct Id(pub i32);
+
+impl Id {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        parse(query).map(|raw: RawId| Id(raw.id))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Id {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Id::from_query(parts.uri.query())
+    }
+}
+
+// Search responses name the text search dictionary they were matched with
+pub const DICTIONARY_HEADER: &str = "x-search-dictionary";
+
+pub fn with_dictionary(mut response: Response, dictionary: SearchDictionary) -> Response {
+    response.headers_mut().insert(
+        DICTIONARY_HEADER,
+        HeaderValue::from_static(dictionary.as_str()),
+    );
+    response
+}
+
+#[derive(Deserialize)]
+struct RawSearch {
+    term: String,
+    dictionary: Option<SearchDictionary>,
+    #[serde(default)]
+    highlight: bool,
+    #[serde(default)]
+    rank: bool,
+}
+
+// `term` of the GET search routes, and the dictionary to search in when not the server's
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub term: String,
+    pub dictionary: Option<SearchDictionary>,
+    // `highlight=true` adds each match's ts_headline as `headline`
+    pub highlight: bool,
+    // `rank=true` orders the matches by ts_rank, best first, instead of leaving them unordered
+    pub rank: bool,
+}
+
+impl Search {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawSearch = parse(query)?;
+        Ok(Search {
+            term: raw.term,
+            dictionary: raw.dictionary,
+            highlight: raw.highlight,
+            rank: raw.rank,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Search {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Search::from_query(parts.uri.query())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSupplierProducts {
+    sort: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+// `sort` (comma-separated `column:asc|desc` keys, e.g. `unit_price:desc,name:asc`), `limit`
+// and `offset` of the supplier product listing. Without `sort` it's in id order.
+#[derive(Clone, Debug)]
+pub struct SupplierProducts {
+    pub sort: Vec<ProductSortKey>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl SupplierProducts {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawSupplierProducts = parse(query)?;
+        let sort = match raw.sort.as_deref() {
+            None => Vec::new(),
+            Some(sort) => parse_product_sort(sort).map_err(ParamError)?,
+        };
+        let (limit, offset) = page(raw.limit, raw.offset)?;
+        Ok(SupplierProducts {
+            sort,
+            limit,
+            offset,
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for SupplierProducts {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        SupplierProducts::from_query(parts.uri.query())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawTop {
+    count: Option<i64>,
+}
+
+// `count` of the ranking routes (/top-products, /employee-leaderboard), capped like a limit
+#[derive(Clone, Copy, Debug)]
+pub struct Top {
+    pub count: i64,
+}
+
+impl Top {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawTop = parse(query)?;
+        let count = match raw.count {
+            None => TOP_DEFAULT_COUNT,
+            Some(count) if count < 0 => {
+                return Err(ParamError("count must not be negative".into()));
+            }
+            Some(count) => count.min(MAX_LIMIT),
+        };
+        Ok(Top { count })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Top {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Top::from_query(parts.uri.query())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawAutocomplete {
+    q: String,
+    limit: Option<i64>,
+}
+
+// `q`, the text typed so far, and `limit` of the autocomplete routes
+#[derive(Clone, Debug)]
+pub struct Autocomplete {
+    pub q: String,
+    pub limit: i64,
+}
+
+impl Autocomplete {
+    pub fn from_query(query: Option<&str>) -> Result<Self, ParamError> {
+        let raw: RawAutocomplete = parse(query)?;
+        let limit = match raw.limit {
+            None => AUTOCOMPLETE_DEFAULT_LIMIT,
+            Some(limit) if limit < 0 => {
+                return Err(ParamError("limit must not be negative".into()));
+            }
+            Some(limit) => limit.min(AUTOCOMPLETE_MAX_LIMIT),
+        };
+        Ok(Autocomplete { q: raw.q, limit })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Autocomplete {
+    type Rejection = ParamError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Autocomplete::from_query(parts.uri.query())
+    }
+}
+
+// Body of the POST search routes: the GET routes' term, dictionary and highlight, plus a
+// limit (with the list routes' default and cap) and the query's structured filters
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchBody<F> {
+    pub term: String,
+    pub dictionary: Option<SearchDictionary>,
+    #[serde(default)]
+    pub highlight: bool,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub filters: F,
+}
+
+impl<F> SearchBody<F> {
+    pub fn limit(&self) -> Result<i64, ParamError> {
+        checked_limit(self.limit)
+    }
+}
+
+// One query of a POST /batch body, named by its route and with the route's parameters, e.g.
+// `{"q":"customer-by-id","id":1}`
+#[derive(Deserialize, Debug)]
+#[serde(tag = "q", rename_all = "kebab-case")]
+pub enum BatchQuery {
+    Customers {
+        limit: Option<i64>,
+        offset: Option<i64>,
+    },
+    CustomerById {
+        id: i32,
+    },
+    SearchCustomer {
+        term: String,
+        dictionary: Option<SearchDictionary>,
+    },
+    Employees {
+        limit: Option<i64>,
+        offset: Option<i64>,
+    },
+    EmployeeWithRecipient {
+        id: i32,
+    },
+    Suppliers {
+        limit: Option<i64>,
+        offset: Option<i64>,
+    },
+    SupplierById {
+        id: i32,
+    },
+    Products {
+        limit: Option<i64>,
+        offset: Option<i64>,
+    },
+    ProductWithSupplier {
+        id: i32,
+    },
+    SearchProduct {
+        term: String,
+        dictionary: Option<SearchDictionary>,
+    },
+    OrdersWithDetails {
+        limit: Option<i64>,
+        offset: Option<i64>,
+    },
+    OrderWithDetails {
+        id: i32,
+    },
+    OrderWithDetailsAndProducts {
+        id: i32,
+    },
+}
+
+// Between one and MAX_BATCH queries, with limits and offsets as the list routes take them
+pub fn check_batch(queries: &[BatchQuery]) -> Result<(), ParamError> {
+    if queries.is_empty() || queries.len() > MAX_BATCH {
+        return Err(ParamError(format!(
+            "a batch takes 1 to {} queries",
+            MAX_BATCH
+        )));
+    }
+    for query in queries {
+        if let BatchQuery::Customers { limit, offset }
+        | BatchQuery::Employees { limit, offset }
+        | BatchQuery::Suppliers { limit, offset }
+        | BatchQuery::Products { limit, offset }
+        | BatchQuery::OrdersWithDetails { limit, offset } = query
+        {
+            page(*limit, *offset)?;
+        }
+    }
+    Ok(())
+}
+
+// Body of POST /orders: the order's columns and, in `details`, its lines
+#[derive(Deserialize, Debug)]
+pub struct OrderBody {
+    #[serde(flatten)]
+    pub order: NewOrder,
+    pub details: Vec<OrderLine>,
+}
+
+impl OrderBody {
+    // At least one line, each of a positive quantity and a discount between 0 and 1
+    pub fn check(&self) -> Result<(), ParamError> {
+        if self.details.is_empty() {
+            return Err(ParamError("an order needs at least one detail".into()));
+        }
+        for line in &self.details {
+            if line.quantity <= 0 {
+                return Err(ParamError("quantity must be positive".into()));
+            }
+            if !(0.0..=1.0).contains(&line.discount) {
+                return Err(ParamError("discount must be between 0 and 1".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    fn parts(uri: &str, headers: &[(&str, &str)]) -> Parts {
+        let mut request = Request::get(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn pagination() {
+        let cases = [
+            (None, Some((DEFAULT_LIMIT, 0))),
+            (Some(""), Some((DEFAULT_LIMIT, 0))),
+            (Some("limit=5&offset=10"), Some((5, 10))),
+            (Some("limit=0"), Some((0, 0))),
+            (Some("limit=1000"), Some((MAX_LIMIT, 0))),
+            (Some("limit=5000&offset=3"), Some((MAX_LIMIT, 3))),
+            (Some("limit=-1"), None),
+            (Some("offset=-1"), None),
+            (Some("limit=ten"), None),
+            (Some("limit=1.5"), None),
+            (Some("offset=99999999999999999999"), None),
+        ];
+        for (query, expected) in cases {
+            let page = Pagination::from_query(query).ok();
+            assert_eq!(
+                page.map(|page| (page.limit, page.offset)),
+                expected,
+                "{:?}",
+                query
+            );
+        }
+
+        let page = Pagination::from_query(Some("format=ndjson")).unwrap();
+        assert_eq!(page.format.as_deref(), Some("ndjson"));
+    }
+
+    #[test]
+    fn cursor() {
+        let cases = [
+            (None, Some((0, DEFAULT_LIMIT))),
+            (Some("cursor=42&limit=10"), Some((42, 10))),
+            (Some("limit=99999"), Some((0, MAX_LIMIT))),
+            (Some("limit=-1"), None),
+            (Some("cursor=last"), None),
+            (Some("cursor=1.5"), None),
+        ];
+        for (query, expected) in cases {
+            let cursor = Cursor::from_query(query).ok();
+            assert_eq!(
+                cursor.map(|cursor| (cursor.cursor, cursor.limit)),
+                expected,
+                "{:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn orders_stream() {
+        let cases = [
+            ("/orders-stream", None, Some((STREAM_DEFAULT_RATE, 1, 0))),
+            (
+                "/orders-stream?rate=50&batch=20&cursor=7",
+                None,
+                Some((50.0, 20, 7)),
+            ),
+            ("/orders-stream?rate=0.5", None, Some((0.5, 1, 0))),
+            (
+                "/orders-stream?rate=1e9",
+                None,
+                Some((STREAM_MAX_RATE, 1, 0)),
+            ),
+            (
+                "/orders-stream?batch=5000",
+                None,
+                Some((STREAM_DEFAULT_RATE, MAX_LIMIT, 0)),
+            ),
+            // Last-Event-ID takes the place of `cursor`
+            (
+                "/orders-stream?cursor=7",
+                Some("12"),
+                Some((STREAM_DEFAULT_RATE, 1, 12)),
+            ),
+            (
+                "/orders-stream",
+                Some(" 12 "),
+                Some((STREAM_DEFAULT_RATE, 1, 12)),
+            ),
+            ("/orders-stream?rate=0", None, None),
+            ("/orders-stream?rate=-1", None, None),
+            ("/orders-stream?rate=inf", None, None),
+            ("/orders-stream?rate=NaN", None, None),
+            ("/orders-stream?rate=fast", None, None),
+            ("/orders-stream?batch=0", None, None),
+            ("/orders-stream?batch=-3", None, None),
+            ("/orders-stream?cursor=x", None, None),
+            ("/orders-stream", Some("abc"), None),
+            ("/orders-stream?cursor=7", Some(""), None),
+        ];
+        for (uri, last_event_id, expected) in cases {
+            let headers: Vec<_> = last_event_id
+                .map(|id| ("last-event-id", id))
+                .into_iter()
+                .collect();
+            let stream = OrdersStream::from_parts(&parts(uri, &headers)).ok();
+            assert_eq!(
+                stream.map(|stream| (stream.rate, stream.batch, stream.cursor)),
+                expected,
+                "{} with Last-Event-ID {:?}",
+                uri,
+                last_event_id
+            );
+        }
+    }
+
+    #[test]
+    fn cpu_profile() {
+        use ProfileFormat::{Flamegraph, Pprof};
+
+        let defaults = (PROFILE_DEFAULT_SECONDS, PROFILE_DEFAULT_FREQUENCY);
+        let cases = [
+            ("", None, Some((defaults, Pprof))),
+            ("?seconds=1&frequency=1", None, Some(((1, 1), Pprof))),
+            (
+                "?seconds=600&frequency=1000",
+                None,
+                Some(((600, 1000), Pprof)),
+            ),
+            // Without `format`, a browser asking for SVG gets a flame graph
+            ("", Some("image/svg+xml"), Some((defaults, Flamegraph))),
+            (
+                "",
+                Some("text/html, image/svg+xml;q=0.9"),
+                Some((defaults, Flamegraph)),
+            ),
+            (
+                "",
+                Some("application/octet-stream"),
+                Some((defaults, Pprof)),
+            ),
+            (
+                "?format=pprof",
+                Some("image/svg+xml"),
+                Some((defaults, Pprof)),
+            ),
+            ("?format=flamegraph", None, Some((defaults, Flamegraph))),
+            ("?seconds=0", None, None),
+            ("?seconds=601", None, None),
+            ("?seconds=-1", None, None),
+            ("?frequency=0", None, None),
+            ("?frequency=1001", None, None),
+            ("?frequency=-99", None, None),
+            ("?format=svg", None, None),
+        ];
+        for (query, accept, expected) in cases {
+            let headers: Vec<_> = accept
+                .map(|accept| ("accept", accept))
+                .into_iter()
+                .collect();
+            let uri = format!("/debug/pprof/profile{}", query);
+            let profile = CpuProfile::from_parts(&parts(&uri, &headers)).ok();
+            assert_eq!(
+                profile.map(|profile| ((profile.seconds, profile.frequency), profile.format)),
+                expected,
+                "{} with Accept {:?}",
+                uri,
+                accept
+            );
+        }
+    }
+}
@@ -0,0 +1,196 @@
+// Database-side health for the `/debug/pg-*` endpoints (and `/debug/statements`), queried
+// through the same pool as the benchmark queries so a results file can capture both sides
+// of a run.
+
+use bench_core::config::StatementCache;
+use diesel::{
+    prelude::*,
+    sql_types::{Array, BigInt, Bool, Double, Integer, Nullable, Text},
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+
+// Only the columns pg_stat_bgwriter kept in Postgres 17, where checkpoint counters moved
+// to pg_stat_checkpointer
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct BgWriterStats {
+    #[diesel(sql_type = BigInt)]
+    pub buffers_clean: i64,
+    #[diesel(sql_type = BigInt)]
+    pub maxwritten_clean: i64,
+    #[diesel(sql_type = BigInt)]
+    pub buffers_alloc: i64,
+}
+
+// pg_stat_database row of the benchmark database
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct DatabaseStats {
+    #[diesel(sql_type = Integer)]
+    pub numbackends: i32,
+    #[diesel(sql_type = BigInt)]
+    pub xact_commit: i64,
+    #[diesel(sql_type = BigInt)]
+    pub xact_rollback: i64,
+    #[diesel(sql_type = BigInt)]
+    pub blks_read: i64,
+    #[diesel(sql_type = BigInt)]
+    pub blks_hit: i64,
+    #[diesel(sql_type = BigInt)]
+    pub tup_returned: i64,
+    #[diesel(sql_type = BigInt)]
+    pub tup_fetched: i64,
+    #[diesel(sql_type = BigInt)]
+    pub tup_inserted: i64,
+    #[diesel(sql_type = BigInt)]
+    pub tup_updated: i64,
+    #[diesel(sql_type = BigInt)]
+    pub tup_deleted: i64,
+    #[diesel(sql_type = BigInt)]
+    pub temp_files: i64,
+    #[diesel(sql_type = BigInt)]
+    pub temp_bytes: i64,
+    #[diesel(sql_type = BigInt)]
+    pub deadlocks: i64,
+}
+
+// Shared buffer hit ratios (0..1); None until the relations have been read at all
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct CacheHitRatios {
+    #[diesel(sql_type = Nullable<Double>)]
+    pub database: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    pub tables: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    pub indexes: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PgSystemStats {
+    pub bgwriter: BgWriterStats,
+    pub database: DatabaseStats,
+    pub cache_hit_ratios: CacheHitRatios,
+}
+
+pub async fn pg_system_stats(conn: &mut AsyncPgConnection) -> QueryResult<PgSystemStats> {
+    let bgwriter = diesel::sql_query(
+        "SELECT buffers_clean, maxwritten_clean, buffers_alloc FROM pg_stat_bgwriter",
+    )
+    .get_result(conn)
+    .await?;
+
+    let database = diesel::sql_query(
+        "SELECT numbackends, xact_commit, xact_rollback, blks_read, blks_hit, tup_returned, \
+         tup_fetched, tup_inserted, tup_updated, tup_deleted, temp_files, temp_bytes, deadlocks \
+         FROM pg_stat_database WHERE datname = current_database()",
+    )
+    .get_result(conn)
+    .await?;
+
+    let cache_hit_ratios = diesel::sql_query(
+        "SELECT \
+         (SELECT blks_hit::float8 / NULLIF(blks_hit + blks_read, 0) \
+          FROM pg_stat_database WHERE datname = current_database()) AS database, \
+         (SELECT sum(heap_blks_hit)::float8 / NULLIF(sum(heap_blks_hit) + sum(heap_blks_read), 0) \
+          FROM pg_statio_user_tables) AS tables, \
+         (SELECT sum(idx_blks_hit)::float8 / NULLIF(sum(idx_blks_hit) + sum(idx_blks_read), 0) \
+          FROM pg_statio_user_indexes) AS indexes",
+    )
+    .get_result(conn)
+    .await?;
+
+    Ok(PgSystemStats {
+        bgwriter,
+        database,
+        cache_hit_ratios,
+    })
+}
+
+// A backend waiting on a lock it hasn't been granted
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct LockWait {
+    #[diesel(sql_type = Integer)]
+    pub pid: i32,
+    #[diesel(sql_type = Array<Integer>)]
+    pub blocking_pids: Vec<i32>,
+    #[diesel(sql_type = Text)]
+    pub locktype: String,
+    #[diesel(sql_type = Text)]
+    pub mode: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub relation: Option<String>,
+    #[diesel(sql_type = Nullable<Double>)]
+    pub waiting_ms: Option<f64>,
+    // Truncated to keep the response small under heavy contention
+    #[diesel(sql_type = Nullable<Text>)]
+    pub query: Option<String>,
+}
+
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct LockModeCount {
+    #[diesel(sql_type = Text)]
+    pub mode: String,
+    #[diesel(sql_type = Bool)]
+    pub granted: bool,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PgLocks {
+    pub waiting: Vec<LockWait>,
+    pub by_mode: Vec<LockModeCount>,
+}
+
+pub async fn pg_locks(conn: &mut AsyncPgConnection) -> QueryResult<PgLocks> {
+    let waiting = diesel::sql_query(
+        "SELECT a.pid, pg_blocking_pids(a.pid) AS blocking_pids, l.locktype, l.mode, \
+         l.relation::regclass::text AS relation, \
+         extract(epoch FROM now() - a.state_change)::float8 * 1000 AS waiting_ms, \
+         left(a.query, 200) AS query \
+         FROM pg_locks l JOIN pg_stat_activity a ON a.pid = l.pid \
+         WHERE NOT l.granted AND a.datname = current_database() \
+         ORDER BY waiting_ms DESC",
+    )
+    .load(conn)
+    .await?;
+
+    let by_mode = diesel::sql_query(
+        "SELECT mode, granted, count(*) AS count FROM pg_locks \
+         WHERE database IS NULL \
+         OR database = (SELECT oid FROM pg_database WHERE datname = current_database()) \
+         GROUP BY mode, granted ORDER BY mode, granted",
+    )
+    .load(conn)
+    .await?;
+
+    Ok(PgLocks { waiting, by_mode })
+}
+
+// Prepared statements of one pooled connection, which with Diesel's statement cache on are
+// the statements it has cached (the pool's health check among them)
+#[derive(QueryableByName, Debug, Serialize)]
+pub struct ConnectionStatements {
+    #[diesel(sql_type = Integer)]
+    pub backend_pid: i32,
+    #[diesel(sql_type = BigInt)]
+    pub statements: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatementCacheStats {
+    pub cache: StatementCache,
+    // One entry per connection that was idle in the pool
+    pub connections: Vec<ConnectionStatements>,
+    pub total: i64,
+}
+
+pub async fn connection_statements(
+    conn: &mut AsyncPgConnection,
+) -> QueryResult<ConnectionStatements> {
+    diesel::sql_query(
+        "SELECT pg_backend_pid() AS backend_pid, count(*) AS statements \
+         FROM pg_prepared_statements",
+    )
+    .get_result(conn)
+    .await
+}
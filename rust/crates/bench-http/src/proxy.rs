@@ -0,0 +1,251 @@
+// `rust proxy`: a reverse proxy in front of another benchmarked server that forwards every
+// request to `--upstream` and records it to `--record` as a trace (`bench_driver::trace`):
+// the request, the upstream's response and its timings. `bench run --requests <trace>`
+// replays a trace, so a workload captured in front of one server runs unchanged against the
+// others, and the recorded responses show what each one answered.
+//
+// Responses are buffered whole before they are sent on, to record them, so clients see the
+// upstream's full-body latency as their time to first byte. Records are written by a thread
+// of their own and dropped, with a warning, when it falls behind.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{SyncSender, TrySendError, sync_channel},
+    },
+    time::Instant,
+};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, Uri, header, uri::Scheme},
+    response::{IntoResponse, Response},
+};
+use bench_driver::trace::TraceRecord;
+use clap::Args;
+use http_body_util::{BodyExt, Full};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+
+use crate::{
+    client_limits::{self, ClientLimits},
+    listen::ListenConfig,
+};
+
+// Records buffered for the writer thread
+const CHANNEL_CAPACITY: usize = 8192;
+
+#[derive(Args)]
+pub struct ProxyArgs {
+    /// Server to forward the requests to, e.g. http://localhost:3000
+    #[arg(long, env = "PROXY_UPSTREAM")]
+    pub upstream: Uri,
+
+    /// File to append the trace to, one JSON line per request
+    #[arg(long, env = "PROXY_RECORD", default_value = "trace.ndjson")]
+    pub record: PathBuf,
+
+    #[command(flatten)]
+    pub listen: ListenConfig,
+}
+
+struct Proxy {
+    client: Client<HttpConnector, Full<Bytes>>,
+    upstream: Uri,
+    started: Instant,
+    recorder: TraceRecorder,
+}
+
+pub async fn run(args: ProxyArgs) {
+    if args.upstream.scheme() != Some(&Scheme::HTTP) || args.upstream.authority().is_none() {
+        eprintln!(
+            "The upstream must be an http:// URL with a host, got {}",
+            args.upstream
+        );
+        std::process::exit(1);
+    }
+    let recorder = match TraceRecorder::start(&args.record) {
+        Ok(recorder) => recorder,
+        Err(err) => {
+            eprintln!(
+                "Failed to open trace file {}: {:?}",
+                args.record.display(),
+                err
+            );
+            std::process::exit(1);
+        }
+    };
+    let listeners = match args.listen.bind_all() {
+        Ok(listeners) => listeners,
+        Err(err) => {
+            eprintln!("Failed to bind to {}: {:?}", args.listen.addr(), err);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Proxying port {} to {}, recording to {}",
+        args.listen.port,
+        args.upstream,
+        args.record.display()
+    );
+    let proxy = Proxy {
+        client: Client::builder(TokioExecutor::new()).build_http(),
+        upstream: args.upstream,
+        started: Instant::now(),
+        recorder,
+    };
+    let app = Router::new().fallback(forward).with_state(Arc::new(proxy));
+    client_limits::serve(
+        listeners,
+        app,
+        ClientLimits::from_env(),
+        args.listen.tcp_nodelay,
+    )
+    .await;
+}
+
+async fn forward(State(proxy): State<Arc<Proxy>>, request: Request) -> Response {
+    let start_ms = proxy.started.elapsed().as_secs_f64() * 1000.0;
+    let (mut parts, body) = request.into_parts();
+    let Ok(body) = body.collect().await.map(|body| body.to_bytes()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let path = parts
+        .uri
+        .path_and_query()
+        .map_or("/", |path| path.as_str())
+        .to_owned();
+
+    let mut record = TraceRecord {
+        start_ms,
+        method: parts.method.to_string(),
+        path: path.clone(),
+        request_headers: headers(&parts.headers),
+        request_body: text(&body),
+        status: 0,
+        response_headers: BTreeMap::new(),
+        response_body: None,
+        first_byte_ms: 0.0,
+        duration_ms: 0.0,
+        error: None,
+    };
+
+    let mut upstream = proxy.upstream.clone().into_parts();
+    upstream.path_and_query = Some(path.parse().expect("path of a valid URI"));
+    parts.uri = Uri::from_parts(upstream).expect("upstream URI with a new path");
+    // The client sets the upstream's
+    parts.headers.remove(header::HOST);
+
+    let started = Instant::now();
+    let exchange = async {
+        let response = proxy
+            .client
+            .request(Request::from_parts(parts, Full::new(body)))
+            .await
+            .map_err(|err| err.to_string())?;
+        let first_byte = started.elapsed();
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await.map_err(|err| err.to_string())?;
+        Ok::<_, String>((parts, first_byte, body.to_bytes()))
+    };
+
+    let response = match exchange.await {
+        Ok((mut parts, first_byte, body)) => {
+            record.status = parts.status.as_u16();
+            record.response_headers = headers(&parts.headers);
+            record.response_body = text(&body);
+            record.first_byte_ms = first_byte.as_secs_f64() * 1000.0;
+            // Framing is the proxy's own now that the body is whole
+            parts.headers.remove(header::TRANSFER_ENCODING);
+            parts.headers.remove(header::CONNECTION);
+            Response::from_parts(parts, Body::from(body))
+        }
+        Err(err) => {
+            eprintln!("Failed to forward {} {}: {}", record.method, path, err);
+            record.status = StatusCode::BAD_GATEWAY.as_u16();
+            record.first_byte_ms = started.elapsed().as_secs_f64() * 1000.0;
+            record.error = Some(err);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    };
+    record.duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+    proxy.recorder.record(record);
+    response
+}
+
+// Repeated headers are joined with commas
+fn headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut map = BTreeMap::<String, String>::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        map.entry(name.to_string())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    map
+}
+
+fn text(body: &Bytes) -> Option<String> {
+    (!body.is_empty()).then(|| String::from_utf8_lossy(body).into_owned())
+}
+
+struct TraceRecorder {
+    sender: SyncSender<TraceRecord>,
+    dropped: AtomicU64,
+}
+
+impl TraceRecorder {
+    // Starts the writer thread appending to `path`
+    fn start(path: &Path) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::options().create(true).append(true).open(path)?);
+        let (sender, receiver) = sync_channel::<TraceRecord>(CHANNEL_CAPACITY);
+
+        std::thread::spawn(move || {
+            while let Ok(record) = receiver.recv() {
+                let mut next = Some(record);
+                while let Some(record) = next {
+                    let written = serde_json::to_writer(&mut writer, &record)
+                        .map_err(std::io::Error::from)
+                        .and_then(|_| writer.write_all(b"\n"));
+                    if let Err(err) = written {
+                        eprintln!("Failed to write trace record: {:?}", err);
+                        return;
+                    }
+                    next = receiver.try_recv().ok();
+                }
+                // Flush whenever the channel drains so the trace is usable while recording
+                if let Err(err) = writer.flush() {
+                    eprintln!("Failed to flush the trace: {:?}", err);
+                    return;
+                }
+            }
+        });
+
+        Ok(TraceRecorder {
+            sender,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    fn record(&self, record: TraceRecord) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(record) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped.is_power_of_two() {
+                eprintln!("Trace recording can't keep up, {} records dropped", dropped);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// `/readyz`, for orchestration scripts to wait on. A server started with `--auto-seed`
+// listens from the start but seeds an empty database before it serves: until then `/readyz`
+// answers 503 with the seed's progress,
+//
+//   {"status":"seeding","table":"orders","rows":12000,"tablesDone":2,"tables":6}
+//
+// and every other request a bare 503. Afterwards, and always without `--auto-seed`, it
+// answers 200 `{"status":"ready"}`.
+
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bench_core::seed::{SeedProgress, SeedProgressSnapshot};
+use serde::Serialize;
+use tower::ServiceExt;
+
+#[derive(Serialize)]
+struct Status {
+    status: &'static str,
+    #[serde(flatten)]
+    progress: Option<SeedProgressSnapshot>,
+}
+
+pub async fn ready() -> Response {
+    let status = Status {
+        status: "ready",
+        progress: None,
+    };
+    Json(status).into_response()
+}
+
+// The server while it seeds, then the application
+#[derive(Default)]
+pub struct Readiness {
+    progress: SeedProgress,
+    app: OnceLock<Router>,
+}
+
+impl Readiness {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Readiness::default())
+    }
+
+    pub fn progress(&self) -> &SeedProgress {
+        &self.progress
+    }
+
+    // Hands every request from now on to `app`
+    pub fn ready(&self, app: Router) {
+        let _ = self.app.set(app);
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new().fallback(gate).with_state(self)
+    }
+}
+
+async fn gate(State(readiness): State<Arc<Readiness>>, request: Request) -> Response {
+    if let Some(app) = readiness.app.get() {
+        let Ok(response) = app.clone().oneshot(request).await;
+        return response;
+    }
+
+    if request.uri().path() != "/readyz" {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    let status = Status {
+        status: "seeding",
+        progress: Some(readiness.progress.snapshot()),
+    };
+    (StatusCode::SERVICE_UNAVAILABLE, Json(status)).into_response()
+}
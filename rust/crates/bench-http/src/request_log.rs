@@ -0,0 +1,159 @@
+// Per-request timing log, to attribute a run's latency to the database, to serialization
+// or to everything else (routing, extractors, middleware, the framework). Enabled with
+// REQUEST_LOG=1, it writes one JSON line per request to stdout as a tracing event:
+//
+//   {"timestamp":"...","level":"INFO","target":"request","method":"GET",
+//    "route":"/customer-by-id","path":"/customer-by-id","status":200,"total_us":412,
+//    "db_us":301,"db_queries":1,"serialize_us":9}
+//
+// `db_us` is the time Diesel spent in queries on the server's pools, from sending each to
+// having its rows (the QUERY_BACKEND backends other than `diesel` aren't timed), including
+// the health check the pool runs on each connection it hands out, so a single-query route
+// shows `db_queries` 2. `serialize_us` is the time the query routes spent encoding their
+// response bodies, which for NDJSON streams happens after the response left and isn't
+// counted.
+
+use std::{
+    cell::Cell,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use diesel::connection::{Instrumentation, InstrumentationEvent, set_default_instrumentation};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{Layer, filter::filter_fn, registry::LookupSpan};
+
+#[derive(Default)]
+struct Timings {
+    db: Cell<Duration>,
+    db_queries: Cell<u32>,
+    serialize: Cell<Duration>,
+}
+
+tokio::task_local! {
+    // Of the request the current task is handling
+    static TIMINGS: Timings;
+}
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+// Whether REQUEST_LOG is set. The first call sets up the query timing of every connection
+// opened from then on, so the server makes it before connecting its pool; the JSON output
+// is `layer`, which `telemetry::init` installs.
+pub fn init() -> bool {
+    *ENABLED.get_or_init(|| {
+        if !matches!(
+            std::env::var("REQUEST_LOG").as_deref(),
+            Ok("1") | Ok("true")
+        ) {
+            return false;
+        }
+
+        if let Err(err) = set_default_instrumentation(|| Some(Box::new(QueryTimer::default()))) {
+            eprintln!("Failed to time queries for the request log: {:?}", err);
+        }
+        true
+    })
+}
+
+// Writes the log's events as JSON lines, when it's enabled. It leaves out the spans of
+// `telemetry`, which only its exporter handles.
+pub fn layer<S>() -> Option<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    init().then(|| {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(false)
+            .with_writer(std::io::stdout)
+            .with_filter(filter_fn(|metadata| {
+                metadata.is_event() && *metadata.level() <= Level::INFO
+            }))
+    })
+}
+
+// Diesel instrumentation of a connection, adding its query times to the request's
+#[derive(Default)]
+struct QueryTimer {
+    started: Option<Instant>,
+}
+
+impl Instrumentation for QueryTimer {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { .. } => {
+                if let Some(started) = self.started.take() {
+                    let _ = TIMINGS.try_with(|timings| {
+                        timings.db.set(timings.db.get() + started.elapsed());
+                        timings.db_queries.set(timings.db_queries.get() + 1);
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Runs `serialize`, counting its time against the current request when it's being logged
+pub fn time_serialization<R>(serialize: impl FnOnce() -> R) -> R {
+    if TIMINGS.try_with(|_| ()).is_err() {
+        return serialize();
+    }
+    let started = Instant::now();
+    let result = serialize();
+    let _ = TIMINGS.try_with(|timings| {
+        timings
+            .serialize
+            .set(timings.serialize.get() + started.elapsed());
+    });
+    result
+}
+
+pub async fn log(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let path = request.uri().path().to_string();
+
+    let started = Instant::now();
+    let (response, (db, db_queries, serialize)) = TIMINGS
+        .scope(Timings::default(), async {
+            let response = next.run(request).await;
+            let timings = TIMINGS.with(|timings| {
+                (
+                    timings.db.get(),
+                    timings.db_queries.get(),
+                    timings.serialize.get(),
+                )
+            });
+            (response, timings)
+        })
+        .await;
+    let total = started.elapsed();
+
+    tracing::info!(
+        target: "request",
+        method = %method,
+        route,
+        path,
+        status = response.status().as_u16(),
+        total_us = total.as_micros() as u64,
+        db_us = db.as_micros() as u64,
+        db_queries,
+        serialize_us = serialize.as_micros() as u64,
+    );
+
+    response
+}
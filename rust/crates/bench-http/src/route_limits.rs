@@ -0,0 +1,219 @@
+// Per-route concurrency caps, as API gateways enforce them: at most so many requests of a
+// route run at once (say 4 `/orders-with-details` reports) and the rest queue for a turn,
+// while the other routes go on. Configured with:
+//
+//   ROUTE_CONCURRENCY       `route=limit` pairs, comma-separated, routes as registered:
+//                           `/orders-with-details=4,/search-customer=16` (unset disables)
+//   ROUTE_QUEUE_TIMEOUT_MS  how long a request may queue before it's a 503 (default 0,
+//                           queueing as long as it takes)
+//
+// Only the query routes can be capped. `/stats/route-limits` shows each capped route's
+// queue: requests running and waiting, how many had to wait and for how long, and timeouts.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hdrhistogram::Histogram;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::error::ErrorCode;
+
+#[derive(Serialize)]
+pub struct RouteLimitStats {
+    pub route: String,
+    pub limit: usize,
+    pub running: usize,
+    pub waiting: u64,
+    pub admitted: u64,
+    // Of the admitted requests, those that waited for a turn
+    pub queued: u64,
+    pub timed_out: u64,
+    pub wait_p50_us: u64,
+    pub wait_p99_us: u64,
+    pub wait_max_us: u64,
+}
+
+struct RouteLimit {
+    limit: usize,
+    permits: Semaphore,
+    waiting: AtomicU64,
+    admitted: AtomicU64,
+    queued: AtomicU64,
+    timed_out: AtomicU64,
+    // Of the queued requests, in microseconds
+    waits: Mutex<Histogram<u64>>,
+}
+
+impl RouteLimit {
+    fn new(limit: usize) -> Self {
+        RouteLimit {
+            limit,
+            permits: Semaphore::new(limit),
+            waiting: AtomicU64::new(0),
+            admitted: AtomicU64::new(0),
+            queued: AtomicU64::new(0),
+            timed_out: AtomicU64::new(0),
+            waits: Mutex::new(Histogram::new(3).expect("valid histogram precision")),
+        }
+    }
+}
+
+// Counts a request as waiting until dropped, also when its client leaves meanwhile
+struct Waiting<'a>(&'a AtomicU64);
+
+impl<'a> Waiting<'a> {
+    fn new(waiting: &'a AtomicU64) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Waiting(waiting)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct RouteLimits {
+    routes: HashMap<String, RouteLimit>,
+    queue_timeout: Option<Duration>,
+}
+
+// Parses ROUTE_CONCURRENCY's `route=limit` pairs
+fn parse_limits(value: &str) -> Result<HashMap<String, usize>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (route, limit) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected route=limit, got `{}`", pair))?;
+            let limit = limit
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&limit| limit > 0)
+                .ok_or_else(|| format!("invalid limit for {}: `{}`", route, limit))?;
+            Ok((route.trim().to_string(), limit))
+        })
+        .collect()
+}
+
+impl RouteLimits {
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var("ROUTE_CONCURRENCY").ok()?;
+        let limits = match parse_limits(&value) {
+            Ok(limits) if !limits.is_empty() => limits,
+            Ok(_) => return None,
+            Err(err) => {
+                eprintln!("Ignoring invalid ROUTE_CONCURRENCY: {}", err);
+                return None;
+            }
+        };
+        let queue_timeout = std::env::var("ROUTE_QUEUE_TIMEOUT_MS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|&millis| millis > 0)
+            .map(Duration::from_millis);
+
+        Some(RouteLimits {
+            routes: limits
+                .into_iter()
+                .map(|(route, limit)| (route, RouteLimit::new(limit)))
+                .collect(),
+            queue_timeout,
+        })
+    }
+
+    pub fn stats(&self) -> Vec<RouteLimitStats> {
+        let mut stats: Vec<RouteLimitStats> = self
+            .routes
+            .iter()
+            .map(|(route, limit)| {
+                let waits = limit.waits.lock();
+                RouteLimitStats {
+                    route: route.clone(),
+                    limit: limit.limit,
+                    running: limit.limit - limit.permits.available_permits(),
+                    waiting: limit.waiting.load(Ordering::Relaxed),
+                    admitted: limit.admitted.load(Ordering::Relaxed),
+                    queued: limit.queued.load(Ordering::Relaxed),
+                    timed_out: limit.timed_out.load(Ordering::Relaxed),
+                    wait_p50_us: waits.value_at_quantile(0.50),
+                    wait_p99_us: waits.value_at_quantile(0.99),
+                    wait_max_us: waits.max(),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.route.cmp(&b.route));
+        stats
+    }
+}
+
+fn timed_out() -> Response {
+    let mut response = StatusCode::SERVICE_UNAVAILABLE.into_response();
+    response.extensions_mut().insert(ErrorCode::Overloaded);
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+    response
+}
+
+pub async fn limit(
+    State(limits): State<Arc<RouteLimits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| limits.routes.get(path.as_str()))
+    else {
+        return next.run(request).await;
+    };
+
+    let _permit = match route.permits.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            let waiting = Waiting::new(&route.waiting);
+            let started = Instant::now();
+            let acquired = match limits.queue_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, route.permits.acquire())
+                    .await
+                    .ok(),
+                None => Some(route.permits.acquire().await),
+            };
+            drop(waiting);
+
+            // The semaphores are never closed, so an error is a timeout
+            let Some(Ok(permit)) = acquired else {
+                route.timed_out.fetch_add(1, Ordering::Relaxed);
+                return timed_out();
+            };
+            route.queued.fetch_add(1, Ordering::Relaxed);
+            let _ = route
+                .waits
+                .lock()
+                .record(started.elapsed().as_micros() as u64);
+            permit
+        }
+    };
+    route.admitted.fetch_add(1, Ordering::Relaxed);
+
+    next.run(request).await
+}
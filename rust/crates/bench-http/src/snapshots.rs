@@ -0,0 +1,168 @@
+// Consistent paginated reads during write benchmarks: `POST /snapshots` opens a REPEATABLE
+// READ transaction on a dedicated connection, exports its snapshot and returns the id as a
+// token. Paginated requests carrying it in `X-Snapshot` run in their own transaction on that
+// snapshot, so every page sees the data as of the POST.
+//
+//   SNAPSHOTS         1 to enable the endpoints and header
+//   SNAPSHOT_TTL_MS   lifetime of a token (default 60000); DELETE /snapshots/{token} ends
+//                     it early
+//   SNAPSHOT_MAX      tokens held at once, each holding a connection (default 16)
+//
+// Only the default database is pinned; the response cache and hot set are bypassed for
+// requests with the header.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bench_core::{BenchResult, DbPool};
+use diesel::{QueryResult, QueryableByName, sql_types::Text};
+use diesel_async::{
+    AsyncPgConnection, RunQueryDsl, SimpleAsyncConnection,
+    scoped_futures::{ScopedBoxFuture, ScopedFutureExt},
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+pub const SNAPSHOT_HEADER: &str = "x-snapshot";
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_MAX: usize = 16;
+
+#[derive(QueryableByName)]
+struct ExportedSnapshot {
+    #[diesel(sql_type = Text)]
+    snapshot: String,
+}
+
+// The exporting transaction has to stay open for the snapshot to remain importable
+struct Held {
+    _conn: AsyncPgConnection,
+    expires_at: Instant,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotToken {
+    pub token: String,
+    pub expires_in_ms: u64,
+}
+
+pub struct Snapshots {
+    pool: DbPool,
+    ttl: Duration,
+    max: usize,
+    held: Mutex<HashMap<String, Held>>,
+}
+
+impl Snapshots {
+    pub fn from_env(pool: DbPool) -> Option<Self> {
+        if !matches!(std::env::var("SNAPSHOTS").as_deref(), Ok("1") | Ok("true")) {
+            return None;
+        }
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .filter(|value| *value > 0)
+        };
+
+        Some(Snapshots {
+            pool,
+            ttl: number("SNAPSHOT_TTL_MS").map_or(DEFAULT_TTL, Duration::from_millis),
+            max: number("SNAPSHOT_MAX").map_or(DEFAULT_MAX, |max| max as usize),
+            held: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // Drops expired snapshots, closing their connections
+    fn expire(&self) {
+        let now = Instant::now();
+        self.held.lock().retain(|_, held| held.expires_at > now);
+    }
+
+    // Ok(None) when SNAPSHOT_MAX tokens are already held
+    pub async fn create(&self) -> BenchResult<Option<SnapshotToken>> {
+        self.expire();
+        if self.held.lock().len() >= self.max {
+            return Ok(None);
+        }
+
+        // Outside the pool, so held snapshots don't take connections from requests
+        let mut conn = self.pool.dedicated_connection().await?;
+        conn.batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .await?;
+        let exported: ExportedSnapshot =
+            diesel::sql_query("SELECT pg_export_snapshot() AS snapshot")
+                .get_result(&mut conn)
+                .await?;
+
+        self.held.lock().insert(
+            exported.snapshot.clone(),
+            Held {
+                _conn: conn,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+
+        Ok(Some(SnapshotToken {
+            token: exported.snapshot,
+            expires_in_ms: self.ttl.as_millis() as u64,
+        }))
+    }
+
+    // false when the token wasn't held
+    pub fn release(&self, token: &str) -> bool {
+        self.held.lock().remove(token).is_some()
+    }
+
+    pub fn is_held(&self, token: &str) -> bool {
+        self.expire();
+        self.held.lock().contains_key(token)
+    }
+
+    // Expires tokens in the background as well, so an abandoned one doesn't hold back vacuum
+    // until the next request
+    pub fn spawn_expiry(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                self.expire();
+            }
+        });
+    }
+}
+
+// Runs `query` on `conn`, inside a read-only transaction on `snapshot` when there is one.
+// The token must come from `Snapshots::is_held`: it is spliced into the SQL
+pub async fn run_in<'a, T, F>(
+    conn: &'a mut AsyncPgConnection,
+    snapshot: Option<&'a str>,
+    query: F,
+) -> QueryResult<T>
+where
+    F: for<'r> FnOnce(&'r mut AsyncPgConnection) -> ScopedBoxFuture<'a, 'r, QueryResult<T>>
+        + Send
+        + 'a,
+    T: Send + 'a,
+{
+    let Some(snapshot) = snapshot else {
+        return query(conn).await;
+    };
+
+    conn.build_transaction()
+        .repeatable_read()
+        .read_only()
+        .run(|conn| {
+            async move {
+                conn.batch_execute(&format!("SET TRANSACTION SNAPSHOT '{}'", snapshot))
+                    .await?;
+                query(conn).await
+            }
+            .scope_boxed()
+        })
+        .await
+}
@@ -0,0 +1,275 @@
+// The 13 benchmark queries as SQL-over-HTTP requests (Neon's serverless `/sql` protocol),
+// for builds without a Postgres pool, e.g. the serverless scenarios covered for the JS
+// ORMs. Each query has Postgres render the response as JSON in the same shape the Diesel
+// handlers serialize, so a handler only routes, sends one request and returns the text.
+//
+// This module needs nothing but serde, so the routing and SQL can be compiled for a WASI
+// target with the fetch supplied by the host. The rest of this crate (diesel-async, tokio
+// sockets, mimalloc) can't, so a WASI component has to take this module on its own.
+
+use serde::{Deserialize, Serialize};
+
+// A list query wrapped to return its rows as one JSON array (`[]` when empty), in id order
+macro_rules! json_rows {
+    ($($sql:expr),+ $(,)?) => {
+        concat!(
+            "SELECT coalesce(json_agg(t ORDER BY t.id), '[]')::text AS json FROM (",
+            $($sql),+,
+            ") t"
+        )
+    };
+}
+
+// A single-row query wrapped to return it as a JSON object (SQL NULL when missing)
+macro_rules! json_row {
+    ($($sql:expr),+ $(,)?) => {
+        concat!(
+            "SELECT (SELECT row_to_json(t) FROM (",
+            $($sql),+,
+            ") t)::text AS json"
+        )
+    };
+}
+
+// Column lists of the camelCase models in `models.rs`
+macro_rules! customer_columns {
+    () => {
+        "id, company_name AS \"companyName\", contact_name AS \"contactName\", \
+         contact_title AS \"contactTitle\", address, city, postal_code AS \"postalCode\", \
+         region, country, phone, fax"
+    };
+}
+
+macro_rules! employee_columns {
+    () => {
+        "id, last_name AS \"lastName\", first_name AS \"firstName\", title, \
+         title_of_courtesy AS \"titleOfCourtesy\", birth_date AS \"birthDate\", \
+         hire_date AS \"hireDate\", address, city, postal_code AS \"postalCode\", country, \
+         home_phone AS \"homePhone\", extension, notes, recipient_id AS \"recipientId\""
+    };
+}
+
+macro_rules! supplier_columns {
+    () => {
+        "id, company_name AS \"companyName\", contact_name AS \"contactName\", \
+         contact_title AS \"contactTitle\", address, city, region, postal_code AS \"postalCode\", \
+         country, phone"
+    };
+}
+
+macro_rules! product_columns {
+    () => {
+        "id, name, qt_per_unit AS \"qtPerUnit\", unit_price AS \"unitPrice\", \
+         units_in_stock AS \"unitsInStock\", units_on_order AS \"unitsOnOrder\", \
+         reorder_level AS \"reorderLevel\", discontinued, supplier_id AS \"supplierId\""
+    };
+}
+
+// p11/p12 (`P11Row`)
+macro_rules! order_summary {
+    () => {
+        "SELECT o.id, o.shipped_date, o.ship_name, o.ship_city, o.ship_country, \
+         count(d.product_id) AS products_count, sum(d.quantity) AS quantity_sum, \
+         sum(d.quantity::float8 * d.unit_price) AS total_price \
+         FROM orders o LEFT JOIN order_details d ON d.order_id = o.id "
+    };
+}
+
+const P1: &str = json_rows!(
+    "SELECT ",
+    customer_columns!(),
+    " FROM customers ORDER BY id LIMIT $1 OFFSET $2"
+);
+const P2: &str = json_row!(
+    "SELECT ",
+    customer_columns!(),
+    " FROM customers WHERE id = $1"
+);
+const P3: &str = json_rows!(
+    "SELECT ",
+    customer_columns!(),
+    " FROM customers WHERE to_tsvector('english', company_name) @@ plainto_tsquery('english', $1)"
+);
+const P4: &str = json_rows!(
+    "SELECT ",
+    employee_columns!(),
+    " FROM employees ORDER BY id LIMIT $1 OFFSET $2"
+);
+const P5: &str = json_row!(
+    "SELECT e.*, r.id AS recipient_employee_id, r.last_name AS recipient_last_name, \
+     r.first_name AS recipient_first_name, r.title AS recipient_title, \
+     r.title_of_courtesy AS recipient_title_of_courtesy, r.birth_date AS recipient_birth_date, \
+     r.hire_date AS recipient_hire_date, r.address AS recipient_address, \
+     r.city AS recipient_city, r.postal_code AS recipient_postal_code, \
+     r.country AS recipient_country, r.home_phone AS recipient_home_phone, \
+     r.extension AS recipient_extension, r.notes AS recipient_notes, \
+     r.recipient_id AS recipient_recipient_id \
+     FROM employees e LEFT JOIN employees r ON e.recipient_id = r.id WHERE e.id = $1"
+);
+const P6: &str = json_rows!(
+    "SELECT ",
+    supplier_columns!(),
+    " FROM suppliers ORDER BY id LIMIT $1 OFFSET $2"
+);
+const P7: &str = json_row!(
+    "SELECT ",
+    supplier_columns!(),
+    " FROM suppliers WHERE id = $1"
+);
+const P8: &str = json_rows!(
+    "SELECT ",
+    product_columns!(),
+    " FROM products ORDER BY id LIMIT $1 OFFSET $2"
+);
+const P9: &str = json_row!(
+    "SELECT p.*, s.id AS supplier_supplier_id, s.company_name AS supplier_company_name, \
+     s.contact_name AS supplier_contact_name, s.contact_title AS supplier_contact_title, \
+     s.address AS supplier_address, s.city AS supplier_city, s.region AS supplier_region, \
+     s.postal_code AS supplier_postal_code, s.country AS supplier_country, \
+     s.phone AS supplier_phone \
+     FROM products p JOIN suppliers s ON s.id = p.supplier_id WHERE p.id = $1"
+);
+const P10: &str = json_rows!(
+    "SELECT ",
+    product_columns!(),
+    " FROM products WHERE to_tsvector('english', name) @@ plainto_tsquery('english', $1)"
+);
+const P11: &str = json_rows!(
+    order_summary!(),
+    "GROUP BY o.id ORDER BY o.id LIMIT $1 OFFSET $2"
+);
+const P12: &str = json_row!(order_summary!(), "WHERE o.id = $1 GROUP BY o.id");
+const P13: &str = json_row!(
+    "SELECT o.*, coalesce((SELECT json_agg(d) FROM (\
+     SELECT od.unit_price, od.quantity, od.discount, od.order_id, od.product_id, od.id, \
+     p.id AS product_product_id, p.name AS product_name, p.qt_per_unit AS product_qt_per_unit, \
+     p.unit_price AS product_unit_price, p.units_in_stock AS product_units_in_stock, \
+     p.units_on_order AS product_units_on_order, p.reorder_level AS product_reorder_level, \
+     p.discontinued AS product_discontinued, p.supplier_id AS product_supplier_id \
+     FROM order_details od JOIN products p ON p.id = od.product_id \
+     WHERE od.order_id = o.id) d), '[]') AS details \
+     FROM orders o WHERE o.id = $1"
+);
+
+// Body of a POST to the `/sql` endpoint. Parameters go as text, like the JS driver sends them
+#[derive(Serialize, Debug)]
+pub struct SqlRequest {
+    pub query: &'static str,
+    pub params: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SqlResponse {
+    pub rows: Vec<JsonRow>,
+}
+
+// The single `json` column every query above returns
+#[derive(Deserialize, Debug)]
+pub struct JsonRow {
+    pub json: Option<String>,
+}
+
+impl SqlResponse {
+    // The HTTP response body: the rendered JSON, or `null` for a missing single row
+    pub fn into_body(self) -> String {
+        self.rows
+            .into_iter()
+            .next()
+            .and_then(|row| row.json)
+            .unwrap_or_else(|| "null".to_string())
+    }
+}
+
+// Routes served by `route_request`
+pub const PATHS: [&str; 13] = [
+    "/customers",
+    "/customer-by-id",
+    "/search-customer",
+    "/employees",
+    "/employee-with-recipient",
+    "/suppliers",
+    "/supplier-by-id",
+    "/products",
+    "/product-with-supplier",
+    "/search-product",
+    "/orders-with-details",
+    "/order-with-details",
+    "/order-with-details-and-products",
+];
+
+#[derive(Debug)]
+pub enum RouteError {
+    UnknownPath,
+    InvalidParams(String),
+}
+
+#[derive(Deserialize)]
+struct LimitOffset {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct IdParam {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+struct SearchParam {
+    term: String,
+}
+
+fn parse<'a, T: Deserialize<'a>>(query: Option<&'a str>) -> Result<T, RouteError> {
+    serde_urlencoded::from_str(query.unwrap_or(""))
+        .map_err(|err| RouteError::InvalidParams(err.to_string()))
+}
+
+// The SQL request serving a benchmark route, with the defaults, cap and checks of the
+// handlers' extractors (`params`, which this module can't use as it needs axum)
+pub fn route_request(path: &str, query: Option<&str>) -> Result<SqlRequest, RouteError> {
+    let list = |sql: &'static str| -> Result<SqlRequest, RouteError> {
+        let params: LimitOffset = parse(query)?;
+        let limit = params.limit.unwrap_or(100);
+        let offset = params.offset.unwrap_or(0);
+        if limit < 0 || offset < 0 {
+            return Err(RouteError::InvalidParams(
+                "limit and offset must not be negative".into(),
+            ));
+        }
+        Ok(SqlRequest {
+            query: sql,
+            params: vec![limit.min(1000).to_string(), offset.to_string()],
+        })
+    };
+    let by_id = |sql: &'static str| -> Result<SqlRequest, RouteError> {
+        let params: IdParam = parse(query)?;
+        Ok(SqlRequest {
+            query: sql,
+            params: vec![params.id.to_string()],
+        })
+    };
+    let search = |sql: &'static str| -> Result<SqlRequest, RouteError> {
+        let params: SearchParam = parse(query)?;
+        Ok(SqlRequest {
+            query: sql,
+            params: vec![params.term],
+        })
+    };
+
+    match path {
+        "/customers" => list(P1),
+        "/customer-by-id" => by_id(P2),
+        "/search-customer" => search(P3),
+        "/employees" => list(P4),
+        "/employee-with-recipient" => by_id(P5),
+        "/suppliers" => list(P6),
+        "/supplier-by-id" => by_id(P7),
+        "/products" => list(P8),
+        "/product-with-supplier" => by_id(P9),
+        "/search-product" => search(P10),
+        "/orders-with-details" => list(P11),
+        "/order-with-details" => by_id(P12),
+        "/order-with-details-and-products" => by_id(P13),
+        _ => Err(RouteError::UnknownPath),
+    }
+}
@@ -0,0 +1,324 @@
+// Host and container resources for `/stats/system`, so a results file records the limits
+// the server actually ran under rather than what the host has.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::Serialize;
+use sysinfo::{Networks, System};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CPU_ROOT: &str = "/sys/devices/system/cpu";
+const NODE_ROOT: &str = "/sys/devices/system/node";
+// /proc/diskstats counts 512-byte sectors regardless of the device's sector size
+const SECTOR_BYTES: u64 = 512;
+
+#[derive(Serialize)]
+pub struct CgroupStats {
+    pub path: String,
+    // cpu.max quota / period; None when unlimited
+    pub cpu_limit_cores: Option<f64>,
+    pub memory_limit_bytes: Option<u64>,
+    pub memory_current_bytes: Option<u64>,
+    // cpu.stat counters, cumulative since the cgroup was created
+    pub nr_periods: u64,
+    pub nr_throttled: u64,
+    pub throttled_usec: u64,
+}
+
+// Per-core frequency scaling state; fields are None where sysfs doesn't expose them
+// (VMs, containers without /sys, non-Linux)
+#[derive(Serialize)]
+pub struct CoreStats {
+    pub cpu: usize,
+    pub frequency_mhz: Option<u64>,
+    pub max_frequency_mhz: Option<u64>,
+    pub governor: Option<String>,
+    // Cumulative; an increase during a run means the core was thermally throttled
+    pub thermal_throttle_count: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct NumaNode {
+    pub node: usize,
+    // Kernel cpulist format, e.g. "0-7,16-23"
+    pub cpus: String,
+}
+
+#[derive(Serialize)]
+pub struct DiskIo {
+    pub device: String,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct InterfaceIo {
+    pub interface: String,
+    pub received_bytes: u64,
+    pub transmitted_bytes: u64,
+}
+
+// Bytes moved since the previous sample, to tell network-, disk- and CPU-bound runs apart
+#[derive(Serialize)]
+pub struct IoInterval {
+    pub interval_secs: f64,
+    pub disks: Vec<DiskIo>,
+    pub interfaces: Vec<InterfaceIo>,
+}
+
+#[derive(Serialize)]
+pub struct SystemStats {
+    pub cpu_usage: Vec<i32>,
+    pub cpus: usize,
+    pub total_memory_bytes: u64,
+    pub used_memory_bytes: u64,
+    // The tighter of the host and cgroup limits
+    pub effective_cpus: f64,
+    pub effective_memory_bytes: u64,
+    pub cgroup: Option<CgroupStats>,
+    pub cores: Vec<CoreStats>,
+    pub numa_nodes: Vec<NumaNode>,
+    pub turbo_enabled: Option<bool>,
+    pub io: IoInterval,
+    // The global allocator the server was built with
+    pub allocator: &'static str,
+}
+
+// The process's cgroup v2 directory; None on cgroup v1 or outside Linux
+fn cgroup_dir() -> Option<(String, PathBuf)> {
+    let content = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = content.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let dir = PathBuf::from(CGROUP_ROOT).join(path.trim_start_matches('/'));
+    dir.join("cgroup.controllers")
+        .exists()
+        .then(|| (path.to_string(), dir))
+}
+
+fn read(dir: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(dir.join(file))
+        .ok()
+        .map(|content| content.trim().to_string())
+}
+
+// "max" means no limit
+fn read_limit(dir: &Path, file: &str) -> Option<u64> {
+    read(dir, file)?.parse().ok()
+}
+
+pub fn cgroup_stats() -> Option<CgroupStats> {
+    let (path, dir) = cgroup_dir()?;
+
+    // cpu.max is "<quota> <period>" or "max <period>"
+    let cpu_limit_cores = read(&dir, "cpu.max").and_then(|cpu_max| {
+        let mut parts = cpu_max.split_whitespace();
+        let quota: f64 = parts.next()?.parse().ok()?;
+        let period: f64 = parts.next()?.parse().ok()?;
+        Some(quota / period)
+    });
+
+    let mut stats = CgroupStats {
+        path,
+        cpu_limit_cores,
+        memory_limit_bytes: read_limit(&dir, "memory.max"),
+        memory_current_bytes: read_limit(&dir, "memory.current"),
+        nr_periods: 0,
+        nr_throttled: 0,
+        throttled_usec: 0,
+    };
+
+    for line in read(&dir, "cpu.stat").unwrap_or_default().lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let value = value.parse().unwrap_or(0);
+        match key {
+            "nr_periods" => stats.nr_periods = value,
+            "nr_throttled" => stats.nr_throttled = value,
+            "throttled_usec" => stats.throttled_usec = value,
+            _ => {}
+        }
+    }
+
+    Some(stats)
+}
+
+fn read_khz_as_mhz(dir: &Path, file: &str) -> Option<u64> {
+    read_limit(dir, file).map(|khz| khz / 1000)
+}
+
+pub fn core_stats(cpus: usize) -> Vec<CoreStats> {
+    (0..cpus)
+        .map(|cpu| {
+            let dir = Path::new(CPU_ROOT).join(format!("cpu{}", cpu));
+            let cpufreq = dir.join("cpufreq");
+            CoreStats {
+                cpu,
+                frequency_mhz: read_khz_as_mhz(&cpufreq, "scaling_cur_freq"),
+                max_frequency_mhz: read_khz_as_mhz(&cpufreq, "cpuinfo_max_freq"),
+                governor: read(&cpufreq, "scaling_governor"),
+                thermal_throttle_count: read_limit(
+                    &dir.join("thermal_throttle"),
+                    "core_throttle_count",
+                ),
+            }
+        })
+        .collect()
+}
+
+pub fn numa_nodes() -> Vec<NumaNode> {
+    let Ok(entries) = fs::read_dir(NODE_ROOT) else {
+        return Vec::new();
+    };
+
+    let mut nodes: Vec<NumaNode> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let node = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()?;
+            Some(NumaNode {
+                node,
+                cpus: read(&entry.path(), "cpulist")?,
+            })
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.node);
+    nodes
+}
+
+// intel_pstate exposes no_turbo, acpi-cpufreq and amd-pstate expose boost
+pub fn turbo_enabled() -> Option<bool> {
+    let cpu = Path::new(CPU_ROOT);
+    if let Some(no_turbo) = read(&cpu.join("intel_pstate"), "no_turbo") {
+        return Some(no_turbo == "0");
+    }
+    read(&cpu.join("cpufreq"), "boost").map(|boost| boost == "1")
+}
+
+// Cumulative (read, written) bytes per whole-disk device; partitions and virtual devices
+// without a /sys/block entry are skipped
+fn disk_counters() -> HashMap<String, (u64, u64)> {
+    let content = fs::read_to_string("/proc/diskstats").unwrap_or_default();
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let device = *fields.get(2)?;
+            if !Path::new("/sys/block").join(device).exists() || device.starts_with("loop") {
+                return None;
+            }
+            let read: u64 = fields.get(5)?.parse().ok()?;
+            let written: u64 = fields.get(9)?.parse().ok()?;
+            Some((
+                device.to_string(),
+                (read * SECTOR_BYTES, written * SECTOR_BYTES),
+            ))
+        })
+        .collect()
+}
+
+// Keeps the previous readings so every sample reports the delta since the last one
+pub struct IoCounters {
+    networks: Networks,
+    disks: HashMap<String, (u64, u64)>,
+    last: Instant,
+}
+
+impl IoCounters {
+    pub fn new() -> Self {
+        IoCounters {
+            networks: Networks::new_with_refreshed_list(),
+            disks: disk_counters(),
+            last: Instant::now(),
+        }
+    }
+
+    pub fn sample(&mut self) -> IoInterval {
+        self.networks.refresh();
+        let disks = disk_counters();
+        let now = Instant::now();
+
+        let mut disk_io: Vec<DiskIo> = disks
+            .iter()
+            .map(|(device, &(read, written))| {
+                let (prev_read, prev_written) =
+                    self.disks.get(device).copied().unwrap_or((read, written));
+                DiskIo {
+                    device: device.clone(),
+                    read_bytes: read.saturating_sub(prev_read),
+                    written_bytes: written.saturating_sub(prev_written),
+                }
+            })
+            .collect();
+        disk_io.sort_by(|a, b| a.device.cmp(&b.device));
+
+        let mut interfaces: Vec<InterfaceIo> = self
+            .networks
+            .iter()
+            .map(|(interface, data)| InterfaceIo {
+                interface: interface.clone(),
+                received_bytes: data.received(),
+                transmitted_bytes: data.transmitted(),
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.interface.cmp(&b.interface));
+
+        let interval = IoInterval {
+            interval_secs: (now - self.last).as_secs_f64(),
+            disks: disk_io,
+            interfaces,
+        };
+        self.disks = disks;
+        self.last = now;
+        interval
+    }
+}
+
+impl Default for IoCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Expects `sys` to have fresh CPU and memory readings
+pub fn system_stats(sys: &System, io: IoInterval) -> SystemStats {
+    let cgroup = cgroup_stats();
+    let cpus = sys.cpus().len();
+    let total_memory_bytes = sys.total_memory();
+
+    let effective_cpus = cgroup
+        .as_ref()
+        .and_then(|cgroup| cgroup.cpu_limit_cores)
+        .map_or(cpus as f64, |limit| limit.min(cpus as f64));
+    let effective_memory_bytes = cgroup
+        .as_ref()
+        .and_then(|cgroup| cgroup.memory_limit_bytes)
+        .map_or(total_memory_bytes, |limit| limit.min(total_memory_bytes));
+
+    SystemStats {
+        cpu_usage: sys
+            .cpus()
+            .iter()
+            .map(|cpu| cpu.cpu_usage().round() as i32)
+            .collect(),
+        cpus,
+        total_memory_bytes,
+        used_memory_bytes: sys.used_memory(),
+        effective_cpus,
+        effective_memory_bytes,
+        cgroup,
+        cores: core_stats(cpus),
+        numa_nodes: numa_nodes(),
+        turbo_enabled: turbo_enabled(),
+        io,
+        allocator: crate::allocator::NAME,
+    }
+}
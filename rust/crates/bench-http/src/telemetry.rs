@@ -0,0 +1,163 @@
+// Tracing spans of the requests, to look at single benchmark requests in Jaeger or Tempo:
+// where their time went between waiting for a pool connection, the queries and the rest.
+// Servers built with the `otel` feature export them over OTLP/HTTP when
+// OTEL_EXPORTER_OTLP_ENDPOINT is set (e.g. http://localhost:4318), as the service
+// OTEL_SERVICE_NAME, or `drizzle-benchmarks-rust` without it. The spans:
+//
+//   request        "GET /customers": http.request.method, http.route,
+//                  http.response.status_code
+//   pool.checkout  a handler waiting for a connection: pool.wait_us
+//   query          one of the benchmark queries, named as such ("p11"): db.query.name,
+//                  db.rows (of a by-id query, 0 or 1)
+//
+// Spans are batched and sent every few seconds, so those of the last moments before the
+// server stops are lost. Without the exporter none are recorded.
+
+use std::{future::Future, sync::OnceLock, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use bench_core::DbPool;
+use diesel_async::{
+    AsyncPgConnection,
+    pooled_connection::bb8::{PooledConnection, RunError},
+};
+use tracing::{Instrument, field::Empty, info_span};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::request_log;
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+// Whether spans are exported. The first call installs the tracing subscriber, with the
+// request log's output as well, so the server makes it before serving.
+pub fn init() -> bool {
+    *ENABLED.get_or_init(|| {
+        #[cfg(feature = "otel")]
+        let exporter = otlp::layer();
+        #[cfg(not(feature = "otel"))]
+        let exporter: Option<tracing_subscriber::layer::Identity> = None;
+
+        let enabled = exporter.is_some();
+        let _ = tracing_subscriber::registry()
+            .with(request_log::layer())
+            .with(exporter)
+            .try_init();
+        enabled
+    })
+}
+
+// Middleware wrapping each request in a `request` span
+pub async fn trace(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {}", method, route),
+        otel.kind = "server",
+        http.request.method = %method,
+        http.route = route,
+        http.response.status_code = Empty,
+    );
+
+    let response = next.run(request).instrument(span.clone()).await;
+    span.record("http.response.status_code", response.status().as_u16());
+    response
+}
+
+// `pool.get()`, in a `pool.checkout` span
+pub async fn checkout(pool: &DbPool) -> Result<PooledConnection<'_, AsyncPgConnection>, RunError> {
+    let span = info_span!("pool.checkout", pool.wait_us = Empty);
+    if span.is_disabled() {
+        return pool.get().await;
+    }
+    let started = Instant::now();
+    let conn = pool.get().instrument(span.clone()).await;
+    span.record("pool.wait_us", started.elapsed().as_micros() as u64);
+    conn
+}
+
+// What `query` counts as a query's rows
+pub trait Rows {
+    fn rows(&self) -> usize;
+}
+
+impl<T> Rows for Vec<T> {
+    fn rows(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> Rows for Option<T> {
+    fn rows(&self) -> usize {
+        usize::from(self.is_some())
+    }
+}
+
+// Runs the benchmark query `name` in a `query` span
+pub async fn query<T: Rows, E>(
+    name: &'static str,
+    query: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let span = info_span!(
+        "query",
+        otel.name = name,
+        db.query.name = name,
+        db.rows = Empty,
+    );
+    let result = query.instrument(span.clone()).await;
+    if let Ok(rows) = &result {
+        span.record("db.rows", rows.rows() as u64);
+    }
+    result
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+    use tracing::{Subscriber, level_filters::LevelFilter};
+    use tracing_subscriber::{Layer, registry::LookupSpan};
+
+    const SERVICE_NAME: &str = "drizzle-benchmarks-rust";
+
+    // The exporting layer, unless OTEL_EXPORTER_OTLP_ENDPOINT is unset. The exporter reads
+    // the other OTEL_EXPORTER_OTLP_* variables itself.
+    pub fn layer<S>() -> Option<impl Layer<S>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+        let exporter = match SpanExporter::builder().with_http().build() {
+            Ok(exporter) => exporter,
+            Err(err) => {
+                eprintln!("Failed to set up the OTLP exporter: {:?}", err);
+                return None;
+            }
+        };
+        let mut resource = Resource::builder();
+        if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+            resource = resource.with_service_name(SERVICE_NAME);
+        }
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource.build())
+            .build();
+
+        println!("Exporting traces to {}", endpoint);
+        // Only ours: hyper's and h2's spans are at debug and trace
+        Some(
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer("bench-http"))
+                .with_filter(LevelFilter::INFO),
+        )
+    }
+}
@@ -0,0 +1,121 @@
+-- The benchmark tables on MySQL, for `QUERY_BACKEND=mysql` (`backend-mysql` feature): the
+-- drizzle migrations' tables with their columns in the same order, and FULLTEXT indexes for
+-- p3 and p10 in place of the tsvector GIN indexes. Apply with
+--
+--   diesel migration run --migration-dir migrations-mysql --database-url "$MYSQL_DATABASE_URL"
+--
+-- The search terms of data/requests.json are two letters, below the three InnoDB starts indexing
+-- words at, and MATCH takes no dictionary. Start the MySQL server with, before applying:
+--
+--   innodb_ft_min_token_size = 2      (the words p3/p10 look up)
+--   innodb_ft_enable_stopword = OFF   (close to the `simple` dictionary; ON for `english`,
+--                                      which MySQL doesn't stem)
+--
+-- Then copy the rows of a Postgres database `rust seed` filled:
+--
+--   for table in customers employees suppliers products orders order_details; do
+--     psql "$DATABASE_URL" -c "\copy $table TO '$table.csv' WITH (FORMAT csv, NULL '\N')"
+--     mysql --local-infile=1 -e "SET foreign_key_checks = 0;
+--       LOAD DATA LOCAL INFILE '$table.csv' INTO TABLE $table
+--       FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"'" <database>
+--   done
+
+CREATE TABLE customers (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    company_name TEXT NOT NULL,
+    contact_name VARCHAR(255) NOT NULL,
+    contact_title VARCHAR(255) NOT NULL,
+    address VARCHAR(255) NOT NULL,
+    city VARCHAR(255) NOT NULL,
+    postal_code VARCHAR(255),
+    region VARCHAR(255),
+    country VARCHAR(255) NOT NULL,
+    phone VARCHAR(255) NOT NULL,
+    fax VARCHAR(255),
+    FULLTEXT INDEX customers_company_name_idx (company_name)
+);
+
+CREATE TABLE employees (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    last_name VARCHAR(255) NOT NULL,
+    first_name VARCHAR(255),
+    title VARCHAR(255) NOT NULL,
+    title_of_courtesy VARCHAR(255) NOT NULL,
+    birth_date DATE NOT NULL,
+    hire_date DATE NOT NULL,
+    address VARCHAR(255) NOT NULL,
+    city VARCHAR(255) NOT NULL,
+    postal_code VARCHAR(255) NOT NULL,
+    country VARCHAR(255) NOT NULL,
+    home_phone VARCHAR(255) NOT NULL,
+    extension INT NOT NULL,
+    notes TEXT NOT NULL,
+    recipient_id INT,
+    INDEX recepient_idx (recipient_id),
+    CONSTRAINT employees_recipient_id_employees_id_fk
+        FOREIGN KEY (recipient_id) REFERENCES employees (id)
+);
+
+CREATE TABLE suppliers (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    company_name VARCHAR(255) NOT NULL,
+    contact_name VARCHAR(255) NOT NULL,
+    contact_title VARCHAR(255) NOT NULL,
+    address VARCHAR(255) NOT NULL,
+    city VARCHAR(255) NOT NULL,
+    region VARCHAR(255),
+    postal_code VARCHAR(255) NOT NULL,
+    country VARCHAR(255) NOT NULL,
+    phone VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE products (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name TEXT NOT NULL,
+    qt_per_unit VARCHAR(255) NOT NULL,
+    unit_price DOUBLE NOT NULL,
+    units_in_stock INT NOT NULL,
+    units_on_order INT NOT NULL,
+    reorder_level INT NOT NULL,
+    discontinued INT NOT NULL,
+    supplier_id INT NOT NULL,
+    INDEX supplier_idx (supplier_id),
+    FULLTEXT INDEX products_name_idx (name),
+    CONSTRAINT products_supplier_id_suppliers_id_fk
+        FOREIGN KEY (supplier_id) REFERENCES suppliers (id) ON DELETE CASCADE
+);
+
+CREATE TABLE orders (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_date DATE NOT NULL,
+    required_date DATE NOT NULL,
+    shipped_date DATE,
+    ship_via INT NOT NULL,
+    freight DOUBLE NOT NULL,
+    ship_name VARCHAR(255) NOT NULL,
+    ship_city VARCHAR(255) NOT NULL,
+    ship_region VARCHAR(255),
+    ship_postal_code VARCHAR(255),
+    ship_country VARCHAR(255) NOT NULL,
+    customer_id INT NOT NULL,
+    employee_id INT NOT NULL,
+    CONSTRAINT orders_customer_id_customers_id_fk
+        FOREIGN KEY (customer_id) REFERENCES customers (id) ON DELETE CASCADE,
+    CONSTRAINT orders_employee_id_employees_id_fk
+        FOREIGN KEY (employee_id) REFERENCES employees (id) ON DELETE CASCADE
+);
+
+CREATE TABLE order_details (
+    unit_price DOUBLE NOT NULL,
+    quantity INT NOT NULL,
+    discount DOUBLE NOT NULL,
+    order_id INT NOT NULL,
+    product_id INT NOT NULL,
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    INDEX order_id_idx (order_id),
+    INDEX product_id_idx (product_id),
+    CONSTRAINT order_details_order_id_orders_id_fk
+        FOREIGN KEY (order_id) REFERENCES orders (id) ON DELETE CASCADE,
+    CONSTRAINT order_details_product_id_products_id_fk
+        FOREIGN KEY (product_id) REFERENCES products (id) ON DELETE CASCADE
+);